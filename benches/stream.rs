@@ -0,0 +1,132 @@
+//! Completions consumed by a pool of worker threads: per-submission promises funnelled to the workers, against the
+//! ring-wide completion stream the workers pull from directly.
+//!
+//! Run with `cargo bench --features test-util`, adding `crossbeam` to back the stream with an MPMC channel.
+
+use std::{
+    hint::black_box,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+        mpsc,
+    },
+    thread,
+    time::Duration,
+};
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use ring_promise::{
+    PRingSender,
+    adapters::{Completions, QueueSubmissions},
+    submission::Submission,
+    test_util::{MockEntry, MockRing},
+    traits::{FullRing, Submitter},
+};
+
+/// Completions consumed per iteration.
+const ENTRIES: usize = 10_000;
+/// Worker threads consuming them.
+const WORKERS: usize = 4;
+
+/// A backend completing every entry as soon as it is submitted, so the benchmark measures delivery alone.
+struct EchoRing(MockRing<MockEntry, MockEntry>);
+
+impl Submitter for EchoRing {
+    fn submit(&mut self) {
+        self.submit_counted();
+    }
+
+    fn submit_counted(&mut self) -> Option<usize> {
+        let submitted = self.0.submit_counted();
+        self.0.complete_all();
+        submitted
+    }
+}
+
+impl FullRing<MockEntry, MockEntry, QueueSubmissions<MockEntry>, Completions<MockEntry>>
+    for EchoRing
+{
+    fn completion(&mut self) -> Completions<MockEntry> {
+        self.0.completion()
+    }
+
+    fn submission(&mut self) -> QueueSubmissions<MockEntry> {
+        self.0.submission()
+    }
+
+    fn is_synchronous(&self) -> bool {
+        true
+    }
+}
+
+/// Submits `ENTRIES` entries, handing each promise to a pool of workers that wait on them.
+fn oneshot(sender: &PRingSender<MockEntry, MockEntry>) {
+    let (tx, rx) = mpsc::channel::<Submission<MockEntry>>();
+    let rx = Arc::new(Mutex::new(rx));
+    let workers: Vec<_> = (0..WORKERS)
+        .map(|_| {
+            let rx = Arc::clone(&rx);
+            thread::spawn(move || {
+                loop {
+                    let next = rx.lock().unwrap().recv();
+                    let Ok(submission) = next else {
+                        break;
+                    };
+                    black_box(submission.wait().unwrap());
+                }
+            })
+        })
+        .collect();
+
+    for tag in 0..ENTRIES as u64 {
+        tx.send(sender.submit(MockEntry::new(tag))).unwrap();
+    }
+    drop(tx);
+    workers
+        .into_iter()
+        .for_each(|worker| worker.join().unwrap());
+}
+
+/// Submits `ENTRIES` entries into the completion stream, pulled by a pool of workers.
+fn stream(sender: &PRingSender<MockEntry, MockEntry>) {
+    let stream = sender.completion_stream().unwrap();
+    let consumed = Arc::new(AtomicUsize::new(0));
+    let workers: Vec<_> = (0..WORKERS)
+        .map(|_| {
+            let (stream, consumed) = (stream.clone(), Arc::clone(&consumed));
+            thread::spawn(move || {
+                while consumed.load(Ordering::Relaxed) < ENTRIES {
+                    if let Some(completion) = stream.next_timeout(Duration::from_millis(1)) {
+                        black_box(completion.1.unwrap());
+                        consumed.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            })
+        })
+        .collect();
+
+    for tag in 0..ENTRIES as u64 {
+        sender.submit_streamed(MockEntry::new(tag)).unwrap();
+    }
+    workers
+        .into_iter()
+        .for_each(|worker| worker.join().unwrap());
+}
+
+fn delivery(c: &mut Criterion) {
+    let mut group = c.benchmark_group("delivery");
+    group.throughput(Throughput::Elements(ENTRIES as u64));
+
+    let sender = PRingSender::new(EchoRing(MockRing::echo()));
+    group.bench_function("oneshot", |b| b.iter(|| oneshot(&sender)));
+
+    let sender = PRingSender::builder()
+        .completion_stream()
+        .build(EchoRing(MockRing::echo()));
+    group.bench_function("stream", |b| b.iter(|| stream(&sender)));
+
+    group.finish();
+}
+
+criterion_group!(benches, delivery);
+criterion_main!(benches);
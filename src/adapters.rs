@@ -0,0 +1,287 @@
+//! Ready-made queue types for assembling a [`FullRing`](crate::traits::FullRing) from plain collections.
+//!
+//! `QueueSubmissions` is a `VecDeque`-backed submission queue whose clones share the same storage, so
+//! `FullRing::submission` can hand out a clone while the ring keeps one to drain on `submit`. `Completions` is an
+//! owned completion queue built from a `Vec` or `VecDeque` of completion entries. `FilteredCompletions` wraps another
+//! completion queue, keeping back the entries a predicate rejects, e.g. internal completions carrying reserved user data.
+
+use std::{
+    collections::{VecDeque, vec_deque},
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    registry::lock,
+    traits::{CompletionQueueEntry as CQE, SubmissionQueue, SubmissionQueueEntry as SQE},
+};
+
+/// A submission queue backed by a shared `VecDeque`, optionally bounded.
+#[derive(Debug)]
+pub struct QueueSubmissions<S> {
+    /// The pushed entries, shared between clones.
+    queue: Arc<Mutex<VecDeque<S>>>,
+    /// The maximum number of queued entries, or `None` if unbounded.
+    capacity: Option<usize>,
+}
+
+impl<S> Clone for QueueSubmissions<S> {
+    fn clone(&self) -> Self {
+        Self {
+            queue: Arc::clone(&self.queue),
+            capacity: self.capacity,
+        }
+    }
+}
+
+impl<S> Default for QueueSubmissions<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> QueueSubmissions<S> {
+    /// Creates an empty, unbounded queue.
+    pub fn new() -> Self {
+        VecDeque::new().into()
+    }
+
+    /// Creates an empty queue that rejects pushes once it holds `capacity` entries.
+    pub fn bounded(capacity: usize) -> Self {
+        Self {
+            queue: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            capacity: Some(capacity),
+        }
+    }
+
+    /// The maximum number of queued entries, or `None` if unbounded.
+    pub fn capacity(&self) -> Option<usize> {
+        self.capacity
+    }
+
+    /// The number of queued entries.
+    pub fn len(&self) -> usize {
+        lock(&self.queue).len()
+    }
+
+    /// Whether the queue is empty.
+    pub fn is_empty(&self) -> bool {
+        lock(&self.queue).is_empty()
+    }
+
+    /// Removes and returns every queued entry, oldest first.
+    pub fn drain(&self) -> VecDeque<S> {
+        std::mem::take(&mut *lock(&self.queue))
+    }
+}
+
+impl<S> From<VecDeque<S>> for QueueSubmissions<S> {
+    /// Creates an unbounded queue holding `queue`.
+    fn from(queue: VecDeque<S>) -> Self {
+        Self {
+            queue: Arc::new(Mutex::new(queue)),
+            capacity: None,
+        }
+    }
+}
+
+impl<S> From<Vec<S>> for QueueSubmissions<S> {
+    /// Creates an unbounded queue holding `entries`, the first element being the oldest.
+    fn from(entries: Vec<S>) -> Self {
+        VecDeque::from(entries).into()
+    }
+}
+
+impl<S: SQE> SubmissionQueue<S> for QueueSubmissions<S> {
+    fn push(&mut self, entry: S) -> Result<(), S> {
+        let mut queue = lock(&self.queue);
+
+        match self.capacity {
+            Some(capacity) if queue.len() >= capacity => Err(entry),
+            _ => {
+                queue.push_back(entry);
+                Ok(())
+            }
+        }
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        self.capacity
+    }
+}
+
+/// An owned completion queue yielding entries oldest first.
+#[derive(Debug)]
+pub struct Completions<C> {
+    /// The remaining completions.
+    entries: vec_deque::IntoIter<C>,
+}
+
+impl<C> Default for Completions<C> {
+    fn default() -> Self {
+        VecDeque::new().into()
+    }
+}
+
+impl<C> From<VecDeque<C>> for Completions<C> {
+    fn from(entries: VecDeque<C>) -> Self {
+        Self {
+            entries: entries.into_iter(),
+        }
+    }
+}
+
+impl<C> From<Vec<C>> for Completions<C> {
+    fn from(entries: Vec<C>) -> Self {
+        VecDeque::from(entries).into()
+    }
+}
+
+impl<C: CQE> Iterator for Completions<C> {
+    type Item = C;
+
+    fn next(&mut self) -> Option<C> {
+        self.entries.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.entries.size_hint()
+    }
+}
+
+impl<C: CQE> ExactSizeIterator for Completions<C> {}
+
+/// A completion queue yielding only the entries of another that match a predicate.
+///
+/// Entries the predicate rejects are skipped rather than handed to the registry.
+#[derive(Debug, Clone)]
+pub struct FilteredCompletions<I, F> {
+    /// The unfiltered completions.
+    inner: I,
+    /// Whether an entry should be yielded.
+    predicate: F,
+}
+
+impl<I, F> FilteredCompletions<I, F> {
+    /// Wraps `inner`, yielding only the entries for which `predicate` returns `true`.
+    pub fn new(inner: I, predicate: F) -> Self {
+        Self { inner, predicate }
+    }
+
+    /// Unwraps the underlying completion queue.
+    pub fn into_inner(self) -> I {
+        self.inner
+    }
+}
+
+impl<C, I, F> Iterator for FilteredCompletions<I, F>
+where
+    C: CQE,
+    I: Iterator<Item = C>,
+    F: FnMut(&C) -> bool,
+{
+    type Item = C;
+
+    fn next(&mut self) -> Option<C> {
+        self.inner.find(&mut self.predicate)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.inner.size_hint().1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::MockEntry;
+
+    fn tags<I: IntoIterator<Item = MockEntry>>(entries: I) -> Vec<u64> {
+        entries.into_iter().map(|entry| entry.tag()).collect()
+    }
+
+    #[test]
+    fn submissions_drain_in_push_order() {
+        let mut queue = QueueSubmissions::new();
+        for tag in 0..4 {
+            queue.push(MockEntry::new(tag)).unwrap();
+        }
+
+        assert_eq!(queue.len(), 4);
+        assert_eq!(tags(queue.drain()), [0, 1, 2, 3]);
+        assert!(queue.is_empty());
+        assert!(queue.drain().is_empty());
+    }
+
+    #[test]
+    fn bounded_submissions_reject_pushes_once_full() {
+        let mut queue = QueueSubmissions::bounded(2);
+        assert_eq!(queue.capacity(), Some(2));
+        assert_eq!(SubmissionQueue::capacity(&queue), Some(2));
+
+        queue.push(MockEntry::new(0)).unwrap();
+        queue.push(MockEntry::new(1)).unwrap();
+        let rejected = queue.push(MockEntry::new(2)).unwrap_err();
+        assert_eq!(rejected.tag(), 2);
+        assert_eq!(queue.len(), 2);
+
+        // Draining makes room again.
+        assert_eq!(tags(queue.drain()), [0, 1]);
+        queue.push(MockEntry::new(3)).unwrap();
+        assert_eq!(tags(queue.drain()), [3]);
+    }
+
+    #[test]
+    fn submission_clones_share_storage() {
+        let mut queue = QueueSubmissions::bounded(1);
+        let clone = queue.clone();
+
+        queue.push(MockEntry::new(0)).unwrap();
+        assert_eq!(clone.capacity(), Some(1));
+        assert_eq!(clone.len(), 1);
+        assert_eq!(tags(clone.drain()), [0]);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn submissions_from_collections_are_unbounded_and_keep_their_order() {
+        let from_vec = QueueSubmissions::from(vec![MockEntry::new(0), MockEntry::new(1)]);
+        assert_eq!(from_vec.capacity(), None);
+
+        let mut deque = VecDeque::from([MockEntry::new(1)]);
+        deque.push_front(MockEntry::new(0));
+        let mut from_deque = QueueSubmissions::from(deque);
+        assert_eq!(from_deque.capacity(), None);
+        from_deque.push(MockEntry::new(2)).unwrap();
+
+        assert_eq!(tags(from_vec.drain()), [0, 1]);
+        assert_eq!(tags(from_deque.drain()), [0, 1, 2]);
+        assert_eq!(QueueSubmissions::<MockEntry>::default().capacity(), None);
+    }
+
+    #[test]
+    fn completions_yield_oldest_first() {
+        let mut completions = Completions::from(vec![MockEntry::new(0), MockEntry::new(1)]);
+        assert_eq!(completions.len(), 2);
+        assert_eq!(completions.next().map(|entry| entry.tag()), Some(0));
+        assert_eq!(completions.len(), 1);
+        assert_eq!(tags(completions), [1]);
+
+        let deque = VecDeque::from([MockEntry::new(2), MockEntry::new(3)]);
+        assert_eq!(tags(Completions::from(deque)), [2, 3]);
+        assert_eq!(Completions::<MockEntry>::default().next(), None);
+    }
+
+    #[test]
+    fn filtered_completions_skip_rejected_entries() {
+        let completions = Completions::from((0..6).map(MockEntry::new).collect::<Vec<_>>());
+        let mut filtered = FilteredCompletions::new(completions, |entry: &MockEntry| {
+            entry.tag().is_multiple_of(2)
+        });
+
+        assert_eq!(filtered.size_hint(), (0, Some(6)));
+        assert_eq!(filtered.next().map(|entry| entry.tag()), Some(0));
+        assert_eq!(tags(filtered.by_ref()), [2, 4]);
+        assert_eq!(filtered.next(), None);
+        assert_eq!(filtered.into_inner().len(), 0);
+    }
+}
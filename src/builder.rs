@@ -0,0 +1,651 @@
+//! Builder for configuring and spawning a ring thread.
+//!
+//! `PRingBuilder` collects the optional knobs of the ring thread and spawns it with [`PRingBuilder::build`], or hands
+//! back its [`RingDriver`] with [`PRingBuilder::build_driver`].
+//! [`PRingSender::new`] is shorthand for building with the defaults.
+
+use std::{any::Any, marker::PhantomData, sync::Arc, thread::Scope, time::Duration};
+
+use crate::{
+    PRingSender,
+    driver::RingDriver,
+    metrics::{Metrics, MetricsRecorder},
+    reap::{Always, ReapStrategy},
+    registry::{Completion, DeliverFn},
+    stream::StreamSender,
+    traits::{
+        CompletionQueue, CompletionQueueEntry as CQE, FullRing, SubmissionQueue,
+        SubmissionQueueEntry as SQE,
+    },
+};
+
+/// Ring thread configuration assembled by [`PRingBuilder`].
+pub(crate) struct Config<S, C> {
+    /// Number of callback workers per node, or `None` to run callbacks inline on the ring thread.
+    pub(crate) callback_pool: Option<usize>,
+    /// Number of NUMA nodes to split the callback workers over.
+    pub(crate) callback_nodes: usize,
+    /// Finds the NUMA node of the calling thread, or `None` to not route callbacks by node.
+    pub(crate) current_node: Option<fn() -> usize>,
+    /// Run on every callback worker with its node before it takes any callbacks.
+    pub(crate) pin_callback_worker: Option<fn(usize)>,
+    /// Number of pushed entries to accumulate before calling `submit`.
+    pub(crate) submit_threshold: usize,
+    /// Longest an accumulated entry may wait for `submit`.
+    pub(crate) max_defer: Duration,
+    /// Capacity of the signal channel, or `None` for an unbounded channel.
+    pub(crate) channel_capacity: Option<usize>,
+    /// Capacity of the overflow queue of a bounded channel, or `None` to block senders instead.
+    pub(crate) overflow_capacity: Option<usize>,
+    /// How long to spin-poll the CQ with outstanding entries before blocking on the channel.
+    pub(crate) spin_before_block: Duration,
+    /// Copies entries so they can be retained alongside their destinations, or `None` to not retain them.
+    pub(crate) retain: Option<fn(&S) -> S>,
+    /// How many recently completed user data values to remember when telling duplicates from orphans.
+    pub(crate) dedup_window: usize,
+    /// Number of reusable completion channels to allocate up front, or `None` to allocate one per submission.
+    pub(crate) completion_pool: Option<usize>,
+    /// Decides whether to reap right after submitting.
+    pub(crate) reap_strategy: Box<dyn ReapStrategy + Send>,
+    /// Called with the panic payload if the ring thread panics.
+    pub(crate) on_panic: Option<PanicHook>,
+    /// Longest the ring thread goes without reaping, or `None` to only reap as work comes and goes.
+    pub(crate) housekeeping_interval: Option<Duration>,
+    /// How long to keep reaping outstanding entries once every sender is gone, or `None` to reap only once.
+    pub(crate) drain_timeout: Option<Duration>,
+    /// Receives completions that no entry is waiting for, or `None` to drop them.
+    pub(crate) orphan_sink: Option<OrphanSink<C>>,
+    /// Receives completions reporting an error, or `None` to only deliver them to their entries.
+    pub(crate) error_sink: Option<ErrorSink<C>>,
+    /// Whether each reap delivers its completions by priority rather than in CQ order.
+    pub(crate) prioritized: bool,
+    /// Whether each reap delivers its completions oldest submission first rather than in CQ order.
+    pub(crate) oldest_first: bool,
+    /// Whether each reap delivers its completions newest submission first rather than in CQ order.
+    pub(crate) newest_first: bool,
+    /// Most completions a single reap delivers, or `None` for no limit.
+    pub(crate) max_reap_burst: Option<usize>,
+    /// Shortest time between two discretionary reaps, or `None` to reap whenever asked to.
+    pub(crate) reap_cooldown: Option<Duration>,
+    /// Delivers completions bound for a oneshot channel, or `None` to send them directly.
+    pub(crate) deliver: Option<DeliverFn<C>>,
+    /// Whether to reap on a thread of its own, if the ring can split off its completion side.
+    pub(crate) split_threads: bool,
+    /// Where the ring thread reports its activity, or `None` to not report it.
+    pub(crate) metrics: Option<Metrics>,
+    /// Whether a panic in the backend's completion handling is contained to the completion rather than unwinding.
+    pub(crate) contain_completion_panics: bool,
+    /// How long a delivered completion can go untaken before its consumer counts as slow, or `None` to not track it.
+    pub(crate) slow_consumer_threshold: Option<Duration>,
+    /// Whether entries are failed in place of the completions an overflowing CQ dropped.
+    pub(crate) fail_lost_completions: bool,
+    /// Whether the ring streams completions out through a channel shared by the whole ring.
+    pub(crate) completion_stream: bool,
+    /// Streams the completions no entry is waiting for when there is no orphan sink, or `None` to drop them.
+    pub(crate) orphan_stream: Option<StreamSender<C>>,
+    /// Most entries in flight before submitting waits for room, or `None` for no cap.
+    pub(crate) max_in_flight: Option<usize>,
+    /// SQ-full retries for a single entry after which a warning is logged.
+    #[cfg(feature = "logging")]
+    pub(crate) sq_full_warn_after: usize,
+    /// Shortest time between two SQ-full warnings.
+    #[cfg(feature = "logging")]
+    pub(crate) sq_full_warn_every: Duration,
+}
+
+/// A sink for completions that no entry is waiting for.
+pub(crate) type OrphanSink<C> = Box<dyn FnMut(C) + Send>;
+
+/// Where completions reporting an error go, besides or instead of the entries waiting for them.
+pub(crate) enum ErrorSink<C> {
+    /// Receives a copy made with the function, while the completion is delivered as usual.
+    Copy(Box<dyn FnMut(C) + Send>, fn(&C) -> C),
+    /// Receives the completion, and the entry waiting for it resolves with `RingError::Diverted`.
+    Divert(Box<dyn FnMut(C) + Send>),
+}
+
+/// A hook run with the payload of a ring thread panic.
+pub(crate) type PanicHook = Box<dyn FnOnce(&(dyn Any + Send)) + Send>;
+
+impl<S, C> Default for Config<S, C> {
+    fn default() -> Self {
+        Self {
+            callback_pool: None,
+            callback_nodes: 1,
+            current_node: None,
+            pin_callback_worker: None,
+            submit_threshold: 1,
+            max_defer: Duration::ZERO,
+            spin_before_block: Duration::ZERO,
+            channel_capacity: None,
+            overflow_capacity: None,
+            retain: None,
+            dedup_window: 0,
+            completion_pool: None,
+            reap_strategy: Box::new(Always),
+            on_panic: None,
+            housekeeping_interval: None,
+            drain_timeout: None,
+            orphan_sink: None,
+            error_sink: None,
+            prioritized: false,
+            oldest_first: false,
+            newest_first: false,
+            max_reap_burst: None,
+            reap_cooldown: None,
+            deliver: None,
+            split_threads: false,
+            metrics: None,
+            contain_completion_panics: true,
+            slow_consumer_threshold: None,
+            fail_lost_completions: false,
+            completion_stream: false,
+            orphan_stream: None,
+            max_in_flight: None,
+            #[cfg(feature = "logging")]
+            sq_full_warn_after: 8,
+            #[cfg(feature = "logging")]
+            sq_full_warn_every: Duration::from_secs(10),
+        }
+    }
+}
+
+/// A builder for [`PRingSender`].
+///
+/// Obtained from [`PRingSender::builder`].
+pub struct PRingBuilder<S: SQE, C: CQE> {
+    /// The configuration handed to the ring thread.
+    config: Config<S, C>,
+    _marker: PhantomData<fn() -> (S, C)>,
+}
+
+impl<S: SQE, C: CQE> Default for PRingBuilder<S, C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: SQE, C: CQE> PRingBuilder<S, C> {
+    /// Creates a builder with the default configuration.
+    pub fn new() -> Self {
+        Self {
+            config: Config::default(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Runs completion callbacks on a pool of `workers` threads instead of inline on the ring thread.
+    ///
+    /// A slow callback run inline blocks the ring thread, and with it every submission. With a pool, the ring
+    /// thread only hands `(completion, callback)` pairs off to the workers and goes straight back to servicing the ring.
+    ///
+    /// # Ordering
+    /// Callbacks no longer run in completion order: two completions reaped back to back may have their callbacks
+    /// run concurrently, or in either order. Promises are unaffected and are still resolved by the ring thread.
+    ///
+    /// # Shutdown
+    /// When the ring thread exits the pool is drained: every callback already handed to it runs to completion
+    /// before the workers are joined.
+    pub fn callback_pool(mut self, workers: usize) -> Self {
+        self.config.callback_pool = Some(workers);
+        self
+    }
+
+    /// Runs completion callbacks on `workers_per_node` workers for each of `nodes` NUMA nodes, routing callbacks to
+    /// the node they were submitted from.
+    ///
+    /// [`PRingSender::submit_with_callback`] calls `current_node` on the submitting thread to record its node, e.g. by
+    /// looking up the CPU from `sched_getcpu` in the machine's topology, and the callback later runs on a worker of
+    /// that node, modulo `nodes`. Pin the workers to their nodes with
+    /// [`pin_callback_workers`](Self::pin_callback_workers). Other callbacks are spread over the nodes in turn. The
+    /// ordering and shutdown behaviour of [`callback_pool`](Self::callback_pool) apply.
+    pub fn numa_callback_pool(
+        mut self,
+        nodes: usize,
+        workers_per_node: usize,
+        current_node: fn() -> usize,
+    ) -> Self {
+        self.config.callback_pool = Some(workers_per_node);
+        self.config.callback_nodes = nodes.max(1);
+        self.config.current_node = Some(current_node);
+        self
+    }
+
+    /// Runs `pin` on every callback worker, with the node it serves, before it takes any callbacks.
+    ///
+    /// Meant for setting each worker's CPU affinity to its node under a
+    /// [`numa_callback_pool`](Self::numa_callback_pool); the node is always zero with a plain
+    /// [`callback_pool`](Self::callback_pool).
+    pub fn pin_callback_workers(mut self, pin: fn(usize)) -> Self {
+        self.config.pin_callback_worker = Some(pin);
+        self
+    }
+
+    /// Defers notifying the kernel until `threshold` entries have been pushed onto the SQ.
+    ///
+    /// Entries are still pushed as soon as they are received, but `Submitter::submit` is only called once
+    /// `threshold` of them have accumulated, or once the oldest of them has waited [`max_defer`](Self::max_defer).
+    /// The accumulation persists across moments where the ring thread has nothing else to do, so set
+    /// `max_defer` to bound the added latency. A threshold of `1` (the default) submits after every entry.
+    pub fn submit_threshold(mut self, threshold: usize) -> Self {
+        self.config.submit_threshold = threshold.max(1);
+        self
+    }
+
+    /// Bounds how long a pushed entry may wait for `Submitter::submit` under [`submit_threshold`](Self::submit_threshold).
+    ///
+    /// Defaults to zero, which submits as soon as the ring thread runs out of signals to process.
+    pub fn max_defer(mut self, max_defer: Duration) -> Self {
+        self.config.max_defer = max_defer;
+        self
+    }
+
+    /// Spin-polls the completion queue for up to `spin` before blocking on the channel.
+    ///
+    /// When entries are outstanding and there are no signals to process, the ring thread repeatedly reaps instead
+    /// of going straight to sleep, catching completions as soon as they land at the cost of a busy core. Spinning
+    /// stops early once nothing is outstanding or a signal arrives. Zero (the default) blocks immediately.
+    pub fn spin_before_block(mut self, spin: Duration) -> Self {
+        self.config.spin_before_block = spin;
+        self
+    }
+
+    /// Bounds the channel between senders and the ring thread to `capacity` signals.
+    ///
+    /// Once the channel is full, sending blocks until the ring thread catches up, unless an
+    /// [`overflow_capacity`](Self::overflow_capacity) is set. The channel is unbounded by default. A capacity of zero
+    /// is treated as one.
+    pub fn channel_capacity(mut self, capacity: usize) -> Self {
+        self.config.channel_capacity = Some(capacity.max(1));
+        self
+    }
+
+    /// Allocates `size` reusable completion channels up front instead of one per submission.
+    ///
+    /// Submissions borrow a channel until both the `Submission` and the ring thread are done with it, so at most
+    /// `size` submissions are outstanding at a time. Submitting while every channel is in use resolves with
+    /// `RingError::AtCapacity` rather than allocating, bounding per-submission allocation for constrained
+    /// environments. Only plain submissions draw from the pool; shared, callback and waker submissions do not.
+    pub fn completion_pool(mut self, size: usize) -> Self {
+        self.config.completion_pool = Some(size);
+        self
+    }
+
+    /// Runs `hook` with the panic payload if the ring thread panics, before the panic carries on unwinding.
+    ///
+    /// The payload is usually a `&'static str` or a `String`, e.g. for logging the message or bumping an error
+    /// counter. By the time the hook runs, the outstanding entries have been left for
+    /// [`PRingSender::take_recovered`]. A panic inside the hook itself is caught and ignored.
+    pub fn on_panic<F>(mut self, hook: F) -> Self
+    where
+        F: FnOnce(&(dyn Any + Send)) + Send + 'static,
+    {
+        self.config.on_panic = Some(Box::new(hook));
+        self
+    }
+
+    /// Reaps the completion queue at least every `interval`, even with nothing outstanding.
+    ///
+    /// Reaps normally only happen around submissions and explicit requests, so completions a backend produces on its
+    /// own, e.g. from a multishot operation nobody is waiting on any more, can pile up in an idle ring's CQ. With an
+    /// interval, the ring thread wakes up to consume them, handing them to the
+    /// [`orphan_sink`](Self::orphan_sink) if there is one.
+    pub fn housekeeping_interval(mut self, interval: Duration) -> Self {
+        self.config.housekeeping_interval = Some(interval);
+        self
+    }
+
+    /// Keeps reaping on shutdown until every outstanding entry is delivered, for at most `timeout`.
+    ///
+    /// By default, once every sender is gone the ring thread submits what is deferred, reaps once and exits, and
+    /// whatever is still outstanding resolves with `RingError::Disconnected`. With this, it drains instead: it reaps
+    /// on every turn, whatever the [`reap_strategy`](Self::reap_strategy), blocking for completions with
+    /// [`Submitter::submit_and_wait`](crate::traits::Submitter::submit_and_wait) in between, so the drain finishes
+    /// as fast as the backend allows. Retries and timeouts still run while draining. Entries held behind a gate that
+    /// never opened resolve with `RingError::Disconnected` straight away. The timeout is only checked between waits,
+    /// so a backend that blocks in `submit_and_wait` can hold the drain up past it.
+    pub fn drain_on_shutdown(mut self, timeout: Duration) -> Self {
+        self.config.drain_timeout = Some(timeout);
+        self
+    }
+
+    /// Reports submissions, completions, in-flight entries, SQ-full retries and latency to `recorder`.
+    ///
+    /// Metric names start with `prefix`, e.g. `ring_promise`; the [`metrics`](crate::metrics) module lists them.
+    pub fn metrics(mut self, recorder: impl MetricsRecorder + 'static, prefix: &str) -> Self {
+        self.config.metrics = Some(Metrics::new(Arc::new(recorder), prefix));
+        self
+    }
+
+    /// Reports the same metrics as [`metrics`](Self::metrics) through the `metrics` crate facade, to the recorder
+    /// installed globally when each is emitted.
+    ///
+    /// Metric names start with `prefix`, e.g. `ring_promise`. Replaces any recorder set with `metrics`.
+    #[cfg(feature = "metrics-facade")]
+    pub fn metrics_facade(mut self, prefix: &str) -> Self {
+        self.config.metrics = Some(Metrics::facade(prefix));
+        self
+    }
+
+    /// Hands completions that no entry is waiting for to `sink` instead of dropping them.
+    ///
+    /// The sink runs on the ring thread with the completion as reaped, before [`FullRing::interpret`]. Repeats counted
+    /// as duplicates under a [`dedup_window`](Self::dedup_window) and the completions of linked timeout entries do not
+    /// reach it.
+    pub fn orphan_sink(mut self, sink: impl FnMut(C) + Send + 'static) -> Self {
+        self.config.orphan_sink = Some(Box::new(sink));
+        self
+    }
+
+    /// Streams completions out through a single channel shared by the whole ring, for consumers pulling them from a
+    /// pool of workers.
+    ///
+    /// Entries submitted with [`PRingSender::submit_streamed`] have their completion pushed into the
+    /// [stream](crate::stream), as do completions no entry is waiting for unless an
+    /// [`orphan_sink`](Self::orphan_sink) takes them. Take the consuming end with
+    /// [`PRingSender::completion_stream`]. Other submissions still resolve their own promise or callback.
+    pub fn completion_stream(mut self) -> Self {
+        self.config.completion_stream = true;
+        self
+    }
+
+    /// Hands completions that report an error to `sink` instead of the entries waiting for them.
+    ///
+    /// Centralizes error handling, e.g. logging or tripping a circuit breaker, in one place on the ring thread. A
+    /// completion goes to the sink if [`CQE::is_error`] says so, before [`FullRing::interpret`], and
+    /// its entry resolves with `RingError::Diverted`, bypassing any retry policy. Use
+    /// [`error_sink`](Self::error_sink) to have the entry resolve as usual as well.
+    pub fn divert_errors(mut self, sink: impl FnMut(C) + Send + 'static) -> Self {
+        self.config.error_sink = Some(ErrorSink::Divert(Box::new(sink)));
+        self
+    }
+
+    /// Delivers the completions of each reap in order of their submission's priority, highest first.
+    ///
+    /// By default completions are delivered in the order the CQ yields them. With this, the ring thread collects every
+    /// completion a reap finds and sorts them by the priority given to
+    /// [`PRingSender::submit_with_priority`] first, so latency-critical waiters are not stuck behind a burst of bulk
+    /// completions. Completions of equal priority keep their CQ order. This costs a sort per reap, so it is off by
+    /// default.
+    pub fn prioritized_delivery(mut self) -> Self {
+        self.config.prioritized = true;
+        self
+    }
+
+    /// Delivers the completions of each reap in the order their entries were submitted, oldest first.
+    ///
+    /// By default completions are delivered in the order the CQ yields them. With this, the ring thread sorts every
+    /// completion a reap finds by when its entry was submitted, so entries that have waited longest are not held up
+    /// further by ones submitted just now, trimming tail latency. This only reorders completions within a single reap,
+    /// never across reaps. Combined with [`prioritized_delivery`](Self::prioritized_delivery), completions are sorted
+    /// by priority first and by age within each priority. This costs a sort per reap, so it is off by default.
+    ///
+    /// Replaces [`newest_first_delivery`](Self::newest_first_delivery).
+    pub fn oldest_first_delivery(mut self) -> Self {
+        self.config.oldest_first = true;
+        self.config.newest_first = false;
+        self
+    }
+
+    /// Delivers the completions of each reap in the reverse order their entries were submitted, newest first.
+    ///
+    /// The opposite of [`oldest_first_delivery`](Self::oldest_first_delivery), trading fairness for latency: the
+    /// entries submitted most recently are the likeliest to still have their data in cache on the consuming side, so
+    /// handling their completions first makes the most of it, at the cost of older entries waiting for the rest of the
+    /// reap. Like it, this only reorders completions within a single reap, sorts by age within each priority when
+    /// combined with [`prioritized_delivery`](Self::prioritized_delivery), and costs a sort per reap. Off by default.
+    ///
+    /// Replaces [`oldest_first_delivery`](Self::oldest_first_delivery).
+    pub fn newest_first_delivery(mut self) -> Self {
+        self.config.newest_first = true;
+        self.config.oldest_first = false;
+        self
+    }
+
+    /// Hands every completion bound for a `oneshot::Sender` to `deliver`, instead of sending it directly.
+    ///
+    /// Controls how completions leave the ring thread, e.g. to send them from a task spawned onto an async runtime so
+    /// it wakes correctly, or to forward them into a channel type of your own before completing the sender. `deliver`
+    /// runs on the ring thread, or on the reaper thread under [`split_threads`](Self::split_threads), for plain,
+    /// retrying, timed and prioritized submissions. Callbacks, wakers, shared and pooled completions keep their own
+    /// delivery. Dropping the sender resolves the submission with `RingError::Disconnected`.
+    pub fn deliver_with<F>(mut self, deliver: F) -> Self
+    where
+        F: FnMut(Completion<C>, oneshot::Sender<Completion<C>>) + Send + 'static,
+    {
+        self.config.deliver = Some(Box::new(deliver));
+        self
+    }
+
+    /// Reaps and delivers completions on a thread of their own, leaving the ring thread to submit.
+    ///
+    /// Splits the submit and complete hot paths across two cores for very high throughput. The ring has to hand out
+    /// its completion side with [`FullRing::split_completion`]; if it does not, both stay on the ring thread. The
+    /// reaper polls the CQ continuously while entries are outstanding, parks while none are, and shares the registry
+    /// with the ring thread behind a mutex. The [`reap_strategy`](Self::reap_strategy) no longer applies, and entries
+    /// retried after a failure are only resubmitted once the ring thread next wakes up, which a
+    /// [`housekeeping_interval`](Self::housekeeping_interval) bounds. Off by default.
+    pub fn split_threads(mut self, split: bool) -> Self {
+        self.config.split_threads = split;
+        self
+    }
+
+    /// Whether to contain panics in the backend's completion handling instead of letting them take the ring down.
+    ///
+    /// When contained, a completion iterator that panics ends that reap early, leaving what the CQ still holds to the
+    /// next reap, and a completion whose [`FullRing::interpret`] panics resolves with `RingError::Backend`. Either way
+    /// the panic is counted in [`PRingSender::errored_completions`], and logged with the `logging` feature. Turn this
+    /// off in tests, or wherever a buggy backend should fail loudly, to have the panic unwind the ring thread as any
+    /// other would. On by default.
+    pub fn contain_completion_panics(mut self, contain: bool) -> Self {
+        self.config.contain_completion_panics = contain;
+        self
+    }
+
+    /// Tracks completions delivered but not yet taken, flagging those left untaken for longer than `threshold`.
+    ///
+    /// A completion sits in its `Submission` from the moment the ring thread delivers it until the consumer takes it,
+    /// e.g. with [`Submission::wait`](crate::submission::Submission::wait). A consumer that holds on to submissions
+    /// without looking at them keeps their buffers and descriptors alive, which shows up as memory growth rather than
+    /// latency. [`PRingSender::slow_consumers`] lists the submissions past the threshold. Only submissions handed out
+    /// as a `Submission` are tracked. Off by default, as tracking costs a lock per delivery.
+    pub fn slow_consumer_threshold(mut self, threshold: Duration) -> Self {
+        self.config.slow_consumer_threshold = Some(threshold);
+        self
+    }
+
+    /// Fails an outstanding entry with `RingError::CompletionLost` for every completion the CQ reports dropped.
+    ///
+    /// A completion dropped by an overflowing CQ never arrives, so its entry would wait forever. The ring thread
+    /// checks [`FullRing::overflow_count`] after every reap, and this has it fail as many entries as the count went up
+    /// by. The CQ does not say which completions it dropped, so this is a best-effort guess: the entries failed are
+    /// the ones submitted earliest, as those are the likeliest to have completed by the time the CQ overflowed. A
+    /// long-running entry, e.g. a poll, can be failed in place of a newer one whose completion was the one dropped,
+    /// and the newer one then still waits forever. Without this, lost completions are only counted, see
+    /// [`PRingSender::lost_completions`]. Off by default.
+    pub fn fail_lost_completions(mut self) -> Self {
+        self.config.fail_lost_completions = true;
+        self
+    }
+
+    /// Caps the entries in flight at `max`, having submitting wait for room rather than handing the ring more.
+    ///
+    /// An entry counts as in flight from when it is submitted until it is no longer pending, whether it completed,
+    /// failed, timed out, was cancelled or was rejected before reaching the ring thread, so the cap cannot leak. Once
+    /// `max` entries are in flight, [`PRingSender::submit`] and the other single-entry submissions block until one
+    /// leaves, and [`PRingSender::submit_async`] futures wait without blocking, sending their entry once polled with
+    /// room to spare. Batches and chains wait for room for all of their entries at once; one larger than the cap could
+    /// never be sent, so [`PRingSender::batch_submit`] and [`PRingSender::submit_pipeline`] reject it with
+    /// `AtCapacity`, while [`PRingSender::submit_bulk_detached`] sends it in chunks that fit. Entries held behind a
+    /// [`Gate`](crate::gate::Gate) count as in flight.
+    ///
+    /// Blocking submissions must not be made from a completion callback running on the ring thread: only the ring
+    /// thread makes room, so it would wait forever. A maximum of zero is treated as one. Uncapped by default.
+    pub fn max_in_flight(mut self, max: usize) -> Self {
+        self.config.max_in_flight = Some(max);
+        self
+    }
+
+    /// Logs a warning when a single entry needs more than `after` attempts to fit in a full SQ, at most once `every`.
+    ///
+    /// The warning carries the number of entries in flight and the SQ capacity, if
+    /// [`SubmissionQueue::capacity`] knows it. A full SQ means the ring is
+    /// saturated or not reaped often enough. Defaults to 8 attempts and 10 seconds.
+    #[cfg(feature = "logging")]
+    pub fn sq_full_warning(mut self, after: usize, every: Duration) -> Self {
+        self.config.sq_full_warn_after = after;
+        self.config.sq_full_warn_every = every;
+        self
+    }
+
+    /// Sets the policy for reaping the completion queue right after submitting.
+    ///
+    /// Defaults to [`Always`], reaping after every submission. Reaps the ring thread needs regardless, e.g. on an
+    /// explicit [`PRingSender::reap`], do not consult the strategy.
+    pub fn reap_strategy(mut self, strategy: impl ReapStrategy + Send + 'static) -> Self {
+        self.config.reap_strategy = Box::new(strategy);
+        self
+    }
+
+    /// Caps how many completions a single reap delivers, so a flood of completions cannot starve submissions.
+    ///
+    /// Every reap still takes everything off the CQ, but delivers at most `max` completions and holds the rest back.
+    /// The ring thread then checks the channel for signals before delivering the next `max`, so submissions keep being
+    /// pushed however fast the CQ refills. The cap applies to every reap, including those done while waiting for room
+    /// in the SQ or under [`split_threads`](Self::split_threads). Unlimited by default. Zero is treated as one.
+    pub fn max_reap_burst(mut self, max: usize) -> Self {
+        self.config.max_reap_burst = Some(max.max(1));
+        self
+    }
+
+    /// Waits at least `cooldown` between reaps, so a flood of completions cannot keep the ring thread busy reaping.
+    ///
+    /// Meant for rings sharing a core with other latency-sensitive work: completions sit in the CQ for up to
+    /// `cooldown` longer, in exchange for the ring thread giving the core up between reaps. Reaps asked for by the
+    /// [`reap_strategy`](Self::reap_strategy), on a timer or while idle wait the cooldown out. Those the ring needs
+    /// straight away, for a [`Watermark`](crate::reap::Watermark) being crossed, CQ pressure, a full SQ, an explicit
+    /// [`PRingSender::reap`](crate::PRingSender::reap) or shutdown, override it. Under
+    /// [`split_threads`](Self::split_threads), the reaper thread sleeps for `cooldown` between reaps instead of
+    /// polling. Disabled by default.
+    pub fn reap_cooldown(mut self, cooldown: Duration) -> Self {
+        self.config.reap_cooldown = Some(cooldown);
+        self
+    }
+
+    /// Remembers the user data of the last `window` completions, counting repeats among them as duplicates.
+    ///
+    /// Completions whose user data has no registered entry are normally dropped as orphans. With a window, those
+    /// matching a recent completion are instead counted in
+    /// [`PRingSender::duplicate_completions`](crate::PRingSender::duplicate_completions), telling benign duplicate
+    /// CQEs from a backend apart from genuine user data mix-ups. Zero (the default) disables the window.
+    pub fn dedup_window(mut self, window: usize) -> Self {
+        self.config.dedup_window = window;
+        self
+    }
+
+    /// Spills up to `capacity` submissions into a local overflow queue when the bounded channel is full.
+    ///
+    /// This smooths over short bursts without blocking senders: spilled signals are moved into the channel by the
+    /// next send that finds room, or picked up by the ring thread once it drains the channel. Submissions beyond the
+    /// overflow capacity resolve with `RingError::AtCapacity`. Has no effect without a
+    /// [`channel_capacity`](Self::channel_capacity).
+    pub fn overflow_capacity(mut self, capacity: usize) -> Self {
+        self.config.overflow_capacity = Some(capacity);
+        self
+    }
+
+    /// Spawns the ring thread for `ring` with this configuration.
+    ///
+    /// # Type Parameters
+    /// - `SQ`: The submission queue type.
+    /// - `CQ`: The completion queue type.
+    /// - `Ring`: The full ring type implementing `FullRing`.
+    pub fn build<SQ, CQ, Ring>(self, ring: Ring) -> PRingSender<S, C>
+    where
+        S: 'static,
+        C: 'static,
+        SQ: SubmissionQueue<S> + 'static,
+        CQ: CompletionQueue<C> + 'static,
+        Ring: FullRing<S, C, SQ, CQ> + Send + 'static,
+    {
+        PRingSender::spawn(ring, self.config)
+    }
+
+    /// Spawns the ring thread for `ring` within `scope`, with this configuration.
+    ///
+    /// The ring thread lives no longer than the scope, so `ring` only has to outlive the scope rather than be
+    /// `'static`, and can borrow from the scope's environment. So can entries and completions, e.g. an entry
+    /// pointing into a buffer on the spawning thread's stack: plain submissions only need them to outlive the scope.
+    /// Methods handing a completion to a callback, promise or pool of their own, e.g.
+    /// [`PRingSender::submit_with_callback`], still require `'static` entries and completions, as those do not know
+    /// the scope.
+    ///
+    /// The scope waits for the ring thread before it ends, and the ring thread only exits once every sender is dropped,
+    /// so make sure the returned sender and all its clones are dropped within the scope, or it never ends.
+    /// [`PRingSender::into_ring`] always returns `None` for a scoped ring; the ring is dropped on the ring thread.
+    ///
+    /// # Type Parameters
+    /// - `SQ`: The submission queue type.
+    /// - `CQ`: The completion queue type.
+    /// - `Ring`: The full ring type implementing `FullRing`.
+    pub fn build_scoped<'scope, 'env, SQ, CQ, Ring>(
+        self,
+        scope: &'scope Scope<'scope, 'env>,
+        ring: Ring,
+    ) -> PRingSender<S, C>
+    where
+        S: 'scope,
+        C: 'scope,
+        SQ: SubmissionQueue<S> + 'scope,
+        CQ: CompletionQueue<C> + 'scope,
+        Ring: FullRing<S, C, SQ, CQ> + Send + 'scope,
+    {
+        PRingSender::spawn_scoped(scope, ring, self.config)
+    }
+
+    /// Creates a sender for `ring` with this configuration, handing back the driver instead of spawning the ring thread.
+    ///
+    /// Nothing happens to submitted entries until the driver is stepped or run, e.g. by a test asserting on the
+    /// registry between steps. Running the driver on a thread of your choosing is equivalent to [`build`](Self::build).
+    ///
+    /// # Type Parameters
+    /// - `SQ`: The submission queue type.
+    /// - `CQ`: The completion queue type.
+    /// - `Ring`: The full ring type implementing `FullRing`.
+    #[allow(clippy::type_complexity)]
+    pub fn build_driver<SQ, CQ, Ring>(
+        self,
+        ring: Ring,
+    ) -> (PRingSender<S, C>, RingDriver<S, C, SQ, CQ, Ring>)
+    where
+        SQ: SubmissionQueue<S>,
+        CQ: CompletionQueue<C>,
+        Ring: FullRing<S, C, SQ, CQ>,
+    {
+        PRingSender::with_driver(ring, self.config)
+    }
+}
+
+impl<S: SQE, C: CQE + Clone> PRingBuilder<S, C> {
+    /// Hands a copy of every completion that reports an error to `sink`, on top of delivering it to its entry.
+    ///
+    /// Centralizes error handling, e.g. logging or tripping a circuit breaker, in one place on the ring thread while
+    /// each submission still sees its own failure. A completion goes to the sink if
+    /// [`CQE::is_error`] says so, before [`FullRing::interpret`], so every failed attempt of a
+    /// retried entry is reported. Use [`divert_errors`](Self::divert_errors) to keep errors from the entries instead.
+    pub fn error_sink(mut self, sink: impl FnMut(C) + Send + 'static) -> Self {
+        self.config.error_sink = Some(ErrorSink::Copy(Box::new(sink), C::clone));
+        self
+    }
+}
+
+impl<S: SQE + Clone, C: CQE> PRingBuilder<S, C> {
+    /// Keeps a copy of every outstanding entry on the ring thread until it completes.
+    ///
+    /// Retained entries are what make outstanding work recoverable: if the ring thread panics, they are handed
+    /// back through [`PRingSender::take_recovered`] so a supervisor can resubmit them onto a fresh ring.
+    pub fn retain_entries(mut self) -> Self {
+        self.config.retain = Some(S::clone);
+        self
+    }
+}
@@ -0,0 +1,220 @@
+//! Cooperative cancellation for submissions.
+//!
+//! A `CancellationToken` can be shared between any number of submissions and tasks. Cancelling it resolves every
+//! submission made with [`PRingSender::submit_cancellable`](crate::PRingSender::submit_cancellable) that has not
+//! completed yet with [`RingError::Cancelled`](crate::error::RingError::Cancelled).
+//!
+//! Specific entries can also be cancelled by user data with
+//! [`PRingSender::cancel_batch`](crate::PRingSender::cancel_batch), which reports a [`CancelOutcome`] for each.
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex, Weak},
+};
+
+use crate::registry::lock;
+
+/// What [`PRingSender::cancel_batch`](crate::PRingSender::cancel_batch) did to a single entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CancelOutcome {
+    /// The entry was outstanding and now resolves with `RingError::Cancelled`.
+    Cancelled,
+    /// The entry had already been delivered, or cancelled before.
+    AlreadyCompleted,
+    /// No entry was ever submitted with this user data.
+    Unknown,
+}
+
+/// A hook run when the token is cancelled.
+type Hook = Box<dyn FnOnce() + Send + 'static>;
+
+/// The hooks registered with a token that has not been cancelled yet.
+#[derive(Default)]
+struct Hooks {
+    /// The id handed to the next hook.
+    next_id: u64,
+    /// The hooks still registered, by id, in the order they were registered.
+    registered: BTreeMap<u64, Hook>,
+}
+
+/// A cloneable, thread-safe cancellation signal.
+///
+/// Clones share the same state: cancelling one cancels all of them.
+#[derive(Clone)]
+pub struct CancellationToken {
+    /// Hooks to run on cancellation, or `None` once cancelled.
+    hooks: Arc<Mutex<Option<Hooks>>>,
+}
+
+/// Keeps a hook registered with [`CancellationToken::on_cancel`], removing it from the token when dropped.
+pub(crate) struct HookGuard {
+    /// The token's hooks, which may outlive every clone of the token.
+    hooks: Weak<Mutex<Option<Hooks>>>,
+    /// The hook's id.
+    id: u64,
+}
+
+impl Drop for HookGuard {
+    fn drop(&mut self) {
+        if let Some(hooks) = self.hooks.upgrade() {
+            // Dropped outside the lock, in case the hook owns anything that touches the token.
+            let hook = lock(&hooks)
+                .as_mut()
+                .and_then(|hooks| hooks.registered.remove(&self.id));
+            drop(hook);
+        }
+    }
+}
+
+impl Default for CancellationToken {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CancellationToken {
+    /// Creates a token that has not been cancelled.
+    pub fn new() -> Self {
+        Self {
+            hooks: Arc::new(Mutex::new(Some(Hooks::default()))),
+        }
+    }
+
+    /// Cancels the token. Only the first call has any effect.
+    pub fn cancel(&self) {
+        let hooks = lock(&self.hooks).take();
+
+        // Hooks run outside the lock so they are free to touch the token.
+        hooks
+            .into_iter()
+            .flat_map(|hooks| hooks.registered.into_values())
+            .for_each(|hook| hook());
+    }
+
+    /// Returns whether the token has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        lock(&self.hooks).is_none()
+    }
+
+    /// Runs `hook` on cancellation, or immediately if the token is already cancelled.
+    ///
+    /// The hook stays registered until the returned guard is dropped, so a hook for work that has finished can be
+    /// removed instead of piling up on a long-lived token. Returns `None` if the hook already ran.
+    pub(crate) fn on_cancel(&self, hook: Hook) -> Option<HookGuard> {
+        let mut hooks = lock(&self.hooks);
+        match hooks.as_mut() {
+            Some(hooks) => {
+                let id = hooks.next_id;
+                hooks.next_id += 1;
+                hooks.registered.insert(id, hook);
+
+                Some(HookGuard {
+                    hooks: Arc::downgrade(&self.hooks),
+                    id,
+                })
+            }
+            None => {
+                drop(hooks);
+                hook();
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+    use crate::{
+        PRingSender,
+        driver::StepOutcome,
+        error::RingError,
+        test_util::{MockEntry, MockRing},
+        traits::UserData,
+    };
+
+    #[test]
+    fn entries_are_not_submitted_under_a_cancelled_token() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder().build_driver(ring.clone());
+        let token = CancellationToken::new();
+        token.cancel();
+
+        let submission = sender.submit_cancellable(MockEntry::new(1), &token);
+        assert_eq!(submission.try_take(), Some(Err(RingError::Cancelled)));
+        assert_eq!(driver.try_step(), StepOutcome::Idle);
+        assert!(ring.in_flight().is_empty());
+    }
+
+    #[test]
+    fn cancelling_resolves_submitted_entries() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder().build_driver(ring.clone());
+        let token = CancellationToken::new();
+
+        let submission = sender.submit_cancellable(MockEntry::new(1), &token);
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        assert_eq!(ring.in_flight(), vec![submission.user_data()]);
+
+        token.cancel();
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        assert_eq!(submission.wait(), Err(RingError::Cancelled));
+    }
+
+    #[test]
+    fn hooks_are_forgotten_once_their_entry_resolves() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder().build_driver(ring.clone());
+        let token = CancellationToken::new();
+
+        let submission = sender.submit_cancellable(MockEntry::new(1), &token);
+        assert_eq!(lock(&token.hooks).as_ref().unwrap().registered.len(), 1);
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        assert_eq!(ring.complete_all(), 1);
+        sender.reap();
+        assert_eq!(driver.step(), StepOutcome::Handled);
+
+        assert_eq!(submission.wait().unwrap().tag(), 1);
+        assert!(lock(&token.hooks).as_ref().unwrap().registered.is_empty());
+    }
+
+    #[test]
+    fn cancel_batch_reports_what_happened_to_each_entry() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder().build_driver(ring.clone());
+
+        let done = sender.submit(MockEntry::new(1));
+        let pending = sender.submit(MockEntry::new(2));
+        while driver.try_step() == StepOutcome::Handled {}
+        assert!(ring.complete_ud(done.user_data()));
+
+        // The reap before the batch catches the completed entry.
+        let user_data = [
+            done.user_data(),
+            pending.user_data(),
+            UserData::new(1 << 40),
+        ];
+        let canceller = sender.clone();
+        let batch = thread::spawn(move || canceller.cancel_batch(user_data));
+        while driver.step() != StepOutcome::Handled {}
+
+        assert_eq!(
+            batch.join().unwrap().unwrap(),
+            [
+                CancelOutcome::AlreadyCompleted,
+                CancelOutcome::Cancelled,
+                CancelOutcome::Unknown
+            ]
+        );
+        assert_eq!(done.wait().unwrap().tag(), 1);
+        assert_eq!(pending.wait().unwrap_err(), RingError::Cancelled);
+
+        drop(driver);
+        assert_eq!(
+            sender.cancel_batch([UserData::new(0)]).unwrap_err(),
+            RingError::ThreadGone
+        );
+    }
+}
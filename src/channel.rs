@@ -0,0 +1,396 @@
+//! The channel carrying signals from senders to the ring thread.
+//!
+//! The channel is either unbounded, or bounded with an optional overflow queue. With an overflow queue, signals that
+//! find the channel full spill into a small lock-protected `VecDeque` instead of blocking the sender. Spilled signals
+//! are moved into the channel by the next send that finds room, or taken straight from the queue by the ring thread
+//! once it has drained the channel.
+//!
+//! Every sender-side critical section ends with an attempt to move spilled signals into the channel, so whenever
+//! the overflow queue is non-empty the channel was full the last time a sender looked. The ring thread can therefore
+//! block on the channel as soon as both it and the queue are empty without leaving spilled signals stranded.
+//!
+//! The channel itself is a [`SignalChannel`] picked at compile time: `std::sync::mpsc` by default, or
+//! `crossbeam-channel` with the `crossbeam` feature.
+
+#[cfg(not(any(feature = "std-channel", feature = "crossbeam")))]
+compile_error!("either the `std-channel` or the `crossbeam` feature must be enabled");
+
+use std::{
+    collections::VecDeque,
+    sync::{
+        Arc, Mutex,
+        mpsc::{RecvError, RecvTimeoutError, SendError, TryRecvError, TrySendError},
+    },
+    time::Duration,
+};
+
+use crate::registry::lock;
+
+/// A channel implementation carrying signals to the ring thread.
+///
+/// Whatever the implementation, failures are reported with the `std::sync::mpsc` error types.
+pub(crate) trait SignalChannel {
+    /// The sending half.
+    type Sender<T>;
+    /// The receiving half.
+    type Receiver<T>;
+
+    /// Creates an unbounded channel.
+    fn unbounded<T>() -> (Self::Sender<T>, Self::Receiver<T>);
+
+    /// Creates a channel holding up to `capacity` items.
+    fn bounded<T>(capacity: usize) -> (Self::Sender<T>, Self::Receiver<T>);
+
+    /// Sends `item`, blocking while a bounded channel is full.
+    fn send<T>(sender: &Self::Sender<T>, item: T) -> Result<(), SendError<T>>;
+
+    /// Sends `item` without blocking.
+    fn try_send<T>(sender: &Self::Sender<T>, item: T) -> Result<(), TrySendError<T>>;
+
+    /// Blocks until an item arrives.
+    fn recv<T>(receiver: &Self::Receiver<T>) -> Result<T, RecvError>;
+
+    /// Blocks until an item arrives or `timeout` elapses.
+    fn recv_timeout<T>(
+        receiver: &Self::Receiver<T>,
+        timeout: Duration,
+    ) -> Result<T, RecvTimeoutError>;
+
+    /// Receives an item without blocking.
+    fn try_recv<T>(receiver: &Self::Receiver<T>) -> Result<T, TryRecvError>;
+}
+
+/// The `std::sync::mpsc` channel.
+#[cfg(not(feature = "crossbeam"))]
+pub(crate) struct StdChannel;
+
+/// The sending half of a `std::sync::mpsc` channel, which has a different type when bounded.
+#[cfg(not(feature = "crossbeam"))]
+pub(crate) enum StdSender<T> {
+    /// An unbounded channel.
+    Unbounded(std::sync::mpsc::Sender<T>),
+    /// A bounded channel.
+    Bounded(std::sync::mpsc::SyncSender<T>),
+}
+
+#[cfg(not(feature = "crossbeam"))]
+impl SignalChannel for StdChannel {
+    type Sender<T> = StdSender<T>;
+    type Receiver<T> = std::sync::mpsc::Receiver<T>;
+
+    fn unbounded<T>() -> (Self::Sender<T>, Self::Receiver<T>) {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        (StdSender::Unbounded(sender), receiver)
+    }
+
+    fn bounded<T>(capacity: usize) -> (Self::Sender<T>, Self::Receiver<T>) {
+        let (sender, receiver) = std::sync::mpsc::sync_channel(capacity);
+        (StdSender::Bounded(sender), receiver)
+    }
+
+    fn send<T>(sender: &Self::Sender<T>, item: T) -> Result<(), SendError<T>> {
+        match sender {
+            StdSender::Unbounded(sender) => sender.send(item),
+            StdSender::Bounded(sender) => sender.send(item),
+        }
+    }
+
+    fn try_send<T>(sender: &Self::Sender<T>, item: T) -> Result<(), TrySendError<T>> {
+        match sender {
+            StdSender::Unbounded(sender) => sender
+                .send(item)
+                .map_err(|SendError(item)| TrySendError::Disconnected(item)),
+            StdSender::Bounded(sender) => sender.try_send(item),
+        }
+    }
+
+    fn recv<T>(receiver: &Self::Receiver<T>) -> Result<T, RecvError> {
+        receiver.recv()
+    }
+
+    fn recv_timeout<T>(
+        receiver: &Self::Receiver<T>,
+        timeout: Duration,
+    ) -> Result<T, RecvTimeoutError> {
+        receiver.recv_timeout(timeout)
+    }
+
+    fn try_recv<T>(receiver: &Self::Receiver<T>) -> Result<T, TryRecvError> {
+        receiver.try_recv()
+    }
+}
+
+/// The `crossbeam-channel` channel.
+#[cfg(feature = "crossbeam")]
+pub(crate) struct CrossbeamChannel;
+
+#[cfg(feature = "crossbeam")]
+impl SignalChannel for CrossbeamChannel {
+    type Sender<T> = crossbeam_channel::Sender<T>;
+    type Receiver<T> = crossbeam_channel::Receiver<T>;
+
+    fn unbounded<T>() -> (Self::Sender<T>, Self::Receiver<T>) {
+        crossbeam_channel::unbounded()
+    }
+
+    fn bounded<T>(capacity: usize) -> (Self::Sender<T>, Self::Receiver<T>) {
+        crossbeam_channel::bounded(capacity)
+    }
+
+    fn send<T>(sender: &Self::Sender<T>, item: T) -> Result<(), SendError<T>> {
+        sender
+            .send(item)
+            .map_err(|crossbeam_channel::SendError(item)| SendError(item))
+    }
+
+    fn try_send<T>(sender: &Self::Sender<T>, item: T) -> Result<(), TrySendError<T>> {
+        sender.try_send(item).map_err(|err| match err {
+            crossbeam_channel::TrySendError::Full(item) => TrySendError::Full(item),
+            crossbeam_channel::TrySendError::Disconnected(item) => TrySendError::Disconnected(item),
+        })
+    }
+
+    fn recv<T>(receiver: &Self::Receiver<T>) -> Result<T, RecvError> {
+        receiver.recv().map_err(|_| RecvError)
+    }
+
+    fn recv_timeout<T>(
+        receiver: &Self::Receiver<T>,
+        timeout: Duration,
+    ) -> Result<T, RecvTimeoutError> {
+        receiver.recv_timeout(timeout).map_err(|err| match err {
+            crossbeam_channel::RecvTimeoutError::Timeout => RecvTimeoutError::Timeout,
+            crossbeam_channel::RecvTimeoutError::Disconnected => RecvTimeoutError::Disconnected,
+        })
+    }
+
+    fn try_recv<T>(receiver: &Self::Receiver<T>) -> Result<T, TryRecvError> {
+        receiver.try_recv().map_err(|err| match err {
+            crossbeam_channel::TryRecvError::Empty => TryRecvError::Empty,
+            crossbeam_channel::TryRecvError::Disconnected => TryRecvError::Disconnected,
+        })
+    }
+}
+
+/// The channel implementation in use.
+#[cfg(not(feature = "crossbeam"))]
+type Backend = StdChannel;
+/// The channel implementation in use.
+#[cfg(feature = "crossbeam")]
+type Backend = CrossbeamChannel;
+
+/// Why a signal could not be sent. Carries the signal back to the caller.
+pub(crate) enum SendFailure<T> {
+    /// The ring thread has hung up.
+    Disconnected(T),
+    /// The channel and the overflow queue are both full.
+    AtCapacity(T),
+}
+
+/// The sending half of the signal channel.
+pub(crate) struct SignalSender<T> {
+    /// The channel to the ring thread.
+    sender: <Backend as SignalChannel>::Sender<T>,
+    /// Where signals spill when a bounded channel is full, if anywhere. Without it, sending blocks instead.
+    overflow: Option<Arc<Overflow<T>>>,
+}
+
+impl<T> SignalSender<T> {
+    /// Sends `item`. `capped` items fail with `AtCapacity` rather than grow the overflow queue past its capacity.
+    pub(crate) fn send(&self, item: T, capped: bool) -> Result<(), SendFailure<T>> {
+        match &self.overflow {
+            None => Backend::send(&self.sender, item)
+                .map_err(|SendError(item)| SendFailure::Disconnected(item)),
+            Some(overflow) => overflow.send(&self.sender, item, capped),
+        }
+    }
+
+    /// Like `send` with `capped` unset, but fails with `AtCapacity` where `send` would block on a full channel.
+    pub(crate) fn try_send(&self, item: T) -> Result<(), SendFailure<T>> {
+        match &self.overflow {
+            None => Backend::try_send(&self.sender, item).map_err(|err| match err {
+                TrySendError::Full(item) => SendFailure::AtCapacity(item),
+                TrySendError::Disconnected(item) => SendFailure::Disconnected(item),
+            }),
+            // Spilling never blocks.
+            Some(overflow) => overflow.send(&self.sender, item, false),
+        }
+    }
+}
+
+/// The receiving half of the signal channel.
+pub(crate) struct SignalReceiver<T>(<Backend as SignalChannel>::Receiver<T>);
+
+impl<T> SignalReceiver<T> {
+    /// Blocks until a signal arrives.
+    pub(crate) fn recv(&self) -> Result<T, RecvError> {
+        Backend::recv(&self.0)
+    }
+
+    /// Blocks until a signal arrives or `timeout` elapses.
+    pub(crate) fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        Backend::recv_timeout(&self.0, timeout)
+    }
+
+    /// Receives a signal without blocking.
+    pub(crate) fn try_recv(&self) -> Result<T, TryRecvError> {
+        Backend::try_recv(&self.0)
+    }
+}
+
+/// Signals that found the bounded channel full.
+pub(crate) struct Overflow<T> {
+    /// Spilled signals, oldest first.
+    queue: Mutex<VecDeque<T>>,
+    /// How many capped signals the queue may hold.
+    capacity: usize,
+}
+
+impl<T> Overflow<T> {
+    /// Sends `item` through `sender`, spilling into the queue if the channel is full.
+    fn send(
+        &self,
+        sender: &<Backend as SignalChannel>::Sender<T>,
+        item: T,
+        capped: bool,
+    ) -> Result<(), SendFailure<T>> {
+        let mut queue = lock(&self.queue);
+
+        // Older spilled signals go first, so the channel stays in order.
+        while let Some(spilled) = queue.pop_front() {
+            match Backend::try_send(sender, spilled) {
+                Ok(()) => {}
+                Err(TrySendError::Full(spilled)) => {
+                    queue.push_front(spilled);
+                    break;
+                }
+                Err(TrySendError::Disconnected(_)) => return Err(SendFailure::Disconnected(item)),
+            }
+        }
+
+        let item = if queue.is_empty() {
+            match Backend::try_send(sender, item) {
+                Ok(()) => return Ok(()),
+                Err(TrySendError::Full(item)) => item,
+                Err(TrySendError::Disconnected(item)) => {
+                    return Err(SendFailure::Disconnected(item));
+                }
+            }
+        } else {
+            item
+        };
+
+        if capped && queue.len() >= self.capacity {
+            return Err(SendFailure::AtCapacity(item));
+        }
+        queue.push_back(item);
+
+        Ok(())
+    }
+
+    /// Receives the next signal from the channel, or failing that from the queue.
+    ///
+    /// Only returns `TryRecvError::Disconnected` once the queue is empty too.
+    pub(crate) fn try_recv(&self, receiver: &SignalReceiver<T>) -> Result<T, TryRecvError> {
+        // Holding the lock keeps senders from moving spilled signals into the channel behind our back.
+        let mut queue = lock(&self.queue);
+
+        receiver
+            .try_recv()
+            .or_else(|err| queue.pop_front().ok_or(err))
+    }
+}
+
+/// Creates the signal channel.
+///
+/// `capacity` bounds the channel, `None` leaving it unbounded. `overflow` is the capacity of the overflow queue, and
+/// only applies to a bounded channel.
+pub(crate) fn signal_channel<T>(
+    capacity: Option<usize>,
+    overflow: Option<usize>,
+) -> (SignalSender<T>, SignalReceiver<T>, Option<Arc<Overflow<T>>>) {
+    let (sender, receiver, overflow) = match capacity {
+        None => {
+            let (sender, receiver) = Backend::unbounded();
+            (sender, receiver, None)
+        }
+        Some(capacity) => {
+            let (sender, receiver) = Backend::bounded(capacity);
+            let overflow = overflow.map(|capacity| {
+                Arc::new(Overflow {
+                    queue: Mutex::new(VecDeque::new()),
+                    capacity,
+                })
+            });
+            (sender, receiver, overflow)
+        }
+    };
+
+    (
+        SignalSender {
+            sender,
+            overflow: overflow.clone(),
+        },
+        SignalReceiver(receiver),
+        overflow,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signals_spill_into_the_overflow_queue_in_order() {
+        let (sender, receiver, overflow) = signal_channel(Some(1), Some(2));
+        let overflow = overflow.unwrap();
+
+        assert!(sender.send(1, true).is_ok());
+        assert!(sender.send(2, true).is_ok());
+        assert!(sender.send(3, true).is_ok());
+        // The queue is full for capped signals, but not for the others.
+        assert!(matches!(
+            sender.send(4, true),
+            Err(SendFailure::AtCapacity(4))
+        ));
+        assert!(sender.try_send(5).is_ok());
+
+        let received: Vec<_> = std::iter::from_fn(|| overflow.try_recv(&receiver).ok()).collect();
+        assert_eq!(received, [1, 2, 3, 5]);
+        assert_eq!(overflow.try_recv(&receiver), Err(TryRecvError::Empty));
+    }
+
+    #[test]
+    fn the_next_send_moves_spilled_signals_into_the_channel() {
+        let (sender, receiver, overflow) = signal_channel(Some(1), Some(4));
+        let overflow = overflow.unwrap();
+
+        assert!(sender.send(1, false).is_ok());
+        assert!(sender.send(2, false).is_ok());
+        assert_eq!(receiver.try_recv(), Ok(1));
+
+        // 2 goes ahead of 3, which spills in its place.
+        assert!(sender.send(3, false).is_ok());
+        assert_eq!(receiver.try_recv(), Ok(2));
+        assert_eq!(receiver.try_recv(), Err(TryRecvError::Empty));
+        assert_eq!(overflow.try_recv(&receiver), Ok(3));
+    }
+
+    #[test]
+    fn a_full_channel_without_overflow_refuses_try_send() {
+        let (sender, receiver, overflow) = signal_channel(Some(1), None);
+        assert!(overflow.is_none());
+
+        assert!(sender.try_send(1).is_ok());
+        assert!(matches!(
+            sender.try_send(2),
+            Err(SendFailure::AtCapacity(2))
+        ));
+
+        drop(receiver);
+        assert!(matches!(
+            sender.send(3, false),
+            Err(SendFailure::Disconnected(3))
+        ));
+    }
+}
@@ -0,0 +1,289 @@
+//! A fixed pool of reusable completion channels.
+//!
+//! Every submission normally allocates a oneshot channel for its completion. With a pool, a fixed number of slots is
+//! allocated up front instead, and each submission borrows one for as long as either end of it is alive. Once
+//! both ends are dropped, the last one resets the slot and hands it back, so steady-state submissions allocate
+//! nothing.
+
+use std::{
+    fmt,
+    sync::{
+        Arc, Condvar, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{
+    error::RingError,
+    registry::{Completion, lock},
+    traits::CompletionQueueEntry as CQE,
+};
+
+/// A reusable completion channel.
+struct PooledSlot<C: CQE> {
+    /// The completion once delivered.
+    state: Mutex<SlotState<C>>,
+    /// Notified once the completion is delivered.
+    delivered: Condvar,
+    /// How many ends of the slot are alive; the last one to go returns the slot to the pool.
+    ends: AtomicUsize,
+}
+
+/// The contents of a pooled slot.
+struct SlotState<C: CQE> {
+    /// The delivered completion, until it is taken.
+    completion: Option<Completion<C>>,
+    /// Whether a completion has been delivered, taken or not.
+    delivered: bool,
+}
+
+/// The free slots of a completion pool.
+pub(crate) struct CompletionPool<C: CQE> {
+    /// Slots not borrowed by any submission.
+    free: Mutex<Vec<Arc<PooledSlot<C>>>>,
+}
+
+impl<C: CQE> CompletionPool<C> {
+    /// Allocates a pool of `size` slots.
+    pub(crate) fn new(size: usize) -> Self {
+        let free = (0..size)
+            .map(|_| {
+                Arc::new(PooledSlot {
+                    state: Mutex::new(SlotState {
+                        completion: None,
+                        delivered: false,
+                    }),
+                    delivered: Condvar::new(),
+                    ends: AtomicUsize::new(0),
+                })
+            })
+            .collect();
+
+        Self {
+            free: Mutex::new(free),
+        }
+    }
+
+    /// Borrows a slot, returning `None` if every slot is in use.
+    pub(crate) fn acquire(self: &Arc<Self>) -> Option<(PooledSender<C>, PooledReceiver<C>)> {
+        let slot = lock(&self.free).pop()?;
+        slot.ends.store(2, Ordering::Relaxed);
+
+        let sender = PooledSender {
+            slot: Arc::clone(&slot),
+            pool: Arc::clone(self),
+            done: false,
+        };
+        let receiver = PooledReceiver {
+            slot,
+            pool: Arc::clone(self),
+        };
+        Some((sender, receiver))
+    }
+
+    /// Drops one end of `slot`, resetting it and taking it back if that was the last one.
+    fn release(&self, slot: &Arc<PooledSlot<C>>) {
+        if slot.ends.fetch_sub(1, Ordering::AcqRel) != 1 {
+            return;
+        }
+
+        {
+            let mut state = lock(&slot.state);
+            state.completion = None;
+            state.delivered = false;
+        }
+        lock(&self.free).push(Arc::clone(slot));
+    }
+}
+
+/// The ring thread's end of a pooled completion channel.
+///
+/// If this is dropped without sending, e.g. because the ring thread exited, the receiver gets
+/// `RingError::Disconnected` instead.
+pub struct PooledSender<C: CQE> {
+    /// The borrowed slot.
+    slot: Arc<PooledSlot<C>>,
+    /// The pool the slot goes back to.
+    pool: Arc<CompletionPool<C>>,
+    /// Whether this end has already been given up.
+    done: bool,
+}
+
+impl<C: CQE> PooledSender<C> {
+    /// Delivers `completion` to the receiver.
+    pub(crate) fn send(mut self, completion: Completion<C>) {
+        self.finish(completion);
+    }
+
+    /// Delivers `completion` and gives up this end of the slot.
+    fn finish(&mut self, completion: Completion<C>) {
+        self.done = true;
+
+        let last = {
+            let mut state = lock(&self.slot.state);
+            state.completion = Some(completion);
+            state.delivered = true;
+
+            // Give up this end while still holding the lock, so a receiver that sees the completion is sure to be
+            // the one returning the slot, and a freshly woken waiter can reuse it straight away.
+            let last = self.slot.ends.fetch_sub(1, Ordering::AcqRel) == 1;
+            if last {
+                state.completion = None;
+                state.delivered = false;
+            }
+            last
+        };
+
+        if last {
+            // The vector was allocated with room for every slot, so this never reallocates.
+            lock(&self.pool.free).push(Arc::clone(&self.slot));
+        } else {
+            self.slot.delivered.notify_all();
+        }
+    }
+}
+
+impl<C: CQE> Drop for PooledSender<C> {
+    fn drop(&mut self) {
+        if !self.done {
+            self.finish(Err(RingError::Disconnected));
+        }
+    }
+}
+
+/// The submitter's end of a pooled completion channel.
+pub(crate) struct PooledReceiver<C: CQE> {
+    /// The borrowed slot.
+    slot: Arc<PooledSlot<C>>,
+    /// The pool the slot goes back to.
+    pool: Arc<CompletionPool<C>>,
+}
+
+impl<C: CQE> fmt::Debug for PooledReceiver<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PooledReceiver").finish_non_exhaustive()
+    }
+}
+
+impl<C: CQE> PooledReceiver<C> {
+    /// Takes the delivered completion. A completion already taken reads as `RingError::Disconnected`.
+    fn take(state: &mut SlotState<C>) -> Completion<C> {
+        state
+            .completion
+            .take()
+            .unwrap_or(Err(RingError::Disconnected))
+    }
+
+    /// Blocks until the completion arrives.
+    pub(crate) fn recv(&self) -> Completion<C> {
+        let mut state = lock(&self.slot.state);
+        while !state.delivered {
+            state = self
+                .slot
+                .delivered
+                .wait(state)
+                .unwrap_or_else(|err| err.into_inner());
+        }
+
+        Self::take(&mut state)
+    }
+
+    /// Blocks until the completion arrives or `deadline` passes.
+    pub(crate) fn recv_deadline(&self, deadline: Instant) -> Completion<C> {
+        let mut state = lock(&self.slot.state);
+        while !state.delivered {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            if timeout.is_zero() {
+                return Err(RingError::Timeout);
+            }
+
+            state = self
+                .slot
+                .delivered
+                .wait_timeout(state, timeout)
+                .unwrap_or_else(|err| err.into_inner())
+                .0;
+        }
+
+        Self::take(&mut state)
+    }
+
+    /// Blocks until the completion arrives or `timeout` elapses.
+    pub(crate) fn recv_timeout(&self, timeout: Duration) -> Completion<C> {
+        match Instant::now().checked_add(timeout) {
+            Some(deadline) => self.recv_deadline(deadline),
+            None => self.recv(),
+        }
+    }
+
+    /// Takes the completion if it has arrived.
+    pub(crate) fn try_recv(&self) -> Option<Completion<C>> {
+        let mut state = lock(&self.slot.state);
+
+        state.delivered.then(|| Self::take(&mut state))
+    }
+}
+
+impl<C: CQE> Drop for PooledReceiver<C> {
+    fn drop(&mut self) {
+        self.pool.release(&self.slot);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        PRingSender,
+        driver::StepOutcome,
+        test_util::{MockEntry, MockRing},
+    };
+
+    #[test]
+    fn slots_are_reused_once_both_ends_are_done() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder()
+            .completion_pool(1)
+            .build_driver(ring.clone());
+
+        let first = sender.submit(MockEntry::new(1));
+        // Every slot is borrowed, so this one never reaches the ring.
+        let refused = sender.submit(MockEntry::new(2));
+        assert_eq!(refused.wait().unwrap_err(), RingError::AtCapacity);
+
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        assert_eq!(ring.in_flight().len(), 1);
+        ring.complete_all();
+        sender.reap();
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        assert_eq!(first.wait().unwrap().tag(), 1);
+
+        let second = sender.submit(MockEntry::new(3));
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        ring.complete_all();
+        sender.reap();
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        assert_eq!(second.wait().unwrap().tag(), 3);
+    }
+
+    #[test]
+    fn a_dropped_sender_disconnects_the_receiver() {
+        let pool = Arc::new(CompletionPool::<MockEntry>::new(1));
+
+        let (tx, rx) = pool.acquire().unwrap();
+        assert!(pool.acquire().is_none());
+        assert!(rx.try_recv().is_none());
+        assert_eq!(
+            rx.recv_timeout(Duration::from_millis(1)).unwrap_err(),
+            RingError::Timeout
+        );
+
+        drop(tx);
+        assert_eq!(rx.recv().unwrap_err(), RingError::Disconnected);
+        // Still borrowed by the receiver.
+        assert!(pool.acquire().is_none());
+        drop(rx);
+        assert!(pool.acquire().is_some());
+    }
+}
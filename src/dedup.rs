@@ -0,0 +1,246 @@
+//! Request deduplication: identical operations requested concurrently share one submission.
+//!
+//! A [`SingleFlight`] sits in front of a ring and remembers the entries it has in flight by a dedup key the caller
+//! picks, e.g. the block a read targets. Asking for a key already in flight piggybacks on that entry instead of
+//! submitting another: every request for it resolves with its own clone of the one completion. Once the entry
+//! completes, the key is forgotten and the next request for it is submitted afresh, so this saves redundant I/O for
+//! hot keys without ever caching a stale result.
+//!
+//! ```no_run
+//! # #[cfg(feature = "test-util")]
+//! # fn main() -> Result<(), ring_promise::error::RingError> {
+//! # use ring_promise::{PRingSender, dedup::SingleFlight, test_util::{MockEntry, MockRing}};
+//! # let sender = PRingSender::new(MockRing::<MockEntry, MockEntry>::echo());
+//! # let (fd, buf, offset) = (3, [0u8; 4096], 8192);
+//! # let read = |_fd: i32, _buf: [u8; 4096], offset: u64| MockEntry::new(offset);
+//! let reads = SingleFlight::new(sender);
+//! // Concurrent readers of the same block share one read.
+//! let block = reads.submit(read(fd, buf, offset), offset).wait_nopanic()?;
+//! # Ok(())
+//! # }
+//! # #[cfg(not(feature = "test-util"))]
+//! # fn main() {}
+//! ```
+
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    sync::{Arc, Mutex},
+};
+
+use promisery::Promise;
+
+use crate::{
+    PRingSender,
+    error::RingError,
+    flags::SubmitFlags,
+    registry::{Completer, Completion, lock},
+    submission::{Fanout, FanoutDelivery, SubscriptionHandle},
+    traits::{CompletionQueueEntry as CQE, SubmissionQueueEntry as SQE, UserData},
+};
+
+/// The entries in flight, by dedup key.
+type InFlight<K, C> = Arc<Mutex<HashMap<K, SubscriptionHandle<C>>>>;
+
+/// Deduplicates concurrent requests for the same operation into a single submission.
+///
+/// Clones share the same keys in flight. Keys are only remembered while their entry is in flight; a `SingleFlight` does
+/// not cache completions.
+pub struct SingleFlight<S: SQE, C: CQE + Clone, K> {
+    /// Submits the entries.
+    sender: PRingSender<S, C>,
+    /// The entries in flight, by dedup key.
+    in_flight: InFlight<K, C>,
+}
+
+impl<S: SQE, C: CQE + Clone, K> Clone for SingleFlight<S, C, K> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+            in_flight: Arc::clone(&self.in_flight),
+        }
+    }
+}
+
+impl<S, C, K> SingleFlight<S, C, K>
+where
+    S: SQE,
+    C: CQE + Clone + 'static,
+    K: Hash + Eq + Clone + Send + 'static,
+{
+    /// Deduplicates requests submitted through `sender`.
+    pub fn new(sender: PRingSender<S, C>) -> Self {
+        Self {
+            sender,
+            in_flight: Arc::default(),
+        }
+    }
+
+    /// Submits `entry` unless an entry with the same `dedup_key` is already in flight, in which case the request
+    /// piggybacks on that one. Returns a promise for a clone of the completion either way.
+    ///
+    /// `entry` is dropped unsubmitted when piggybacking, so only requests for the same operation should share a key.
+    /// A request arriving just as the entry for its key completes either piggybacks and gets that completion, or finds
+    /// the key forgotten and submits anew; it never waits on an entry that has already completed.
+    ///
+    /// # Arguments
+    /// * `entry` - The submission queue entry to submit.
+    /// * `dedup_key` - Identifies the operation; requests with equal keys share one submission while it is in flight.
+    pub fn submit(&self, entry: S, dedup_key: K) -> Promise<C, RingError> {
+        let mut in_flight = lock(&self.in_flight);
+        if let Some(shared) = in_flight.get(&dedup_key) {
+            return shared.subscribe().into_promise();
+        }
+
+        let fanout = Arc::new(Fanout::new());
+        let ud = self.sender.next_ud();
+        let handle = SubscriptionHandle::new(ud, Arc::clone(&fanout));
+        let submission = handle.subscribe();
+        in_flight.insert(dedup_key.clone(), handle);
+        // Released before sending, which may block until the ring thread has room, and so until it has run the
+        // delivery of another entry, which needs the lock.
+        drop(in_flight);
+
+        let delivery = SharedDelivery {
+            key: Some(dedup_key),
+            user_data: ud,
+            in_flight: Arc::clone(&self.in_flight),
+            fanout: FanoutDelivery(fanout),
+        };
+        self.sender.enqueue_as(
+            ud,
+            entry,
+            Completer::Callback(Box::new(move |completion| delivery.deliver(completion))),
+            SubmitFlags::empty(),
+        );
+
+        submission.into_promise()
+    }
+
+    /// The number of dedup keys with an entry in flight.
+    #[inline]
+    pub fn in_flight(&self) -> usize {
+        lock(&self.in_flight).len()
+    }
+
+    /// The sender the entries are submitted with.
+    #[inline]
+    pub fn sender(&self) -> &PRingSender<S, C> {
+        &self.sender
+    }
+}
+
+/// Delivers a shared entry's completion to every request sharing it, forgetting its key first.
+///
+/// The key is forgotten before the completion goes out, so a request finding the key in flight is sure to be among
+/// those the completion reaches. If the ring drops this without delivering, e.g. because the ring thread exited, the
+/// key is forgotten all the same and the requests resolve with `RingError::Disconnected`.
+struct SharedDelivery<C: CQE + Clone, K: Hash + Eq> {
+    /// The entry's dedup key, or `None` once forgotten.
+    key: Option<K>,
+    /// The user data of the entry, telling it apart from a later entry for the same key.
+    user_data: UserData,
+    /// The entries in flight, by dedup key.
+    in_flight: InFlight<K, C>,
+    /// The requests sharing the completion.
+    fanout: FanoutDelivery<C>,
+}
+
+impl<C: CQE + Clone, K: Hash + Eq> SharedDelivery<C, K> {
+    /// Sends a clone of `completion` to every request sharing the entry.
+    fn deliver(mut self, completion: Completion<C>) {
+        self.forget();
+        self.fanout.0.deliver(completion);
+    }
+
+    /// Removes the entry's key from the keys in flight, unless already done.
+    fn forget(&mut self) {
+        let Some(key) = self.key.take() else {
+            return;
+        };
+
+        let mut in_flight = lock(&self.in_flight);
+        if in_flight
+            .get(&key)
+            .is_some_and(|shared| shared.user_data() == self.user_data)
+        {
+            in_flight.remove(&key);
+        }
+    }
+}
+
+impl<C: CQE + Clone, K: Hash + Eq> Drop for SharedDelivery<C, K> {
+    fn drop(&mut self) {
+        // The fan-out resolves the requests with `RingError::Disconnected` once dropped, after this.
+        self.forget();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        driver::StepOutcome,
+        test_util::{MockEntry, MockRing},
+    };
+
+    #[test]
+    fn identical_concurrent_requests_share_one_submission() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder().build_driver(ring.clone());
+        let reads = SingleFlight::new(sender.clone());
+
+        let first = reads.submit(MockEntry::new(1), "block");
+        let second = reads.submit(MockEntry::new(2), "block");
+        assert_eq!(reads.in_flight(), 1);
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        assert_eq!(driver.try_step(), StepOutcome::Idle);
+        assert_eq!(ring.in_flight().len(), 1);
+
+        assert_eq!(ring.complete_all(), 1);
+        sender.reap();
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        assert_eq!(first.wait_nopanic().unwrap().tag(), 1);
+        assert_eq!(second.wait_nopanic().unwrap().tag(), 1);
+    }
+
+    #[test]
+    fn keys_are_forgotten_once_delivered() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder().build_driver(ring.clone());
+        let reads = SingleFlight::new(sender.clone());
+
+        let first = reads.submit(MockEntry::new(1), "block");
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        assert_eq!(ring.complete_all(), 1);
+        sender.reap();
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        assert_eq!(first.wait_nopanic().unwrap().tag(), 1);
+        assert_eq!(reads.in_flight(), 0);
+
+        // Submitted afresh rather than handed the stale completion.
+        let second = reads.submit(MockEntry::new(2), "block");
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        assert_eq!(ring.in_flight().len(), 1);
+        assert_eq!(ring.complete_all(), 1);
+        sender.reap();
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        assert_eq!(second.wait_nopanic().unwrap().tag(), 2);
+    }
+
+    #[test]
+    fn keys_are_forgotten_once_rejected() {
+        let (sender, driver) = PRingSender::builder().build_driver(MockRing::echo());
+        let reads = SingleFlight::new(sender);
+        drop(driver);
+
+        let first = reads.submit(MockEntry::new(1), "block");
+        assert_eq!(reads.in_flight(), 0);
+        assert_eq!(first.wait_nopanic().unwrap_err(), RingError::ThreadGone);
+
+        // Not piggybacking on the rejected entry.
+        let second = reads.submit(MockEntry::new(2), "block");
+        assert_eq!(reads.in_flight(), 0);
+        assert_eq!(second.wait_nopanic().unwrap_err(), RingError::ThreadGone);
+    }
+}
@@ -0,0 +1,2522 @@
+//! The ring thread's event loop, one step at a time.
+//!
+//! A `RingDriver` owns the ring, the registry and the receiving end of the signal channel. The ring thread spawned by
+//! [`PRingBuilder::build`] simply [`run`](RingDriver::run)s one until every sender is gone. A driver obtained from
+//! [`PRingBuilder::build_driver`] can instead be stepped by hand, e.g. to assert on the registry between signals in a
+//! test without racing a background thread.
+//!
+//! Under [`PRingBuilder::split_threads`], running a driver also spawns a reaper thread that owns the ring's
+//! completion side. The registry and the rest of the state needed to deliver completions are shared with it behind a
+//! mutex, so the driver only submits.
+
+use std::{
+    any::Any,
+    cell::Cell,
+    cmp::Reverse,
+    collections::{HashMap, HashSet, VecDeque},
+    hint,
+    marker::PhantomData,
+    ops::Deref,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        Arc, Mutex, MutexGuard,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{RecvTimeoutError, TryRecvError},
+    },
+    thread::{self, Thread},
+    time::{Duration, Instant},
+};
+
+#[cfg(doc)]
+use crate::builder::PRingBuilder;
+use crate::{
+    RingSignal, Signal,
+    builder::{Config, ErrorSink, OrphanSink},
+    cancel::CancelOutcome,
+    channel::{Overflow, SignalReceiver},
+    dump::RingDump,
+    error::{BackendError, RingError},
+    metrics::Metrics,
+    multiplex::Turn,
+    pool::CallbackPool,
+    reap::{ReapReason, RingThreadCtx},
+    registry::{Completer, Origin, PendingSet, Recovered, Registry, lock},
+    retry::Retry,
+    stats::RingStats,
+    stream::StreamSender,
+    traits::{
+        CompletionHalf, CompletionQueue, CompletionQueueEntry as CQE, FullRing, SubmissionQueue,
+        SubmissionQueueEntry as SQE, UserData,
+    },
+};
+
+/// What a single [`RingDriver::step`] did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepOutcome {
+    /// A signal was received and handled.
+    Handled,
+    /// The oldest deferred entry waited long enough, so the deferred entries were submitted and the CQ reaped.
+    Deadline,
+    /// No signal was ready and nothing was due, so the CQ was reaped instead.
+    ///
+    /// Returned by [`RingDriver::try_step`] whenever the channel is empty, and by [`RingDriver::step`] when a blocking
+    /// receive times out before anything is due, e.g. on a spurious wakeup.
+    Idle,
+    /// Every sender has been dropped and every signal has been handled.
+    Disconnected,
+}
+
+/// Drives a ring: receives signals, pushes and submits entries, and delivers completions.
+pub struct RingDriver<S, C, SQ, CQ, Ring>
+where
+    S: SQE,
+    C: CQE,
+{
+    /// The ring being driven.
+    ring: Ring,
+    /// Receives signals from the senders.
+    receiver: SignalReceiver<RingSignal<S, C>>,
+    /// Signals spilled from a full bounded channel, if the channel has an overflow queue.
+    overflow: Option<Arc<Overflow<RingSignal<S, C>>>>,
+    /// Where completions are delivered, shared with the reaper thread if there is one.
+    delivery: Arc<Mutex<Delivery<C, S>>>,
+    /// The reaper thread, while one reaps on the driver's behalf.
+    reaper: Option<Thread>,
+    /// The configuration the driver was built with.
+    config: Config<S, C>,
+    /// Entries to accumulate before submitting; 1 for synchronous backends.
+    submit_threshold: usize,
+    /// Entries pushed onto the SQ but not yet submitted.
+    deferred: usize,
+    /// When the oldest deferred entry was pushed.
+    deferred_since: Instant,
+    /// Entries timed out by the driver rather than the backend, with when they expire and their user data.
+    deadlines: Vec<(Instant, u64)>,
+    /// Entries held back until their gate opens, in arrival order, by gate id.
+    gated: HashMap<u64, Vec<S>>,
+    /// Entries pushed since the last submit, by user data, to be stamped with when the backend was handed them. Only
+    /// tracked with metrics enabled.
+    unsubmitted: Vec<u64>,
+    /// Counters shared with the senders.
+    stats: Arc<RingStats>,
+    /// When the last SQ-full warning was logged.
+    #[cfg(feature = "logging")]
+    last_sq_full_warning: Option<Instant>,
+    /// Where outstanding state is left if the driver panics while running.
+    recovered: Arc<Mutex<Option<Recovered<S>>>>,
+    /// The fence holding signals back, if one has yet to lift.
+    fence: Option<Fence<S, C>>,
+    /// The ring's overflow count as of the last check.
+    overflowed: u64,
+    /// Whether a reap was skipped for the cooldown, and is owed once it is over.
+    reap_postponed: bool,
+    /// Times an entry found the SQ full.
+    sq_full_retries: u64,
+    _marker: PhantomData<fn() -> (SQ, CQ)>,
+}
+
+/// Signals held back until the entries outstanding when a `Signal::Fence` arrived have been delivered.
+struct Fence<S, C: CQE> {
+    /// Fires once those entries have been delivered.
+    lifted: oneshot::Receiver<()>,
+    /// Signals received since the fence, in arrival order.
+    held: VecDeque<RingSignal<S, C>>,
+}
+
+/// Completions taken off a backend's CQ, containing a panic of its iterator rather than letting it unwind the ring.
+///
+/// A panic ends the iteration, since the iterator may be left in any state, and whatever the CQ still holds is left
+/// to the next reap.
+struct Contained<'a, I> {
+    /// The backend's completions.
+    cqes: I,
+    /// Where contained panics are counted.
+    errored: &'a Cell<u64>,
+    /// Whether the iterator panicked.
+    done: bool,
+}
+
+impl<I: Iterator> Iterator for Contained<'_, I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        contain(|| self.cqes.next(), self.errored, "completion iterator").unwrap_or_else(|| {
+            self.done = true;
+            None
+        })
+    }
+}
+
+/// Runs `f`, containing a panic by counting it in `errored` and returning `None`. `what` names the panicking code in
+/// the log.
+fn contain<R>(f: impl FnOnce() -> R, errored: &Cell<u64>, what: &str) -> Option<R> {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(result) => Some(result),
+        Err(payload) => {
+            errored.set(errored.get() + 1);
+
+            #[cfg(feature = "logging")]
+            {
+                let message = payload
+                    .downcast_ref::<&str>()
+                    .copied()
+                    .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+                    .unwrap_or("a non-string payload");
+                log::error!("contained a panic in the backend's {what}: {message}");
+            }
+            #[cfg(not(feature = "logging"))]
+            let _ = (what, payload);
+
+            None
+        }
+    }
+}
+
+/// Everything needed to deliver reaped completions.
+///
+/// Shared between the ring thread and the reaper thread under [`split_threads`](PRingBuilder::split_threads).
+struct Delivery<C: CQE, S> {
+    /// Where completions are delivered.
+    registry: Registry<C, S>,
+    /// User data of linked timeout entries whose completions are yet to be reaped.
+    linked_timeouts: HashSet<u64>,
+    /// User data of outstanding entries whose routing key differs from it, by key.
+    keys: HashMap<u64, u64>,
+    /// User data of the most recent completions, oldest first, holding up to `dedup_window` values.
+    recent: VecDeque<u64>,
+    /// How many recently completed user data values to remember.
+    dedup_window: usize,
+    /// Whether each reap delivers its completions by priority rather than in CQ order.
+    prioritized: bool,
+    /// Whether each reap delivers its completions oldest submission first rather than in CQ order.
+    oldest_first: bool,
+    /// Whether each reap delivers its completions newest submission first rather than in CQ order.
+    newest_first: bool,
+    /// Most completions a single reap delivers, or `None` for no limit.
+    max_reap_burst: Option<usize>,
+    /// Completions taken off the CQ but held back by a reap that hit `max_reap_burst`, oldest first.
+    held_back: VecDeque<C>,
+    /// Receives completions that no entry is waiting for, or `None` to drop them.
+    orphan_sink: Option<OrphanSink<C>>,
+    /// Streams completions that no entry is waiting for when there is no orphan sink.
+    orphan_stream: Option<StreamSender<C>>,
+    /// Receives completions reporting an error, or `None` to only deliver them to their entries.
+    error_sink: Option<ErrorSink<C>>,
+    /// Where delivered completions are reported, or `None` to not report them.
+    metrics: Option<Metrics>,
+    /// Whether a panic in the backend's completion handling is contained to the completion rather than unwinding.
+    contain_panics: bool,
+    /// Entries waiting to be resubmitted, with when they are due and their user data.
+    retries: Vec<(Instant, u64, S)>,
+    /// Entries pushed since the last reap.
+    submitted_since_reap: usize,
+    /// Entries submitted whose completions have not been reaped yet, as far as the ring thread can tell.
+    unreaped: usize,
+    /// Completions delivered by the last reap.
+    last_reaped: usize,
+    /// Completions delivered so far.
+    completed: u64,
+    /// When the last reap happened.
+    last_reap: Instant,
+    /// Counters shared with the senders.
+    stats: Arc<RingStats>,
+}
+
+impl<C: CQE, S> Delivery<C, S> {
+    /// Delivers every completion in `cqes`, routing them by `key` and interpreting them with `interpret`.
+    ///
+    /// `cancelled` tells which entry a completion reports as cancelled, if any. Returns how many completions there
+    /// were, delivered or not.
+    ///
+    /// With a `max_reap_burst`, completions past the limit are held back and delivered first by the next reap.
+    fn reap(
+        &mut self,
+        cqes: impl IntoIterator<Item = C>,
+        key: impl Fn(&C) -> u64,
+        interpret: impl Fn(C) -> Result<C, BackendError>,
+        cancelled: impl Fn(&C) -> Option<u64>,
+    ) -> usize {
+        if !self.contain_panics {
+            return self.reap_from(cqes, key, interpret, cancelled);
+        }
+
+        let errored = Cell::new(0);
+        let cqes = Contained {
+            cqes: cqes.into_iter(),
+            errored: &errored,
+            done: false,
+        };
+        let interpret = |cqe| {
+            contain(|| interpret(cqe), &errored, "interpret").unwrap_or_else(|| {
+                Err(BackendError::Other(
+                    "the backend panicked interpreting the completion".to_owned(),
+                ))
+            })
+        };
+        let seen = self.reap_from(cqes, key, interpret, cancelled);
+
+        if errored.get() > 0 {
+            self.stats
+                .record_error(RingError::Backend(BackendError::Other(format!(
+                    "contained {} panics in the backend's completion handling",
+                    errored.get()
+                ))));
+            self.stats.completions_errored(errored.get());
+            if let Some(metrics) = &self.metrics {
+                metrics.completions_errored(errored.get());
+            }
+        }
+
+        seen
+    }
+
+    /// Does the work of [`reap`](Self::reap), letting any panic in the backend's completion handling unwind.
+    fn reap_from(
+        &mut self,
+        cqes: impl IntoIterator<Item = C>,
+        key: impl Fn(&C) -> u64,
+        interpret: impl Fn(C) -> Result<C, BackendError>,
+        cancelled: impl Fn(&C) -> Option<u64>,
+    ) -> usize {
+        let (seen, taken) = if let Some(burst) = self.max_reap_burst {
+            // Everything is taken off the CQ regardless, so it cannot overflow while completions are held back.
+            let taken = if self.reorders() {
+                let mut cqes: Vec<C> = cqes.into_iter().collect();
+                // Completions held back by earlier reaps stay ahead, ordering only ever applies within one reap.
+                self.order(&mut cqes, &key);
+                let taken = cqes.len();
+                self.held_back.extend(cqes);
+                taken
+            } else {
+                let held = self.held_back.len();
+                self.held_back.extend(cqes);
+                self.held_back.len() - held
+            };
+            let burst = burst.min(self.held_back.len());
+            let cqes: Vec<C> = self.held_back.drain(..burst).collect();
+
+            (self.deliver(cqes, key, interpret, cancelled), taken)
+        } else if self.reorders() {
+            let mut cqes: Vec<C> = cqes.into_iter().collect();
+            self.order(&mut cqes, &key);
+            let seen = self.deliver(cqes, key, interpret, cancelled);
+            (seen, seen)
+        } else {
+            let seen = self.deliver(cqes, key, interpret, cancelled);
+            (seen, seen)
+        };
+
+        // Entries completing more than once would otherwise take it below zero.
+        self.unreaped = self.unreaped.saturating_sub(taken);
+
+        seen
+    }
+
+    /// Whether reaps deliver their completions in another order than the CQ's.
+    fn reorders(&self) -> bool {
+        self.prioritized || self.oldest_first || self.newest_first
+    }
+
+    /// Sorts `cqes` into delivery order: highest priority first if prioritized, then oldest or newest submission first
+    /// if delivering by age.
+    fn order(&self, cqes: &mut [C], key: impl Fn(&C) -> u64) {
+        let origins = self.registry.origins();
+        // Stable, so completions that compare equal keep their CQ order.
+        cqes.sort_by_cached_key(|cqe| {
+            let ud = self.route(cqe, &key).1;
+            let priority = if self.prioritized {
+                self.registry.priority(ud.into())
+            } else {
+                0
+            };
+            // Completions nobody is waiting for have no submission time, and go first to be done with quickly.
+            let since = if self.oldest_first || self.newest_first {
+                origins.get(&ud).map(Origin::since)
+            } else {
+                None
+            };
+            let (older, newer) = if self.newest_first {
+                (None, since.map(Reverse))
+            } else {
+                (since, None)
+            };
+
+            (Reverse(priority), older, newer)
+        });
+    }
+
+    /// The routing key of a completion and the user data of the entry it belongs to.
+    fn route(&self, cqe: &C, key: impl Fn(&C) -> u64) -> (u64, u64) {
+        let key = key(cqe);
+        let ud = if self.keys.is_empty() {
+            key
+        } else {
+            self.keys.get(&key).copied().unwrap_or(key)
+        };
+
+        (key, ud)
+    }
+
+    /// Delivers `cqes`, in order, as the completions of a reap. Returns how many there were.
+    fn deliver(
+        &mut self,
+        cqes: impl IntoIterator<Item = C>,
+        key: impl Fn(&C) -> u64,
+        interpret: impl Fn(C) -> Result<C, BackendError>,
+        cancelled: impl Fn(&C) -> Option<u64>,
+    ) -> usize {
+        let mut seen = 0;
+        let mut reaped = 0;
+
+        for cqe in cqes {
+            seen += 1;
+
+            // The cancelled entry's own completion may never come, or only after this one; either way it is late.
+            if let Some(target) = cancelled(&cqe)
+                && self.registry.cancel(target.into())
+            {
+                reaped += 1;
+                self.remember(target);
+            }
+
+            // The key has to be read up front, `interpret` may turn the entry into an error.
+            let (key, ud) = self.route(&cqe, &key);
+            // A linked timeout's own completion only says whether it fired; the entry it timed out reports that.
+            if !self.linked_timeouts.is_empty() && self.linked_timeouts.remove(&ud) {
+                self.keys.remove(&key);
+                self.registry.unreserve(ud.into());
+                continue;
+            }
+
+            if !self.registry.contains(ud.into()) {
+                self.keys.remove(&key);
+                self.registry.release(ud.into());
+
+                if self.recent.contains(&ud) {
+                    // A benign repeat rather than a completion nobody asked for.
+                    self.stats.duplicate_completion();
+                } else if let Some(sink) = &mut self.orphan_sink {
+                    sink(cqe);
+                } else if let Some(stream) = &self.orphan_stream {
+                    stream.send(CQE::get_ud(&cqe), Ok(cqe));
+                }
+                continue;
+            }
+
+            let completion = match &mut self.error_sink {
+                Some(ErrorSink::Divert(sink)) if cqe.is_error() => {
+                    sink(cqe);
+                    Err(RingError::Diverted)
+                }
+                Some(ErrorSink::Copy(sink, copy)) if cqe.is_error() => {
+                    sink(copy(&cqe));
+                    interpret(cqe).map_err(RingError::Backend)
+                }
+                _ => interpret(cqe).map_err(RingError::Backend),
+            };
+            if let Err(err @ RingError::Backend(_)) = &completion {
+                self.stats.record_error(err.clone());
+            }
+            // Read before resolving, which forgets when the entry was submitted.
+            let timing = self
+                .metrics
+                .as_ref()
+                .and_then(|_| self.registry.origins().get(&ud).map(Origin::timing));
+
+            if let Err(RingError::Backend(err)) = &completion
+                && let Some((entry, delay)) = self.registry.retry(ud.into(), err)
+            {
+                // Pushed by the driver rather than here, since pushing may have to reap.
+                self.retries.push((Instant::now() + delay, ud, entry));
+                continue;
+            }
+
+            if self.registry.resolve(ud.into(), completion) {
+                reaped += 1;
+                self.remember(ud);
+
+                if let Some(metrics) = &self.metrics {
+                    metrics.completed(timing);
+                    metrics.in_flight(self.registry.len());
+                }
+            }
+
+            // Entries expecting several completions keep routing by their key until the last one.
+            if !self.keys.is_empty() && !self.registry.contains(ud.into()) {
+                self.keys.remove(&key);
+            }
+        }
+
+        self.submitted_since_reap = 0;
+        self.last_reaped = reaped;
+        self.completed += reaped as u64;
+        self.last_reap = Instant::now();
+
+        seen
+    }
+
+    /// Records `ud` as recently completed, forgetting the oldest completion once the window is full.
+    fn remember(&mut self, ud: u64) {
+        if self.dedup_window == 0 {
+            return;
+        }
+        if self.recent.len() == self.dedup_window {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(ud);
+    }
+}
+
+/// The registry of a [`RingDriver`], locked for as long as this is held.
+pub struct RegistryGuard<'a, C: CQE, S>(MutexGuard<'a, Delivery<C, S>>);
+
+impl<C: CQE, S> Deref for RegistryGuard<'_, C, S> {
+    type Target = Registry<C, S>;
+
+    fn deref(&self) -> &Registry<C, S> {
+        &self.0.registry
+    }
+}
+
+/// Stops the reaper thread when dropped, even if the ring thread is unwinding.
+struct StopReaper<'a> {
+    /// Tells the reaper to reap once more and exit.
+    stop: &'a AtomicBool,
+    /// The reaper thread.
+    reaper: Thread,
+}
+
+impl Drop for StopReaper<'_> {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        self.reaper.unpark();
+    }
+}
+
+/// The body of the reaper thread: reaps `half` into `delivery` until told to `stop`.
+///
+/// Polls continuously while entries are outstanding, or every `cooldown` if given, and parks while none are, for at
+/// most `idle` if given.
+fn reap_loop<C, S, CQ>(
+    mut half: Box<dyn CompletionHalf<C, CQ>>,
+    delivery: &Mutex<Delivery<C, S>>,
+    stop: &AtomicBool,
+    idle: Option<Duration>,
+    cooldown: Option<Duration>,
+) where
+    C: CQE,
+    CQ: CompletionQueue<C>,
+{
+    loop {
+        let stopping = stop.load(Ordering::Acquire);
+
+        let (reaped, outstanding, held_back) = {
+            let mut delivery = lock(delivery);
+            let cqes = half.completion();
+            let reaped = delivery.reap(
+                cqes,
+                |cqe| half.completion_key(cqe),
+                |cqe| half.interpret(cqe),
+                |cqe| half.is_cancel_completion(cqe).map(UserData::get),
+            );
+            (
+                reaped,
+                !delivery.registry.is_empty(),
+                !delivery.held_back.is_empty(),
+            )
+        };
+
+        // One last reap after being told to stop, for anything the ring thread submitted on its way out, and more if
+        // that one held completions back.
+        if stopping && !held_back {
+            break;
+        }
+
+        if outstanding && let Some(cooldown) = cooldown {
+            thread::sleep(cooldown);
+        } else if reaped == 0 {
+            match (outstanding, idle) {
+                (true, _) => thread::yield_now(),
+                // The ring thread unparks the reaper whenever it pushes an entry.
+                (false, None) => thread::park(),
+                (false, Some(idle)) => thread::park_timeout(idle),
+            }
+        }
+    }
+}
+
+impl<S, C, SQ, CQ, Ring> RingDriver<S, C, SQ, CQ, Ring>
+where
+    S: SQE,
+    C: CQE,
+    SQ: SubmissionQueue<S>,
+    CQ: CompletionQueue<C>,
+    Ring: FullRing<S, C, SQ, CQ>,
+{
+    /// Creates a driver for `ring`, receiving signals on `receiver` and `overflow`.
+    pub(crate) fn new(
+        ring: Ring,
+        receiver: SignalReceiver<RingSignal<S, C>>,
+        overflow: Option<Arc<Overflow<RingSignal<S, C>>>>,
+        mut config: Config<S, C>,
+        pending: PendingSet,
+        recovered: Arc<Mutex<Option<Recovered<S>>>>,
+        stats: Arc<RingStats>,
+    ) -> Self {
+        let mut registry = Registry::new();
+        registry.share_pending(pending);
+        if config.slow_consumer_threshold.is_some() {
+            registry.track_delivered();
+        }
+        if let Some(deliver) = config.deliver.take() {
+            registry.set_deliver(deliver);
+        }
+        if let Some(workers) = config.callback_pool {
+            registry.set_callback_pool(CallbackPool::new(
+                config.callback_nodes,
+                workers,
+                config.pin_callback_worker,
+            ));
+        }
+
+        // Deferring submissions only delays the completions of a synchronous backend.
+        let submit_threshold = if ring.is_synchronous() {
+            1
+        } else {
+            config.submit_threshold
+        };
+
+        stats.set_coalesce(submit_threshold);
+        // Overflows from before the ring thread started cannot be pinned on its entries.
+        let overflowed = ring.overflow_count();
+
+        let delivery = Delivery {
+            registry,
+            linked_timeouts: HashSet::new(),
+            keys: HashMap::new(),
+            recent: VecDeque::with_capacity(config.dedup_window),
+            dedup_window: config.dedup_window,
+            prioritized: config.prioritized,
+            oldest_first: config.oldest_first,
+            newest_first: config.newest_first,
+            max_reap_burst: config.max_reap_burst,
+            held_back: VecDeque::new(),
+            orphan_sink: config.orphan_sink.take(),
+            orphan_stream: config.orphan_stream.take(),
+            error_sink: config.error_sink.take(),
+            metrics: config.metrics.clone(),
+            contain_panics: config.contain_completion_panics,
+            retries: Vec::new(),
+            submitted_since_reap: 0,
+            unreaped: 0,
+            last_reaped: 0,
+            completed: 0,
+            last_reap: Instant::now(),
+            stats: Arc::clone(&stats),
+        };
+
+        Self {
+            ring,
+            receiver,
+            overflow,
+            delivery: Arc::new(Mutex::new(delivery)),
+            reaper: None,
+            config,
+            submit_threshold,
+            deferred: 0,
+            deferred_since: Instant::now(),
+            deadlines: Vec::new(),
+            gated: HashMap::new(),
+            unsubmitted: Vec::new(),
+            stats,
+            #[cfg(feature = "logging")]
+            last_sq_full_warning: None,
+            recovered,
+            fence: None,
+            overflowed,
+            reap_postponed: false,
+            sq_full_retries: 0,
+            _marker: PhantomData,
+        }
+    }
+
+    /// The ring being driven.
+    pub fn ring(&self) -> &Ring {
+        &self.ring
+    }
+
+    /// The ring being driven, mutably.
+    pub fn ring_mut(&mut self) -> &mut Ring {
+        &mut self.ring
+    }
+
+    /// The registry of outstanding entries.
+    ///
+    /// The registry is locked until the guard is dropped, holding up a reaper thread under
+    /// [`split_threads`](PRingBuilder::split_threads).
+    pub fn registry(&self) -> RegistryGuard<'_, C, S> {
+        RegistryGuard(lock(&self.delivery))
+    }
+
+    /// The number of entries pushed onto the SQ but not yet submitted.
+    pub fn deferred(&self) -> usize {
+        self.deferred
+    }
+
+    /// Runs the driver until every sender has been dropped, then submits anything still deferred and returns the ring.
+    ///
+    /// If the ring panics, the outstanding entries are left behind for
+    /// [`PRingSender::take_recovered`](crate::PRingSender::take_recovered) and the
+    /// [`on_panic`](PRingBuilder::on_panic) hook runs before the panic carries on unwinding. Stepping the driver by
+    /// hand does neither.
+    ///
+    /// Under [`split_threads`](PRingBuilder::split_threads), this is also where the ring's completion side is split
+    /// off and the reaper thread runs, until the driver returns.
+    pub fn run(mut self) -> Ring {
+        // Everything the loop touches is asserted unwind safe: on a panic the ring is abandoned with the
+        // driver, and the registry is only drained for recovery.
+        let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+            let half = if self.config.split_threads {
+                self.ring.split_completion()
+            } else {
+                None
+            };
+
+            let Some(half) = half else {
+                self.drive();
+                return;
+            };
+
+            let stop = AtomicBool::new(false);
+            let delivery = Arc::clone(&self.delivery);
+            let idle = self.config.housekeeping_interval;
+            let cooldown = self.config.reap_cooldown;
+            thread::scope(|scope| {
+                let stop = &stop;
+                let reaper = scope.spawn(move || reap_loop(half, &delivery, stop, idle, cooldown));
+
+                // Stops the reaper once the driver is done, or if it panics, before the scope joins it.
+                let _stop = StopReaper {
+                    stop,
+                    reaper: reaper.thread().clone(),
+                };
+                self.reaper = Some(reaper.thread().clone());
+                self.drive();
+            });
+            self.reaper = None;
+        }));
+
+        // Senders stop sending from here on, rather than queueing signals nobody will receive.
+        self.stats.ring_exited();
+
+        if let Err(payload) = outcome {
+            // Carry on unwinding once the outstanding work is left behind.
+            self.abandon(&*payload);
+            panic::resume_unwind(payload);
+        }
+
+        self.ring
+    }
+
+    /// Gives the driver a turn on a thread it shares with other drivers, handling up to `budget` signals without
+    /// blocking, or reaping if none are ready.
+    ///
+    /// Once every sender is gone, the driver finishes up as [`run`](Self::run) would. A panic is contained to this
+    /// driver: its outstanding entries are left behind and the [`on_panic`](PRingBuilder::on_panic) hook runs, as for
+    /// a ring thread that panicked, and the driver is done.
+    pub(crate) fn turn(&mut self, budget: usize) -> Turn {
+        let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+            for handled in 0..budget {
+                match self.try_step() {
+                    StepOutcome::Handled => {}
+                    StepOutcome::Deadline => return Turn::Busy,
+                    StepOutcome::Idle if handled == 0 => return Turn::Idle,
+                    StepOutcome::Idle => return Turn::Busy,
+                    StepOutcome::Disconnected => {
+                        self.finish();
+                        return Turn::Exited;
+                    }
+                }
+            }
+            Turn::Busy
+        }));
+
+        match outcome {
+            Ok(Turn::Exited) => {
+                self.stats.ring_exited();
+                Turn::Exited
+            }
+            Ok(turn) => turn,
+            Err(payload) => {
+                self.stats.ring_exited();
+                self.abandon(&*payload);
+                Turn::Exited
+            }
+        }
+    }
+
+    /// Leaves the outstanding work behind for a supervisor after a panic, then runs the panic hook.
+    fn abandon(&mut self, payload: &(dyn Any + Send)) {
+        self.stats.record_error(RingError::ThreadGone);
+        *lock(&self.recovered) = Some(lock(&self.delivery).registry.drain_outstanding());
+
+        if let Some(hook) = self.config.on_panic.take() {
+            // A panicking hook must not replace the original panic.
+            let _ = panic::catch_unwind(AssertUnwindSafe(|| hook(payload)));
+        }
+    }
+
+    /// Steps until every sender has been dropped, then finishes up.
+    fn drive(&mut self) {
+        while self.step() != StepOutcome::Disconnected {}
+        self.finish();
+    }
+
+    /// Submits anything still deferred once every sender has been dropped, draining the outstanding entries if
+    /// configured to.
+    fn finish(&mut self) {
+        // Hand anything still deferred to the kernel before going away.
+        if self.deferred > 0 {
+            self.submit_deferred();
+            self.reap(ReapReason::Shutdown);
+        }
+        if let Some(timeout) = self.config.drain_timeout {
+            self.drain(Instant::now() + timeout);
+        }
+        // Whatever a capped reap held back is already off the CQ, so nothing else would deliver it.
+        while self.reaper.is_none() && !lock(&self.delivery).held_back.is_empty() {
+            self.reap(ReapReason::Shutdown);
+        }
+    }
+
+    /// Reaps on every turn, regardless of the reap strategy, until every outstanding entry is delivered or `until`.
+    fn drain(&mut self, until: Instant) {
+        // No gate can open any more, so held entries would only hold the drain up.
+        for gate in std::mem::take(&mut self.gated).into_values() {
+            let registry = &mut lock(&self.delivery).registry;
+            for entry in gate {
+                registry.resolve(entry.get_ud(), Err(RingError::Disconnected));
+            }
+        }
+
+        while (!self.registry().is_empty() || self.is_holding()) && Instant::now() < until {
+            self.run_due();
+            self.submit_deferred();
+
+            if self.reaper.is_some() {
+                // The reaper polls the CQ continuously while entries are outstanding.
+                thread::yield_now();
+                continue;
+            }
+
+            self.ring.submit_and_wait(1);
+            self.reap(ReapReason::Shutdown);
+        }
+    }
+
+    /// Handles one signal, blocking until one arrives.
+    ///
+    /// With deferred entries, blocks at most until the oldest of them has waited
+    /// [`max_defer`](PRingBuilder::max_defer), then submits them instead. Likewise, blocks at most until the next
+    /// retry is due, then pushes it, or until the next timeout expires, then resolves its entry. Only a disconnected
+    /// channel yields [`StepOutcome::Disconnected`]; a receive that times out never ends the loop.
+    pub fn step(&mut self) -> StepOutcome {
+        match self.next_signal() {
+            Ok(signal) => {
+                self.stats.signal_received();
+                self.handle(signal);
+                StepOutcome::Handled
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                // The receive may have woken early, or spuriously on some transports, before anything was due.
+                if self.run_due() {
+                    StepOutcome::Deadline
+                } else {
+                    self.reap(ReapReason::Idle);
+                    StepOutcome::Idle
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => StepOutcome::Disconnected,
+        }
+    }
+
+    /// Handles one signal if one is ready, reaping the CQ otherwise. Never blocks.
+    pub fn try_step(&mut self) -> StepOutcome {
+        match self.try_recv() {
+            Ok(signal) => {
+                self.stats.signal_received();
+                self.handle(signal);
+                StepOutcome::Handled
+            }
+            Err(TryRecvError::Empty) => {
+                if self.run_due() {
+                    StepOutcome::Deadline
+                } else {
+                    self.reap(ReapReason::Idle);
+                    StepOutcome::Idle
+                }
+            }
+            Err(TryRecvError::Disconnected) => StepOutcome::Disconnected,
+        }
+    }
+
+    /// Receives the next signal without blocking.
+    fn try_recv(&self) -> Result<RingSignal<S, C>, TryRecvError> {
+        // Spilled signals count as received, so they are drained just like the channel.
+        match &self.overflow {
+            Some(overflow) => overflow.try_recv(&self.receiver),
+            None => self.receiver.try_recv(),
+        }
+    }
+
+    /// Receives the next signal, spinning or blocking as configured.
+    ///
+    /// Errors with `Timeout` once the deferred entries, a retry or a timeout are due, and with `Disconnected` when every
+    /// sender has been dropped.
+    fn next_signal(&mut self) -> Result<RingSignal<S, C>, RecvTimeoutError> {
+        // A reaper thread already polls the CQ.
+        let ready = if self.deferred == 0
+            && self.reaper.is_none()
+            && !self.config.spin_before_block.is_zero()
+            && !self.registry().is_empty()
+        {
+            // With completions outstanding, spin on the CQ for a while in case one lands before the next signal.
+            let spin_until = Instant::now() + self.config.spin_before_block;
+            loop {
+                match self.try_recv() {
+                    Ok(signal) => break Some(Ok(signal)),
+                    Err(TryRecvError::Disconnected) => {
+                        break Some(Err(RecvTimeoutError::Disconnected));
+                    }
+                    Err(TryRecvError::Empty) => {}
+                }
+
+                self.reap(ReapReason::Idle);
+                if self.registry().is_empty() || Instant::now() >= spin_until {
+                    break None;
+                }
+                hint::spin_loop();
+            }
+        } else if self.overflow.is_some() {
+            // Spilled signals never wake a blocked receive, so they have to be picked up first.
+            match self.try_recv() {
+                Ok(signal) => Some(Ok(signal)),
+                Err(TryRecvError::Disconnected) => Some(Err(RecvTimeoutError::Disconnected)),
+                Err(TryRecvError::Empty) => None,
+            }
+        } else {
+            None
+        };
+
+        // Blocks when there are no `Signal`s to consume, unless there are deferred entries or retries whose
+        // deadline we have to honour.
+        match (ready, self.deadline()) {
+            (Some(signal), _) => signal,
+            (None, None) => self
+                .receiver
+                .recv()
+                .map_err(|_| RecvTimeoutError::Disconnected),
+            (None, Some(deadline)) => self
+                .receiver
+                .recv_timeout(deadline.saturating_duration_since(Instant::now())),
+        }
+    }
+
+    /// The next time the driver has to act without a signal, when the deferred entries, a retry, a timeout, the
+    /// housekeeping reap or a reap postponed by the cooldown are due.
+    fn deadline(&self) -> Option<Instant> {
+        let deferred = (self.deferred > 0).then(|| self.deferred_since + self.config.max_defer);
+        // Reaping straight away means as soon as the cooldown allows.
+        let now = Instant::now().max(self.next_reap());
+        let delivery = lock(&self.delivery);
+        let retry = delivery.retries.iter().map(|(due, ..)| *due).min();
+        // Completions held back by a capped reap are due straight away, once the channel has been checked.
+        let held_back = (self.reaper.is_none() && !delivery.held_back.is_empty()).then_some(now);
+        drop(delivery);
+        let timeout = self.deadlines.iter().map(|(due, _)| *due).min();
+        let housekeeping = self.housekeeping_due();
+        // Polls the CQ until the fence lifts.
+        let fenced = self.is_holding().then_some(now);
+        let postponed = self.reap_postponed.then_some(now);
+
+        deferred
+            .into_iter()
+            .chain(retry)
+            .chain(held_back)
+            .chain(timeout)
+            .chain(housekeeping)
+            .chain(fenced)
+            .chain(postponed)
+            .min()
+    }
+
+    /// When the housekeeping reap is next due, if there is one.
+    fn housekeeping_due(&self) -> Option<Instant> {
+        lock(&self.delivery)
+            .last_reap
+            .checked_add(self.config.housekeeping_interval?)
+    }
+
+    /// Submits the deferred entries, pushes the retries, expires the timeouts and runs the housekeeping reap or a
+    /// postponed one if they are due. Returns whether anything was.
+    fn run_due(&mut self) -> bool {
+        let now = Instant::now();
+
+        let deferred_due = self.deferred > 0 && now >= self.deferred_since + self.config.max_defer;
+        if deferred_due {
+            self.submit_deferred();
+            self.maybe_reap();
+        }
+
+        let retried = self.push_due_retries(now);
+        let expired = self.expire_due_timeouts(now);
+
+        // The held signals wait on completions nobody may ask to reap.
+        let fenced = self.is_holding();
+        if fenced {
+            if self.reaper.is_some() {
+                thread::yield_now();
+            } else {
+                self.reap(ReapReason::Timer);
+            }
+            self.lift_fence();
+        }
+
+        // Any of the above may have reaped already, pushing the housekeeping reap back.
+        let housekeeping_due = self.housekeeping_due().is_some_and(|due| now >= due);
+        if housekeeping_due {
+            self.reap(ReapReason::Timer);
+        }
+
+        let postponed_due = self.reap_postponed && now >= self.next_reap();
+        if postponed_due {
+            self.reap(ReapReason::Timer);
+        }
+
+        housekeeping_due || expired || retried || deferred_due || fenced || postponed_due
+    }
+
+    /// Whether a fence is holding signals back.
+    fn is_holding(&self) -> bool {
+        self.fence
+            .as_ref()
+            .is_some_and(|fence| !fence.held.is_empty())
+    }
+
+    /// Handles the signals held back by the fence, once the entries outstanding before it have been delivered.
+    fn lift_fence(&mut self) {
+        // A barrier dropped along with the registry's outstanding entries never fires.
+        let lifted = self.fence.as_ref().is_some_and(|fence| {
+            !matches!(fence.lifted.try_recv(), Err(oneshot::TryRecvError::Empty))
+        });
+        if !lifted {
+            return;
+        }
+
+        let held = self
+            .fence
+            .take()
+            .map(|fence| fence.held)
+            .unwrap_or_default();
+        // A held fence goes up again as it is handled, and holds the signals after it in turn.
+        for signal in held {
+            self.handle(signal);
+        }
+    }
+
+    /// Holds `signal` back if a fence is up and the signal has to wait for it, handing it back otherwise.
+    fn hold(&mut self, signal: RingSignal<S, C>) -> Option<RingSignal<S, C>> {
+        self.lift_fence();
+        let Some(fence) = &mut self.fence else {
+            return Some(signal);
+        };
+
+        let delivery = lock(&self.delivery);
+        let wait = match &signal {
+            // Entries sent after the fence, and signals that would miss them or see them out of order.
+            Signal::Entry(..)
+            | Signal::Retry(..)
+            | Signal::Timeout(..)
+            | Signal::Prioritized(..)
+            | Signal::Slotted(..)
+            | Signal::Sync(..)
+            | Signal::Batch(..)
+            | Signal::Chain(..)
+            | Signal::Gated(..)
+            | Signal::Fence
+            | Signal::Snapshot(_)
+            | Signal::FlushSync(_)
+            | Signal::Barrier(_)
+            | Signal::RegisterBuffers(..) => true,
+            // Entries sent before the fence are registered already, and can be acted on straight away.
+            Signal::Cancel(ud) | Signal::Reassign(ud, ..) => !delivery.registry.contains(*ud),
+            Signal::CancelBatch(uds, _) => uds.iter().any(|&ud| !delivery.registry.contains(ud)),
+            // Opens the gate for the entries held behind it now, and for those still to arrive behind the fence later.
+            Signal::OpenGate(gate) => {
+                fence.held.push_back(Signal::OpenGate(*gate));
+                false
+            }
+            Signal::SetCoalesce(_)
+            | Signal::Reap
+            | Signal::Flush
+            | Signal::Below(..)
+            | Signal::WarmUp(_)
+            | Signal::Dump(_) => false,
+        };
+        drop(delivery);
+
+        if wait {
+            fence.held.push_back(signal);
+            None
+        } else {
+            Some(signal)
+        }
+    }
+
+    /// Resolves every entry whose timeout expired by `now` with `RingError::Timeout`. Returns whether any did.
+    fn expire_due_timeouts(&mut self, now: Instant) -> bool {
+        if !self.deadlines.iter().any(|(due, _)| *due <= now) {
+            return false;
+        }
+
+        // A completion that has already landed beats the timeout.
+        self.reap(ReapReason::Timer);
+
+        // Entries that completed in time have left the registry, so resolving them does nothing.
+        let registry = &mut lock(&self.delivery).registry;
+        self.deadlines.retain(|&(due, ud)| {
+            if due > now {
+                return true;
+            }
+            registry.resolve(ud.into(), Err(RingError::Timeout));
+            false
+        });
+
+        true
+    }
+
+    /// Pushes every retry due by `now`. Returns whether there were any.
+    fn push_due_retries(&mut self, now: Instant) -> bool {
+        let mut pushed = false;
+
+        // Pushing may reap and queue further retries, which are picked up by the same loop if already due.
+        loop {
+            let due = {
+                let mut delivery = lock(&self.delivery);
+                let Some(i) = delivery.retries.iter().position(|(due, ..)| *due <= now) else {
+                    break;
+                };
+                let (_, ud, entry) = delivery.retries.swap_remove(i);
+                // The entry may have been cancelled while waiting.
+                delivery.registry.contains(ud.into()).then_some((ud, entry))
+            };
+
+            if let Some((ud, entry)) = due {
+                self.push(ud, entry);
+                pushed = true;
+            }
+        }
+
+        pushed
+    }
+
+    /// Delivers every completion currently in the CQ.
+    ///
+    /// Leaves the CQ alone while a reaper thread owns it; it reaps continuously anyway. Otherwise counts the reap for
+    /// `reason`. Either way, then checks whether the CQ dropped any completions.
+    ///
+    /// A reap the [`reap_cooldown`](PRingBuilder::reap_cooldown) holds back is skipped, and made up for once the
+    /// cooldown is over.
+    fn reap(&mut self, reason: ReapReason) {
+        if self.reaper.is_none() {
+            if !reason.overrides_cooldown() && Instant::now() < self.next_reap() {
+                self.reap_postponed = true;
+                return;
+            }
+            self.reap_postponed = false;
+
+            self.stats.reaped(reason);
+            if let Some(metrics) = &self.config.metrics {
+                metrics.reaped(reason);
+            }
+
+            let cqes = self.ring.completion();
+            let ring = &self.ring;
+            lock(&self.delivery).reap(
+                cqes,
+                |cqe| ring.completion_key(cqe),
+                |cqe| ring.interpret(cqe),
+                |cqe| ring.is_cancel_completion(cqe).map(UserData::get),
+            );
+        }
+
+        // After the reap, so nothing still in the CQ is presumed lost.
+        self.check_overflow();
+    }
+
+    /// The earliest a discretionary reap may happen, given the cooldown.
+    fn next_reap(&self) -> Instant {
+        let last_reap = lock(&self.delivery).last_reap;
+        self.config
+            .reap_cooldown
+            .and_then(|cooldown| last_reap.checked_add(cooldown))
+            .unwrap_or(last_reap)
+    }
+
+    /// Accounts for completions the CQ dropped since the last check, failing entries in their place if configured to.
+    fn check_overflow(&mut self) {
+        let overflowed = self.ring.overflow_count();
+        let lost = overflowed.saturating_sub(self.overflowed);
+        if lost == 0 {
+            return;
+        }
+        self.overflowed = overflowed;
+
+        self.stats.completions_lost(lost);
+        self.stats.record_error(RingError::CompletionLost);
+        if let Some(metrics) = &self.config.metrics {
+            metrics.completions_lost(lost);
+        }
+        #[cfg(feature = "logging")]
+        log::warn!(
+            "CQ overflowed, dropping {lost} completions: in_flight={}",
+            self.registry().len()
+        );
+
+        if self.config.fail_lost_completions {
+            let lost = usize::try_from(lost).unwrap_or(usize::MAX);
+            lock(&self.delivery)
+                .registry
+                .fail_oldest(lost, RingError::CompletionLost);
+        }
+    }
+
+    /// Takes a snapshot of the driver's state, as it stands between two signals.
+    fn dump(&self) -> RingDump {
+        let delivery = lock(&self.delivery);
+
+        RingDump {
+            taken_at: Instant::now(),
+            in_flight: delivery.registry.len(),
+            outstanding: delivery.registry.outstanding(),
+            peak_in_flight: self.stats.peak_in_flight(),
+            deferred: self.deferred,
+            submitted: self.stats.submitted(),
+            completed: delivery.completed,
+            sq_full_retries: self.sq_full_retries,
+            last_error: self.stats.last_error(),
+            reap_strategy: self.config.reap_strategy.describe(),
+        }
+    }
+
+    /// Reaps if the reap strategy says so.
+    fn maybe_reap(&mut self) {
+        let ctx = {
+            let delivery = lock(&self.delivery);
+            RingThreadCtx {
+                in_flight: delivery.registry.len(),
+                submitted_since_reap: delivery.submitted_since_reap,
+                last_reaped: delivery.last_reaped,
+                last_reap: delivery.last_reap,
+            }
+        };
+
+        if self.config.reap_strategy.should_reap(&ctx) {
+            let reason = self.config.reap_strategy.reason();
+            self.reap(reason);
+        }
+    }
+
+    /// Submits the deferred entries, if there are any.
+    fn submit_deferred(&mut self) {
+        if self.deferred > 0 {
+            self.submit();
+        }
+    }
+
+    /// Calls `Submitter::submit_counted`, counting the deferred entries as one batch.
+    ///
+    /// Returns how many entries the backend accepted, or the number deferred if it does not tell.
+    fn submit(&mut self) -> usize {
+        self.relieve_cq_pressure();
+
+        let deferred = self.deferred;
+        if deferred > 0 {
+            self.stats.batch_submitted(deferred);
+            lock(&self.delivery).unreaped += deferred;
+            self.deferred = 0;
+        }
+        let accepted = self.ring.submit_counted().unwrap_or(deferred);
+
+        if !self.unsubmitted.is_empty() {
+            let now = Instant::now();
+            let delivery = lock(&self.delivery);
+            let mut origins = delivery.registry.origins();
+            for ud in self.unsubmitted.drain(..) {
+                if let Some(origin) = origins.get_mut(&ud) {
+                    origin.hand_over(now);
+                }
+            }
+        }
+
+        accepted
+    }
+
+    /// Reaps ahead of a submit that could fill the CQ close to its capacity, if the ring knows it.
+    fn relieve_cq_pressure(&mut self) {
+        // A reaper thread keeps the CQ drained on its own.
+        if self.reaper.is_some() {
+            return;
+        }
+        // A CQ reported to hold nothing is as good as one of unknown size.
+        let Some(capacity) = self
+            .ring
+            .completion_capacity()
+            .filter(|&capacity| capacity > 0)
+        else {
+            return;
+        };
+
+        // A quarter of the CQ is left as headroom for entries completing more than once.
+        let unreaped = lock(&self.delivery).unreaped;
+        if unreaped + self.deferred >= capacity - capacity / 4 {
+            self.reap(ReapReason::CqPressure);
+        }
+    }
+
+    /// Pushes an entry registered under `entry_ud` onto the SQ, submitting once enough entries are deferred.
+    fn push(&mut self, entry_ud: u64, entry: S) {
+        self.push_entry(entry_ud, entry);
+        self.submit_at_threshold();
+    }
+
+    /// Pushes an entry followed by its linked timeout entry, submitting once enough entries are deferred.
+    ///
+    /// Returns `false` if the SQ had no room left for the timeout entry, in which case the entry is submitted on its
+    /// own instead.
+    fn push_linked(&mut self, entry_ud: u64, entry: S, timeout_ud: u64, timeout: S) -> bool {
+        self.push_entry(entry_ud, entry);
+
+        // Nothing may be submitted between the two, or the chain would be split.
+        self.alias(timeout_ud, &timeout);
+        // Recorded up front, a reaper thread may see the completion as soon as it is pushed.
+        lock(&self.delivery).linked_timeouts.insert(timeout_ud);
+        let linked = match self.ring.submission().push(timeout) {
+            Ok(()) => {
+                self.count_pushed();
+                true
+            }
+            Err(_) => {
+                let mut delivery = lock(&self.delivery);
+                delivery.linked_timeouts.remove(&timeout_ud);
+                delivery.keys.retain(|_, ud| *ud != timeout_ud);
+                drop(delivery);
+                // Submitting ends the chain with the entry, leaving it to the driver to time out.
+                self.submit();
+                false
+            }
+        };
+
+        self.submit_at_threshold();
+        linked
+    }
+
+    /// Records the routing key of an entry registered under `entry_ud`, if it differs from the user data.
+    fn alias(&mut self, entry_ud: u64, entry: &S) {
+        let key = self.ring.submission_key(entry);
+        if key != entry_ud {
+            lock(&self.delivery).keys.insert(key, entry_ud);
+        }
+    }
+
+    /// Pushes an entry registered under `entry_ud` onto the SQ, reaping and submitting until there is room.
+    fn push_entry(&mut self, entry_ud: u64, entry: S) {
+        self.push_entry_at(entry_ud, entry, None);
+    }
+
+    /// Like [`push_entry`](Self::push_entry), pushing into SQ slot `index` if given rather than appending.
+    fn push_entry_at(&mut self, entry_ud: u64, entry: S, index: Option<usize>) {
+        self.alias(entry_ud, &entry);
+
+        // Temporary holder for the entry, required by rust's ownership shinnanigans.
+        let mut entry_holder = Some(entry);
+        #[cfg(feature = "logging")]
+        let mut attempts = 0;
+
+        // Loops until submission of entry is successful.
+        // Fails if the SQ is full, possible if we are handed a ring with a full SQ or
+        // we have been pushing SQEs and not reaping their CQEs.
+        while let Err(failure_entry) = match index {
+            Some(index) => self
+                .ring
+                .submission()
+                .push_at(index, entry_holder.take().unwrap()),
+            None => self.ring.submission().push(entry_holder.take().unwrap()),
+        } {
+            entry_holder = Some(failure_entry);
+
+            self.sq_full_retries += 1;
+            if let Some(metrics) = &self.config.metrics {
+                metrics.sq_full_retry();
+            }
+
+            #[cfg(feature = "logging")]
+            {
+                attempts += 1;
+                if attempts == self.config.sq_full_warn_after + 1 {
+                    self.warn_sq_full(attempts);
+                }
+            }
+
+            // The SQ could be full because the CQ is full.
+            self.reap(ReapReason::SqFull);
+            // CQ is now empty, so we should wake the kernel. This also submits anything deferred.
+            self.submit();
+        }
+
+        if self.config.metrics.is_some() {
+            self.unsubmitted.push(entry_ud);
+        }
+        self.count_pushed();
+    }
+
+    /// Logs that an entry took `attempts` tries to fit in the SQ, unless a warning was logged too recently.
+    #[cfg(feature = "logging")]
+    fn warn_sq_full(&mut self, attempts: usize) {
+        let now = Instant::now();
+        if self
+            .last_sq_full_warning
+            .is_some_and(|last| now.duration_since(last) < self.config.sq_full_warn_every)
+        {
+            return;
+        }
+        self.last_sq_full_warning = Some(now);
+
+        let capacity = self.ring.submission().capacity();
+        log::warn!(
+            "SQ still full after {attempts} attempts to push an entry: in_flight={}, sq_capacity={}",
+            self.registry().len(),
+            capacity.map_or_else(|| "unknown".to_owned(), |capacity| capacity.to_string()),
+        );
+    }
+
+    /// Accounts for an entry just pushed onto the SQ.
+    fn count_pushed(&mut self) {
+        if self.deferred == 0 {
+            self.deferred_since = Instant::now();
+        }
+        self.deferred += 1;
+        lock(&self.delivery).submitted_since_reap += 1;
+        if let Some(metrics) = &self.config.metrics {
+            metrics.submitted();
+        }
+
+        // A parked reaper has to start polling for the entry's completion.
+        if let Some(reaper) = &self.reaper {
+            reaper.unpark();
+        }
+    }
+
+    /// Submits the deferred entries once there are enough of them, then reaps if the strategy agrees.
+    fn submit_at_threshold(&mut self) {
+        if self.deferred >= self.submit_threshold {
+            // Inform the kernel of our new submissions.
+            self.submit_deferred();
+            // Might as well reap the CQ as well, if the strategy agrees.
+            self.maybe_reap();
+        }
+    }
+
+    /// Registers `tx` under `entry_ud`, retaining a copy of `entry` if entries are retained, then runs `setup`.
+    ///
+    /// The registry stays locked throughout, so a reaper thread never sees the entry half set up. Returns `false`,
+    /// having resolved `tx` with `RingError::DuplicateUserData`, if another entry is outstanding under `entry_ud`, in
+    /// which case the entry must not be pushed.
+    fn register(
+        &self,
+        entry_ud: u64,
+        tx: Completer<C>,
+        entry: &S,
+        setup: impl FnOnce(&mut Registry<C, S>),
+    ) -> bool {
+        let registry = &mut lock(&self.delivery).registry;
+
+        // Replacing the outstanding entry's destination would leave it never resolved.
+        if let Err(tx) = registry.try_insert(entry_ud.into(), tx) {
+            registry.reject(tx, RingError::DuplicateUserData);
+            self.stats.record_error(RingError::DuplicateUserData);
+            return false;
+        }
+        self.stats.record_in_flight(registry.len());
+        if let Some(metrics) = &self.config.metrics {
+            metrics.in_flight(registry.len());
+        }
+        if let Some(retain) = self.config.retain {
+            registry.retain(entry_ud.into(), retain(entry));
+        }
+        setup(registry);
+
+        true
+    }
+
+    /// Resolves the entry registered under `entry_ud` with `RingError::Invalid` if the SQ refuses it.
+    ///
+    /// Returns whether it was refused, in which case it must not be pushed.
+    fn reject_invalid(&mut self, entry_ud: u64, entry: &S) -> bool {
+        match self.ring.submission().validate(entry) {
+            Ok(()) => false,
+            Err(err) => {
+                self.stats.record_error(RingError::Invalid(err.clone()));
+                lock(&self.delivery)
+                    .registry
+                    .resolve(entry_ud.into(), Err(RingError::Invalid(err)));
+                true
+            }
+        }
+    }
+
+    /// Handles a single signal.
+    fn handle(&mut self, signal: RingSignal<S, C>) {
+        let Some(signal) = self.hold(signal) else {
+            return;
+        };
+
+        match signal {
+            Signal::Entry(mut entry, tx, flags) => {
+                // The sender has already tagged the entry with trackable user data.
+                let entry_ud = entry.get_ud().get();
+
+                // Submit to the registry.
+                if !self.register(entry_ud, tx, &entry, |_| {}) {
+                    return;
+                }
+
+                if !flags.is_empty() {
+                    entry.set_flags(flags.bits());
+                }
+                if self.reject_invalid(entry_ud, &entry) {
+                    return;
+                }
+
+                self.push(entry_ud, entry);
+            }
+            Signal::Retry(entry, tx, policy, copy) => {
+                let entry_ud = entry.get_ud().get();
+
+                let retry = Retry {
+                    spare: copy(&entry),
+                    copy,
+                    policy,
+                    attempts: 0,
+                };
+                let registered = self.register(entry_ud, tx, &entry, |registry| {
+                    registry.set_retry(entry_ud.into(), retry);
+                });
+                if !registered || self.reject_invalid(entry_ud, &entry) {
+                    return;
+                }
+
+                self.push(entry_ud, entry);
+            }
+            Signal::Timeout(mut entry, tx, timeout, timeout_ud) => {
+                let entry_ud = entry.get_ud().get();
+                let timeout_ud = timeout_ud.get();
+
+                if !self.register(entry_ud, tx, &entry, |_| {})
+                    || self.reject_invalid(entry_ud, &entry)
+                {
+                    lock(&self.delivery).registry.unreserve(timeout_ud.into());
+                    return;
+                }
+
+                let linked = match self.ring.timeout_entry(timeout) {
+                    Some(mut timeout_entry) => {
+                        timeout_entry.set_ud(timeout_ud.into());
+                        self.ring.link(&mut entry);
+                        self.push_linked(entry_ud, entry, timeout_ud, timeout_entry)
+                    }
+                    None => {
+                        self.push(entry_ud, entry);
+                        false
+                    }
+                };
+                // Otherwise given back once the timeout entry's own completion comes in.
+                if !linked {
+                    lock(&self.delivery).registry.unreserve(timeout_ud.into());
+                }
+
+                // A timeout too far out to represent never expires.
+                if !linked && let Some(due) = Instant::now().checked_add(timeout) {
+                    self.deadlines.push((due, entry_ud));
+                }
+            }
+            Signal::Prioritized(entry, tx, priority) => {
+                let entry_ud = entry.get_ud().get();
+
+                let registered = self.register(entry_ud, tx, &entry, |registry| {
+                    registry.set_priority(entry_ud.into(), priority);
+                });
+                if !registered || self.reject_invalid(entry_ud, &entry) {
+                    return;
+                }
+
+                self.push(entry_ud, entry);
+            }
+            Signal::Slotted(entry, tx, index) => {
+                let entry_ud = entry.get_ud().get();
+
+                if !self.register(entry_ud, tx, &entry, |_| {})
+                    || self.reject_invalid(entry_ud, &entry)
+                {
+                    return;
+                }
+
+                self.push_entry_at(entry_ud, entry, Some(index));
+                self.submit_at_threshold();
+            }
+            Signal::Sync(entry, tx) => {
+                let entry_ud = entry.get_ud().get();
+
+                if !self.register(entry_ud, tx, &entry, |_| {})
+                    || self.reject_invalid(entry_ud, &entry)
+                {
+                    return;
+                }
+
+                // Its caller is blocked on it, so neither the submit threshold nor the reap strategy gets a say.
+                self.push_entry(entry_ud, entry);
+                self.submit_deferred();
+                self.reap(ReapReason::Explicit);
+            }
+            Signal::Batch(entries) => {
+                // A chunk pushed onto an empty SQ fits without waiting for room.
+                let chunk = self
+                    .ring
+                    .submission()
+                    .capacity()
+                    .unwrap_or(usize::MAX)
+                    .max(1);
+                let mut entries = entries.into_iter().peekable();
+
+                while entries.peek().is_some() {
+                    // Anything already deferred would leave the chunk short of room.
+                    self.submit_deferred();
+
+                    for (entry, tx) in entries.by_ref().take(chunk) {
+                        let entry_ud = entry.get_ud().get();
+
+                        if !self.register(entry_ud, tx, &entry, |_| {})
+                            || self.reject_invalid(entry_ud, &entry)
+                        {
+                            continue;
+                        }
+
+                        self.push_entry(entry_ud, entry);
+                    }
+
+                    if entries.peek().is_some() {
+                        self.submit_deferred();
+                        self.reap(ReapReason::SqFull);
+                    }
+                }
+
+                self.submit_at_threshold();
+            }
+            Signal::Chain(entries) => {
+                let too_long = self
+                    .ring
+                    .submission()
+                    .capacity()
+                    .is_some_and(|capacity| entries.len() > capacity);
+                let invalid = entries
+                    .iter()
+                    .position(|(entry, _)| self.ring.submission().validate(entry).is_err());
+                let duplicate = {
+                    let registry = &lock(&self.delivery).registry;
+                    let mut seen = HashSet::new();
+                    entries.iter().position(|(entry, _)| {
+                        registry.contains(entry.get_ud()) || !seen.insert(entry.get_ud())
+                    })
+                };
+
+                if too_long || invalid.is_some() || duplicate.is_some() {
+                    // Pushing part of a chain would run it without the rest, so none of it is pushed.
+                    let registry = &mut lock(&self.delivery).registry;
+                    for (index, (entry, tx)) in entries.into_iter().enumerate() {
+                        let entry_ud = entry.get_ud().get();
+                        let err = match self.ring.submission().validate(&entry) {
+                            _ if duplicate == Some(index) => RingError::DuplicateUserData,
+                            Err(err) if invalid == Some(index) => RingError::Invalid(err),
+                            _ if too_long => RingError::AtCapacity,
+                            _ => RingError::Cancelled,
+                        };
+                        // The rest of the chain is only cancelled on account of the entry at fault.
+                        if err != RingError::Cancelled {
+                            self.stats.record_error(err.clone());
+                        }
+
+                        match registry.try_insert(entry_ud.into(), tx) {
+                            Ok(()) => {
+                                registry.resolve(entry_ud.into(), Err(err));
+                            }
+                            Err(tx) => registry.reject(tx, RingError::DuplicateUserData),
+                        }
+                    }
+                    return;
+                }
+
+                // Nothing may be submitted between the links, or the chain would be split.
+                self.submit_deferred();
+
+                let last = entries.len() - 1;
+                for (index, (mut entry, tx)) in entries.into_iter().enumerate() {
+                    let entry_ud = entry.get_ud().get();
+
+                    // Duplicates were ruled out above, so every entry registers.
+                    self.register(entry_ud, tx, &entry, |_| {});
+                    if index < last {
+                        self.ring.link(&mut entry);
+                    }
+                    self.push_entry(entry_ud, entry);
+                }
+
+                self.submit_at_threshold();
+            }
+            Signal::Gated(entry, tx, gate) => {
+                let entry_ud = entry.get_ud().get();
+
+                if self.register(entry_ud, tx, &entry, |_| {}) {
+                    self.gated.entry(gate).or_default().push(entry);
+                }
+            }
+            Signal::OpenGate(gate) => {
+                let Some(entries) = self.gated.remove(&gate) else {
+                    return;
+                };
+
+                for entry in entries {
+                    let entry_ud = entry.get_ud().get();
+                    // Cancelled while it was held back.
+                    if !lock(&self.delivery).registry.contains(entry_ud.into()) {
+                        continue;
+                    }
+                    if self.reject_invalid(entry_ud, &entry) {
+                        continue;
+                    }
+
+                    self.push_entry(entry_ud, entry);
+                }
+
+                self.submit_at_threshold();
+            }
+            Signal::SetCoalesce(threshold) => {
+                // Synchronous backends keep submitting every entry straight away.
+                if !self.ring.is_synchronous() {
+                    self.submit_threshold = threshold.max(1);
+                    self.stats.set_coalesce(self.submit_threshold);
+                }
+                // Lowering the threshold may leave enough entries deferred already.
+                self.submit_at_threshold();
+            }
+            Signal::Reap => {
+                self.reap(ReapReason::Explicit);
+            }
+            Signal::Cancel(ud) => {
+                // Catch anything that completed in the meantime before giving up on it.
+                self.reap(ReapReason::Explicit);
+                lock(&self.delivery).registry.cancel(ud);
+            }
+            Signal::CancelBatch(uds, tx) => {
+                self.reap(ReapReason::Explicit);
+
+                // Locked once for the whole batch, so a reaper thread cannot deliver anything halfway through.
+                let registry = &mut lock(&self.delivery).registry;
+                let outcomes = uds
+                    .into_iter()
+                    .map(|ud| {
+                        if registry.cancel(ud) {
+                            CancelOutcome::Cancelled
+                        } else {
+                            CancelOutcome::AlreadyCompleted
+                        }
+                    })
+                    .collect();
+
+                let _ = tx.send(outcomes);
+            }
+            Signal::Reassign(ud, tx, reply) => {
+                let replaced = lock(&self.delivery).registry.replace(ud, tx).is_some();
+
+                let _ = reply.send(replaced);
+            }
+            Signal::Snapshot(tx) => {
+                let entries = lock(&self.delivery).registry.retained(self.config.retain);
+
+                let _ = tx.send(entries);
+            }
+            Signal::Dump(tx) => {
+                let dump = self.dump();
+
+                let _ = tx.send(dump);
+            }
+            Signal::FlushSync(tx) => {
+                // Submits even with nothing deferred, so the backend is caught up on the SQ either way.
+                let accepted = self.submit();
+                let _ = tx.send(accepted);
+                self.reap(ReapReason::Explicit);
+            }
+            Signal::Flush => {
+                self.submit_deferred();
+                self.reap(ReapReason::Explicit);
+            }
+            Signal::WarmUp(tx) => {
+                let sq_capacity = self.ring.submission().capacity();
+                let capacity = sq_capacity
+                    .max(self.ring.completion_capacity())
+                    .unwrap_or(0);
+                lock(&self.delivery).registry.reserve(capacity);
+                self.reap(ReapReason::Explicit);
+
+                let _ = tx.send(());
+            }
+            Signal::Barrier(tx) => {
+                // Deferred entries would otherwise hold the barrier up until their deadline.
+                self.submit_deferred();
+                self.reap(ReapReason::Explicit);
+                lock(&self.delivery).registry.barrier(tx);
+            }
+            Signal::Fence => {
+                // Deferred entries come before the fence, and have to reach the backend for it to ever lift.
+                self.submit_deferred();
+                self.reap(ReapReason::Explicit);
+
+                let (tx, lifted) = oneshot::channel();
+                lock(&self.delivery).registry.barrier(tx);
+                self.fence = Some(Fence {
+                    lifted,
+                    held: VecDeque::new(),
+                });
+            }
+            Signal::Below(threshold, tx) => {
+                // Deferred entries would otherwise hold the count up until their deadline.
+                self.submit_deferred();
+                self.reap(ReapReason::Explicit);
+                lock(&self.delivery).registry.notify_below(threshold, tx);
+            }
+            Signal::RegisterBuffers(bufs, tx) => {
+                // Entries already in the SQ were built against the old buffer set.
+                self.submit_deferred();
+                let result = self.ring.register_buffers(bufs);
+                if let Err(err) = &result {
+                    self.stats.record_error(RingError::Backend(err.clone()));
+                }
+                let _ = tx.send(result);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::atomic::AtomicU64, thread, time::Duration};
+
+    use super::*;
+    use crate::{
+        PRingSender,
+        adapters::{Completions, QueueSubmissions},
+        error::ValidationError,
+        test_util::{MockEntry, MockRing},
+        traits::Submitter,
+    };
+
+    /// A backend whose CQ never runs dry: every look at it finds a fresh batch of completions no entry waits for, on
+    /// top of those the mock completes.
+    struct FloodRing(MockRing<MockEntry, MockEntry>);
+
+    impl FloodRing {
+        /// Completions added to the CQ each time it is looked at.
+        const FLOOD: usize = 64;
+    }
+
+    impl Submitter for FloodRing {
+        fn submit(&mut self) {
+            self.0.submit();
+        }
+
+        fn submit_counted(&mut self) -> Option<usize> {
+            self.0.submit_counted()
+        }
+    }
+
+    impl FullRing<MockEntry, MockEntry, QueueSubmissions<MockEntry>, Completions<MockEntry>>
+        for FloodRing
+    {
+        fn completion(&mut self) -> Completions<MockEntry> {
+            let mut cqes: Vec<_> = self.0.completion().collect();
+            cqes.extend((0..Self::FLOOD).map(|_| {
+                let mut orphan = MockEntry::new(0);
+                SQE::set_ud(&mut orphan, u64::MAX.into());
+                orphan
+            }));
+            cqes.into()
+        }
+
+        fn submission(&mut self) -> QueueSubmissions<MockEntry> {
+            self.0.submission()
+        }
+    }
+
+    /// A backend reporting a CQ of the given size.
+    struct SizedCqRing(MockRing<MockEntry, MockEntry>, usize);
+
+    impl Submitter for SizedCqRing {
+        fn submit(&mut self) {
+            self.0.submit();
+        }
+
+        fn submit_counted(&mut self) -> Option<usize> {
+            self.0.submit_counted()
+        }
+    }
+
+    impl FullRing<MockEntry, MockEntry, QueueSubmissions<MockEntry>, Completions<MockEntry>>
+        for SizedCqRing
+    {
+        fn completion(&mut self) -> Completions<MockEntry> {
+            self.0.completion()
+        }
+
+        fn submission(&mut self) -> QueueSubmissions<MockEntry> {
+            self.0.submission()
+        }
+
+        fn completion_capacity(&self) -> Option<usize> {
+            Some(self.1)
+        }
+    }
+
+    #[test]
+    fn a_cq_of_unknown_size_never_counts_as_under_pressure() {
+        for (capacity, pressure_reaps) in [(0, 0), (1, 2)] {
+            let ring = MockRing::echo();
+            let (sender, mut driver) =
+                PRingSender::builder().build_driver(SizedCqRing(ring.clone(), capacity));
+
+            let _first = sender.submit(MockEntry::new(1));
+            let _second = sender.submit(MockEntry::new(2));
+            assert_eq!(driver.step(), StepOutcome::Handled);
+            assert_eq!(driver.step(), StepOutcome::Handled);
+            assert_eq!(
+                sender.cq_pressure_reaps(),
+                pressure_reaps,
+                "capacity {capacity}"
+            );
+        }
+    }
+
+    #[test]
+    fn step_keeps_running_and_reaps_through_a_receive_timeout() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder()
+            .housekeeping_interval(Duration::from_millis(5))
+            .build_driver(ring.clone());
+
+        let submission = sender.submit(MockEntry::new(1));
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        assert!(ring.complete_next().is_some());
+
+        // Nothing is sent, so the receive times out on the housekeeping deadline and the completion is reaped.
+        let outcome = driver.step();
+        assert!(matches!(outcome, StepOutcome::Deadline | StepOutcome::Idle));
+        assert_eq!(submission.try_take().unwrap().unwrap().tag(), 1);
+
+        // Still serving signals afterwards.
+        let submission = sender.submit(MockEntry::new(2));
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        assert!(ring.complete_next().is_some());
+        assert_ne!(driver.step(), StepOutcome::Disconnected);
+        assert_eq!(submission.try_take().unwrap().unwrap().tag(), 2);
+    }
+
+    #[test]
+    fn only_a_disconnected_channel_ends_run() {
+        let ring = MockRing::echo();
+        let (sender, driver) = PRingSender::builder()
+            .housekeeping_interval(Duration::from_millis(1))
+            .build_driver(ring.clone());
+        let running = thread::spawn(move || driver.run());
+
+        // Plenty of receive timeouts go by without ending the loop.
+        thread::sleep(Duration::from_millis(50));
+        assert!(!running.is_finished());
+
+        let submission = sender.submit(MockEntry::new(1));
+        while ring.complete_next().is_none() {
+            thread::yield_now();
+        }
+        let completion = submission.wait_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(completion.tag(), 1);
+        assert!(!running.is_finished());
+
+        drop(sender);
+        running.join().unwrap();
+    }
+
+    #[test]
+    fn a_capped_reap_does_not_starve_submissions_under_a_completion_flood() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder()
+            .max_reap_burst(4)
+            .build_driver(FloodRing(ring.clone()));
+
+        // Every reap finds more completions than it may deliver, so the backlog only ever grows.
+        sender.reap();
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        for _ in 0..16 {
+            assert_eq!(driver.step(), StepOutcome::Idle);
+        }
+
+        let submission = sender.submit(MockEntry::new(1));
+        let mut steps = 0;
+        while !ring.in_flight().contains(&submission.user_data()) {
+            assert!(
+                steps < 2,
+                "the entry was not submitted within {steps} steps"
+            );
+            driver.step();
+            steps += 1;
+        }
+    }
+
+    /// A backend reporting every completion twice.
+    struct RepeatingRing(MockRing<MockEntry, MockEntry>);
+
+    impl Submitter for RepeatingRing {
+        fn submit(&mut self) {
+            self.0.submit();
+        }
+
+        fn submit_counted(&mut self) -> Option<usize> {
+            self.0.submit_counted()
+        }
+    }
+
+    impl FullRing<MockEntry, MockEntry, QueueSubmissions<MockEntry>, Completions<MockEntry>>
+        for RepeatingRing
+    {
+        fn completion(&mut self) -> Completions<MockEntry> {
+            let cqes: Vec<_> = self.0.completion().flat_map(|cqe| [cqe, cqe]).collect();
+            cqes.into()
+        }
+
+        fn submission(&mut self) -> QueueSubmissions<MockEntry> {
+            self.0.submission()
+        }
+    }
+
+    #[test]
+    fn repeats_within_the_dedup_window_count_as_duplicates() {
+        for (window, duplicates) in [(0, 0), (1, 2)] {
+            let ring = MockRing::echo();
+            let (sender, mut driver) = PRingSender::builder()
+                .dedup_window(window)
+                .build_driver(RepeatingRing(ring.clone()));
+
+            let submissions: Vec<_> = (1..=2)
+                .map(|tag| sender.submit(MockEntry::new(tag)))
+                .collect();
+            while driver.try_step() == StepOutcome::Handled {}
+            assert_eq!(ring.complete_all(), 2);
+            sender.reap();
+            while driver.try_step() == StepOutcome::Handled {}
+
+            // A window of one still catches both, as each repeat directly follows its original.
+            assert_eq!(
+                sender.duplicate_completions(),
+                duplicates,
+                "window {window}"
+            );
+            for (submission, tag) in submissions.into_iter().zip(1..) {
+                assert_eq!(submission.wait().unwrap().tag(), tag);
+            }
+        }
+    }
+
+    /// A backend reaped through a completion half of its own, recording which threads submit and reap.
+    struct SplitRing {
+        ring: MockRing<MockEntry, MockEntry>,
+        submitted_on: Arc<Mutex<Option<thread::ThreadId>>>,
+        reaped_on: Arc<Mutex<Option<thread::ThreadId>>>,
+    }
+
+    /// The completion side of a [`SplitRing`].
+    struct SplitHalf(
+        MockRing<MockEntry, MockEntry>,
+        Arc<Mutex<Option<thread::ThreadId>>>,
+    );
+
+    impl CompletionHalf<MockEntry, Completions<MockEntry>> for SplitHalf {
+        fn completion(&mut self) -> Completions<MockEntry> {
+            let cqes = self.0.completion();
+            if cqes.len() > 0 {
+                *lock(&self.1) = Some(thread::current().id());
+            }
+            cqes
+        }
+    }
+
+    impl Submitter for SplitRing {
+        fn submit(&mut self) {
+            self.ring.submit();
+            *lock(&self.submitted_on) = Some(thread::current().id());
+        }
+    }
+
+    impl FullRing<MockEntry, MockEntry, QueueSubmissions<MockEntry>, Completions<MockEntry>>
+        for SplitRing
+    {
+        fn completion(&mut self) -> Completions<MockEntry> {
+            panic!("the completion side was split off");
+        }
+
+        fn submission(&mut self) -> QueueSubmissions<MockEntry> {
+            self.ring.submission()
+        }
+
+        fn split_completion(
+            &mut self,
+        ) -> Option<Box<dyn CompletionHalf<MockEntry, Completions<MockEntry>>>> {
+            Some(Box::new(SplitHalf(
+                self.ring.clone(),
+                Arc::clone(&self.reaped_on),
+            )))
+        }
+    }
+
+    #[test]
+    fn split_threads_reap_apart_from_submitting() {
+        let ring = MockRing::echo();
+        let split = SplitRing {
+            ring: ring.clone(),
+            submitted_on: Arc::default(),
+            reaped_on: Arc::default(),
+        };
+        let (submitted_on, reaped_on) = (
+            Arc::clone(&split.submitted_on),
+            Arc::clone(&split.reaped_on),
+        );
+        let (sender, driver) = PRingSender::builder()
+            .split_threads(true)
+            .build_driver(split);
+        let ring_thread = thread::spawn(move || driver.run());
+
+        let submission = sender.submit(MockEntry::new(1));
+        while ring.in_flight().is_empty() {
+            thread::yield_now();
+        }
+        // No explicit reap: the reaper polls while the entry is outstanding.
+        assert_eq!(ring.complete_all(), 1);
+        assert_eq!(
+            submission
+                .wait_timeout(Duration::from_secs(5))
+                .unwrap()
+                .tag(),
+            1
+        );
+
+        drop(sender);
+        ring_thread.join().unwrap();
+        let (submitted_on, reaped_on) = (lock(&submitted_on).unwrap(), lock(&reaped_on).unwrap());
+        assert_ne!(submitted_on, reaped_on);
+    }
+
+    /// A backend whose entries tagged from `CancelRing::OP` up are cancel operations, naming their target's user data
+    /// by how far past it they are tagged.
+    struct CancelRing(MockRing<MockEntry, MockEntry>);
+
+    impl CancelRing {
+        const OP: u64 = 1 << 32;
+    }
+
+    impl Submitter for CancelRing {
+        fn submit(&mut self) {
+            self.0.submit();
+        }
+
+        fn submit_counted(&mut self) -> Option<usize> {
+            self.0.submit_counted()
+        }
+    }
+
+    impl FullRing<MockEntry, MockEntry, QueueSubmissions<MockEntry>, Completions<MockEntry>>
+        for CancelRing
+    {
+        fn completion(&mut self) -> Completions<MockEntry> {
+            self.0.completion()
+        }
+
+        fn submission(&mut self) -> QueueSubmissions<MockEntry> {
+            self.0.submission()
+        }
+
+        fn is_cancel_completion(&self, cqe: &MockEntry) -> Option<UserData> {
+            cqe.tag().checked_sub(Self::OP).map(UserData::new)
+        }
+    }
+
+    #[test]
+    fn a_kernel_cancel_resolves_its_target_as_cancelled() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder().build_driver(CancelRing(ring.clone()));
+
+        let target = sender.submit(MockEntry::new(1));
+        let cancel = sender.submit(MockEntry::new(CancelRing::OP + target.user_data().get()));
+        while driver.try_step() == StepOutcome::Handled {}
+
+        assert!(ring.complete_ud(cancel.user_data()));
+        sender.reap();
+        while driver.try_step() == StepOutcome::Handled {}
+        assert_eq!(target.try_take(), Some(Err(RingError::Cancelled)));
+        assert!(cancel.wait().is_ok());
+
+        // The target's own completion finds nothing waiting for it.
+        assert!(ring.complete_ud(target.user_data()));
+        sender.reap();
+        while driver.try_step() == StepOutcome::Handled {}
+        assert!(sender.outstanding().is_empty());
+    }
+
+    /// An SQ refusing entries tagged zero.
+    struct CheckedSubmissions(QueueSubmissions<MockEntry>);
+
+    impl SubmissionQueue<MockEntry> for CheckedSubmissions {
+        fn push(&mut self, entry: MockEntry) -> Result<(), MockEntry> {
+            self.0.push(entry)
+        }
+
+        fn validate(&self, entry: &MockEntry) -> Result<(), ValidationError> {
+            match entry.tag() {
+                0 => Err(ValidationError::Unsupported),
+                _ => Ok(()),
+            }
+        }
+    }
+
+    /// A mock backend behind a [`CheckedSubmissions`] SQ.
+    struct CheckedRing(MockRing<MockEntry, MockEntry>);
+
+    impl Submitter for CheckedRing {
+        fn submit(&mut self) {
+            self.0.submit();
+        }
+    }
+
+    impl FullRing<MockEntry, MockEntry, CheckedSubmissions, Completions<MockEntry>> for CheckedRing {
+        fn completion(&mut self) -> Completions<MockEntry> {
+            self.0.completion()
+        }
+
+        fn submission(&mut self) -> CheckedSubmissions {
+            CheckedSubmissions(self.0.submission())
+        }
+    }
+
+    #[test]
+    fn entries_the_sq_refuses_are_never_pushed() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder().build_driver(CheckedRing(ring.clone()));
+
+        let refused = sender.submit(MockEntry::new(0));
+        let accepted = sender.submit(MockEntry::new(1));
+        while driver.try_step() == StepOutcome::Handled {}
+
+        assert_eq!(
+            refused.wait().unwrap_err(),
+            RingError::Invalid(ValidationError::Unsupported)
+        );
+        assert_eq!(ring.in_flight(), [accepted.user_data()]);
+    }
+
+    /// A backend that panics interpreting the completion of the entry tagged [`Self::POISON`].
+    struct PoisonRing(MockRing<MockEntry, MockEntry>);
+
+    impl PoisonRing {
+        const POISON: u64 = 2;
+    }
+
+    impl Submitter for PoisonRing {
+        fn submit(&mut self) {
+            self.0.submit();
+        }
+    }
+
+    impl FullRing<MockEntry, MockEntry, QueueSubmissions<MockEntry>, Completions<MockEntry>>
+        for PoisonRing
+    {
+        fn completion(&mut self) -> Completions<MockEntry> {
+            self.0.completion()
+        }
+
+        fn submission(&mut self) -> QueueSubmissions<MockEntry> {
+            self.0.submission()
+        }
+
+        fn interpret(&self, cqe: MockEntry) -> Result<MockEntry, BackendError> {
+            assert_ne!(cqe.tag(), Self::POISON, "poisoned completion");
+            Ok(cqe)
+        }
+    }
+
+    #[test]
+    fn a_panicking_interpret_fails_only_its_own_completion() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder().build_driver(PoisonRing(ring.clone()));
+
+        let submissions: Vec<_> = (1..=3)
+            .map(|tag| sender.submit(MockEntry::new(tag)))
+            .collect();
+        while driver.try_step() == StepOutcome::Handled {}
+        assert_eq!(ring.complete_all(), 3);
+        sender.reap();
+        while driver.try_step() == StepOutcome::Handled {}
+
+        let results: Vec<_> = submissions.into_iter().map(|s| s.wait()).collect();
+        assert_eq!(results[0].as_ref().unwrap().tag(), 1);
+        assert!(matches!(results[1], Err(RingError::Backend(_))));
+        assert_eq!(results[2].as_ref().unwrap().tag(), 3);
+        assert_eq!(sender.errored_completions(), 1);
+    }
+
+    #[test]
+    fn uncontained_completion_panics_take_the_ring_thread_down() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder()
+            .contain_completion_panics(false)
+            .build_driver(PoisonRing(ring.clone()));
+
+        let poisoned = sender.submit(MockEntry::new(PoisonRing::POISON));
+        while driver.try_step() == StepOutcome::Handled {}
+        ring.complete_all();
+        sender.reap();
+        drop(sender);
+        assert!(thread::spawn(move || driver.run()).join().is_err());
+        assert_eq!(poisoned.wait().unwrap_err(), RingError::Disconnected);
+    }
+
+    /// A backend that completes one entry each time it is waited on.
+    struct WaitingRing(MockRing<MockEntry, MockEntry>);
+
+    impl Submitter for WaitingRing {
+        fn submit(&mut self) {
+            self.0.submit();
+        }
+
+        fn submit_and_wait(&mut self, _: usize) {
+            self.0.submit();
+            self.0.complete_next();
+        }
+    }
+
+    impl FullRing<MockEntry, MockEntry, QueueSubmissions<MockEntry>, Completions<MockEntry>>
+        for WaitingRing
+    {
+        fn completion(&mut self) -> Completions<MockEntry> {
+            self.0.completion()
+        }
+
+        fn submission(&mut self) -> QueueSubmissions<MockEntry> {
+            self.0.submission()
+        }
+    }
+
+    #[test]
+    fn draining_on_shutdown_delivers_what_is_outstanding() {
+        let (sender, mut driver) = PRingSender::builder()
+            .drain_on_shutdown(Duration::from_secs(5))
+            .build_driver(WaitingRing(MockRing::echo()));
+
+        let submissions: Vec<_> = (1..=3)
+            .map(|tag| sender.submit(MockEntry::new(tag)))
+            .collect();
+        while driver.try_step() == StepOutcome::Handled {}
+        drop(sender);
+        driver.run();
+
+        let tags: Vec<_> = submissions
+            .into_iter()
+            .map(|submission| submission.wait().unwrap().tag())
+            .collect();
+        assert_eq!(tags, [1, 2, 3]);
+    }
+
+    #[test]
+    fn shutting_down_without_draining_disconnects_what_is_outstanding() {
+        let (sender, mut driver) =
+            PRingSender::builder().build_driver(WaitingRing(MockRing::echo()));
+
+        let submission = sender.submit(MockEntry::new(1));
+        while driver.try_step() == StepOutcome::Handled {}
+        drop(sender);
+        driver.run();
+        assert_eq!(submission.wait().unwrap_err(), RingError::Disconnected);
+    }
+
+    #[test]
+    fn draining_gives_up_at_its_timeout() {
+        let (sender, mut driver) = PRingSender::builder()
+            .drain_on_shutdown(Duration::from_millis(20))
+            .build_driver(MockRing::echo());
+
+        let submission = sender.submit(MockEntry::new(1));
+        while driver.try_step() == StepOutcome::Handled {}
+        drop(sender);
+        // The mock never completes the entry on its own.
+        driver.run();
+        assert_eq!(submission.wait().unwrap_err(), RingError::Disconnected);
+    }
+
+    /// An SQ recording the slot of every entry pushed into one, and refusing the first such push.
+    struct SlottedSubmissions(QueueSubmissions<MockEntry>, Arc<Mutex<Vec<(usize, u64)>>>);
+
+    impl SubmissionQueue<MockEntry> for SlottedSubmissions {
+        fn push(&mut self, entry: MockEntry) -> Result<(), MockEntry> {
+            self.0.push(entry)
+        }
+
+        fn push_at(&mut self, index: usize, entry: MockEntry) -> Result<(), MockEntry> {
+            let mut slotted = lock(&self.1);
+            slotted.push((index, entry.tag()));
+            if slotted.len() == 1 {
+                return Err(entry);
+            }
+
+            self.0.push(entry)
+        }
+    }
+
+    /// A mock backend behind a [`SlottedSubmissions`] SQ.
+    struct SlottedRing(
+        MockRing<MockEntry, MockEntry>,
+        Arc<Mutex<Vec<(usize, u64)>>>,
+    );
+
+    impl Submitter for SlottedRing {
+        fn submit(&mut self) {
+            self.0.submit();
+        }
+    }
+
+    impl FullRing<MockEntry, MockEntry, SlottedSubmissions, Completions<MockEntry>> for SlottedRing {
+        fn completion(&mut self) -> Completions<MockEntry> {
+            self.0.completion()
+        }
+
+        fn submission(&mut self) -> SlottedSubmissions {
+            SlottedSubmissions(self.0.submission(), Arc::clone(&self.1))
+        }
+    }
+
+    #[test]
+    fn slotted_entries_are_pushed_into_their_slot() {
+        let ring = MockRing::echo();
+        let slotted = Arc::default();
+        let (sender, mut driver) =
+            PRingSender::builder().build_driver(SlottedRing(ring.clone(), Arc::clone(&slotted)));
+
+        let appended = sender.submit(MockEntry::new(1));
+        let placed = sender.submit_at(MockEntry::new(2), 5);
+        while driver.try_step() == StepOutcome::Handled {}
+
+        // The slot was taken at first, so the entry was pushed again once the ring had submitted.
+        assert_eq!(*lock(&slotted), [(5, 2), (5, 2)]);
+        assert_eq!(ring.in_flight(), [appended.user_data(), placed.user_data()]);
+
+        assert_eq!(ring.complete_all(), 2);
+        sender.reap();
+        while driver.try_step() == StepOutcome::Handled {}
+        assert_eq!(placed.wait().unwrap().tag(), 2);
+    }
+
+    /// A backend whose CQ reports the overflows counted by its counter.
+    struct OverflowRing(MockRing<MockEntry, MockEntry>, Arc<AtomicU64>);
+
+    impl Submitter for OverflowRing {
+        fn submit(&mut self) {
+            self.0.submit();
+        }
+    }
+
+    impl FullRing<MockEntry, MockEntry, QueueSubmissions<MockEntry>, Completions<MockEntry>>
+        for OverflowRing
+    {
+        fn completion(&mut self) -> Completions<MockEntry> {
+            self.0.completion()
+        }
+
+        fn submission(&mut self) -> QueueSubmissions<MockEntry> {
+            self.0.submission()
+        }
+
+        fn overflow_count(&self) -> u64 {
+            self.1.load(Ordering::SeqCst)
+        }
+    }
+
+    #[test]
+    fn lost_completions_are_counted_and_optionally_fail_the_oldest_entries() {
+        for fail in [false, true] {
+            let overflowed = Arc::new(AtomicU64::new(5));
+            let mut builder = PRingSender::builder();
+            if fail {
+                builder = builder.fail_lost_completions();
+            }
+            let (sender, mut driver) =
+                builder.build_driver(OverflowRing(MockRing::echo(), Arc::clone(&overflowed)));
+
+            let submissions: Vec<_> = (1..=3)
+                .map(|tag| sender.submit(MockEntry::new(tag)))
+                .collect();
+            while driver.try_step() == StepOutcome::Handled {}
+            // Overflows from before the ring thread started are not counted.
+            assert_eq!(sender.lost_completions(), 0);
+
+            overflowed.fetch_add(2, Ordering::SeqCst);
+            sender.reap();
+            while driver.try_step() == StepOutcome::Handled {}
+            assert_eq!(sender.lost_completions(), 2);
+
+            let lost: Vec<_> = submissions
+                .iter()
+                .map(|submission| submission.try_take().map(Result::unwrap_err))
+                .collect();
+            if fail {
+                let lost_entry = Some(RingError::CompletionLost);
+                assert_eq!(lost, [lost_entry.clone(), lost_entry, None]);
+            } else {
+                assert_eq!(lost, [None, None, None]);
+            }
+        }
+    }
+
+    #[test]
+    fn last_error_keeps_the_latest_failure_but_not_cancellations() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder().build_driver(CheckedRing(ring.clone()));
+        assert!(sender.last_error().is_none());
+
+        let before = Instant::now();
+        let _refused = sender.submit(MockEntry::new(0));
+        let cancelled = sender.submit(MockEntry::new(1));
+        sender.send(Signal::Cancel(cancelled.user_data()));
+        while driver.try_step() == StepOutcome::Handled {}
+        assert_eq!(cancelled.wait().unwrap_err(), RingError::Cancelled);
+
+        let (err, at) = sender.last_error().unwrap();
+        assert_eq!(err, RingError::Invalid(ValidationError::Unsupported));
+        assert!(at >= before);
+    }
+}
@@ -0,0 +1,123 @@
+//! A consistent snapshot of the ring thread's state, for incident debugging.
+//!
+//! [`PRingSender::dump`](crate::PRingSender::dump) has the ring thread itself gather everything the sender's
+//! introspection methods expose piecemeal, between two signals. No counter moves while the [`RingDump`] is taken, so
+//! e.g. the in-flight count always matches the outstanding entries listed. Its `Display` implementation renders it
+//! as a multi-line report, ready to be logged.
+
+use std::{fmt, time::Instant};
+
+use crate::{error::RingError, registry::Outstanding};
+
+/// Everything about a ring thread's state at one point in time.
+#[derive(Debug, Clone)]
+pub struct RingDump {
+    /// When the ring thread took the snapshot.
+    pub taken_at: Instant,
+    /// Entries registered with the ring thread but not yet delivered.
+    pub in_flight: usize,
+    /// Those entries, longest pending first.
+    pub outstanding: Vec<Outstanding>,
+    /// The most entries in flight at once since the ring started or the peak was last reset.
+    pub peak_in_flight: usize,
+    /// Entries pushed onto the SQ but not yet submitted.
+    pub deferred: usize,
+    /// Entries handed to the backend so far.
+    pub submitted: u64,
+    /// Completions delivered so far.
+    pub completed: u64,
+    /// Times an entry found the SQ full and had to wait for room.
+    pub sq_full_retries: u64,
+    /// The most recent error the ring thread ran into, with when it did.
+    pub last_error: Option<(RingError, Instant)>,
+    /// The configured [`ReapStrategy`](crate::reap::ReapStrategy), as it describes itself.
+    pub reap_strategy: String,
+}
+
+impl fmt::Display for RingDump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "ring thread state:")?;
+        writeln!(
+            f,
+            "  in flight: {} (peak {}), deferred: {}",
+            self.in_flight, self.peak_in_flight, self.deferred
+        )?;
+        writeln!(
+            f,
+            "  submitted: {}, completed: {}, SQ-full retries: {}",
+            self.submitted, self.completed, self.sq_full_retries
+        )?;
+        writeln!(f, "  reap strategy: {}", self.reap_strategy)?;
+        match &self.last_error {
+            Some((err, at)) => writeln!(
+                f,
+                "  last error: {err} ({:?} before the dump)",
+                self.taken_at.saturating_duration_since(*at)
+            )?,
+            None => writeln!(f, "  last error: none")?,
+        }
+
+        write!(f, "  outstanding:")?;
+        if self.outstanding.is_empty() {
+            write!(f, " none")?;
+        }
+        for outstanding in &self.outstanding {
+            write!(
+                f,
+                "\n    user data {}, pending for {:?}",
+                outstanding.user_data, outstanding.pending_for
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        PRingSender,
+        driver::StepOutcome,
+        reap::EveryN,
+        test_util::{MockEntry, MockRing},
+    };
+
+    #[test]
+    fn the_dump_agrees_with_the_ring_state() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder()
+            .reap_strategy(EveryN(8))
+            .build_driver(ring.clone());
+
+        let first = sender.submit(MockEntry::new(1));
+        let second = sender.submit(MockEntry::new(2));
+        let dump = sender.dump();
+        while driver.try_step() == StepOutcome::Handled {}
+
+        let dump = dump.wait_nopanic().unwrap();
+        assert_eq!(dump.in_flight, 2);
+        let outstanding: Vec<_> = dump
+            .outstanding
+            .iter()
+            .map(|entry| entry.user_data)
+            .collect();
+        assert_eq!(outstanding, [first.user_data(), second.user_data()]);
+        assert_eq!(dump.completed, 0);
+        assert_eq!(dump.reap_strategy, "EveryN(8)");
+        assert!(dump.last_error.is_none());
+
+        let report = dump.to_string();
+        assert!(report.contains("in flight: 2 (peak 2)"), "{report}");
+        assert!(report.contains("reap strategy: EveryN(8)"), "{report}");
+
+        ring.complete_all();
+        sender.reap();
+        let dump = sender.dump();
+        while driver.try_step() == StepOutcome::Handled {}
+
+        let dump = dump.wait_nopanic().unwrap();
+        assert_eq!(dump.in_flight, 0);
+        assert_eq!(dump.completed, 2);
+        assert!(dump.to_string().ends_with("outstanding: none"));
+    }
+}
@@ -0,0 +1,198 @@
+//! Error types for ring submissions and backends.
+
+use std::{error::Error, fmt, io};
+
+use oneshot::{RecvError, RecvTimeoutError};
+
+/// The reason a submission did not resolve to a completion.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RingError {
+    /// The ring thread dropped the submission without completing it.
+    Disconnected,
+    /// The submission was cancelled before its completion arrived.
+    Cancelled,
+    /// The completion did not arrive in time.
+    Timeout,
+    /// The backend reported the operation as failed.
+    Backend(BackendError),
+    /// The submission was rejected because the ring's queues are full.
+    AtCapacity,
+    /// The submission queue refused the entry as malformed, so it was never pushed.
+    Invalid(ValidationError),
+    /// The completion reported an error and was handed to the ring's error sink instead.
+    Diverted,
+    /// The ring thread had already exited, e.g. after a shutdown or a panic, so the submission was never sent.
+    ThreadGone,
+    /// Another outstanding submission was already registered under the entry's user data, so it was never pushed.
+    DuplicateUserData,
+    /// The CQ overflowed and is presumed to have dropped the completion. See
+    /// [`PRingBuilder::fail_lost_completions`](crate::builder::PRingBuilder::fail_lost_completions).
+    CompletionLost,
+}
+
+impl fmt::Display for RingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Disconnected => f.write_str("submission dropped by the ring thread"),
+            Self::Cancelled => f.write_str("submission cancelled"),
+            Self::Timeout => f.write_str("timed out waiting for completion"),
+            Self::Backend(err) => write!(f, "backend error: {err}"),
+            Self::AtCapacity => f.write_str("ring at capacity"),
+            Self::Invalid(err) => write!(f, "invalid entry: {err}"),
+            Self::Diverted => f.write_str("errored completion diverted to the error sink"),
+            Self::ThreadGone => f.write_str("ring thread has exited"),
+            Self::DuplicateUserData => {
+                f.write_str("user data already in use by an outstanding submission")
+            }
+            Self::CompletionLost => f.write_str("completion presumed dropped by an overflowing CQ"),
+        }
+    }
+}
+
+impl Error for RingError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Backend(err) => Some(err),
+            Self::Invalid(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<BackendError> for RingError {
+    fn from(err: BackendError) -> Self {
+        Self::Backend(err)
+    }
+}
+
+impl From<ValidationError> for RingError {
+    fn from(err: ValidationError) -> Self {
+        Self::Invalid(err)
+    }
+}
+
+impl From<RecvError> for RingError {
+    fn from(_: RecvError) -> Self {
+        Self::Disconnected
+    }
+}
+
+impl From<RecvTimeoutError> for RingError {
+    fn from(err: RecvTimeoutError) -> Self {
+        match err {
+            RecvTimeoutError::Timeout => Self::Timeout,
+            RecvTimeoutError::Disconnected => Self::Disconnected,
+        }
+    }
+}
+
+/// The reason a sender handed an entry back instead of submitting it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubmitError {
+    /// The ring's queues or completion pool are full.
+    AtCapacity,
+    /// The ring thread has exited.
+    Disconnected,
+}
+
+impl fmt::Display for SubmitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AtCapacity => f.write_str("ring at capacity"),
+            Self::Disconnected => f.write_str("ring thread has exited"),
+        }
+    }
+}
+
+impl Error for SubmitError {}
+
+/// A failure reported by a ring backend.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BackendError {
+    /// An OS error code, e.g. the negated result of an io_uring completion.
+    Os(i32),
+    /// The backend does not support the requested operation.
+    Unsupported,
+    /// Any other backend-specific failure.
+    Other(String),
+}
+
+impl fmt::Display for BackendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Os(code) => write!(f, "{}", io::Error::from_raw_os_error(*code)),
+            Self::Unsupported => f.write_str("operation not supported by the backend"),
+            Self::Other(message) => f.write_str(message),
+        }
+    }
+}
+
+impl Error for BackendError {}
+
+/// The reason a submission queue refused an entry before it was pushed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    /// The entry's opcode is not supported by the backend.
+    Unsupported,
+    /// The entry refers to a file descriptor the backend cannot use.
+    BadDescriptor,
+    /// A buffer, length or offset of the entry is not aligned as the backend requires.
+    Misaligned,
+    /// Any other backend-specific constraint.
+    Other(String),
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unsupported => f.write_str("operation not supported by the backend"),
+            Self::BadDescriptor => f.write_str("bad file descriptor"),
+            Self::Misaligned => f.write_str("misaligned buffer, length or offset"),
+            Self::Other(message) => f.write_str(message),
+        }
+    }
+}
+
+impl Error for ValidationError {}
+
+/// The outcome of a [split submission](crate::PRingSender::submit_split) in which at least one chunk failed.
+///
+/// Carries the outcome of every chunk in offset order, so the chunks that did complete can still be used, e.g. to
+/// retry only the failed ranges.
+#[derive(Debug)]
+pub struct SplitError<C> {
+    /// The completion or error of every chunk, in offset order.
+    pub chunks: Vec<Result<C, RingError>>,
+}
+
+impl<C> SplitError<C> {
+    /// The index and error of every failed chunk, in offset order.
+    pub fn failed(&self) -> impl Iterator<Item = (usize, &RingError)> {
+        self.chunks
+            .iter()
+            .enumerate()
+            .filter_map(|(index, chunk)| Some((index, chunk.as_ref().err()?)))
+    }
+}
+
+impl<C> fmt::Display for SplitError<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut failed = self.failed();
+        let Some((index, err)) = failed.next() else {
+            return f.write_str("split submission failed");
+        };
+
+        write!(
+            f,
+            "{} of {} chunks failed, first chunk {index}: {err}",
+            failed.count() + 1,
+            self.chunks.len()
+        )
+    }
+}
+
+impl<C: fmt::Debug> Error for SplitError<C> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.failed().next().map(|(_, err)| err as _)
+    }
+}
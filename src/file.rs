@@ -0,0 +1,350 @@
+//! Synchronous `Read` and `Write` over a single file descriptor, backed by the ring.
+//!
+//! A [`RingFile`] turns every `read` and `write` call into one entry built by the backend's [`FileEntry`], submits it
+//! with [`PRingSender::submit_sync`] and blocks until it completes. It is a drop-in handle for code written against
+//! `std::io`, e.g. to be wrapped in a `BufReader`, at the cost of one round trip through the ring thread per call.
+//!
+//! Entries own their buffer, so the backend can keep it alive until the operation completes, and hand it back in
+//! the completion:
+//!
+//! ```no_run
+//! # use std::{io, os::fd::RawFd};
+//! # use ring_promise::{
+//! #     file::{FileCompletion, FileEntry},
+//! #     traits::{CompletionQueueEntry, SubmissionQueueEntry, UserData},
+//! # };
+//! # struct Sqe(UserData);
+//! # impl Sqe {
+//! #     fn read(_: RawFd, _: Vec<u8>, _: u64) -> Self { Self(UserData::new(0)) }
+//! #     fn write(_: RawFd, _: Vec<u8>, _: u64) -> Self { Self(UserData::new(0)) }
+//! # }
+//! # impl SubmissionQueueEntry for Sqe {
+//! #     fn set_ud(&mut self, ud: UserData) { self.0 = ud; }
+//! #     fn get_ud(&self) -> UserData { self.0 }
+//! # }
+//! # struct Cqe { ud: UserData, res: i32, buf: Vec<u8> }
+//! # impl CompletionQueueEntry for Cqe {
+//! #     fn get_ud(&self) -> UserData { self.ud }
+//! # }
+//! impl FileEntry for Sqe {
+//!     fn read(fd: RawFd, buf: Vec<u8>, offset: u64) -> Self {
+//!         Sqe::read(fd, buf, offset)
+//!     }
+//!     fn write(fd: RawFd, buf: Vec<u8>, offset: u64) -> Self {
+//!         Sqe::write(fd, buf, offset)
+//!     }
+//! }
+//!
+//! impl FileCompletion for Cqe {
+//!     fn result(&self) -> io::Result<usize> {
+//!         usize::try_from(self.res).map_err(|_| io::Error::from_raw_os_error(-self.res))
+//!     }
+//!     fn into_buf(self) -> Vec<u8> {
+//!         self.buf
+//!     }
+//! }
+//! ```
+
+use std::{
+    io::{self, Read, Write},
+    os::fd::RawFd,
+};
+
+use crate::{
+    PRingSender,
+    error::{BackendError, RingError},
+    traits::{CompletionQueueEntry as CQE, SubmissionQueueEntry as SQE},
+};
+
+/// Builds the read and write entries a [`RingFile`] submits.
+pub trait FileEntry: SQE {
+    /// An entry reading up to `buf.len()` bytes from `fd` at `offset` into `buf`.
+    ///
+    /// Descriptors without a position, e.g. sockets and pipes, ignore `offset`.
+    fn read(fd: RawFd, buf: Vec<u8>, offset: u64) -> Self;
+
+    /// An entry writing the whole of `buf` to `fd` at `offset`, or as much of it as the descriptor takes.
+    ///
+    /// Descriptors without a position, e.g. sockets and pipes, ignore `offset`.
+    fn write(fd: RawFd, buf: Vec<u8>, offset: u64) -> Self;
+}
+
+/// Reports the outcome of a [`FileEntry`]'s operation.
+pub trait FileCompletion: CQE {
+    /// The number of bytes read or written, or why the operation failed.
+    fn result(&self) -> io::Result<usize>;
+
+    /// The buffer the entry was built with, holding the data read for a read.
+    fn into_buf(self) -> Vec<u8>;
+}
+
+/// A file descriptor read and written through the ring.
+///
+/// Keeps its own offset, advanced by every byte read or written, starting at zero unless set with
+/// [`seek_to`](Self::seek_to). Does not own the descriptor: closing it is left to the caller, once the handle is
+/// dropped.
+pub struct RingFile<S: SQE, C: CQE> {
+    /// Submits the entries.
+    sender: PRingSender<S, C>,
+    /// The descriptor read and written.
+    fd: RawFd,
+    /// Where the next read or write starts.
+    offset: u64,
+    /// The buffer handed back by the last completion, reused by the next entry.
+    spare: Vec<u8>,
+}
+
+impl<S: SQE, C: CQE> RingFile<S, C> {
+    /// Reads and writes `fd` through the ring `sender` submits to.
+    pub fn new(sender: PRingSender<S, C>, fd: RawFd) -> Self {
+        Self {
+            sender,
+            fd,
+            offset: 0,
+            spare: Vec::new(),
+        }
+    }
+
+    /// The descriptor read and written.
+    #[inline]
+    pub fn fd(&self) -> RawFd {
+        self.fd
+    }
+
+    /// Where the next read or write starts.
+    #[inline]
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Moves the next read or write to `offset`.
+    #[inline]
+    pub fn seek_to(&mut self, offset: u64) {
+        self.offset = offset;
+    }
+
+    /// The sender the entries are submitted with.
+    #[inline]
+    pub fn sender(&self) -> &PRingSender<S, C> {
+        &self.sender
+    }
+
+    /// Takes the spare buffer, resized to `len` bytes.
+    fn buffer(&mut self, len: usize) -> Vec<u8> {
+        let mut buf = std::mem::take(&mut self.spare);
+        buf.clear();
+        buf.resize(len, 0);
+        buf
+    }
+
+    /// Submits `entry` and waits for it, advancing the offset by the bytes transferred. Returns how many there were,
+    /// along with the buffer handed back.
+    fn transfer(&mut self, entry: S) -> io::Result<(usize, Vec<u8>)>
+    where
+        C: FileCompletion,
+    {
+        let completion = self.sender.submit_sync(entry).map_err(io_error)?;
+        let transferred = completion.result()?;
+        self.offset += transferred as u64;
+
+        Ok((transferred, completion.into_buf()))
+    }
+}
+
+impl<S: FileEntry, C: FileCompletion> Read for RingFile<S, C> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let entry = S::read(self.fd, self.buffer(buf.len()), self.offset);
+        let (read, data) = self.transfer(entry)?;
+        // A backend reporting more than it was asked for cannot have put it in the buffer.
+        let read = read.min(buf.len()).min(data.len());
+        buf[..read].copy_from_slice(&data[..read]);
+        self.spare = data;
+
+        Ok(read)
+    }
+}
+
+impl<S: FileEntry, C: FileCompletion> Write for RingFile<S, C> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let mut data = self.buffer(0);
+        data.extend_from_slice(buf);
+        let entry = S::write(self.fd, data, self.offset);
+        let (written, data) = self.transfer(entry)?;
+        self.spare = data;
+
+        Ok(written.min(buf.len()))
+    }
+
+    /// Does nothing: every write has completed by the time it returns.
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Converts a failed submission to the error `std::io` callers expect.
+fn io_error(err: RingError) -> io::Error {
+    match err {
+        RingError::Backend(BackendError::Os(code)) => io::Error::from_raw_os_error(code),
+        RingError::Backend(BackendError::Unsupported) => {
+            io::Error::new(io::ErrorKind::Unsupported, err)
+        }
+        RingError::Timeout => io::Error::new(io::ErrorKind::TimedOut, err),
+        err => io::Error::other(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::{
+        adapters::{Completions, QueueSubmissions},
+        test_util::MockRing,
+        traits::{FullRing, Submitter, UserData},
+    };
+
+    /// A read or write against an in-memory file, serving as both entry and completion.
+    struct FileOp {
+        ud: UserData,
+        fd: RawFd,
+        write: bool,
+        buf: Vec<u8>,
+        offset: u64,
+        /// Bytes transferred, or the OS error code, once performed.
+        result: Result<usize, i32>,
+    }
+
+    impl FileOp {
+        fn new(fd: RawFd, write: bool, buf: Vec<u8>, offset: u64) -> Self {
+            Self {
+                ud: UserData::new(0),
+                fd,
+                write,
+                buf,
+                offset,
+                result: Ok(0),
+            }
+        }
+
+        /// Performs the operation on `file`, the only file behind descriptor 3.
+        fn perform(mut self, file: &mut Vec<u8>) -> Self {
+            if self.fd != 3 {
+                self.result = Err(9);
+                return self;
+            }
+
+            let offset = self.offset as usize;
+            if self.write {
+                if file.len() < offset + self.buf.len() {
+                    file.resize(offset + self.buf.len(), 0);
+                }
+                file[offset..offset + self.buf.len()].copy_from_slice(&self.buf);
+                self.result = Ok(self.buf.len());
+            } else {
+                let read = file.len().saturating_sub(offset).min(self.buf.len());
+                self.buf[..read].copy_from_slice(&file[offset..offset + read]);
+                self.result = Ok(read);
+            }
+            self
+        }
+    }
+
+    impl SQE for FileOp {
+        fn set_ud(&mut self, ud: UserData) {
+            self.ud = ud;
+        }
+
+        fn get_ud(&self) -> UserData {
+            self.ud
+        }
+    }
+
+    impl CQE for FileOp {
+        fn get_ud(&self) -> UserData {
+            self.ud
+        }
+    }
+
+    impl FileEntry for FileOp {
+        fn read(fd: RawFd, buf: Vec<u8>, offset: u64) -> Self {
+            Self::new(fd, false, buf, offset)
+        }
+
+        fn write(fd: RawFd, buf: Vec<u8>, offset: u64) -> Self {
+            Self::new(fd, true, buf, offset)
+        }
+    }
+
+    impl FileCompletion for FileOp {
+        fn result(&self) -> io::Result<usize> {
+            self.result.map_err(io::Error::from_raw_os_error)
+        }
+
+        fn into_buf(self) -> Vec<u8> {
+            self.buf
+        }
+    }
+
+    /// A backend performing every operation as soon as it is submitted.
+    struct FileRing(MockRing<FileOp, FileOp>);
+
+    impl Submitter for FileRing {
+        fn submit(&mut self) {
+            self.submit_counted();
+        }
+
+        fn submit_counted(&mut self) -> Option<usize> {
+            let submitted = self.0.submit_counted();
+            self.0.complete_all();
+            submitted
+        }
+    }
+
+    impl FullRing<FileOp, FileOp, QueueSubmissions<FileOp>, Completions<FileOp>> for FileRing {
+        fn completion(&mut self) -> Completions<FileOp> {
+            self.0.completion()
+        }
+
+        fn submission(&mut self) -> QueueSubmissions<FileOp> {
+            self.0.submission()
+        }
+    }
+
+    fn sender() -> PRingSender<FileOp, FileOp> {
+        let file = Mutex::new(Vec::new());
+        let ring = MockRing::new(move |op: FileOp| op.perform(&mut file.lock().unwrap()));
+        PRingSender::builder().build(FileRing(ring))
+    }
+
+    #[test]
+    fn reads_see_what_was_written() {
+        let mut file = RingFile::new(sender(), 3);
+
+        file.write_all(b"hello world").unwrap();
+        assert_eq!(file.offset(), 11);
+
+        file.seek_to(6);
+        let mut contents = String::new();
+        file.read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "world");
+        assert_eq!(file.offset(), 11);
+    }
+
+    #[test]
+    fn failed_operations_surface_as_io_errors() {
+        let mut file = RingFile::new(sender(), 4);
+
+        let err = file.read(&mut [0; 4]).unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(9));
+        assert_eq!(file.offset(), 0);
+    }
+}
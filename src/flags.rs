@@ -0,0 +1,58 @@
+//! Backend-defined submission flags.
+//!
+//! `SubmitFlags` carries flag bits from a generic caller to the ring thread, which applies them to the entry with
+//! [`SubmissionQueueEntry::set_flags`](crate::traits::SubmissionQueueEntry::set_flags) before pushing it. What each
+//! bit means is up to the backend, e.g. `IOSQE_ASYNC` or `IOSQE_FIXED_FILE` for io_uring.
+
+use std::ops::{BitOr, BitOrAssign};
+
+/// A set of backend-defined submission flags.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct SubmitFlags(u32);
+
+impl SubmitFlags {
+    /// The empty flag set.
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Creates a flag set from raw bits.
+    pub const fn from_bits(bits: u32) -> Self {
+        Self(bits)
+    }
+
+    /// The raw bits of this flag set.
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Whether no flags are set.
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Whether every flag in `other` is also set in `self`.
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl From<u32> for SubmitFlags {
+    fn from(bits: u32) -> Self {
+        Self(bits)
+    }
+}
+
+impl BitOr for SubmitFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for SubmitFlags {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
@@ -0,0 +1,151 @@
+//! Holding submissions back until the application is ready for them.
+//!
+//! A `Gate` can be shared between any number of submissions. Entries submitted with
+//! [`PRingSender::submit_deferred`](crate::PRingSender::submit_deferred) wait on the ring thread, registered but not
+//! pushed, until the gate is [`open`](Gate::open)ed, e.g. once the configuration the reads depend on has loaded. They
+//! are then pushed together, in the order they were submitted. Unlike a
+//! [`barrier`](crate::PRingSender::barrier), which waits for entries already submitted, a gate keeps entries from
+//! reaching the backend in the first place.
+
+use std::sync::{
+    Arc, Mutex,
+    atomic::{AtomicU64, Ordering},
+};
+
+use crate::registry::lock;
+
+/// Source of gate ids, unique across every ring in the process.
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// A hook run when the gate opens.
+type Hook = Box<dyn FnOnce() + Send + 'static>;
+
+/// A cloneable, thread-safe signal releasing the entries held behind it.
+///
+/// Clones share the same state: opening one opens all of them. A gate never closes again.
+#[derive(Clone)]
+pub struct Gate {
+    /// Identifies the gate's held entries on the ring thread.
+    id: u64,
+    /// Hooks to run on opening, or `None` once open.
+    hooks: Arc<Mutex<Option<Vec<Hook>>>>,
+}
+
+impl Default for Gate {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Gate {
+    /// Creates a closed gate.
+    pub fn new() -> Self {
+        Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            hooks: Arc::new(Mutex::new(Some(Vec::new()))),
+        }
+    }
+
+    /// Opens the gate, releasing every entry held behind it. Only the first call has any effect.
+    pub fn open(&self) {
+        let hooks = lock(&self.hooks).take();
+
+        // Hooks run outside the lock so they are free to touch the gate.
+        hooks.into_iter().flatten().for_each(|hook| hook());
+    }
+
+    /// Returns whether the gate has been opened.
+    pub fn is_open(&self) -> bool {
+        lock(&self.hooks).is_none()
+    }
+
+    /// Identifies the gate's held entries on the ring thread.
+    #[inline]
+    pub(crate) fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Runs `hook` on opening, or immediately if the gate is already open.
+    pub(crate) fn on_open(&self, hook: Hook) {
+        let mut hooks = lock(&self.hooks);
+        match hooks.as_mut() {
+            Some(hooks) => hooks.push(hook),
+            None => {
+                drop(hooks);
+                hook();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+    use crate::{
+        PRingSender,
+        driver::StepOutcome,
+        error::RingError,
+        test_util::{MockEntry, MockRing},
+    };
+
+    #[test]
+    fn entries_are_held_until_the_gate_opens() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder().build_driver(ring.clone());
+        let gate = Gate::new();
+
+        let first = sender.submit_deferred(MockEntry::new(1), &gate);
+        let second = sender.submit_deferred(MockEntry::new(2), &gate);
+        while driver.try_step() == StepOutcome::Handled {}
+        assert!(ring.in_flight().is_empty());
+        assert_eq!(sender.outstanding().len(), 2);
+
+        // Each held entry asks for the gate to be opened, so there is more than one signal to handle.
+        gate.open();
+        while driver.try_step() == StepOutcome::Handled {}
+        assert_eq!(ring.in_flight().len(), 2);
+
+        assert_eq!(ring.complete_all(), 2);
+        sender.reap();
+        while driver.try_step() == StepOutcome::Handled {}
+        assert_eq!(first.wait_nopanic().unwrap().tag(), 1);
+        assert_eq!(second.wait_nopanic().unwrap().tag(), 2);
+    }
+
+    #[test]
+    fn entries_go_straight_through_an_open_gate() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder().build_driver(ring.clone());
+        let gate = Gate::new();
+        gate.open();
+        assert!(gate.is_open());
+
+        let _submission = sender.submit_deferred(MockEntry::new(1), &gate);
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        assert_eq!(ring.in_flight().len(), 1);
+    }
+
+    #[test]
+    fn draining_resolves_entries_behind_a_closed_gate() {
+        let ring = MockRing::echo();
+        let (sender, driver) = PRingSender::builder()
+            .drain_on_shutdown(Duration::from_secs(5))
+            .build_driver(ring.clone());
+        let gate = Gate::new();
+
+        let held = sender.submit_deferred(MockEntry::new(1), &gate);
+        drop(sender);
+
+        // The drain does not wait out its timeout on an entry that can never be pushed.
+        let started = Instant::now();
+        driver.run();
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(ring.in_flight().is_empty());
+        assert_eq!(held.wait_nopanic(), Err(RingError::Disconnected));
+
+        // Opening the gate after the ring thread is gone does nothing.
+        gate.open();
+    }
+}
@@ -0,0 +1,433 @@
+//! Groups of submissions completing together.
+//!
+//! A `GroupHandle` collects the completions of every entry submitted into it with
+//! [`PRingSender::submit_grouped`](crate::PRingSender::submit_grouped). Entries can be added over time; once the
+//! group is [sealed](GroupHandle::seal) and its last entry completes, the group callback receives every completion,
+//! tagged with the user data of its entry.
+//!
+//! A [`CompletionGroup`] instead hands out its completions one by one as they arrive, through
+//! [`stream`](CompletionGroup::stream), for processing results as they come rather than all at once.
+
+use std::sync::{Arc, Mutex, MutexGuard, mpsc};
+
+use crate::{
+    PRingSender,
+    error::RingError,
+    flags::SubmitFlags,
+    registry::{Completer, Completion, lock},
+    traits::{CompletionQueueEntry as CQE, SubmissionQueueEntry as SQE, UserData},
+};
+
+/// The completions of a group's entries, tagged with their user data, in completion order.
+pub type GroupCompletions<C> = Vec<(UserData, Completion<C>)>;
+
+/// The callback run once a group completes.
+type GroupCallback<C> = Box<dyn FnOnce(GroupCompletions<C>) + Send + 'static>;
+
+/// The shared state of a group.
+struct GroupState<C: CQE> {
+    /// Entries added but not yet completed.
+    outstanding: usize,
+    /// Whether the group accepts no further entries.
+    sealed: bool,
+    /// Completions collected so far.
+    completions: GroupCompletions<C>,
+    /// The group callback, or `None` once it has run.
+    callback: Option<GroupCallback<C>>,
+}
+
+impl<C: CQE> GroupState<C> {
+    /// Takes the callback and the completions if the group has just completed.
+    fn take_if_done(&mut self) -> Option<(GroupCallback<C>, GroupCompletions<C>)> {
+        if !self.sealed || self.outstanding > 0 {
+            return None;
+        }
+
+        let callback = self.callback.take()?;
+        Some((callback, std::mem::take(&mut self.completions)))
+    }
+}
+
+/// A cloneable handle to a group of submissions.
+///
+/// Clones share the same group. Entries are added with
+/// [`PRingSender::submit_grouped`](crate::PRingSender::submit_grouped) until the group is sealed, after which the
+/// callback runs as soon as nothing is outstanding: on the thread delivering the last completion, or inline in
+/// [`seal`](Self::seal) if that happens to be the last step.
+pub struct GroupHandle<C: CQE> {
+    /// The state shared between clones and the ring thread.
+    state: Arc<Mutex<GroupState<C>>>,
+}
+
+impl<C: CQE> Clone for GroupHandle<C> {
+    fn clone(&self) -> Self {
+        Self {
+            state: Arc::clone(&self.state),
+        }
+    }
+}
+
+impl<C: CQE> GroupHandle<C> {
+    /// Creates an empty group whose `callback` receives every completion once the group is sealed and done.
+    pub fn new<F>(callback: F) -> Self
+    where
+        F: FnOnce(GroupCompletions<C>) + Send + 'static,
+    {
+        Self {
+            state: Arc::new(Mutex::new(GroupState {
+                outstanding: 0,
+                sealed: false,
+                completions: Vec::new(),
+                callback: Some(Box::new(callback)),
+            })),
+        }
+    }
+
+    /// Marks the group as complete once its outstanding entries are, running the callback now if none are.
+    ///
+    /// Only the first call has any effect.
+    pub fn seal(&self) {
+        let done = {
+            let mut state = lock(&self.state);
+            state.sealed = true;
+            state.take_if_done()
+        };
+
+        // The callback runs outside the lock so it is free to touch the group.
+        if let Some((callback, completions)) = done {
+            callback(completions);
+        }
+    }
+
+    /// Returns whether the group has been sealed.
+    #[inline]
+    pub fn is_sealed(&self) -> bool {
+        lock(&self.state).sealed
+    }
+
+    /// The number of entries added to the group that have not completed yet.
+    #[inline]
+    pub fn outstanding(&self) -> usize {
+        lock(&self.state).outstanding
+    }
+
+    /// Adds an entry tagged with `user_data`, returning the member delivering its completion.
+    ///
+    /// Returns `None` if the group has already been sealed.
+    pub(crate) fn join(&self, user_data: UserData) -> Option<GroupMember<C>> {
+        let mut state = lock(&self.state);
+        if state.sealed {
+            return None;
+        }
+
+        state.outstanding += 1;
+        Some(GroupMember {
+            user_data,
+            group: Some(Arc::clone(&self.state)),
+        })
+    }
+}
+
+/// Delivers one entry's completion to its group.
+///
+/// If the ring drops this without delivering, e.g. because the ring thread exited, the entry counts as completed
+/// with `RingError::Disconnected` so the group is not left waiting forever.
+pub(crate) struct GroupMember<C: CQE> {
+    /// The user data of the entry.
+    user_data: UserData,
+    /// The group, or `None` once delivered.
+    group: Option<Arc<Mutex<GroupState<C>>>>,
+}
+
+impl<C: CQE> GroupMember<C> {
+    /// Records `completion`, running the group callback if it was the last one outstanding.
+    pub(crate) fn deliver(mut self, completion: Completion<C>) {
+        self.finish(completion);
+    }
+
+    /// Records `completion` unless it has already been delivered.
+    fn finish(&mut self, completion: Completion<C>) {
+        let Some(group) = self.group.take() else {
+            return;
+        };
+
+        let done = {
+            let mut state = lock(&group);
+            state.outstanding -= 1;
+            state.completions.push((self.user_data, completion));
+            state.take_if_done()
+        };
+
+        if let Some((callback, completions)) = done {
+            callback(completions);
+        }
+    }
+}
+
+impl<C: CQE> Drop for GroupMember<C> {
+    fn drop(&mut self) {
+        self.finish(Err(RingError::Disconnected));
+    }
+}
+
+/// A completion streamed out of a [`CompletionGroup`], tagged with the user data of its entry.
+pub type GroupCompletion<C> = (UserData, Completion<C>);
+
+/// A group of submissions whose completions are streamed out as they arrive.
+///
+/// Entries can be [submitted](Self::submit) into the group over time, including while its completions are being
+/// consumed. The [`stream`](Self::stream) ends once the group is [sealed](Self::seal) and every completion has been
+/// yielded.
+pub struct CompletionGroup<S: SQE, C: CQE> {
+    /// Submits the group's entries.
+    sender: PRingSender<S, C>,
+    /// The sending end of the stream, cloned into every entry, or `None` once the group is sealed.
+    tx: Mutex<Option<mpsc::Sender<GroupCompletion<C>>>>,
+    /// The receiving end of the stream.
+    rx: Mutex<mpsc::Receiver<GroupCompletion<C>>>,
+}
+
+impl<S: SQE, C: CQE + 'static> CompletionGroup<S, C> {
+    /// Creates an empty group, submitting its entries through `sender`.
+    pub fn new(sender: PRingSender<S, C>) -> Self {
+        let (tx, rx) = mpsc::channel();
+
+        Self {
+            sender,
+            tx: Mutex::new(Some(tx)),
+            rx: Mutex::new(rx),
+        }
+    }
+
+    /// Submits `entry` into the group, returning its user data.
+    ///
+    /// # Errors
+    /// Hands `entry` back without submitting it if the group has already been sealed.
+    pub fn submit(&self, entry: S) -> Result<UserData, S> {
+        let Some(tx) = lock(&self.tx).clone() else {
+            return Err(entry);
+        };
+
+        let ud = self.sender.next_ud();
+        let member = StreamMember {
+            user_data: ud,
+            tx: Some(tx),
+        };
+        self.sender.enqueue_as(
+            ud,
+            entry,
+            Completer::Callback(Box::new(move |completion| member.deliver(completion))),
+            SubmitFlags::empty(),
+        );
+
+        Ok(ud)
+    }
+
+    /// Stops the group from taking further entries, so its stream ends once their completions have been yielded.
+    ///
+    /// Only the first call has any effect.
+    pub fn seal(&self) {
+        lock(&self.tx).take();
+    }
+
+    /// Returns whether the group has been sealed.
+    #[inline]
+    pub fn is_sealed(&self) -> bool {
+        lock(&self.tx).is_none()
+    }
+
+    /// The sender the group's entries are submitted with.
+    #[inline]
+    pub fn sender(&self) -> &PRingSender<S, C> {
+        &self.sender
+    }
+
+    /// Iterates over the completions of the group's entries as they arrive, in completion order.
+    ///
+    /// Blocks for the next completion while entries are outstanding or the group is unsealed, and ends once the group
+    /// is sealed and every completion has been yielded. Only one stream can be taken at a time: a second call blocks
+    /// until the first stream is dropped.
+    pub fn stream(&self) -> GroupStream<'_, C> {
+        GroupStream { rx: lock(&self.rx) }
+    }
+}
+
+/// The completions of a [`CompletionGroup`], as they arrive.
+///
+/// Returned by [`CompletionGroup::stream`].
+pub struct GroupStream<'a, C: CQE> {
+    /// The receiving end of the group's stream, held for as long as the stream lives.
+    rx: MutexGuard<'a, mpsc::Receiver<GroupCompletion<C>>>,
+}
+
+impl<C: CQE> GroupStream<'_, C> {
+    /// Takes the next completion if one has arrived, without blocking.
+    ///
+    /// Returns `None` both when nothing has arrived yet and once the stream has ended.
+    pub fn try_next(&mut self) -> Option<GroupCompletion<C>> {
+        self.rx.try_recv().ok()
+    }
+}
+
+impl<C: CQE> Iterator for GroupStream<'_, C> {
+    type Item = GroupCompletion<C>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.rx.recv().ok()
+    }
+}
+
+/// Streams one entry's completion out of its [`CompletionGroup`].
+///
+/// If the ring drops this without delivering, e.g. because the ring thread exited, the entry yields
+/// `RingError::Disconnected` so the stream is not left waiting forever.
+struct StreamMember<C: CQE> {
+    /// The user data of the entry.
+    user_data: UserData,
+    /// The sending end of the group's stream, or `None` once delivered.
+    tx: Option<mpsc::Sender<GroupCompletion<C>>>,
+}
+
+impl<C: CQE> StreamMember<C> {
+    /// Yields `completion` from the group's stream.
+    fn deliver(mut self, completion: Completion<C>) {
+        self.finish(completion);
+    }
+
+    /// Yields `completion` unless it has already been delivered.
+    fn finish(&mut self, completion: Completion<C>) {
+        if let Some(tx) = self.tx.take() {
+            let _ = tx.send((self.user_data, completion));
+        }
+    }
+}
+
+impl<C: CQE> Drop for StreamMember<C> {
+    fn drop(&mut self) {
+        self.finish(Err(RingError::Disconnected));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        driver::StepOutcome,
+        test_util::{MockEntry, MockRing},
+    };
+
+    /// The tags of `completions`, in the order given.
+    fn tags(completions: &[(UserData, Completion<MockEntry>)]) -> Vec<u64> {
+        completions
+            .iter()
+            .map(|(_, completion)| completion.as_ref().unwrap().tag())
+            .collect()
+    }
+
+    #[test]
+    fn groups_complete_once_sealed_and_done() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder().build_driver(ring.clone());
+        let (tx, rx) = mpsc::channel();
+        let group = GroupHandle::new(move |completions| tx.send(completions).unwrap());
+
+        let first = sender
+            .submit_grouped(MockEntry::new(1), &group)
+            .ok()
+            .unwrap();
+        let second = sender
+            .submit_grouped(MockEntry::new(2), &group)
+            .ok()
+            .unwrap();
+        while driver.try_step() == StepOutcome::Handled {}
+        assert_eq!(group.outstanding(), 2);
+
+        assert!(ring.complete_ud(second));
+        assert!(ring.complete_ud(first));
+        sender.reap();
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        // Done, but not sealed yet.
+        assert!(rx.try_recv().is_err());
+
+        group.seal();
+        assert!(sender.submit_grouped(MockEntry::new(3), &group).is_err());
+        let completions = rx.try_recv().unwrap();
+        assert_eq!(tags(&completions), [2, 1]);
+        assert_eq!(completions[0].0, second);
+    }
+
+    #[test]
+    fn dropped_members_count_as_disconnected() {
+        let (tx, rx) = mpsc::channel();
+        let group = GroupHandle::<MockEntry>::new(move |completions| tx.send(completions).unwrap());
+
+        let member = group.join(UserData::new(1)).unwrap();
+        group.seal();
+        assert!(rx.try_recv().is_err());
+
+        drop(member);
+        let completions = rx.try_recv().unwrap();
+        assert_eq!(completions.len(), 1);
+        assert_eq!(
+            completions[0],
+            (UserData::new(1), Err(RingError::Disconnected))
+        );
+        assert_eq!(group.outstanding(), 0);
+    }
+
+    #[test]
+    fn streams_yield_completions_in_completion_order() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder().build_driver(ring.clone());
+        let group = CompletionGroup::new(sender.clone());
+
+        let uds: Vec<_> = (1..=3)
+            .map(|tag| group.submit(MockEntry::new(tag)).ok().unwrap())
+            .collect();
+        while driver.try_step() == StepOutcome::Handled {}
+
+        let mut stream = group.stream();
+        assert!(stream.try_next().is_none());
+        for &ud in [uds[2], uds[0], uds[1]].iter() {
+            assert!(ring.complete_ud(ud));
+            sender.reap();
+            assert_eq!(driver.step(), StepOutcome::Handled);
+        }
+        group.seal();
+
+        let completions: Vec<_> = stream.collect();
+        assert_eq!(tags(&completions), [3, 1, 2]);
+    }
+
+    #[test]
+    fn sealed_streams_end_once_drained() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder().build_driver(ring.clone());
+        let group = CompletionGroup::new(sender.clone());
+
+        let completed = group.submit(MockEntry::new(1)).ok().unwrap();
+        let abandoned = group.submit(MockEntry::new(2)).ok().unwrap();
+        group.seal();
+        assert!(group.is_sealed());
+        assert_eq!(group.submit(MockEntry::new(3)).unwrap_err().tag(), 3);
+        while driver.try_step() == StepOutcome::Handled {}
+
+        assert!(ring.complete_ud(completed));
+        sender.reap();
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        // The ring thread exits with the second entry outstanding.
+        drop((sender, driver));
+
+        let streamed: Vec<_> = group
+            .stream()
+            .map(|(ud, completion)| (ud, completion.map(|cqe| cqe.tag())))
+            .collect();
+        assert_eq!(
+            streamed,
+            [
+                (completed, Ok(1)),
+                (abandoned, Err(RingError::Disconnected))
+            ]
+        );
+    }
+}
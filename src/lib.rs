@@ -12,47 +12,310 @@
 //! - Minimal dependencies
 
 use std::{
-    sync::mpsc::{Receiver, Sender, channel},
+    any::Any,
+    cmp::Reverse,
+    collections::VecDeque,
+    mem,
+    sync::{
+        Arc, Condvar, Mutex, Weak,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    task::{Context, Poll, Waker},
     thread,
+    time::{Duration, Instant},
 };
 
+use oneshot::RecvError;
+use promisery::Promise;
+
 /// Represents a signal sent to the ring thread.
 ///
-/// - `Entry(T, S)`: Submits an entry of type `T` with a sender for completion of type `S`.
+/// - `Entry(T, S, flags)`: Submits an entry of type `T` with a sender for completion of type `S`, applying `flags`.
+/// - `Retry(T, S, policy, copy)`: Like `Entry`, resubmitting copies of the entry on transient failures per `policy`.
+/// - `Timeout(T, S, timeout, ud)`: Like `Entry`, resolving with `RingError::Timeout` unless completed within `timeout`.
+/// - `Prioritized(T, S, priority)`: Like `Entry`, delivering the completion ahead of lower priorities in a reap.
+/// - `Slotted(T, S, index)`: Like `Entry`, pushing the entry into SQ slot `index` rather than appending it.
+/// - `Sync(T, S)`: Like `Entry`, submitting the entry straight away and reaping right after.
+/// - `Batch(entries)`: Submits each entry with its sender, in chunks that fit the SQ.
+/// - `Chain(entries)`: Submits the entries with their senders as a chain, each linked to the next.
+/// - `Gated(T, S, gate)`: Registers an entry with its sender, holding it back until `OpenGate(gate)`.
+/// - `OpenGate(gate)`: Submits the entries held back behind the gate, in the order they arrived.
+/// - `SetCoalesce(usize)`: Changes how many entries the ring thread accumulates before submitting.
 /// - `Reap`: Requests the ring to reap completions.
+/// - `Cancel(ud)`: Cancels the outstanding entry with the given user data.
+/// - `CancelBatch(uds, tx)`: Cancels every outstanding entry among `uds`, reporting the outcomes over `tx`.
+/// - `Reassign(ud, S, tx)`: Delivers the outstanding entry's completion to `S` instead, reporting success over `tx`.
+/// - `Snapshot(tx)`: Sends copies of the retained entries still outstanding over `tx`.
+/// - `Flush`: Submits any deferred entries immediately.
+/// - `FlushSync(tx)`: Submits any deferred entries immediately, reporting how many the backend accepted over `tx`.
+/// - `Barrier(tx)`: Fires `tx` once the entries outstanding right now have all completed.
+/// - `Fence`: Holds back the entries sent afterwards until the entries outstanding right now have all completed.
+/// - `Below(threshold, tx)`: Fires `tx` once fewer than `threshold` entries are outstanding.
+/// - `RegisterBuffers(bufs, tx)`: Registers fixed buffers with the ring, reporting the outcome over `tx`.
+/// - `Dump(tx)`: Sends a snapshot of the ring thread's state over `tx`.
+/// - `WarmUp(tx)`: Reserves room for a full ring's worth of entries and touches the SQ and CQ, firing `tx` once done.
 #[derive(Debug)]
-pub enum Signal<T, S> {
+pub(crate) enum Signal<T, S> {
     /// Submit an entry and a completion sender.
-    Entry(T, S),
+    ///
+    /// The entry's user data must already be set; the ring thread registers the sender under it. Non-empty flags are
+    /// applied to the entry just before it is pushed.
+    Entry(T, S, SubmitFlags),
+    /// Submit an entry and a completion sender, retrying it on transient failures.
+    ///
+    /// The ring thread keeps a spare made with the copy function, and pushes a copy of it whenever the entry's
+    /// completion fails with an error `RetryPolicy` deems worth retrying, until the policy gives up.
+    Retry(T, S, RetryPolicy, fn(&T) -> T),
+    /// Submit an entry and a completion sender, timing the entry out unless it completes in time.
+    ///
+    /// If the ring provides a [`timeout_entry`](FullRing::timeout_entry) it is linked behind the entry under the spare
+    /// user data, and the backend cancels the entry. Otherwise the ring thread resolves the sender with
+    /// `RingError::Timeout` once the deadline passes, and ignores the entry's eventual completion.
+    Timeout(T, S, Duration, UserData),
+    /// Submit a batch of entries, each with its completion sender.
+    ///
+    /// User data must already be set, as for `Entry`. If the SQ knows its [`capacity`](SubmissionQueue::capacity),
+    /// the ring thread pushes the batch in chunks of that size, submitting and reaping after each, so every chunk fits
+    /// the SQ in one go instead of pushes alternating with reaps as the SQ fills up. The last chunk is submitted like
+    /// any other entries, once the submit threshold is reached.
+    Batch(Vec<(T, S)>),
+    /// Submit a chain of entries, each with its completion sender, the backend running each only once the previous
+    /// one succeeded.
+    ///
+    /// User data must already be set, as for `Entry`. The ring thread [`link`](FullRing::link)s every entry but the
+    /// last and pushes them back to back, without submitting in between. If any entry fails
+    /// [`validation`](SubmissionQueue::validate), none are pushed: it resolves with `RingError::Invalid` and the others
+    /// with `RingError::Cancelled`. A chain longer than the SQ's [`capacity`](SubmissionQueue::capacity) cannot be
+    /// pushed in one go, and every entry resolves with `RingError::AtCapacity` instead.
+    Chain(Vec<(T, S)>),
+    /// Register an entry and a completion sender, but hold the entry back until the gate with this id opens.
+    ///
+    /// User data must already be set, as for `Entry`. The entry counts as outstanding while held, so it can be
+    /// cancelled, and if the gate never opens it resolves with `RingError::Disconnected` when the ring thread exits.
+    Gated(T, S, u64),
+    /// Submit the entries held back behind the gate with this id, in the order they arrived.
+    ///
+    /// Entries cancelled while held are dropped. Opening a gate nothing is held behind does nothing.
+    OpenGate(u64),
+    /// Replace the [submit threshold](crate::builder::PRingBuilder::submit_threshold) for entries pushed from now on.
+    ///
+    /// Zero is treated as one. Already deferred entries are submitted straight away if they reach the new threshold.
+    /// Has no effect on a synchronous backend, which always submits every entry straight away.
+    SetCoalesce(usize),
+    /// Submit an entry and a completion sender, with a priority for [prioritized
+    /// delivery](crate::builder::PRingBuilder::prioritized_delivery).
+    ///
+    /// Higher priorities are delivered first. The priority is ignored unless the ring delivers by priority.
+    Prioritized(T, S, i32),
+    /// Submit an entry and a completion sender, pushing the entry into a specific slot of the SQ with
+    /// [`push_at`](SubmissionQueue::push_at).
+    ///
+    /// Otherwise handled like `Entry`. An SQ without fixed slots appends the entry as usual.
+    Slotted(T, S, usize),
+    /// Submit an entry and a completion sender for a caller blocking on the completion.
+    ///
+    /// Otherwise handled like `Entry`, but the entry is never held back by the submit threshold: the ring thread
+    /// pushes it, submits it and reaps in the one go. On a [synchronous](FullRing::is_synchronous) backend the
+    /// completion is ready by then, so it is delivered before the ring thread looks at the next signal.
+    Sync(T, S),
     /// Request to reap completions.
     Reap,
+    /// Resolve the outstanding entry with this user data as cancelled.
+    ///
+    /// The entry may already have been handed to the kernel; its eventual completion is ignored.
+    Cancel(UserData),
+    /// Resolve every outstanding entry among these user data as cancelled, and send back what happened to each.
+    ///
+    /// The whole batch is handled between two reaps, so no completion is delivered halfway through. User data that is
+    /// not outstanding is reported as [`CancelOutcome::AlreadyCompleted`].
+    CancelBatch(Vec<UserData>, oneshot::Sender<Vec<CancelOutcome>>),
+    /// Swap the completion sender of the outstanding entry with this user data, and send back whether it was still
+    /// outstanding.
+    ///
+    /// The previous sender is dropped, so whoever waited on it sees the ring disconnect. Nothing is reaped first:
+    /// every completion not delivered yet goes to the new sender.
+    Reassign(UserData, S, oneshot::Sender<bool>),
+    /// Send back copies of every retained entry that is still outstanding, in submission order.
+    ///
+    /// Nothing is reaped first, so entries that completed but were not delivered yet are included. Without
+    /// [`PRingBuilder::retain_entries`] only retrying entries have copies to send.
+    Snapshot(oneshot::Sender<Vec<T>>),
+    /// Submit every deferred entry now, then reap.
+    Flush,
+    /// Call `Submitter::submit_counted` now, even with nothing deferred, send back how many entries the backend
+    /// accepted, then reap.
+    FlushSync(oneshot::Sender<usize>),
+    /// Fire the sender once every entry outstanding when this is received has been delivered.
+    ///
+    /// Entries submitted afterwards do not hold the barrier up.
+    Barrier(oneshot::Sender<()>),
+    /// Hold back every entry sent afterwards until every entry outstanding when this is received has been delivered.
+    ///
+    /// Deferred entries are submitted first. Signals that depend on the held entries, e.g. a `Barrier` or a `Cancel`
+    /// naming one of them, are held back along with them. Everything held is handled in arrival order once the fence
+    /// lifts.
+    Fence,
+    /// Fire the sender once fewer entries than the threshold are outstanding, counting those registered so far.
+    ///
+    /// Deferred entries are submitted straight away, since they would otherwise hold the count up until their deadline.
+    Below(usize, oneshot::Sender<()>),
+    /// Register fixed buffers with the ring and send back the outcome.
+    ///
+    /// Deferred entries are submitted first, and entries sent afterwards are only pushed once registration is done.
+    RegisterBuffers(Vec<Vec<u8>>, oneshot::Sender<Result<(), BackendError>>),
+    /// Reserve room in the registry for as many entries as the SQ or CQ holds, whichever is more, get the SQ and reap
+    /// the CQ once, then fire the sender.
+    WarmUp(oneshot::Sender<()>),
+    /// Send back a snapshot of the ring thread's state, taken between this signal and the next.
+    ///
+    /// Nothing is submitted or reaped first, so the snapshot shows the ring exactly as the signal found it.
+    Dump(oneshot::Sender<RingDump>),
 }
 
-use oneshot::RecvError;
-use promisery::Promise;
-
 use crate::{
-    registry::Registry,
+    builder::{Config, PRingBuilder},
+    cancel::{CancelOutcome, CancellationToken},
+    channel::{SendFailure, SignalSender, signal_channel},
+    completion_pool::CompletionPool,
+    driver::RingDriver,
+    dump::RingDump,
+    error::{BackendError, RingError, SubmitError},
+    flags::SubmitFlags,
+    gate::Gate,
+    group::GroupHandle,
+    limit::{InFlightLimit, Permit},
+    reap::ReapReason,
+    registry::{
+        Collector, Completer, Completion, DeliveredSet, InflightSample, Origin, Outstanding,
+        PendingSet, Recovered, Tally, lock,
+    },
+    retry::RetryPolicy,
+    stats::RingStats,
+    stream::{CompletionStream, StreamSender},
+    submission::{
+        CompletionReceiver, CompletionSlot, Fanout, FanoutDelivery, Pipeline, Submission,
+        SubmitFuture, SubscriptionHandle,
+    },
     traits::{
         CompletionQueue, CompletionQueueEntry as CQE, FullRing, SubmissionQueue,
-        SubmissionQueueEntry as SQE,
+        SubmissionQueueEntry as SQE, UserData,
     },
 };
 
+pub mod adapters;
+pub mod builder;
+pub mod cancel;
+mod channel;
+mod completion_pool;
+pub mod dedup;
+pub mod driver;
+pub mod dump;
+pub mod error;
+#[cfg(unix)]
+pub mod file;
+pub mod flags;
+pub mod gate;
+pub mod group;
+mod limit;
+pub mod local;
+pub mod metrics;
+pub mod multiplex;
+mod pool;
+pub mod reap;
 pub mod registry;
+pub mod retry;
+pub mod shard;
+pub mod split;
+mod stats;
+pub mod stream;
+pub mod submission;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
 pub mod traits;
 
+/// The signals exchanged between senders and the ring thread.
+type RingSignal<S, C> = Signal<S, Completer<C>>;
+
 /// A thread-safe sender for submitting entries to a ring and receiving completions as promises.
 ///
 /// `PRingSender` is generic over submission queue entries (`S`) and completion queue entries (`C`).
 /// It spawns a background thread that manages the ring and handles submission and completion events.
 ///
 /// This struct is safe to share between threads as it is an abstraction over `std::mpsc::Sender`, so it
-/// can be used in exactly the same way as one would use that.
-#[derive(Clone)]
+/// can be used in exactly the same way as one would use that. Clones share the same channel, which is closed
+/// (stopping the ring thread) when the last clone is dropped.
 pub struct PRingSender<S: SQE, C: CQE> {
+    /// State shared by every clone.
+    shared: Arc<Shared<S, C>>,
+}
+
+/// State shared by every clone of a `PRingSender`.
+///
+/// Only senders hold this; the ring thread is handed its own references to the parts it needs, so that dropping
+/// the last sender drops the channel sender and lets the ring thread exit.
+struct Shared<S: SQE, C: CQE> {
     /// The channel sender for communicating with the ring thread.
-    sender: Sender<Signal<S, oneshot::Sender<C>>>,
+    sender: SignalSender<RingSignal<S, C>>,
+    /// The next user data value to hand out.
+    next_ud: AtomicU64,
+    /// Whether `next_ud` has wrapped around, so values it hands out may still be outstanding.
+    wrapped: AtomicBool,
+    /// User data of every entry submitted but not yet delivered, shared with the ring thread.
+    pending: PendingSet,
+    /// Outstanding state left behind by a ring thread that panicked.
+    recovered: Arc<Mutex<Option<Recovered<S>>>>,
+    /// Counters shared with the ring thread.
+    stats: Arc<RingStats>,
+    /// Reusable completion channels, or `None` to allocate one per submission.
+    completions: Option<Arc<CompletionPool<C>>>,
+    /// Finds the NUMA node of the submitting thread, if callbacks are routed by node.
+    current_node: Option<fn() -> usize>,
+    /// Completions delivered but not yet taken, shared with the ring thread, if tracked.
+    delivered: Option<DeliveredSet>,
+    /// How long a delivered completion can go untaken before its consumer counts as slow.
+    slow_consumer_threshold: Option<Duration>,
+    /// Caps the entries in flight, if they are capped.
+    limit: Option<Arc<InFlightLimit>>,
+    /// Both ends of the ring's completion stream, if it has one.
+    stream: Option<(StreamSender<C>, CompletionStream<C>)>,
+    /// Waits for the ring thread to exit and hands back its ring, or `None` if the driver is not run by the crate.
+    join: Mutex<Option<RingJoin>>,
+}
+
+/// Waits for the ring thread to exit, returning its ring unless it panicked.
+type RingJoin = Box<dyn FnOnce() -> Option<Box<dyn Any + Send>> + Send>;
+
+impl<S: SQE, C: CQE> Clone for PRingSender<S, C> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+/// A non-owning reference to a ring thread, obtained from [`PRingSender::downgrade`].
+///
+/// A `WeakRingSender` does not keep the ring thread alive: once every `PRingSender` is dropped the thread exits and
+/// [`upgrade`](Self::upgrade) returns `None`. Hold one wherever a `PRingSender` would otherwise form a cycle, such as
+/// inside a completion callback.
+pub struct WeakRingSender<S: SQE, C: CQE> {
+    /// The shared state of the senders, if any are still alive.
+    shared: Weak<Shared<S, C>>,
+}
+
+impl<S: SQE, C: CQE> Clone for WeakRingSender<S, C> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: Weak::clone(&self.shared),
+        }
+    }
+}
+
+impl<S: SQE, C: CQE> WeakRingSender<S, C> {
+    /// Attempts to get a `PRingSender` back, returning `None` if every `PRingSender` has been dropped.
+    pub fn upgrade(&self) -> Option<PRingSender<S, C>> {
+        self.shared.upgrade().map(|shared| PRingSender { shared })
+    }
 }
 
 impl<S: SQE, C: CQE> PRingSender<S, C> {
@@ -70,90 +333,453 @@ impl<S: SQE, C: CQE> PRingSender<S, C> {
     /// A new `PRingSender` instance.
     pub fn new<SQ, CQ, Ring>(ring: Ring) -> Self
     where
+        S: 'static,
+        C: 'static,
         SQ: SubmissionQueue<S> + 'static,
         CQ: CompletionQueue<C> + 'static,
         Ring: FullRing<S, C, SQ, CQ> + Send + 'static,
     {
-        let (sender, receiver) = channel();
+        Self::builder().build(ring)
+    }
 
-        thread::spawn(Self::thread_fn_generator(ring, receiver));
+    /// Creates a new `PRingSender` from a ring instance, running the ring thread within `scope`.
+    ///
+    /// Unlike [`new`](Self::new), the ring only has to live as long as the scope, so it can borrow from the
+    /// environment, e.g. a buffer arena its entries point into, instead of owning or `Arc`-wrapping it. Entries and
+    /// completions only have to outlive the scope too. See [`PRingBuilder::build_scoped`].
+    ///
+    /// # Arguments
+    /// * `scope` - The scope the ring thread is spawned in.
+    /// * `ring` - The ring instance to manage.
+    pub fn scoped<'scope, 'env, SQ, CQ, Ring>(
+        scope: &'scope thread::Scope<'scope, 'env>,
+        ring: Ring,
+    ) -> Self
+    where
+        S: 'scope,
+        C: 'scope,
+        SQ: SubmissionQueue<S> + 'scope,
+        CQ: CompletionQueue<C> + 'scope,
+        Ring: FullRing<S, C, SQ, CQ> + Send + 'scope,
+    {
+        Self::builder().build_scoped(scope, ring)
+    }
 
-        Self { sender }
+    /// Creates a builder for configuring the ring thread before spawning it.
+    pub fn builder() -> PRingBuilder<S, C> {
+        PRingBuilder::new()
     }
 
-    /// Generates the background thread function for managing the ring.
-    /// Handles submission, completion, and reaping logic.
-    fn thread_fn_generator<Ring, SQ, CQ>(
-        mut ring: Ring,
-        receiver: Receiver<Signal<S, oneshot::Sender<C>>>,
-    ) -> impl FnOnce() -> ()
+    /// Spawns the ring thread with the given configuration.
+    pub(crate) fn spawn<SQ, CQ, Ring>(ring: Ring, config: Config<S, C>) -> Self
+    where
+        S: 'static,
+        C: 'static,
+        SQ: SubmissionQueue<S> + 'static,
+        CQ: CompletionQueue<C> + 'static,
+        Ring: FullRing<S, C, SQ, CQ> + Send + 'static,
+    {
+        let (sender, driver) = Self::with_driver(ring, config);
+
+        // The thread exits when the receiver disconnects, which happens when the last sender (PRingSender) gets
+        // dropped. This means we don't actually have to keep track of this thread at all, it will take care of itself.
+        // The handle is only kept for `into_ring`.
+        let handle = thread::spawn(move || driver.run());
+        let join: RingJoin = Box::new(move || {
+            handle
+                .join()
+                .ok()
+                .map(|ring| Box::new(ring) as Box<dyn Any + Send>)
+        });
+        *lock(&sender.shared.join) = Some(join);
+
+        sender
+    }
+
+    /// Spawns the ring thread within `scope`, with the given configuration.
+    pub(crate) fn spawn_scoped<'scope, 'env, SQ, CQ, Ring>(
+        scope: &'scope thread::Scope<'scope, 'env>,
+        ring: Ring,
+        config: Config<S, C>,
+    ) -> Self
+    where
+        S: 'scope,
+        C: 'scope,
+        SQ: SubmissionQueue<S> + 'scope,
+        CQ: CompletionQueue<C> + 'scope,
+        Ring: FullRing<S, C, SQ, CQ> + Send + 'scope,
+    {
+        let (sender, driver) = Self::with_driver(ring, config);
+
+        // Exits like any ring thread once the last sender is dropped, which the scope waits for before it ends. The
+        // handle cannot outlive the scope, so it is not kept for `into_ring`.
+        scope.spawn(move || driver.run());
+
+        sender
+    }
+
+    /// Creates a sender along with the driver for its ring, without spawning a thread to run it.
+    pub(crate) fn with_driver<SQ, CQ, Ring>(
+        ring: Ring,
+        mut config: Config<S, C>,
+    ) -> (Self, RingDriver<S, C, SQ, CQ, Ring>)
     where
         SQ: SubmissionQueue<S>,
         CQ: CompletionQueue<C>,
-        Ring: FullRing<S, C, SQ, CQ> + 'static,
+        Ring: FullRing<S, C, SQ, CQ>,
     {
-        move || {
-            let mut registry = Registry::new();
+        let (sender, receiver, overflow) =
+            signal_channel(config.channel_capacity, config.overflow_capacity);
+        let pending = PendingSet::default();
+        let recovered = Arc::default();
+        let stats = Arc::<RingStats>::default();
+        let completions = config
+            .completion_pool
+            .map(|size| Arc::new(CompletionPool::new(size)));
+        let current_node = config.current_node;
+        let slow_consumer_threshold = config.slow_consumer_threshold;
+        let limit = config
+            .max_in_flight
+            .map(|max| Arc::new(InFlightLimit::new(max)));
+        let stream = config.completion_stream.then(|| {
+            let (tx, stream) = stream::completion_stream();
+            if config.orphan_sink.is_none() {
+                config.orphan_stream = Some(tx.clone());
+            }
+            (tx, stream)
+        });
 
-            let reap = |ring: &mut Ring, registry: &mut Registry<C>| {
-                registry.batch_complete(ring.completion());
-            };
+        let driver = RingDriver::new(
+            ring,
+            receiver,
+            overflow,
+            config,
+            pending.clone(),
+            Arc::clone(&recovered),
+            Arc::clone(&stats),
+        );
+        let delivered = driver.registry().delivered().cloned();
 
-            // Blocks when there are no `Signal`s to consume. Returns `None` when every sender has been dropped.
-            for signal in receiver {
-                match signal {
-                    Signal::Entry(mut entry, tx) => {
-                        // We need the user data to be trackable.
-                        let entry_ud = registry.next_uuid();
-                        entry.set_ud(entry_ud);
-
-                        // Submit to the registry.
-                        registry.insert(entry_ud, tx);
-
-                        // Temporary holder for the entry, required by rust's ownership shinnanigans.
-                        let mut entry_holder = Some(entry);
-
-                        // Loops until submission of entry is successful.
-                        // Fails if the SQ is full, possible if we are handed a ring with a full SQ or
-                        // we have been pushing SQEs and not reaping their CQEs.
-                        while let Err(failure_entry) =
-                            ring.submission().push(entry_holder.take().unwrap())
-                        {
-                            entry_holder = Some(failure_entry);
-
-                            // The SQ could be full because the CQ is full.
-                            reap(&mut ring, &mut registry);
-                            // CQ is now empty, so we should wake the kernel.
-                            ring.submit();
-                        }
-
-                        // Inform the kernel of our new submission.
-                        ring.submit();
-                        // Might as well reap the CQ as well.
-                        reap(&mut ring, &mut registry);
-                    }
-                    Signal::Reap => {
-                        reap(&mut ring, &mut registry);
-                    }
-                }
+        let sender = Self {
+            shared: Arc::new(Shared {
+                sender,
+                next_ud: AtomicU64::new(0),
+                wrapped: AtomicBool::new(false),
+                pending,
+                recovered,
+                stats,
+                completions,
+                current_node,
+                delivered,
+                slow_consumer_threshold,
+                limit,
+                stream,
+                join: Mutex::new(None),
+            }),
+        };
+
+        (sender, driver)
+    }
+
+    /// Hands out a fresh user data value.
+    ///
+    /// Once the counter has wrapped around, values still outstanding are skipped, as with
+    /// [`Registry::next_uuid`](registry::Registry::next_uuid).
+    #[inline]
+    fn next_ud(&self) -> UserData {
+        loop {
+            let ud = self.shared.next_ud.fetch_add(1, Ordering::Relaxed);
+            if ud == u64::MAX {
+                self.shared.wrapped.store(true, Ordering::Relaxed);
+            }
+
+            if !self.shared.wrapped.load(Ordering::Relaxed)
+                || !lock(&self.shared.pending).contains_key(&ud)
+            {
+                break UserData::new(ud);
             }
-            // Thread joins when receiver produces a `None`, which happens when the last sender (PRingSender) gets dropped.
-            // This means we don't actually have to keep track of this thread at all, it will take care of itself.
         }
     }
 
-    /// Creates a new promise and a oneshot sender for completion.
+    /// Tags `entry` with fresh user data, marks it pending and sends it to the ring thread.
     #[inline]
-    fn new_promise(&self) -> (Promise<C, RecvError>, oneshot::Sender<C>) {
-        let (tx, rx) = oneshot::channel();
+    fn enqueue(&self, entry: S, completer: Completer<C>) -> UserData {
+        self.enqueue_with_flags(entry, completer, SubmitFlags::empty())
+    }
+
+    /// Like `enqueue`, having the ring thread apply `flags` to the entry.
+    #[inline]
+    fn enqueue_with_flags(
+        &self,
+        entry: S,
+        completer: Completer<C>,
+        flags: SubmitFlags,
+    ) -> UserData {
+        let ud = self.next_ud();
+        self.enqueue_as(ud, entry, completer, flags);
+
+        ud
+    }
+
+    /// Tags `entry` with `ud`, which must be fresh, marks it pending and sends it to the ring thread.
+    #[inline]
+    fn enqueue_as(&self, ud: UserData, entry: S, completer: Completer<C>, flags: SubmitFlags) {
+        self.enqueue_signal(ud, entry, |entry| Signal::Entry(entry, completer, flags));
+    }
+
+    /// Tags `entry` with `ud`, which must be fresh, marks it pending and sends it to the ring thread in the signal
+    /// made by `signal`, which is only called once the entry is pending.
+    ///
+    /// Blocks until the entry fits in a [`PRingBuilder::max_in_flight`] cap first, if there is one.
+    #[inline]
+    fn enqueue_signal(&self, ud: UserData, entry: S, signal: impl FnOnce(S) -> RingSignal<S, C>) {
+        let permit = self.acquire();
+        self.enqueue_holding(ud, entry, permit, signal);
+    }
+
+    /// Like `enqueue_signal`, with `permit` already taken for the entry.
+    #[inline]
+    fn enqueue_holding(
+        &self,
+        ud: UserData,
+        mut entry: S,
+        permit: Option<Permit>,
+        signal: impl FnOnce(S) -> RingSignal<S, C>,
+    ) {
+        entry.set_ud(ud);
+
+        // Marked pending before it is sent, so it can never be observed as delivered early.
+        lock(&self.shared.pending).insert(ud.get(), Origin::capture().holding(permit));
+        self.send(signal(entry));
+    }
+
+    /// Takes a permit for one more entry in flight, blocking until there is room, if the count is capped.
+    #[inline]
+    fn acquire(&self) -> Option<Permit> {
+        self.shared.limit.as_ref().map(InFlightLimit::acquire)
+    }
+
+    /// Like `acquire`, but has `cx`'s waker woken once there may be room rather than blocking.
+    #[inline]
+    fn poll_acquire(&self, cx: &mut Context<'_>) -> Poll<Option<Permit>> {
+        match &self.shared.limit {
+            Some(limit) => limit.poll_acquire(cx).map(Some),
+            None => Poll::Ready(None),
+        }
+    }
+
+    /// Takes a permit for each of `n` more entries in flight at once, blocking until there is room for all of them,
+    /// if the count is capped. Returns no permits otherwise.
+    #[inline]
+    fn acquire_batch(&self, n: usize) -> Vec<Permit> {
+        self.shared
+            .limit
+            .as_ref()
+            .map(|limit| limit.acquire_many(n))
+            .unwrap_or_default()
+    }
+
+    /// The most entries a batch sent as one signal may hold, as it could never fit under a lower in-flight cap.
+    #[inline]
+    fn max_batch(&self) -> usize {
+        self.shared
+            .limit
+            .as_ref()
+            .map_or(usize::MAX, |limit| limit.max())
+    }
 
-        (Promise::new(move || rx.recv()), tx)
+    /// Sends a signal down the channel, keeping the backlog count in step.
+    fn dispatch(
+        &self,
+        signal: RingSignal<S, C>,
+        capped: bool,
+    ) -> Result<(), SendFailure<RingSignal<S, C>>> {
+        self.dispatch_with(signal, |sender, signal| sender.send(signal, capped))
+    }
+
+    /// Like [`dispatch`](Self::dispatch), but fails with `AtCapacity` rather than block on a full channel, e.g. where
+    /// blocking would stall an async executor.
+    fn try_dispatch(&self, signal: RingSignal<S, C>) -> Result<(), SendFailure<RingSignal<S, C>>> {
+        self.dispatch_with(signal, SignalSender::try_send)
+    }
+
+    /// Sends a signal down the channel with `send`, keeping the backlog count in step.
+    fn dispatch_with(
+        &self,
+        signal: RingSignal<S, C>,
+        send: impl FnOnce(
+            &SignalSender<RingSignal<S, C>>,
+            RingSignal<S, C>,
+        ) -> Result<(), SendFailure<RingSignal<S, C>>>,
+    ) -> Result<(), SendFailure<RingSignal<S, C>>> {
+        self.shared.stats.signal_sent();
+
+        let result = send(&self.shared.sender, signal);
+        if let Err(failure) = &result {
+            self.shared.stats.signal_received();
+            if matches!(failure, SendFailure::Disconnected(_)) {
+                // Every clone shares the flag, so once one finds the ring thread gone they all do.
+                self.shared.stats.ring_exited();
+            }
+        }
+
+        result
     }
 
     /// Sends a signal to the ring thread.
+    ///
+    /// If the channel is bounded with an overflow queue that is full, the entries the signal carries are rejected by
+    /// resolving their completions with `RingError::AtCapacity`. If the ring thread has exited, e.g. after a shutdown
+    /// or a panic, they resolve with `RingError::ThreadGone` instead, and any other signal is dropped.
+    ///
+    /// Entries must already carry their user data. One whose user data is in use by an outstanding entry resolves with
+    /// `RingError::DuplicateUserData` on the ring thread, without being pushed.
+    #[inline]
+    pub(crate) fn send(&self, signal: RingSignal<S, C>) {
+        let _ = self.send_checked(signal);
+    }
+
+    /// Like [`send`](Self::send), also returning why the signal was not sent.
+    fn send_checked(&self, signal: RingSignal<S, C>) -> Result<(), RingError> {
+        if self.shared.stats.has_exited() {
+            return Err(self.reject(signal, RingError::ThreadGone));
+        }
+
+        let capped = matches!(
+            signal,
+            Signal::Entry(..)
+                | Signal::Retry(..)
+                | Signal::Timeout(..)
+                | Signal::Prioritized(..)
+                | Signal::Slotted(..)
+                | Signal::Sync(..)
+                | Signal::Batch(..)
+                | Signal::Chain(..)
+                | Signal::Gated(..)
+        );
+
+        match self.dispatch(signal, capped) {
+            Ok(()) => Ok(()),
+            Err(SendFailure::AtCapacity(signal)) => Err(self.reject(signal, RingError::AtCapacity)),
+            Err(SendFailure::Disconnected(signal)) => {
+                Err(self.reject(signal, RingError::ThreadGone))
+            }
+        }
+    }
+
+    /// Resolves every entry carried by a signal that was not sent with `err`, handing `err` back.
+    fn reject(&self, signal: RingSignal<S, C>, err: RingError) -> RingError {
+        match signal {
+            Signal::Entry(entry, completer, _)
+            | Signal::Retry(entry, completer, ..)
+            | Signal::Prioritized(entry, completer, _)
+            | Signal::Slotted(entry, completer, _)
+            | Signal::Sync(entry, completer)
+            | Signal::Gated(entry, completer, _) => {
+                lock(&self.shared.pending).remove(&entry.get_ud().get());
+                completer.resolve(Err(err.clone()), None);
+            }
+            Signal::Timeout(entry, completer, _, timeout_ud) => {
+                let mut pending = lock(&self.shared.pending);
+                pending.remove(&entry.get_ud().get());
+                pending.remove(&timeout_ud.get());
+                drop(pending);
+                completer.resolve(Err(err.clone()), None);
+            }
+            Signal::Batch(entries) | Signal::Chain(entries) => {
+                let mut pending = lock(&self.shared.pending);
+                for (entry, completer) in entries {
+                    pending.remove(&entry.get_ud().get());
+                    completer.resolve(Err(err.clone()), None);
+                }
+            }
+            // Dropping whatever reply channel the signal carries hangs up on its caller.
+            _ => {}
+        }
+
+        err
+    }
+
+    /// Shuts the ring thread down and hands its ring back, e.g. to close it deterministically or to reuse it.
+    ///
+    /// The ring thread submits anything still deferred, reaps once more and exits, and this blocks until it has.
+    /// Entries still outstanding after that final reap resolve with `RingError::Disconnected`; use
+    /// [`barrier`](Self::barrier) first to wait for them. `Ring` must be the type the sender was built with.
+    ///
+    /// # Returns
+    /// `None`, leaving the ring thread running, if other clones of this sender are still alive. `None` as well if the
+    /// ring thread panicked, or if the driver was handed out by [`PRingBuilder::build_driver`] rather than run by the
+    /// crate. Otherwise the ring, or `Err` with the ring still boxed if `Ring` is not its type.
+    pub fn into_ring<Ring: 'static>(self) -> Option<Result<Ring, Box<dyn Any + Send>>> {
+        let shared = Arc::into_inner(self.shared)?;
+
+        let Shared { sender, join, .. } = shared;
+        // Dropping the last sender stops the ring thread.
+        drop(sender);
+
+        let join = join.into_inner().unwrap_or_else(|err| err.into_inner())?;
+        let ring = join()?;
+
+        Some(ring.downcast().map(|ring| *ring))
+    }
+
+    /// Restarts the ring in the child of a `fork`, handing back a sender for a new ring thread running `ring`.
+    ///
+    /// Only the forking thread survives in the child, so the inherited sender talks to a ring thread that no longer
+    /// exists, and the ring it drove may hold OS state, e.g. an io_uring fd or an eventfd, that is shared with the
+    /// parent or invalid. Call this in the child right after the fork, before submitting anything, with a ring to
+    /// drive there and the configuration to drive it with. `ring` gets a chance to recreate its OS state through
+    /// [`FullRing::reinit`] first; if that fails, its error is returned and no thread is started.
+    ///
+    /// What carries over and what starts afresh:
+    /// - The user data counter carries over, so values handed out in the child never collide with those handed out
+    ///   before the fork.
+    /// - The channel, the registry, the stats, the completion pool and any callback workers are all new, as
+    ///   configured by `builder`.
+    /// - Entries outstanding at the fork are left to the parent: their submissions never resolve in the child.
+    /// - Every clone of the inherited sender finds the ring thread gone. Drop them without submitting through them, as
+    ///   their locks may have been held by the parent's threads at the fork. Better still, leak them with
+    ///   `mem::forget`, as this does with `self`, so their drop never touches state left over from the parent.
+    ///
+    /// Only the inherited sender's atomics are touched, never its locks. Like any code run in the child of a
+    /// multithreaded process before `exec`, starting the new ring thread relies on the allocator and thread creation
+    /// working after a fork, as they do with glibc.
+    ///
+    /// # Arguments
+    /// * `builder` - The configuration of the new ring thread.
+    /// * `ring` - The ring to drive in the child.
+    pub fn reinit_after_fork<SQ, CQ, Ring>(
+        self,
+        builder: PRingBuilder<S, C>,
+        mut ring: Ring,
+    ) -> Result<Self, BackendError>
+    where
+        S: 'static,
+        C: 'static,
+        SQ: SubmissionQueue<S> + 'static,
+        CQ: CompletionQueue<C> + 'static,
+        Ring: FullRing<S, C, SQ, CQ> + Send + 'static,
+    {
+        // Dropping the inherited state would detach a thread and hang up on a channel that only exist in the parent.
+        let inherited = mem::ManuallyDrop::new(self);
+        inherited.shared.stats.ring_exited();
+
+        ring.reinit()?;
+        let sender = builder.build(ring);
+        let next_ud = inherited.shared.next_ud.load(Ordering::Relaxed);
+        let wrapped = inherited.shared.wrapped.load(Ordering::Relaxed);
+        sender.shared.next_ud.store(next_ud, Ordering::Relaxed);
+        sender.shared.wrapped.store(wrapped, Ordering::Relaxed);
+
+        Ok(sender)
+    }
+
+    /// Creates a `WeakRingSender` that does not keep the ring thread alive.
     #[inline]
-    pub fn send(&self, signal: Signal<S, oneshot::Sender<C>>) {
-        self.sender.send(signal).unwrap();
+    pub fn downgrade(&self) -> WeakRingSender<S, C> {
+        WeakRingSender {
+            shared: Arc::downgrade(&self.shared),
+        }
     }
 
     /// Requests the ring thread to reap completions.
@@ -162,37 +788,2554 @@ impl<S: SQE, C: CQE> PRingSender<S, C> {
         self.send(Signal::Reap);
     }
 
-    /// Submits an entry to the ring and returns a promise for its completion.
+    /// Requests the ring thread to submit any deferred entries immediately.
+    ///
+    /// Only has an effect with [`PRingBuilder::submit_threshold`]; does not wait for the submission to happen.
+    #[inline]
+    pub fn flush(&self) {
+        self.send(Signal::Flush);
+    }
+
+    /// Has the ring thread submit any deferred entries, blocking until it has handed them to the backend.
+    ///
+    /// Unlike [`flush`](Self::flush), this is a hard synchronization point: once it returns, every entry submitted
+    /// before the call has been handed to the kernel, e.g. ahead of a checkpoint or an fsync. It does not wait for the
+    /// entries to complete; use [`barrier`](Self::barrier) for that.
+    ///
+    /// # Returns
+    /// How many entries the backend accepted, as reported by
+    /// [`Submitter::submit_counted`](traits::Submitter::submit_counted), or else how many were deferred.
+    ///
+    /// # Errors
+    /// Returns `RingError::ThreadGone` if the ring thread had already exited, and `RingError::Disconnected` if it
+    /// exited before answering.
+    pub fn flush_sync(&self) -> Result<usize, RingError> {
+        let (tx, rx) = oneshot::channel();
+        self.send_checked(Signal::FlushSync(tx))?;
+
+        Ok(rx.recv()?)
+    }
+
+    /// Has the ring thread allocate and touch what it needs before the first submission, blocking until it has.
+    ///
+    /// The first entries submitted otherwise pay for the ring thread being scheduled in, the registry growing to fit
+    /// them and the backend's first access to its queues. Warming up does all of that ahead of time: the registry and
+    /// the set of pending user data reserve room for as many entries as the SQ or CQ holds, whichever is more, if
+    /// [`SubmissionQueue::capacity`] or [`FullRing::completion_capacity`] tells, and the SQ and CQ are each accessed
+    /// once. Once this returns, the ring thread is running and waiting for entries.
+    ///
+    /// # Errors
+    /// Returns `RingError::ThreadGone` if the ring thread had already exited, and `RingError::Disconnected` if it
+    /// exited before answering.
+    pub fn warm_up(&self) -> Result<(), RingError> {
+        let (tx, rx) = oneshot::channel();
+        self.send_checked(Signal::WarmUp(tx))?;
+
+        Ok(rx.recv()?)
+    }
+
+    /// Returns a promise that is resolved once every entry submitted before this call has been delivered.
+    ///
+    /// This is a point-in-time drain rather than a quiesce: entries submitted after the call do not delay it. Deferred
+    /// entries are submitted straight away rather than held back until their deadline.
+    ///
+    /// # Returns
+    /// A `Promise` that resolves once the entries have been delivered, or rejects with `RecvError` if the ring thread
+    /// has exited or exits first.
+    pub fn barrier(&self) -> Promise<(), RecvError> {
+        let (tx, rx) = oneshot::channel();
+        self.send(Signal::Barrier(tx));
+
+        Promise::new(move || rx.recv())
+    }
+
+    /// Has the ring thread hold back every entry submitted after this call until the entries submitted before it have
+    /// all completed.
+    ///
+    /// A write barrier for backends that do not order operations themselves: everything submitted before the call
+    /// happens before anything submitted after it. Unlike [`barrier`](Self::barrier), which only reports when earlier
+    /// entries are done, this keeps later entries from reaching the backend until then. The ring thread polls the CQ
+    /// while entries are held back. Entries held behind a [`Gate`] count as submitted before, so the fence only lifts
+    /// once their gate has opened and they have completed.
+    ///
+    /// Calls made after this one that concern the held entries, such as [`barrier`](Self::barrier),
+    /// [`flush_sync`](Self::flush_sync) or cancelling a held entry, wait for the fence along with them.
+    #[inline]
+    pub fn submit_barrier(&self) {
+        self.send(Signal::Fence);
+    }
+
+    /// Blocks until fewer than `threshold` entries are in flight.
+    ///
+    /// Lets a producer keep at most `threshold` entries in flight: submit up to `threshold`, then wait here before
+    /// submitting more. Unlike backpressure on the signal channel, this tracks entries the backend has yet to complete
+    /// rather than signals the ring thread has yet to pick up. Returns straight away if few enough entries are in
+    /// flight already. A `threshold` of zero is never reached.
+    ///
+    /// # Errors
+    /// Returns `RingError::ThreadGone` if the ring thread had already exited, and `RingError::Disconnected` if it
+    /// exits before the count drops.
+    pub fn wait_until_below(&self, threshold: usize) -> Result<(), RingError> {
+        // Entries in flight are pending, but not every pending entry has reached the ring thread yet.
+        if lock(&self.shared.pending).len() < threshold {
+            return Ok(());
+        }
+
+        let (tx, rx) = oneshot::channel();
+        self.send_checked(Signal::Below(threshold, tx))?;
+
+        Ok(rx.recv()?)
+    }
+
+    /// Registers `bufs` as the ring's fixed buffers, blocking until the ring thread has done so.
+    ///
+    /// Entries submitted after this returns can refer to the buffers by index, in whatever way the backend's entry type
+    /// allows. Entries submitted beforehand are handed to the backend before the buffers are registered.
+    ///
+    /// # Errors
+    /// Returns `RingError::Backend(BackendError::Unsupported)` if the backend does not support fixed buffers, and
+    /// `RingError::ThreadGone` if the ring thread had already exited, and `RingError::Disconnected` if it exited
+    /// before answering.
+    pub fn register_buffers(&self, bufs: Vec<Vec<u8>>) -> Result<(), RingError> {
+        let (tx, rx) = oneshot::channel();
+        self.send_checked(Signal::RegisterBuffers(bufs, tx))?;
+
+        Ok(rx.recv()??)
+    }
+
+    /// Creates the channel the completion of the entry submitted under `ud` is delivered through, along with the
+    /// submission receiving it.
+    ///
+    /// Returns `None` if the ring has a completion pool and every channel in it is in use.
+    #[inline]
+    fn completion_channel(&self, ud: UserData) -> Option<(Completer<C>, Submission<C>)> {
+        let (tx, rx): (_, CompletionReceiver<C>) = match &self.shared.completions {
+            Some(pool) => {
+                let (tx, rx) = pool.acquire()?;
+                (Completer::Pooled(tx), CompletionReceiver::Pooled(rx))
+            }
+            None => {
+                let (tx, rx) = oneshot::channel();
+                (tx.into(), rx.into())
+            }
+        };
+
+        let submission = Submission::new(ud, rx);
+        Some(match &self.shared.delivered {
+            Some(delivered) => (tx, submission.tracked(delivered)),
+            None => (tx, submission),
+        })
+    }
+
+    /// Submits an entry to the ring and returns a handle to its completion.
+    ///
+    /// With a [`PRingBuilder::completion_pool`] that is exhausted, the submission resolves with
+    /// `RingError::AtCapacity` without the entry being sent.
     ///
     /// # Arguments
     /// * `entry` - The submission queue entry to submit.
     ///
     /// # Returns
-    /// A `Promise` that resolves to the completion queue entry or a receive error.
+    /// A `Submission` that resolves to the completion queue entry or a receive error.
     #[inline]
-    pub fn submit(&self, entry: S) -> Promise<C, RecvError> {
-        let (promise, tx) = self.new_promise();
+    pub fn submit(&self, entry: S) -> Submission<C> {
+        self.submit_with_flags(entry, SubmitFlags::empty())
+    }
 
-        self.send(Signal::Entry(entry, tx));
+    /// Submits an entry to the ring, returning a promise for its completion along with the entry's user data.
+    ///
+    /// User data is assigned by the sender rather than the ring thread, so it is known as soon as this returns, e.g. to
+    /// log or correlate the entry at the call site before the ring thread has even seen it. It is the same value the
+    /// backend sees on the entry and [`Submission::user_data`] reports. Values are unique among outstanding entries,
+    /// even once the counter wraps around.
+    ///
+    /// # Arguments
+    /// * `entry` - The submission queue entry to submit.
+    #[inline]
+    pub fn submit_tagged(&self, entry: S) -> (Promise<C, RingError>, UserData)
+    where
+        C: 'static,
+    {
+        let submission = self.submit(entry);
+        let ud = submission.user_data();
 
-        promise
+        (submission.into_promise(), ud)
     }
 
-    /// Submits a batch of entries to the ring, returning a vector of promises for their completions.
+    /// Submits an entry with backend-defined submission flags, returning a handle to its completion.
+    ///
+    /// The ring thread applies `flags` with [`SubmissionQueueEntry::set_flags`](traits::SubmissionQueueEntry::set_flags)
+    /// just before pushing the entry. Submitting with empty flags is the same as [`submit`](Self::submit).
     ///
     /// # Arguments
-    /// * `entries` - An iterator of submission queue entries.
+    /// * `entry` - The submission queue entry to submit.
+    /// * `flags` - The flags to apply to the entry.
+    #[inline]
+    pub fn submit_with_flags(&self, entry: S, flags: SubmitFlags) -> Submission<C> {
+        let ud = self.next_ud();
+        let Some((tx, submission)) = self.completion_channel(ud) else {
+            return Submission::rejected(ud, RingError::AtCapacity);
+        };
+
+        self.enqueue_as(ud, entry, tx, flags);
+
+        submission
+    }
+
+    /// Submits an entry that the ring thread resubmits on transient failures, returning a handle to its completion.
     ///
-    /// # Returns
-    /// A vector of `Promise`s, one for each entry submitted.
+    /// Whenever the entry's completion is interpreted as an error that `policy` deems worth retrying, the ring thread
+    /// pushes a fresh copy of the entry, with the same user data, after the policy's backoff. The submission only
+    /// resolves once an attempt succeeds, fails with an error not worth retrying, or the policy runs out of attempts.
+    ///
+    /// # Arguments
+    /// * `entry` - The submission queue entry to submit.
+    /// * `policy` - When and how often to resubmit.
     #[inline]
-    pub fn batch_submit<I>(&self, entries: I) -> Vec<Promise<C, RecvError>>
+    pub fn submit_retry(&self, entry: S, policy: RetryPolicy) -> Submission<C>
     where
-        I: IntoIterator<Item = S>,
+        S: Clone,
     {
-        entries
-            .into_iter()
-            .map(|entry| self.submit(entry))
-            .collect::<Vec<_>>()
+        let ud = self.next_ud();
+        let Some((tx, submission)) = self.completion_channel(ud) else {
+            return Submission::rejected(ud, RingError::AtCapacity);
+        };
+
+        self.enqueue_signal(ud, entry, |entry| {
+            Signal::Retry(entry, tx, policy, S::clone)
+        });
+
+        submission
+    }
+
+    /// Submits an entry that times out unless it completes within `timeout`, returning a handle to its completion.
+    ///
+    /// With a backend providing a [`FullRing::timeout_entry`], the timeout is linked to the entry and enforced by the
+    /// kernel, which cancels the entry; the submission then resolves with however the backend reports cancellation,
+    /// e.g. `ECANCELED`. Otherwise, or if the SQ has no room left for the timeout entry, the ring thread resolves the
+    /// submission with `RingError::Timeout` once the deadline passes, and ignores the entry's eventual completion.
+    /// A linked timeout entry gets user data of its own, which is not handed out again before its completion is in.
+    ///
+    /// # Arguments
+    /// * `entry` - The submission queue entry to submit.
+    /// * `timeout` - How long the entry may take to complete.
+    #[inline]
+    pub fn submit_timeout(&self, entry: S, timeout: Duration) -> Submission<C> {
+        let ud = self.next_ud();
+        let Some((tx, submission)) = self.completion_channel(ud) else {
+            return Submission::rejected(ud, RingError::AtCapacity);
+        };
+
+        // Reserved for the linked timeout entry, should the backend provide one, until the ring thread is done with it.
+        let timeout_ud = self.next_ud();
+        lock(&self.shared.pending).insert(timeout_ud.get(), Origin::reservation());
+        self.enqueue_signal(ud, entry, |entry| {
+            Signal::Timeout(entry, tx, timeout, timeout_ud)
+        });
+
+        submission
+    }
+
+    /// Submits an entry whose completion is delivered ahead of lower priorities, returning a handle to it.
+    ///
+    /// Only has an effect with [`PRingBuilder::prioritized_delivery`], where the completions found by each reap are
+    /// delivered highest priority first. Plain submissions have priority zero, so negative priorities suit bulk work.
+    ///
+    /// # Arguments
+    /// * `entry` - The submission queue entry to submit.
+    /// * `priority` - Where the completion goes in a reap; higher goes first.
+    #[inline]
+    pub fn submit_with_priority(&self, entry: S, priority: i32) -> Submission<C> {
+        let ud = self.next_ud();
+        let Some((tx, submission)) = self.completion_channel(ud) else {
+            return Submission::rejected(ud, RingError::AtCapacity);
+        };
+
+        self.enqueue_signal(ud, entry, |entry| Signal::Prioritized(entry, tx, priority));
+
+        submission
+    }
+
+    /// Submits an entry into slot `index` of the SQ, returning a handle to its completion.
+    ///
+    /// For backends that give each logical channel a fixed SQ slot, e.g. a memory-mapped device queue, rather than
+    /// appending. The ring thread pushes the entry with [`SubmissionQueue::push_at`]; an SQ without fixed slots ignores
+    /// the index and appends it. If the slot is taken, the ring thread submits and reaps until the SQ accepts it.
+    ///
+    /// # Arguments
+    /// * `entry` - The submission queue entry to submit.
+    /// * `index` - The SQ slot to push the entry into.
+    #[inline]
+    pub fn submit_at(&self, entry: S, index: usize) -> Submission<C> {
+        let ud = self.next_ud();
+        let Some((tx, submission)) = self.completion_channel(ud) else {
+            return Submission::rejected(ud, RingError::AtCapacity);
+        };
+
+        self.enqueue_signal(ud, entry, |entry| Signal::Slotted(entry, tx, index));
+
+        submission
+    }
+
+    /// Submits an entry expected to complete `n` times, returning a promise for all of its completions.
+    ///
+    /// Meant for operations the backend splits into a known, bounded number of completions sharing the entry's user
+    /// data, such as scatter/gather I/O. The entry stays registered until the `n`th completion arrives, and the promise
+    /// resolves with every completion in the order they arrived. If any of them is an error, the promise rejects
+    /// with it straight away and later completions are ignored. A count of zero is treated as one.
+    ///
+    /// # Arguments
+    /// * `entry` - The submission queue entry to submit.
+    /// * `n` - How many completions the entry produces.
+    #[inline]
+    pub fn submit_expecting(&self, entry: S, n: usize) -> Promise<Vec<C>, RingError>
+    where
+        C: 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+        self.enqueue(entry, Completer::Collect(Collector::new(n, tx)));
+
+        Promise::new(move || rx.recv()?)
+    }
+
+    /// Submits an entry whose completion is pushed into the ring's [completion stream](stream) rather than resolving a
+    /// promise of its own, returning its user data.
+    ///
+    /// Saves the channel a promise needs per submission, and the hop back to a worker pool, for consumers processing
+    /// completions in bulk. The completion is yielded by whichever [`CompletionStream`] clone pulls it first, tagged
+    /// with the returned user data.
+    ///
+    /// # Errors
+    /// Hands `entry` back without submitting it if the ring was built without
+    /// [`completion_stream`](PRingBuilder::completion_stream).
+    pub fn submit_streamed(&self, entry: S) -> Result<UserData, S> {
+        let Some((tx, _)) = &self.shared.stream else {
+            return Err(entry);
+        };
+
+        let ud = self.next_ud();
+        self.enqueue_as(
+            ud,
+            entry,
+            Completer::Stream(ud, tx.clone()),
+            SubmitFlags::empty(),
+        );
+
+        Ok(ud)
+    }
+
+    /// Returns the consuming end of the ring's completion stream, or `None` if the ring was built without
+    /// [`completion_stream`](PRingBuilder::completion_stream).
+    ///
+    /// Every call hands out a clone pulling from the same channel, so each worker can take its own.
+    #[inline]
+    pub fn completion_stream(&self) -> Option<CompletionStream<C>> {
+        self.shared
+            .stream
+            .as_ref()
+            .map(|(_, stream)| stream.clone())
+    }
+
+    /// Submits an entry whose promise resolves with its completion along with the entries in flight around it.
+    ///
+    /// Meant for adaptive concurrency limits, which adjust the limit from each completion's latency and the load it
+    /// completed under. The [`InflightSample`] carries the entries pending right after the entry was submitted, those
+    /// pending as its completion was delivered, and the latency in between, so the controller needs no separate metrics
+    /// polling.
+    ///
+    /// # Arguments
+    /// * `entry` - The submission queue entry to submit.
+    pub fn submit_sampled(&self, entry: S) -> Promise<(C, InflightSample), RingError>
+    where
+        C: 'static,
+    {
+        let since = Instant::now();
+        let (tx, rx) = oneshot::channel();
+        let ud = self.next_ud();
+        self.enqueue_signal(ud, entry, |entry| {
+            // Counted once the entry is pending, so it includes itself.
+            let pending = self.shared.pending.clone();
+            let at_submit = lock(&pending).len();
+            Signal::Entry(
+                entry,
+                Completer::Sampled(at_submit, since, pending, tx),
+                SubmitFlags::empty(),
+            )
+        });
+
+        Promise::new(move || rx.recv()?)
+    }
+
+    /// Submits an entry along with a resource the backend uses, e.g. the buffer a read writes into, holding on to the
+    /// resource until the entry completes and handing it back with the completion.
+    ///
+    /// Ties the resource's lifetime to the operation's rather than the caller's, so it cannot be freed while the
+    /// backend may still write into it. If the entry resolves before the backend is done with it, i.e. with
+    /// `RingError::Cancelled`, `RingError::Timeout` or `RingError::CompletionLost`, the resource is not handed back:
+    /// the ring thread keeps it until the entry's own completion arrives after all, and drops it then. Any other error
+    /// means the backend is done with the entry or never saw it, and the resource is dropped straight away. Resources
+    /// still held when the ring thread exits are dropped with it, before the ring; a backend still using them by then
+    /// has to wait for its operations on drop.
+    ///
+    /// # Arguments
+    /// * `entry` - The submission queue entry to submit.
+    /// * `resource` - What the backend uses for the entry, handed back with its completion.
+    pub fn submit_owning<R>(&self, entry: S, resource: R) -> Promise<(C, R), RingError>
+    where
+        C: 'static,
+        R: Send + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+        self.enqueue(entry, Completer::Owning(Box::new(resource), tx));
+
+        Promise::new(move || {
+            let (cqe, resource) = rx.recv()??;
+            let resource = resource
+                .downcast()
+                .expect("the ring thread hands back the resource it was given");
+
+            Ok((cqe, *resource))
+        })
+    }
+
+    /// Submits an entry whose promise resolves with the first of its completions `filter` accepts.
+    ///
+    /// Meant for operations reporting progress through interim completions sharing the entry's user data before a
+    /// final one, e.g. partial transfers. The entry stays registered until `filter` accepts a completion, and every
+    /// completion before that is discarded. `filter` runs on the ring thread, or on the reaper thread of a split ring,
+    /// so keep it short. An error resolves the promise straight away, without going through `filter`. Unlike
+    /// [`submit_expecting`](Self::submit_expecting), the number of completions does not have to be known up front, but
+    /// an entry whose final completion never matches stays registered until it is cancelled.
+    ///
+    /// # Arguments
+    /// * `entry` - The submission queue entry to submit.
+    /// * `filter` - Whether a completion is the one to resolve the promise with.
+    #[inline]
+    pub fn submit_until<F>(&self, entry: S, filter: F) -> Promise<C, RingError>
+    where
+        C: 'static,
+        F: Fn(&C) -> bool + Send + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+        self.enqueue(entry, Completer::Until(Box::new(filter), tx));
+
+        Promise::new(move || rx.recv()?)
+    }
+
+    /// Submits an entry and blocks until it completes.
+    ///
+    /// The entry is never held back by [`PRingBuilder::submit_threshold`]: it is flushed as soon as the ring thread
+    /// receives it. With a backend that reports [`FullRing::is_synchronous`], the ring thread pushes, submits and
+    /// reaps the entry in a single turn, so this costs one signal through the channel and one trip through the
+    /// oneshot.
+    #[inline]
+    pub fn submit_sync(&self, entry: S) -> Result<C, RingError> {
+        let (tx, rx) = oneshot::channel();
+        let ud = self.next_ud();
+        self.enqueue_signal(ud, entry, |entry| {
+            Signal::Sync(entry, Completer::Promise(tx))
+        });
+
+        rx.recv()?
+    }
+
+    /// Submits an entry that is cancelled if `token` is cancelled before the entry completes.
+    ///
+    /// On cancellation the ring thread resolves the submission with `RingError::Cancelled` and ignores the entry's
+    /// eventual completion. If `token` is already cancelled, the entry is not submitted at all.
+    ///
+    /// `token` only holds a [`WeakRingSender`], so it does not keep the ring thread alive.
+    #[inline]
+    pub fn submit_cancellable(&self, entry: S, token: &CancellationToken) -> Submission<C>
+    where
+        S: 'static,
+        C: 'static,
+    {
+        if token.is_cancelled() {
+            return Submission::rejected(self.next_ud(), RingError::Cancelled);
+        }
+
+        let ud = self.next_ud();
+        let Some((tx, submission)) = self.completion_channel(ud) else {
+            return Submission::rejected(ud, RingError::AtCapacity);
+        };
+
+        let sender = self.downgrade();
+        let cancel = move || {
+            // The ring thread may be gone, in which case the submission has already resolved.
+            if let Some(sender) = sender.upgrade() {
+                let _ = sender.dispatch(Signal::Cancel(ud), false);
+            }
+        };
+        let Some(hook) = token.on_cancel(Box::new(cancel.clone())) else {
+            return Submission::rejected(ud, RingError::Cancelled);
+        };
+
+        // The hook is forgotten once the entry resolves, however it resolves.
+        let tx = Completer::Callback(Box::new(move |completion| {
+            drop(hook);
+            tx.resolve(completion, None);
+        }));
+        self.enqueue_as(ud, entry, tx, SubmitFlags::empty());
+
+        // A cancellation racing the hook's registration sent its `Cancel` ahead of the entry.
+        if token.is_cancelled() {
+            cancel();
+        }
+
+        submission
+    }
+
+    /// Submits an entry that is held back on the ring thread until `gate` opens.
+    ///
+    /// The entry is registered straight away, so it counts as outstanding and can be cancelled, but it only reaches
+    /// the backend once [`Gate::open`] is called. Every entry held behind the same gate is then pushed together, in the
+    /// order they were submitted. If `gate` is already open, the entry is submitted as usual.
+    ///
+    /// `gate` only holds a [`WeakRingSender`], so it does not keep the ring thread alive.
+    ///
+    /// # Arguments
+    /// * `entry` - The submission queue entry to submit.
+    /// * `gate` - The gate holding the entry back.
+    pub fn submit_deferred(&self, entry: S, gate: &Gate) -> Promise<C, RingError>
+    where
+        S: 'static,
+        C: 'static,
+    {
+        if gate.is_open() {
+            return self.submit(entry).into_promise();
+        }
+
+        let ud = self.next_ud();
+        let Some((tx, submission)) = self.completion_channel(ud) else {
+            return Submission::rejected(ud, RingError::AtCapacity).into_promise();
+        };
+
+        self.enqueue_signal(ud, entry, |entry| Signal::Gated(entry, tx, gate.id()));
+
+        let sender = self.downgrade();
+        let id = gate.id();
+        gate.on_open(Box::new(move || {
+            // The ring thread may be gone, in which case the entry has already resolved.
+            if let Some(sender) = sender.upgrade() {
+                let _ = sender.dispatch(Signal::OpenGate(id), false);
+            }
+        }));
+
+        submission.into_promise()
+    }
+
+    /// Cancels the entries submitted under `user_data`, blocking until the ring thread reports what happened to each.
+    ///
+    /// Outstanding entries resolve with `RingError::Cancelled`, as with a [`CancellationToken`], and their eventual
+    /// completions are ignored. The ring thread reaps first and then handles the whole batch at once, so the outcomes,
+    /// in the order of `user_data`, tell exactly which entries were caught in time and which had already completed.
+    ///
+    /// # Errors
+    /// Returns `RingError::ThreadGone` if the ring thread had already exited, and `RingError::Disconnected` if it
+    /// exited before answering.
+    pub fn cancel_batch<I>(&self, user_data: I) -> Result<Vec<CancelOutcome>, RingError>
+    where
+        I: IntoIterator<Item = UserData>,
+    {
+        let user_data: Vec<UserData> = user_data.into_iter().collect();
+        // Anything handed out from here on cannot be meant by the caller.
+        let issued = self.shared.next_ud.load(Ordering::Relaxed);
+
+        let (tx, rx) = oneshot::channel();
+        self.send_checked(Signal::CancelBatch(user_data.clone(), tx))?;
+        let outcomes = rx.recv()?;
+
+        Ok(user_data
+            .into_iter()
+            .zip(outcomes)
+            .map(|(ud, outcome)| match outcome {
+                CancelOutcome::AlreadyCompleted if ud.get() >= issued => CancelOutcome::Unknown,
+                outcome => outcome,
+            })
+            .collect())
+    }
+
+    /// Moves the completion of the outstanding entry submitted under `user_data` to `sender`, without resubmitting it.
+    ///
+    /// Meant for re-routing in-flight work, e.g. to a different consumer after a failover. The previous handle
+    /// resolves with `RingError::Disconnected`, and the completion, whenever it arrives, is sent over `sender` instead.
+    /// Any retry policy or priority the entry was submitted with still applies.
+    ///
+    /// # Returns
+    /// A `Promise` that resolves to `true` if the entry was reassigned, or `false` if it was no longer outstanding,
+    /// e.g. because it already completed. It rejects with `RecvError` if the ring thread has exited or exits first.
+    pub fn reassign(
+        &self,
+        user_data: UserData,
+        sender: oneshot::Sender<Completion<C>>,
+    ) -> Promise<bool, RecvError> {
+        let (tx, rx) = oneshot::channel();
+        self.send(Signal::Reassign(user_data, sender.into(), tx));
+
+        Promise::new(move || rx.recv())
+    }
+
+    /// Copies every outstanding entry, e.g. to persist them so they can be resubmitted after a restart.
+    ///
+    /// Together with resubmitting the persisted entries onto a fresh ring, this gives at-least-once semantics: an
+    /// entry is only left out once it has been delivered. Entries are only copied if the ring was built with
+    /// [`PRingBuilder::retain_entries`]; otherwise the snapshot only holds entries submitted with a retry policy. It
+    /// comes in submission order, and includes entries held behind a [`Gate`].
+    pub fn snapshot(&self) -> Promise<Vec<S>, RecvError>
+    where
+        S: 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+        self.send(Signal::Snapshot(tx));
+
+        Promise::new(move || rx.recv())
+    }
+
+    /// Returns a promise for a consistent snapshot of the ring thread's state, for incident debugging.
+    ///
+    /// The [`RingDump`] bundles what the other introspection methods report one by one: the in-flight count and
+    /// peak, every outstanding entry with how long it has been pending, the entries deferred, submitted and completed,
+    /// SQ-full retries, the last error and the configured reap strategy. The ring thread takes it between two
+    /// signals, so the numbers agree with each other, unlike a series of calls racing the ring thread. Print it for a
+    /// readable report.
+    ///
+    /// # Returns
+    /// A `Promise` that resolves with the snapshot, or rejects with `RecvError` if the ring thread has exited or exits
+    /// first.
+    pub fn dump(&self) -> Promise<RingDump, RecvError> {
+        let (tx, rx) = oneshot::channel();
+        self.send(Signal::Dump(tx));
+
+        Promise::new(move || rx.recv())
+    }
+
+    /// Takes the outstanding state left behind by a ring thread that panicked.
+    ///
+    /// Returns `None` while the ring thread is healthy, and after the state has been taken once. The recovered
+    /// user data covers every entry that was registered but not completed; the entries themselves are only
+    /// included if the ring was built with [`PRingBuilder::retain_entries`]. Their submissions resolve with
+    /// `RingError::Disconnected`, so resubmitting them onto a fresh ring yields new submissions.
+    pub fn take_recovered(&self) -> Option<Recovered<S>> {
+        lock(&self.shared.recovered).take()
+    }
+
+    /// The number of signals sent to the ring thread that it has not yet picked up.
+    ///
+    /// This counts queued submissions and control signals, including any spilled into the overflow queue. Unlike the
+    /// number of outstanding entries, which only grows while the backend is busy, a growing backlog means the ring
+    /// thread itself cannot keep up with its senders.
+    #[inline]
+    pub fn channel_backlog(&self) -> usize {
+        self.shared.stats.backlog()
+    }
+
+    /// Returns the number of completions recognised as duplicates of a recent completion.
+    ///
+    /// Always zero unless the ring was built with a [`PRingBuilder::dedup_window`].
+    #[inline]
+    pub fn duplicate_completions(&self) -> u64 {
+        self.shared.stats.duplicates()
+    }
+
+    /// Changes how many pushed entries the ring thread accumulates before calling `Submitter::submit`.
+    ///
+    /// This retunes [`PRingBuilder::submit_threshold`] on a running ring, e.g. from an adaptive controller raising it
+    /// under load for throughput and lowering it again when latency matters. It takes effect once the ring thread
+    /// gets to the signal; track it with [`coalesce`](Self::coalesce) and the outcome with
+    /// [`average_batch_size`](Self::average_batch_size). Zero is treated as one, and synchronous backends ignore it.
+    #[inline]
+    pub fn set_coalesce(&self, threshold: usize) {
+        self.send(Signal::SetCoalesce(threshold));
+    }
+
+    /// The submit threshold the ring thread currently applies.
+    #[inline]
+    pub fn coalesce(&self) -> usize {
+        self.shared.stats.coalesce()
+    }
+
+    /// The average number of entries handed to the backend per call to `Submitter::submit` so far.
+    ///
+    /// Zero until the ring thread first submits. Batches can come out smaller than the submit threshold, e.g. when
+    /// [`PRingBuilder::max_defer`] expires or the SQ fills up first.
+    #[inline]
+    pub fn average_batch_size(&self) -> f64 {
+        self.shared.stats.average_batch_size()
+    }
+
+    /// Returns the number of reaps the ring thread forced because the CQ was close to overflowing.
+    ///
+    /// Always zero unless the ring reports its [`FullRing::completion_capacity`]. A steadily growing count means
+    /// entries complete faster than the reap strategy reaps them.
+    #[inline]
+    pub fn cq_pressure_reaps(&self) -> u64 {
+        self.shared.stats.reaps(ReapReason::CqPressure)
+    }
+
+    /// Returns the number of completions the backend reported dropped by an overflowing CQ.
+    ///
+    /// Always zero unless the ring reports its [`FullRing::overflow_count`]. Each lost completion leaves an entry that
+    /// never resolves, unless the ring was built with [`PRingBuilder::fail_lost_completions`].
+    #[inline]
+    pub fn lost_completions(&self) -> u64 {
+        self.shared.stats.lost_completions()
+    }
+
+    /// Returns the most recent error the ring thread ran into, with when it did, or `None` if it has not run into any.
+    ///
+    /// A health check for supervisors polling the ring, alongside [`is_thread_alive`](Self::is_thread_alive) and the
+    /// counters. Recorded are failed completions, including contained panics in the backend's completion handling,
+    /// entries refused as invalid or for reusing outstanding user data, failed buffer registrations, completions lost
+    /// to an overflowing CQ, and `RingError::ThreadGone` once the ring thread panics. Cancellations and timeouts are
+    /// asked for rather than run into, so they are left out. Each error still reaches the entries it concerns as well.
+    #[inline]
+    pub fn last_error(&self) -> Option<(RingError, Instant)> {
+        self.shared.stats.last_error()
+    }
+
+    /// Returns the number of reaps the ring thread made for `reason` so far.
+    ///
+    /// Comparing the counts across [`ReapReason::ALL`] shows what actually drives reaping, e.g. mostly
+    /// [`ReapReason::Timer`] under a [`Watermark`](reap::Watermark) strategy means the high watermark is rarely
+    /// reached. Always zero on a ring split with [`PRingBuilder::split_threads`], whose reaper thread reaps
+    /// continuously instead.
+    #[inline]
+    pub fn reaps(&self, reason: ReapReason) -> u64 {
+        self.shared.stats.reaps(reason)
+    }
+
+    /// Returns whether the ring thread is still running, as far as any clone of this sender knows.
+    ///
+    /// Once this turns `false` it stays that way, for every clone: submissions resolve with `RingError::ThreadGone`
+    /// without being sent. It can only turn `false` while other clones are alive if the ring thread panicked or was
+    /// stepped by hand and dropped.
+    #[inline]
+    pub fn is_thread_alive(&self) -> bool {
+        !self.shared.stats.has_exited()
+    }
+
+    /// Returns the number of panics in the backend's completion handling that the reap path contained.
+    ///
+    /// Counts completion iterators that panicked, each ending its reap early, and completions whose
+    /// [`FullRing::interpret`] panicked, each resolving with a `RingError::Backend`. Always zero with
+    /// [`PRingBuilder::contain_completion_panics`] off, since the first panic then takes the ring thread down.
+    #[inline]
+    pub fn errored_completions(&self) -> u64 {
+        self.shared.stats.errored_completions()
+    }
+
+    /// Returns the most entries that have been outstanding at once, since the ring started or the last
+    /// [`reset_peak`](Self::reset_peak).
+    ///
+    /// Counted on the ring thread as entries are registered, so it tells how deep a ring the workload actually needs.
+    #[inline]
+    pub fn peak_in_flight(&self) -> usize {
+        self.shared.stats.peak_in_flight()
+    }
+
+    /// Starts tracking [`peak_in_flight`](Self::peak_in_flight) afresh from the entries outstanding right now.
+    #[inline]
+    pub fn reset_peak(&self) {
+        let in_flight = lock(&self.shared.pending).len();
+        self.shared.stats.reset_peak(in_flight);
+    }
+
+    /// Returns whether the completion for `submission` has yet to be delivered.
+    ///
+    /// This does not block; it only checks the set of outstanding user data maintained alongside the registry.
+    #[inline]
+    pub fn is_pending(&self, submission: &Submission<C>) -> bool {
+        lock(&self.shared.pending).contains_key(&submission.user_data().get())
+    }
+
+    /// Returns every submitted entry whose completion has yet to be delivered, longest pending first.
+    ///
+    /// Meant for tracking down submissions that never resolve. With the `debug-backtrace` feature, each entry also
+    /// carries the backtrace of the call that submitted it. Capturing one on every submission is expensive, so only
+    /// enable the feature for debugging.
+    pub fn outstanding(&self) -> Vec<Outstanding> {
+        let mut outstanding: Vec<Outstanding> = lock(&self.shared.pending)
+            .iter()
+            .filter(|(_, origin)| !origin.is_reservation())
+            .map(|(&user_data, origin)| origin.outstanding(user_data.into()))
+            .collect();
+        outstanding.sort_by_key(|outstanding| Reverse(outstanding.pending_for));
+
+        outstanding
+    }
+
+    /// Returns the submissions whose completion was delivered longer than [`PRingBuilder::slow_consumer_threshold`]
+    /// ago but has not been taken yet, by user data and how long ago it was delivered, longest first.
+    ///
+    /// Always empty unless the ring was built with a threshold. A submission counts as taken once a completion is
+    /// returned from it, or once it is dropped.
+    pub fn slow_consumers(&self) -> Vec<(UserData, Duration)> {
+        let (Some(delivered), Some(threshold)) =
+            (&self.shared.delivered, self.shared.slow_consumer_threshold)
+        else {
+            return Vec::new();
+        };
+
+        let mut slow: Vec<_> = lock(delivered)
+            .iter()
+            .filter_map(|(&user_data, at)| Some((user_data.into(), at.as_ref()?.elapsed())))
+            .filter(|&(_, waiting)| waiting > threshold)
+            .collect();
+        slow.sort_by_key(|&(_, waiting)| Reverse(waiting));
+
+        slow
+    }
+
+    /// Submits an entry to the ring and invokes `callback` with its completion.
+    ///
+    /// The callback runs on the ring thread, or on a worker if the ring was built with
+    /// [`PRingBuilder::callback_pool`]. Keep inline callbacks short, as they hold up the ring thread. With a
+    /// [`PRingBuilder::numa_callback_pool`], the calling thread's node is recorded here and the callback runs on a
+    /// worker of that node.
+    ///
+    /// # Arguments
+    /// * `entry` - The submission queue entry to submit.
+    /// * `callback` - Called with the completion queue entry once it is reaped, or with the error that prevented it.
+    #[inline]
+    pub fn submit_with_callback<F>(&self, entry: S, callback: F)
+    where
+        C: 'static,
+        F: FnOnce(Completion<C>) + Send + 'static,
+    {
+        let completer = match self.shared.current_node {
+            Some(current_node) => Completer::Routed(Box::new(callback), current_node()),
+            None => Completer::Callback(Box::new(callback)),
+        };
+        self.enqueue(entry, completer);
+    }
+
+    /// Submits an entry whose completion is transformed by `map` before its promise resolves.
+    ///
+    /// Each submission carries its own post-processing, e.g. one extracting the bytes read and another parsing a
+    /// header, unlike the ring-wide [`FullRing::interpret`]. `map` runs where completion callbacks run, on the ring
+    /// thread or a [`PRingBuilder::callback_pool`] worker, keeping the work off the consumer's critical path. Keep it
+    /// short if it runs inline. Errors bypass `map` and reject the promise as they are.
+    ///
+    /// # Arguments
+    /// * `entry` - The submission queue entry to submit.
+    /// * `map` - Transforms the completion queue entry into the promise's value once it is reaped.
+    #[inline]
+    pub fn submit_with<R, F>(&self, entry: S, map: F) -> Promise<R, RingError>
+    where
+        C: 'static,
+        R: Send + 'static,
+        F: FnOnce(C) -> R + Send + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+        self.submit_with_callback(entry, move |completion| {
+            let _ = tx.send(completion.map(map));
+        });
+
+        Promise::new(move || rx.recv()?)
+    }
+
+    /// Submits an entry whose completion is shared between any number of subscribers.
+    ///
+    /// Returns a `SubscriptionHandle` for taking further subscriptions, along with a first `Submission`. Each
+    /// subscriber receives its own clone of the completion.
+    ///
+    /// # Arguments
+    /// * `entry` - The submission queue entry to submit.
+    #[inline]
+    pub fn submit_shared(&self, entry: S) -> (SubscriptionHandle<C>, Submission<C>)
+    where
+        C: Clone + 'static,
+    {
+        let fanout = Arc::new(Fanout::new());
+
+        let delivery = FanoutDelivery(Arc::clone(&fanout));
+        let ud = self.enqueue(
+            entry,
+            Completer::Callback(Box::new(move |completion| delivery.0.deliver(completion))),
+        );
+
+        let handle = SubscriptionHandle::new(ud, fanout);
+        let submission = handle.subscribe();
+
+        (handle, submission)
+    }
+
+    /// Submits an entry whose completion is stored in a `CompletionSlot`, waking `waker` once it lands.
+    ///
+    /// This is the lowest-level way to wait for a completion, meant for custom executors: the ring thread fills the
+    /// slot and wakes the waker, and the executor retrieves the completion with [`CompletionSlot::take`]. The waker is
+    /// woken inline on the ring thread, even with a callback pool.
+    ///
+    /// # Arguments
+    /// * `entry` - The submission queue entry to submit.
+    /// * `waker` - Woken once the completion is in the slot.
+    #[inline]
+    pub fn submit_with_waker(&self, entry: S, waker: Waker) -> CompletionSlot<C> {
+        let ud = self.next_ud();
+        let (slot, sender) = CompletionSlot::new(ud, waker);
+
+        self.enqueue_as(ud, entry, Completer::Slot(sender), SubmitFlags::empty());
+
+        slot
+    }
+
+    /// Submits an entry whose completion is written into `slot`, notifying `notify` once it is there.
+    ///
+    /// For synchronous consumers of large completions: the ring thread moves the completion straight into the
+    /// caller's slot instead of through a channel, without a [`CompletionSlot`]'s waker machinery, and a single slot
+    /// and condvar can be reused across submissions. Errors land in the slot like completions. The slot is written and
+    /// the condvar notified inline on the ring thread, even with a callback pool, so wait on `notify` with `slot`'s
+    /// guard until it holds something:
+    ///
+    /// ```no_run
+    /// # #[cfg(feature = "test-util")]
+    /// # fn main() -> Result<(), ring_promise::error::RingError> {
+    /// # use std::sync::{Arc, Condvar, Mutex};
+    /// # use ring_promise::{PRingSender, test_util::{MockEntry, MockRing}};
+    /// # let sender = PRingSender::new(MockRing::<MockEntry, MockEntry>::echo());
+    /// # let entry = MockEntry::new(1);
+    /// let slot = Arc::new(Mutex::new(None));
+    /// let notify = Arc::new(Condvar::new());
+    /// sender.submit_into_slot(entry, Arc::clone(&slot), Arc::clone(&notify));
+    ///
+    /// let mut guard = notify.wait_while(slot.lock().unwrap(), |slot| slot.is_none()).unwrap();
+    /// let completion = guard.take().unwrap()?;
+    /// # Ok(())
+    /// # }
+    /// # #[cfg(not(feature = "test-util"))]
+    /// # fn main() {}
+    /// ```
+    ///
+    /// Returns the user data assigned to the entry. If the ring thread drops the entry without completing it, e.g. on
+    /// shutdown, the slot stays empty.
+    ///
+    /// # Arguments
+    /// * `entry` - The submission queue entry to submit.
+    /// * `slot` - Written with the completion, overwriting whatever it held.
+    /// * `notify` - Notified once the completion is in the slot.
+    #[inline]
+    pub fn submit_into_slot(
+        &self,
+        entry: S,
+        slot: Arc<Mutex<Option<Completion<C>>>>,
+        notify: Arc<Condvar>,
+    ) -> UserData {
+        self.enqueue(entry, Completer::Into(slot, notify))
+    }
+
+    /// Submits an entry, returning a future that resolves with its completion and cancels the entry if dropped first.
+    ///
+    /// Dropping futures is how async code gives up on an operation, e.g. in `select!` or under a timeout, so the
+    /// future sends the ring thread a best-effort cancellation: the submission resolves with `RingError::Cancelled`
+    /// and its eventual completion is ignored, but an entry the backend already picked up may still be carried out.
+    /// The future only holds a [`WeakRingSender`], so it does not keep the ring thread alive.
+    ///
+    /// # Arguments
+    /// * `entry` - The submission queue entry to submit.
+    #[inline]
+    pub fn submit_async(&self, entry: S) -> SubmitFuture<S, C> {
+        let ud = self.next_ud();
+        // Replaced by the future's waker once it is first polled.
+        let (slot, sender) = CompletionSlot::new(ud, Waker::noop().clone());
+
+        // Under a cap, the future waits for room itself rather than blocking here.
+        if self.shared.limit.is_some() {
+            return SubmitFuture::waiting(slot, self.downgrade(), entry, sender);
+        }
+        self.enqueue_as(ud, entry, Completer::Slot(sender), SubmitFlags::empty());
+
+        SubmitFuture::new(slot, self.downgrade())
+    }
+
+    /// Submits `entries` as a chain of dependent operations, returning a `Pipeline` yielding their completions in
+    /// order.
+    ///
+    /// Every entry but the last is [`link`](FullRing::link)ed to the next, so the backend only runs an entry once the
+    /// one before it succeeded, e.g. a read feeding a write. The pipeline is meant to be polled from async code: the
+    /// ring thread wakes it whenever the next completion lands. A failure short-circuits the chain, and the pipeline
+    /// ends after yielding that error. A backend that does not link entries runs them independently, though the
+    /// pipeline still yields their completions in chain order.
+    ///
+    /// Under a [`PRingBuilder::max_in_flight`] cap, this blocks until the whole chain fits. A chain longer than the cap
+    /// never does, and is not sent: the pipeline yields `RingError::AtCapacity` straight away.
+    ///
+    /// # Arguments
+    /// * `entries` - The entries of the chain, in the order they are to run.
+    pub fn submit_pipeline<I>(&self, entries: I) -> Pipeline<C>
+    where
+        I: IntoIterator<Item = S>,
+    {
+        let entries: Vec<_> = entries.into_iter().collect();
+        let mut slots = VecDeque::with_capacity(entries.len());
+        let mut chain = Vec::with_capacity(entries.len());
+
+        if entries.len() > self.max_batch() {
+            for _ in entries {
+                let (slot, sender) = CompletionSlot::new(self.next_ud(), Waker::noop().clone());
+                sender.send(Err(RingError::AtCapacity));
+                slots.push_back(slot);
+            }
+            return Pipeline::new(slots);
+        }
+
+        let mut permits = self.acquire_batch(entries.len()).into_iter();
+        for mut entry in entries {
+            let ud = self.next_ud();
+            // Replaced by the pipeline's waker once it is first polled.
+            let (slot, sender) = CompletionSlot::new(ud, Waker::noop().clone());
+            entry.set_ud(ud);
+
+            // Marked pending before it is sent, so it can never be observed as delivered early.
+            let origin = Origin::capture().holding(permits.next());
+            lock(&self.shared.pending).insert(ud.get(), origin);
+            chain.push((entry, Completer::Slot(sender)));
+            slots.push_back(slot);
+        }
+
+        if !chain.is_empty() {
+            self.send(Signal::Chain(chain));
+        }
+
+        Pipeline::new(slots)
+    }
+
+    /// Submits an entry as part of `group`, returning its user data.
+    ///
+    /// The completion goes to the group instead of a `Submission`: once the group is sealed and every entry in it has
+    /// completed, the group callback receives all of their completions, tagged with their user data. Like any callback,
+    /// it runs on the ring thread, or on a worker if the ring was built with [`PRingBuilder::callback_pool`].
+    ///
+    /// # Arguments
+    /// * `entry` - The submission queue entry to submit.
+    /// * `group` - The group to add the entry to.
+    ///
+    /// # Errors
+    /// Hands `entry` back without submitting it if the group has already been sealed.
+    #[inline]
+    pub fn submit_grouped(&self, entry: S, group: &GroupHandle<C>) -> Result<UserData, S>
+    where
+        C: 'static,
+    {
+        let ud = self.next_ud();
+        let Some(member) = group.join(ud) else {
+            return Err(entry);
+        };
+
+        self.enqueue_as(
+            ud,
+            entry,
+            Completer::Callback(Box::new(move |completion| member.deliver(completion))),
+            SubmitFlags::empty(),
+        );
+
+        Ok(ud)
+    }
+
+    /// Submits a batch of entries without a completion channel per entry, counting how many of them complete.
+    ///
+    /// Each entry's completion is only counted on the ring thread, with no promise, channel or callback of its own, so
+    /// this isolates the throughput of the ring from the cost of delivering completions, e.g. for benchmarks. It also
+    /// suits fire-and-forget bulk work where only the number of finished entries matters. Entries count as completed
+    /// once the backend reports them, failed or not; cancelled, timed out and dropped entries do not count.
+    ///
+    /// Under a [`PRingBuilder::max_in_flight`] cap, the entries are sent in chunks no larger than the cap, each once
+    /// there is room for all of it, so this blocks until all but the last chunk are in flight.
+    ///
+    /// # Arguments
+    /// * `entries` - An iterator of submission queue entries.
+    ///
+    /// # Returns
+    /// A `Promise` that resolves to the number of completed entries once every entry has either completed or been
+    /// dropped by the ring thread, or rejected because the ring thread has exited.
+    pub fn submit_bulk_detached<I>(&self, entries: I) -> Promise<usize, RecvError>
+    where
+        I: IntoIterator<Item = S>,
+    {
+        let (tx, rx) = oneshot::channel();
+        // The total goes out once the last entry lets go of it.
+        let tally = Arc::new(Tally::new(tx));
+
+        let mut entries = entries.into_iter();
+        loop {
+            let chunk: Vec<_> = entries.by_ref().take(self.max_batch()).collect();
+            if chunk.is_empty() {
+                break;
+            }
+
+            let mut permits = self.acquire_batch(chunk.len()).into_iter();
+            let batch = chunk
+                .into_iter()
+                .map(|mut entry| {
+                    let ud = self.next_ud();
+                    entry.set_ud(ud);
+
+                    // Marked pending before it is sent, so it can never be observed as delivered early.
+                    let origin = Origin::capture().holding(permits.next());
+                    lock(&self.shared.pending).insert(ud.get(), origin);
+                    (entry, Completer::Count(Arc::clone(&tally)))
+                })
+                .collect();
+            self.send(Signal::Batch(batch));
+        }
+        drop(tally);
+
+        Promise::new(move || rx.recv())
+    }
+
+    /// Submits a batch of entries to the ring, returning a handle for the completion of each entry it accepted.
+    ///
+    /// The batch reaches the ring thread as a single signal, which pushes it in chunks the size of the SQ's
+    /// [`capacity`](SubmissionQueue::capacity), if known, submitting and reaping between them.
+    ///
+    /// Entries that could not be sent are handed back along with the reason, so they can be retried or dealt with
+    /// otherwise: every remaining entry once a [`PRingBuilder::completion_pool`] runs out, and the whole batch if the
+    /// channel is at capacity or the ring thread has exited. Their user data has already been set, and is replaced
+    /// when they are submitted again.
+    ///
+    /// Under a [`PRingBuilder::max_in_flight`] cap, this blocks until the whole batch fits. A batch larger than the cap
+    /// never does, and is handed back whole with `SubmitError::AtCapacity`.
+    ///
+    /// # Arguments
+    /// * `entries` - An iterator of submission queue entries.
+    ///
+    /// # Returns
+    /// A vector with, for each entry in order, either a `Submission` or the rejected entry and why it was rejected.
+    pub fn batch_submit<I>(&self, entries: I) -> Vec<Result<Submission<C>, (S, SubmitError)>>
+    where
+        I: IntoIterator<Item = S>,
+    {
+        let entries: Vec<_> = entries.into_iter().collect();
+        if entries.len() > self.max_batch() {
+            return entries
+                .into_iter()
+                .map(|entry| Err((entry, SubmitError::AtCapacity)))
+                .collect();
+        }
+
+        let mut results = Vec::with_capacity(entries.len());
+        let mut batch = Vec::with_capacity(entries.len());
+        // Where each entry of the batch sits in `results`.
+        let mut sent = Vec::with_capacity(entries.len());
+
+        for mut entry in entries {
+            let ud = self.next_ud();
+            let Some((tx, submission)) = self.completion_channel(ud) else {
+                results.push(Err((entry, SubmitError::AtCapacity)));
+                continue;
+            };
+            entry.set_ud(ud);
+            sent.push(results.len());
+            batch.push((entry, tx));
+            results.push(Ok(submission));
+        }
+
+        if batch.is_empty() {
+            return results;
+        }
+
+        // Marked pending before it is sent, so it can never be observed as delivered early.
+        let mut permits = self.acquire_batch(batch.len()).into_iter();
+        let mut pending = lock(&self.shared.pending);
+        for (entry, _) in &batch {
+            pending.insert(
+                entry.get_ud().get(),
+                Origin::capture().holding(permits.next()),
+            );
+        }
+        drop(pending);
+
+        let (batch, reason) = match self.dispatch(Signal::Batch(batch), true) {
+            Ok(()) => return results,
+            Err(SendFailure::AtCapacity(Signal::Batch(batch))) => (batch, SubmitError::AtCapacity),
+            Err(SendFailure::Disconnected(Signal::Batch(batch))) => {
+                (batch, SubmitError::Disconnected)
+            }
+            Err(_) => unreachable!("the batch is handed back as it was sent"),
+        };
+
+        let mut pending = lock(&self.shared.pending);
+        for (index, (entry, _)) in sent.into_iter().zip(batch) {
+            pending.remove(&entry.get_ud().get());
+            // Dropping the handle and its sender gives a pooled channel back.
+            results[index] = Err((entry, reason.clone()));
+        }
+
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{atomic::AtomicUsize, mpsc},
+        thread,
+        time::Duration,
+    };
+
+    use super::*;
+    use crate::{
+        adapters::{Completions, QueueSubmissions},
+        driver::StepOutcome,
+        test_util::{MockEntry, MockRing},
+        traits::Submitter,
+    };
+
+    /// A backend that completes every entry as soon as it is submitted.
+    struct SyncRing(MockRing<MockEntry, MockEntry>);
+
+    impl Submitter for SyncRing {
+        fn submit(&mut self) {
+            self.submit_counted();
+        }
+
+        fn submit_counted(&mut self) -> Option<usize> {
+            let submitted = self.0.submit_counted();
+            self.0.complete_all();
+            submitted
+        }
+    }
+
+    impl FullRing<MockEntry, MockEntry, QueueSubmissions<MockEntry>, Completions<MockEntry>>
+        for SyncRing
+    {
+        fn completion(&mut self) -> Completions<MockEntry> {
+            self.0.completion()
+        }
+
+        fn submission(&mut self) -> QueueSubmissions<MockEntry> {
+            self.0.submission()
+        }
+
+        fn is_synchronous(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn submit_sync_takes_one_signal_on_a_synchronous_backend() {
+        let (sender, mut driver) = PRingSender::builder()
+            .submit_threshold(8)
+            .build_driver(SyncRing(MockRing::echo()));
+
+        let (tx, rx) = mpsc::channel();
+        let caller = thread::spawn(move || tx.send(sender.submit_sync(MockEntry::new(7))));
+
+        while driver.step() != StepOutcome::Handled {}
+        let completion = rx.recv_timeout(Duration::from_secs(5)).unwrap().unwrap();
+        assert_eq!(completion.tag(), 7);
+
+        // The caller dropped its sender on return without sending anything else.
+        caller.join().unwrap().unwrap();
+        assert_eq!(driver.try_step(), StepOutcome::Disconnected);
+    }
+
+    #[test]
+    fn submit_sync_bypasses_the_submit_threshold() {
+        let ring = MockRing::echo();
+        let sender = PRingSender::builder()
+            .submit_threshold(8)
+            .build(ring.clone());
+
+        let (tx, rx) = mpsc::channel();
+        let caller = sender.clone();
+        thread::spawn(move || tx.send(caller.submit_sync(MockEntry::new(3))));
+
+        // Submitted on its own, well short of the threshold.
+        while ring.complete_next().is_none() {
+            thread::yield_now();
+        }
+        sender.reap();
+        let completion = rx.recv_timeout(Duration::from_secs(5)).unwrap().unwrap();
+        assert_eq!(completion.tag(), 3);
+    }
+
+    #[test]
+    fn peak_in_flight_tracks_the_high_water_mark_until_reset() {
+        const N: usize = 5;
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder().build_driver(ring.clone());
+
+        let submissions: Vec<_> = (0..N as u64)
+            .map(|tag| sender.submit(MockEntry::new(tag)))
+            .collect();
+        for _ in 0..N {
+            assert_eq!(driver.step(), StepOutcome::Handled);
+        }
+        assert_eq!(ring.in_flight().len(), N);
+        assert_eq!(sender.peak_in_flight(), N);
+
+        assert_eq!(ring.complete_all(), N);
+        sender.reap();
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        for submission in submissions {
+            submission.wait().unwrap();
+        }
+        // The peak outlives the entries that set it.
+        assert_eq!(sender.peak_in_flight(), N);
+
+        sender.reset_peak();
+        assert_eq!(sender.peak_in_flight(), 0);
+
+        let _submission = sender.submit(MockEntry::new(N as u64));
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        assert_eq!(sender.peak_in_flight(), 1);
+    }
+
+    #[test]
+    fn warm_up_completes_before_the_first_entry_is_processed() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder().build_driver(ring.clone());
+
+        let warming = sender.clone();
+        let warm_up = thread::spawn(move || warming.warm_up());
+        while driver.step() != StepOutcome::Handled {}
+        // Answered by the one signal, before any entry reached the ring.
+        warm_up.join().unwrap().unwrap();
+        assert!(ring.in_flight().is_empty());
+
+        // Queued behind a warm-up, an entry is only pushed once the warm-up has been answered.
+        let (tx, warmed) = oneshot::channel();
+        sender.send(Signal::WarmUp(tx));
+        let submission = sender.submit(MockEntry::new(1));
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        assert!(warmed.try_recv().is_ok());
+        assert!(ring.in_flight().is_empty());
+
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        assert_eq!(ring.in_flight(), [submission.user_data()]);
+    }
+
+    /// An entry, and its completion, borrowing the bytes it carries from the submitting thread.
+    struct Borrowed<'a> {
+        ud: UserData,
+        bytes: &'a [u8],
+    }
+
+    impl SQE for Borrowed<'_> {
+        fn set_ud(&mut self, ud: UserData) {
+            self.ud = ud;
+        }
+
+        fn get_ud(&self) -> UserData {
+            self.ud
+        }
+    }
+
+    impl CQE for Borrowed<'_> {
+        fn get_ud(&self) -> UserData {
+            self.ud
+        }
+    }
+
+    /// A backend completing every entry with itself as soon as it is submitted.
+    #[derive(Default)]
+    struct BorrowingRing<'a> {
+        sq: QueueSubmissions<Borrowed<'a>>,
+        completed: Vec<Borrowed<'a>>,
+    }
+
+    impl Submitter for BorrowingRing<'_> {
+        fn submit(&mut self) {
+            self.completed.extend(self.sq.drain());
+        }
+    }
+
+    impl<'a>
+        FullRing<
+            Borrowed<'a>,
+            Borrowed<'a>,
+            QueueSubmissions<Borrowed<'a>>,
+            Completions<Borrowed<'a>>,
+        > for BorrowingRing<'a>
+    {
+        fn completion(&mut self) -> Completions<Borrowed<'a>> {
+            std::mem::take(&mut self.completed).into()
+        }
+
+        fn submission(&mut self) -> QueueSubmissions<Borrowed<'a>> {
+            self.sq.clone()
+        }
+
+        fn is_synchronous(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn scoped_ring_carries_entries_borrowing_from_the_scope() {
+        let buf = [1u8, 2, 3, 4];
+
+        thread::scope(|scope| {
+            let sender = PRingSender::scoped(scope, BorrowingRing::default());
+            let (head, tail) = buf.split_at(2);
+
+            let first = sender.submit(Borrowed {
+                ud: UserData::new(0),
+                bytes: head,
+            });
+            let second = sender.submit(Borrowed {
+                ud: UserData::new(0),
+                bytes: tail,
+            });
+            assert_eq!(first.wait().unwrap().bytes, [1, 2]);
+            assert_eq!(second.wait().unwrap().bytes, [3, 4]);
+        });
+    }
+
+    /// A callback recording the tag of the completion it is delivered, in delivery order.
+    fn record(
+        delivered: &Arc<Mutex<Vec<u64>>>,
+    ) -> impl FnOnce(Completion<MockEntry>) + Send + 'static {
+        let delivered = Arc::clone(delivered);
+        move |completion| lock(&delivered).push(completion.unwrap().tag())
+    }
+
+    #[test]
+    fn oldest_first_delivery_sorts_a_reap_by_submission_time() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder()
+            .oldest_first_delivery()
+            .build_driver(ring.clone());
+
+        let delivered = Arc::default();
+        for tag in [1, 2, 3] {
+            sender.submit_with_callback(MockEntry::new(tag), record(&delivered));
+            assert_eq!(driver.step(), StepOutcome::Handled);
+            // Keeps the submission times apart.
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        // The newest entry completes first, but all three are found by the same reap.
+        for ud in ring.in_flight().into_iter().rev() {
+            assert!(ring.complete_ud(ud));
+        }
+        sender.reap();
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        assert_eq!(*lock(&delivered), [1, 2, 3]);
+    }
+
+    #[test]
+    fn newest_first_delivery_reverses_a_reap_by_submission_time() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder()
+            .newest_first_delivery()
+            .build_driver(ring.clone());
+
+        let delivered = Arc::default();
+        for tag in [1, 2, 3] {
+            sender.submit_with_callback(MockEntry::new(tag), record(&delivered));
+            assert_eq!(driver.step(), StepOutcome::Handled);
+            // Keeps the submission times apart.
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        // All three complete in CQ order and are found by the same reap.
+        assert_eq!(ring.complete_all(), 3);
+        sender.reap();
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        assert_eq!(*lock(&delivered), [3, 2, 1]);
+    }
+
+    #[test]
+    fn newest_first_delivery_orders_within_a_priority() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder()
+            .prioritized_delivery()
+            .newest_first_delivery()
+            .build_driver(ring.clone());
+
+        let delivered = Arc::default();
+        for (tag, priority) in [(1, 0), (2, 1), (3, 0), (4, 1)] {
+            let ud = sender.next_ud();
+            let completer = Completer::Callback(Box::new(record(&delivered)));
+            sender.enqueue_signal(ud, MockEntry::new(tag), |entry| {
+                Signal::Prioritized(entry, completer, priority)
+            });
+            assert_eq!(driver.step(), StepOutcome::Handled);
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        assert_eq!(ring.complete_all(), 4);
+        sender.reap();
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        // Higher priorities first, newest first among equal ones.
+        assert_eq!(*lock(&delivered), [4, 2, 3, 1]);
+    }
+
+    /// The permits free under the in-flight cap, counted by taking as many as there are.
+    fn free_permits(sender: &PRingSender<MockEntry, MockEntry>) -> usize {
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut taken = Vec::new();
+        while let Poll::Ready(Some(permit)) = sender.poll_acquire(&mut cx) {
+            taken.push(permit);
+        }
+        taken.len()
+    }
+
+    #[test]
+    fn oversize_batches_are_rejected_without_taking_permits() {
+        let (sender, mut driver) = PRingSender::builder()
+            .max_in_flight(2)
+            .build_driver(MockRing::echo());
+
+        let results = sender.batch_submit((0..3).map(MockEntry::new));
+        assert_eq!(results.len(), 3);
+        assert!(
+            results
+                .iter()
+                .all(|result| matches!(result, Err((_, SubmitError::AtCapacity))))
+        );
+
+        let mut pipeline = sender.submit_pipeline((0..3).map(MockEntry::new));
+        let mut cx = Context::from_waker(Waker::noop());
+        assert!(matches!(
+            pipeline.poll_next(&mut cx),
+            Poll::Ready(Some(Err(RingError::AtCapacity)))
+        ));
+
+        assert_eq!(driver.try_step(), StepOutcome::Idle);
+        assert_eq!(free_permits(&sender), 2);
+    }
+
+    #[test]
+    fn batches_wait_for_room_for_all_their_entries() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder()
+            .max_in_flight(2)
+            .build_driver(ring.clone());
+        let first = sender.submit(MockEntry::new(0));
+        assert_eq!(driver.step(), StepOutcome::Handled);
+
+        let (tx, rx) = mpsc::channel();
+        let batcher = sender.clone();
+        thread::spawn(move || tx.send(batcher.batch_submit((1..3).map(MockEntry::new))));
+        // One permit is free, and the batch needs two.
+        assert!(rx.recv_timeout(Duration::from_millis(50)).is_err());
+
+        assert_eq!(ring.complete_all(), 1);
+        sender.reap();
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        first.wait().unwrap();
+
+        let results = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(results.iter().all(Result::is_ok));
+        assert_eq!(free_permits(&sender), 0);
+    }
+
+    #[test]
+    fn bulk_submissions_are_sent_in_chunks_that_fit_the_cap() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder()
+            .max_in_flight(2)
+            .build_driver(ring.clone());
+
+        let (tx, rx) = mpsc::channel();
+        let bulk = sender.clone();
+        thread::spawn(move || {
+            let total = bulk.submit_bulk_detached((0..5).map(MockEntry::new)).wait();
+            tx.send(total)
+        });
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let total = loop {
+            assert!(
+                Instant::now() < deadline,
+                "the bulk submission never finished"
+            );
+            driver.try_step();
+            assert!(ring.in_flight().len() <= 2);
+            ring.complete_all();
+            sender.reap();
+            if let Ok(total) = rx.try_recv() {
+                break total;
+            }
+            thread::sleep(Duration::from_millis(1));
+        };
+        assert_eq!(total.unwrap(), Ok(5));
+        assert_eq!(free_permits(&sender), 2);
+    }
+
+    #[test]
+    fn cancelled_batch_entries_give_their_permits_back() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder()
+            .max_in_flight(2)
+            .build_driver(ring.clone());
+
+        let submissions: Vec<_> = sender
+            .batch_submit((0..2).map(MockEntry::new))
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        assert_eq!(free_permits(&sender), 0);
+
+        for submission in &submissions {
+            sender.send(Signal::Cancel(submission.user_data()));
+            assert_eq!(driver.step(), StepOutcome::Handled);
+        }
+        for submission in submissions {
+            assert_eq!(submission.wait().unwrap_err(), RingError::Cancelled);
+        }
+        assert_eq!(free_permits(&sender), 2);
+
+        // A chain failing part way gives back the permits of the entries it short-circuits once they complete.
+        let mut pipeline = sender.submit_pipeline((0..2).map(MockEntry::new));
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        let first = pipeline.user_data().next().unwrap();
+        sender.send(Signal::Cancel(first));
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        assert_eq!(free_permits(&sender), 1);
+
+        ring.complete_all();
+        sender.reap();
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        let mut cx = Context::from_waker(Waker::noop());
+        assert!(matches!(
+            pipeline.poll_next(&mut cx),
+            Poll::Ready(Some(Err(RingError::Cancelled)))
+        ));
+        assert_eq!(free_permits(&sender), 2);
+    }
+
+    #[test]
+    fn timed_out_entries_give_their_permits_back() {
+        let (sender, mut driver) = PRingSender::builder()
+            .max_in_flight(1)
+            .build_driver(MockRing::echo());
+
+        let submission = sender.submit_timeout(MockEntry::new(0), Duration::from_millis(5));
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        assert_eq!(free_permits(&sender), 0);
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let completion = loop {
+            assert!(Instant::now() < deadline, "the entry never timed out");
+            driver.step();
+            if let Some(completion) = submission.try_take() {
+                break completion;
+            }
+        };
+        assert_eq!(completion.unwrap_err(), RingError::Timeout);
+        assert_eq!(free_permits(&sender), 1);
+    }
+
+    #[test]
+    fn batches_failing_to_send_give_their_permits_back() {
+        let (sender, driver) = PRingSender::builder()
+            .max_in_flight(2)
+            .build_driver(MockRing::echo());
+        drop(driver);
+
+        let results = sender.batch_submit((0..2).map(MockEntry::new));
+        assert!(
+            results
+                .iter()
+                .all(|result| matches!(result, Err((_, SubmitError::Disconnected))))
+        );
+        assert_eq!(free_permits(&sender), 2);
+
+        let mut pipeline = sender.submit_pipeline((0..2).map(MockEntry::new));
+        let mut cx = Context::from_waker(Waker::noop());
+        assert!(matches!(
+            pipeline.poll_next(&mut cx),
+            Poll::Ready(Some(Err(_)))
+        ));
+        assert_eq!(free_permits(&sender), 2);
+
+        assert_eq!(
+            sender
+                .submit_bulk_detached((0..2).map(MockEntry::new))
+                .wait()
+                .unwrap(),
+            Ok(0)
+        );
+        assert_eq!(free_permits(&sender), 2);
+    }
+
+    /// A backend linking a timeout entry of its own to entries submitted with a timeout.
+    struct LinkedTimeoutRing(MockRing<MockEntry, MockEntry>);
+
+    impl LinkedTimeoutRing {
+        /// The tag of the timeout entries.
+        const TIMEOUT: u64 = u64::MAX;
+    }
+
+    impl Submitter for LinkedTimeoutRing {
+        fn submit(&mut self) {
+            self.0.submit();
+        }
+
+        fn submit_counted(&mut self) -> Option<usize> {
+            self.0.submit_counted()
+        }
+    }
+
+    impl FullRing<MockEntry, MockEntry, QueueSubmissions<MockEntry>, Completions<MockEntry>>
+        for LinkedTimeoutRing
+    {
+        fn completion(&mut self) -> Completions<MockEntry> {
+            self.0.completion()
+        }
+
+        fn submission(&mut self) -> QueueSubmissions<MockEntry> {
+            self.0.submission()
+        }
+
+        fn timeout_entry(&self, _timeout: Duration) -> Option<MockEntry> {
+            Some(MockEntry::new(Self::TIMEOUT))
+        }
+    }
+
+    #[test]
+    fn linked_timeout_user_data_stays_reserved_until_its_completion() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) =
+            PRingSender::builder().build_driver(LinkedTimeoutRing(ring.clone()));
+
+        let submission = sender.submit_timeout(MockEntry::new(1), Duration::from_secs(60));
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        let [entry_ud, timeout_ud] = ring.in_flight()[..] else {
+            panic!("expected the entry and its timeout in flight");
+        };
+        assert_eq!(entry_ud, submission.user_data());
+        // Reserved, yet not reported as a submission of its own.
+        assert!(lock(&sender.shared.pending).contains_key(&timeout_ud.get()));
+        assert_eq!(sender.outstanding().len(), 1);
+
+        // Once the counter has wrapped, the reserved user data is not handed out again.
+        sender
+            .shared
+            .next_ud
+            .store(timeout_ud.get(), Ordering::Relaxed);
+        sender.shared.wrapped.store(true, Ordering::Relaxed);
+        assert_ne!(sender.next_ud(), timeout_ud);
+
+        assert_eq!(ring.complete_all(), 2);
+        sender.reap();
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        assert_eq!(submission.wait().unwrap().tag(), 1);
+        assert!(lock(&sender.shared.pending).is_empty());
+    }
+
+    #[test]
+    fn timeout_user_data_is_given_back_without_a_timeout_entry() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder().build_driver(ring.clone());
+
+        let submission = sender.submit_timeout(MockEntry::new(1), Duration::from_secs(60));
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        assert_eq!(ring.in_flight(), [submission.user_data()]);
+        // Only the entry itself is still pending.
+        assert_eq!(
+            lock(&sender.shared.pending)
+                .keys()
+                .copied()
+                .collect::<Vec<_>>(),
+            [submission.user_data().get()]
+        );
+
+        assert_eq!(ring.complete_all(), 1);
+        sender.reap();
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        assert_eq!(submission.wait().unwrap().tag(), 1);
+        assert!(lock(&sender.shared.pending).is_empty());
+    }
+
+    #[test]
+    fn dropping_the_last_sender_resolves_outstanding_callbacks() {
+        let ring = MockRing::echo();
+        let sender = PRingSender::new(ring.clone());
+
+        let (tx, rx) = mpsc::channel();
+        sender.submit_with_callback(MockEntry::new(1), move |completion| {
+            tx.send(completion).unwrap();
+        });
+        while ring.in_flight().is_empty() {
+            thread::yield_now();
+        }
+
+        // The ring thread exits with the entry still in flight.
+        drop(sender);
+        let completion = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(completion.unwrap_err(), RingError::Disconnected);
+    }
+
+    #[test]
+    fn sampled_entries_count_the_pending_entries_around_them() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder().build_driver(ring.clone());
+
+        let first = sender.submit_sampled(MockEntry::new(1));
+        let second = sender.submit_sampled(MockEntry::new(2));
+        while driver.try_step() == StepOutcome::Handled {}
+
+        ring.complete_next().unwrap();
+        sender.reap();
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        let (cqe, sample) = first.wait_nopanic().unwrap();
+        assert_eq!((cqe.tag(), sample.at_submit, sample.at_complete), (1, 1, 2));
+
+        ring.complete_next().unwrap();
+        sender.reap();
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        let (cqe, sample) = second.wait_nopanic().unwrap();
+        assert_eq!((cqe.tag(), sample.at_submit, sample.at_complete), (2, 2, 1));
+    }
+
+    #[test]
+    fn into_ring_hands_the_ring_back() {
+        let sender: PRingSender<MockEntry, MockEntry> = PRingSender::new(MockRing::echo());
+        let other = sender.clone();
+        assert!(sender.into_ring::<SyncRing>().is_none());
+
+        // A guess at the wrong type still gets the ring back, boxed.
+        let Some(Err(ring)) = other.into_ring::<SyncRing>() else {
+            panic!("expected the ring back under its own type");
+        };
+        assert!(ring.downcast::<MockRing<MockEntry, MockEntry>>().is_ok());
+    }
+
+    /// A backend that panics as soon as it is asked to submit.
+    struct PanickingRing(MockRing<MockEntry, MockEntry>);
+
+    impl Submitter for PanickingRing {
+        fn submit(&mut self) {
+            panic!("the backend went away");
+        }
+    }
+
+    impl FullRing<MockEntry, MockEntry, QueueSubmissions<MockEntry>, Completions<MockEntry>>
+        for PanickingRing
+    {
+        fn completion(&mut self) -> Completions<MockEntry> {
+            self.0.completion()
+        }
+
+        fn submission(&mut self) -> QueueSubmissions<MockEntry> {
+            self.0.submission()
+        }
+    }
+
+    #[test]
+    fn outstanding_entries_are_recovered_after_a_panic() {
+        let (tx, hooked) = mpsc::channel();
+        let (sender, driver) = PRingSender::builder()
+            .retain_entries()
+            .submit_threshold(2)
+            .on_panic(move |payload| {
+                let _ = tx.send(payload.downcast_ref::<&str>().copied());
+            })
+            .build_driver(PanickingRing(MockRing::echo()));
+
+        let first = sender.submit(MockEntry::new(1));
+        let second = sender.submit(MockEntry::new(2));
+        let user_data = [first.user_data(), second.user_data()];
+        assert!(thread::spawn(move || driver.run()).join().is_err());
+
+        assert_eq!(hooked.try_recv().unwrap(), Some("the backend went away"));
+        assert_eq!(first.wait().unwrap_err(), RingError::Disconnected);
+        assert_eq!(second.wait().unwrap_err(), RingError::Disconnected);
+
+        let mut recovered = sender.take_recovered().unwrap();
+        recovered.user_data.sort();
+        recovered.entries.sort_by_key(MockEntry::tag);
+        assert_eq!(recovered.user_data, user_data);
+        let tags: Vec<_> = recovered.entries.iter().map(MockEntry::tag).collect();
+        assert_eq!(tags, [1, 2]);
+        // Only handed out once.
+        assert!(sender.take_recovered().is_none());
+    }
+
+    #[test]
+    fn barrier_waits_only_for_earlier_entries() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder().build_driver(ring.clone());
+
+        let first = sender.submit(MockEntry::new(1));
+        let second = sender.submit(MockEntry::new(2));
+        let barrier = sender.barrier();
+        let _later = sender.submit(MockEntry::new(3));
+        while driver.try_step() == StepOutcome::Handled {}
+        assert_eq!(ring.in_flight().len(), 3);
+
+        let (tx, passed) = mpsc::channel();
+        thread::spawn(move || tx.send(barrier.wait_nopanic()));
+
+        assert!(ring.complete_ud(first.user_data()));
+        sender.reap();
+        while driver.try_step() == StepOutcome::Handled {}
+        assert!(passed.recv_timeout(Duration::from_millis(50)).is_err());
+
+        // The entry submitted after the barrier is still in flight when it passes.
+        assert!(ring.complete_ud(second.user_data()));
+        sender.reap();
+        while driver.try_step() == StepOutcome::Handled {}
+        assert!(passed.recv_timeout(Duration::from_secs(5)).unwrap().is_ok());
+        assert_eq!(ring.in_flight().len(), 1);
+    }
+
+    /// A backend splitting every operation into three completions.
+    struct ScatterRing(MockRing<MockEntry, MockEntry>);
+
+    impl Submitter for ScatterRing {
+        fn submit(&mut self) {
+            self.0.submit();
+        }
+
+        fn submit_counted(&mut self) -> Option<usize> {
+            self.0.submit_counted()
+        }
+    }
+
+    impl FullRing<MockEntry, MockEntry, QueueSubmissions<MockEntry>, Completions<MockEntry>>
+        for ScatterRing
+    {
+        fn completion(&mut self) -> Completions<MockEntry> {
+            let cqes: Vec<_> = self.0.completion().flat_map(|cqe| [cqe; 3]).collect();
+            cqes.into()
+        }
+
+        fn submission(&mut self) -> QueueSubmissions<MockEntry> {
+            self.0.submission()
+        }
+    }
+
+    #[test]
+    fn expected_completions_are_collected_until_the_count_is_reached() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder().build_driver(ScatterRing(ring.clone()));
+
+        let all = sender.submit_expecting(MockEntry::new(1), 3);
+        // The third completion finds nothing registered any more.
+        let fewer = sender.submit_expecting(MockEntry::new(2), 2);
+        while driver.try_step() == StepOutcome::Handled {}
+        assert_eq!(sender.outstanding().len(), 2);
+        assert_eq!(ring.complete_all(), 2);
+        sender.reap();
+        while driver.try_step() == StepOutcome::Handled {}
+
+        let tags = |completions: Vec<MockEntry>| {
+            completions.iter().map(MockEntry::tag).collect::<Vec<_>>()
+        };
+        assert_eq!(tags(all.wait_nopanic().unwrap()), [1, 1, 1]);
+        assert_eq!(tags(fewer.wait_nopanic().unwrap()), [2, 2]);
+        assert!(sender.outstanding().is_empty());
+    }
+
+    /// A mock backend behind an SQ of two entries, counting how often it submits.
+    struct TwoSlotRing {
+        mock: MockRing<MockEntry, MockEntry>,
+        sq: QueueSubmissions<MockEntry>,
+        submits: Arc<AtomicUsize>,
+    }
+
+    impl Submitter for TwoSlotRing {
+        fn submit(&mut self) {
+            let mut sq = self.mock.submission();
+            for entry in self.sq.drain() {
+                let _ = sq.push(entry);
+            }
+            self.mock.submit();
+            self.submits.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    impl FullRing<MockEntry, MockEntry, QueueSubmissions<MockEntry>, Completions<MockEntry>>
+        for TwoSlotRing
+    {
+        fn completion(&mut self) -> Completions<MockEntry> {
+            self.mock.completion()
+        }
+
+        fn submission(&mut self) -> QueueSubmissions<MockEntry> {
+            self.sq.clone()
+        }
+    }
+
+    #[test]
+    fn batches_arrive_as_one_signal_and_are_pushed_in_sq_sized_chunks() {
+        let ring = MockRing::echo();
+        let submits = Arc::new(AtomicUsize::new(0));
+        let (sender, mut driver) = PRingSender::builder().build_driver(TwoSlotRing {
+            mock: ring.clone(),
+            sq: QueueSubmissions::bounded(2),
+            submits: Arc::clone(&submits),
+        });
+
+        let submissions = sender.batch_submit((1..=5).map(MockEntry::new));
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        assert_eq!(driver.try_step(), StepOutcome::Idle);
+
+        assert_eq!(ring.in_flight().len(), 5);
+        assert_eq!(submits.load(Ordering::SeqCst), 3);
+        assert!(submissions.iter().all(Result::is_ok));
+    }
+
+    #[test]
+    fn batch_entries_that_cannot_be_sent_are_handed_back() {
+        let (sender, driver) = PRingSender::builder()
+            .completion_pool(2)
+            .build_driver(MockRing::echo());
+
+        let mut results = sender.batch_submit((1..=3).map(MockEntry::new));
+        let Some(Err((entry, SubmitError::AtCapacity))) = results.pop() else {
+            panic!("the third entry found no free completion channel");
+        };
+        assert_eq!(entry.tag(), 3);
+        assert!(results.iter().all(Result::is_ok));
+
+        drop(results);
+        drop(driver);
+        let rejected: Vec<_> = sender
+            .batch_submit((4..=5).map(MockEntry::new))
+            .into_iter()
+            .map(|result| match result {
+                Err((entry, SubmitError::Disconnected)) => entry.tag(),
+                _ => panic!("the ring thread is gone"),
+            })
+            .collect();
+        assert_eq!(rejected, [4, 5]);
+    }
+
+    #[test]
+    fn reassigned_entries_complete_to_their_new_sender() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder().build_driver(ring.clone());
+
+        let original = sender.submit(MockEntry::new(1));
+        let ud = original.user_data();
+        let (tx, rx) = oneshot::channel();
+        let reassigned = sender.reassign(ud, tx);
+        while driver.try_step() == StepOutcome::Handled {}
+        assert_eq!(reassigned.wait_nopanic(), Ok(true));
+        assert_eq!(original.wait().unwrap_err(), RingError::Disconnected);
+
+        // Still the same entry in flight, not a resubmission.
+        assert_eq!(ring.in_flight(), [ud]);
+        assert!(ring.complete_ud(ud));
+        sender.reap();
+        while driver.try_step() == StepOutcome::Handled {}
+        assert_eq!(rx.recv().unwrap().unwrap().tag(), 1);
+
+        let (tx, _rx) = oneshot::channel();
+        let late = sender.reassign(ud, tx);
+        while driver.try_step() == StepOutcome::Handled {}
+        assert_eq!(late.wait_nopanic(), Ok(false));
+    }
+
+    #[test]
+    fn detached_bulk_submissions_count_what_completed() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder().build_driver(ring.clone());
+
+        let completed = sender.submit_bulk_detached((1..=3).map(MockEntry::new));
+        while driver.try_step() == StepOutcome::Handled {}
+        assert_eq!(ring.in_flight().len(), 3);
+
+        assert!(ring.complete_next().is_some());
+        assert!(ring.complete_next().is_some());
+        sender.reap();
+        while driver.try_step() == StepOutcome::Handled {}
+
+        // The last entry is dropped with the ring thread rather than completed.
+        drop(driver);
+        assert_eq!(completed.wait_nopanic(), Ok(2));
+    }
+
+    #[test]
+    fn flush_sync_hands_deferred_entries_to_the_backend() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder()
+            .submit_threshold(8)
+            .max_defer(Duration::from_secs(60))
+            .build_driver(ring.clone());
+
+        let _submissions: Vec<_> = (1..=3)
+            .map(|tag| sender.submit(MockEntry::new(tag)))
+            .collect();
+        while driver.try_step() == StepOutcome::Handled {}
+        assert_eq!(driver.deferred(), 3);
+        assert!(ring.in_flight().is_empty());
+
+        let flusher = sender.clone();
+        let flushed = thread::spawn(move || flusher.flush_sync());
+        while driver.step() != StepOutcome::Handled {}
+        // Once it returns, every entry has reached the backend.
+        assert_eq!(flushed.join().unwrap(), Ok(3));
+        assert_eq!(driver.deferred(), 0);
+        assert_eq!(ring.in_flight().len(), 3);
+
+        drop(driver);
+        assert_eq!(sender.flush_sync(), Err(RingError::ThreadGone));
+    }
+
+    #[test]
+    fn snapshots_copy_the_retained_entries_still_outstanding() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder()
+            .retain_entries()
+            .build_driver(ring.clone());
+
+        let _submissions: Vec<_> = (1..=3)
+            .map(|tag| sender.submit(MockEntry::new(tag)))
+            .collect();
+        while driver.try_step() == StepOutcome::Handled {}
+        let second = ring.in_flight()[1];
+        assert!(ring.complete_ud(second));
+        sender.reap();
+        while driver.try_step() == StepOutcome::Handled {}
+
+        // Only the delivered entry is left out, and the rest come in submission order.
+        let snapshot = sender.snapshot();
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        let tags: Vec<_> = snapshot
+            .wait_nopanic()
+            .unwrap()
+            .iter()
+            .map(MockEntry::tag)
+            .collect();
+        assert_eq!(tags, [1, 3]);
+    }
+
+    #[test]
+    fn snapshots_are_empty_without_retained_entries() {
+        let (sender, mut driver) = PRingSender::builder().build_driver(MockRing::echo());
+
+        let _submission = sender.submit(MockEntry::new(1));
+        let snapshot = sender.snapshot();
+        while driver.try_step() == StepOutcome::Handled {}
+        assert_eq!(snapshot.wait_nopanic(), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn wait_until_below_returns_once_enough_entries_completed() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder().build_driver(ring.clone());
+
+        let _submissions: Vec<_> = (1..=3)
+            .map(|tag| sender.submit(MockEntry::new(tag)))
+            .collect();
+        while driver.try_step() == StepOutcome::Handled {}
+        // Already below, so no signal is needed.
+        assert_eq!(sender.wait_until_below(4), Ok(()));
+
+        let (tx, rx) = mpsc::channel();
+        let waiter = sender.clone();
+        thread::spawn(move || tx.send(waiter.wait_until_below(2)));
+        while driver.step() != StepOutcome::Handled {}
+
+        // Two entries outstanding are not below two.
+        ring.complete_next().unwrap();
+        sender.reap();
+        while driver.try_step() == StepOutcome::Handled {}
+        assert!(rx.recv_timeout(Duration::from_millis(50)).is_err());
+
+        ring.complete_next().unwrap();
+        sender.reap();
+        while driver.try_step() == StepOutcome::Handled {}
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok(Ok(())));
+    }
+
+    #[test]
+    fn wait_until_below_fails_once_the_ring_thread_is_gone() {
+        let (sender, mut driver) = PRingSender::builder().build_driver(MockRing::echo());
+
+        let _submission = sender.submit(MockEntry::new(1));
+        while driver.try_step() == StepOutcome::Handled {}
+        let waiter = sender.clone();
+        let waiting = thread::spawn(move || waiter.wait_until_below(1));
+        while driver.step() != StepOutcome::Handled {}
+
+        drop(driver);
+        assert_eq!(waiting.join().unwrap(), Err(RingError::Disconnected));
+    }
+
+    /// A completion that reports an error when its entry's tag was [`Outcome::FAILING`].
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    struct Outcome {
+        ud: UserData,
+        tag: u64,
+    }
+
+    impl Outcome {
+        const FAILING: u64 = 2;
+
+        /// A mock completing each entry with its outcome.
+        fn ring() -> MockRing<MockEntry, Outcome> {
+            MockRing::new(|entry: MockEntry| Outcome {
+                ud: SQE::get_ud(&entry),
+                tag: entry.tag(),
+            })
+        }
+    }
+
+    impl CQE for Outcome {
+        fn get_ud(&self) -> UserData {
+            self.ud
+        }
+
+        fn is_error(&self) -> bool {
+            self.tag == Self::FAILING
+        }
+    }
+
+    #[test]
+    fn the_error_sink_sees_errors_their_entries_still_get() {
+        let ring = Outcome::ring();
+        let sunk = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&sunk);
+        let (sender, mut driver) = PRingSender::builder()
+            .error_sink(move |cqe: Outcome| lock(&sink).push(cqe.tag))
+            .build_driver(ring.clone());
+
+        let submissions: Vec<_> = (1..=3)
+            .map(|tag| sender.submit(MockEntry::new(tag)))
+            .collect();
+        while driver.try_step() == StepOutcome::Handled {}
+        assert_eq!(ring.complete_all(), 3);
+        sender.reap();
+        while driver.try_step() == StepOutcome::Handled {}
+
+        assert_eq!(*lock(&sunk), [Outcome::FAILING]);
+        let tags: Vec<_> = submissions
+            .into_iter()
+            .map(|submission| submission.wait().unwrap().tag)
+            .collect();
+        assert_eq!(tags, [1, 2, 3]);
+    }
+
+    #[test]
+    fn diverted_errors_fail_their_entries() {
+        let ring = Outcome::ring();
+        let sunk = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&sunk);
+        let (sender, mut driver) = PRingSender::builder()
+            .divert_errors(move |cqe: Outcome| lock(&sink).push(cqe.tag))
+            .build_driver(ring.clone());
+
+        let submissions: Vec<_> = (1..=3)
+            .map(|tag| sender.submit(MockEntry::new(tag)))
+            .collect();
+        while driver.try_step() == StepOutcome::Handled {}
+        assert_eq!(ring.complete_all(), 3);
+        sender.reap();
+        while driver.try_step() == StepOutcome::Handled {}
+
+        assert_eq!(*lock(&sunk), [Outcome::FAILING]);
+        let results: Vec<_> = submissions
+            .into_iter()
+            .map(|submission| submission.wait().map(|cqe| cqe.tag))
+            .collect();
+        assert_eq!(results, [Ok(1), Err(RingError::Diverted), Ok(3)]);
+    }
+
+    #[test]
+    fn stale_clones_resolve_submissions_with_thread_gone() {
+        let (sender, driver) = PRingSender::builder().build_driver(PanickingRing(MockRing::echo()));
+        let stale = sender.clone();
+
+        // Pushing the entry makes the backend panic, taking the ring thread down with it.
+        let lost = sender.submit(MockEntry::new(0));
+        assert!(thread::spawn(move || driver.run()).join().is_err());
+        assert_eq!(lost.wait().unwrap_err(), RingError::Disconnected);
+
+        assert_eq!(
+            stale.submit(MockEntry::new(1)).wait().unwrap_err(),
+            RingError::ThreadGone
+        );
+        let delivered = Arc::new(Mutex::new(None));
+        let into = Arc::clone(&delivered);
+        stale.submit_with_callback(MockEntry::new(2), move |completion| {
+            *lock(&into) = Some(completion.map(|cqe| cqe.tag()));
+        });
+        assert_eq!(*lock(&delivered), Some(Err(RingError::ThreadGone)));
+        // Batches hand their entries back instead, so none of them is lost.
+        for (tag, result) in (3..5).zip(stale.batch_submit((3..5).map(MockEntry::new))) {
+            let (entry, err) = result.unwrap_err();
+            assert_eq!((entry.tag(), err), (tag, SubmitError::Disconnected));
+        }
+    }
+
+    #[test]
+    fn submit_barrier_holds_later_entries_until_earlier_ones_complete() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder().build_driver(ring.clone());
+
+        let first = sender.submit(MockEntry::new(1));
+        let second = sender.submit(MockEntry::new(2));
+        sender.submit_barrier();
+        let third = sender.submit(MockEntry::new(3));
+        // The ring thread polls the CQ while the fence is up, so it never runs out of work to step.
+        for _ in 0..8 {
+            let _ = driver.try_step();
+        }
+        assert_eq!(ring.in_flight(), [first.user_data(), second.user_data()]);
+
+        // One earlier entry still outstanding keeps the fence up.
+        assert!(ring.complete_ud(first.user_data()));
+        for _ in 0..8 {
+            let _ = driver.try_step();
+        }
+        assert_eq!(ring.in_flight(), [second.user_data()]);
+
+        assert!(ring.complete_ud(second.user_data()));
+        for _ in 0..8 {
+            let _ = driver.try_step();
+        }
+        assert_eq!(ring.in_flight(), [third.user_data()]);
+        assert_eq!(first.wait().unwrap().tag(), 1);
+        assert_eq!(second.wait().unwrap().tag(), 2);
+    }
+
+    #[test]
+    fn entries_reusing_outstanding_user_data_are_rejected() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder().build_driver(ring.clone());
+
+        let first = sender.submit(MockEntry::new(1));
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        let (tx, rx) = oneshot::channel();
+        sender.enqueue_as(
+            first.user_data(),
+            MockEntry::new(2),
+            Completer::Promise(tx),
+            SubmitFlags::empty(),
+        );
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        assert_eq!(
+            rx.recv().unwrap().unwrap_err(),
+            RingError::DuplicateUserData
+        );
+        // The colliding entry is never pushed, and the outstanding one keeps its destination.
+        assert_eq!(ring.in_flight(), [first.user_data()]);
+
+        assert_eq!(ring.complete_all(), 1);
+        sender.reap();
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        assert_eq!(first.wait().unwrap().tag(), 1);
+    }
+
+    #[test]
+    fn completions_left_untaken_flag_a_slow_consumer() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder()
+            .slow_consumer_threshold(Duration::from_millis(20))
+            .build_driver(ring.clone());
+
+        let taken = sender.submit(MockEntry::new(1));
+        let untaken = sender.submit(MockEntry::new(2));
+        let dropped = sender.submit(MockEntry::new(3));
+        while driver.try_step() == StepOutcome::Handled {}
+        // Outstanding entries have nothing to take yet.
+        thread::sleep(Duration::from_millis(30));
+        assert!(sender.slow_consumers().is_empty());
+
+        assert_eq!(ring.complete_all(), 3);
+        sender.reap();
+        while driver.try_step() == StepOutcome::Handled {}
+        assert_eq!(taken.wait().unwrap().tag(), 1);
+        drop(dropped);
+        thread::sleep(Duration::from_millis(30));
+
+        let slow = sender.slow_consumers();
+        assert_eq!(slow.len(), 1);
+        assert_eq!(slow[0].0, untaken.user_data());
+        assert!(slow[0].1 > Duration::from_millis(20));
+    }
+
+    #[test]
+    fn one_caller_owned_slot_takes_completion_after_completion() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder().build_driver(ring.clone());
+        let slot = Arc::new(Mutex::new(None));
+        let notify = Arc::new(Condvar::new());
+        let take = || {
+            let mut guard = notify
+                .wait_timeout_while(lock(&slot), Duration::from_secs(5), |slot| slot.is_none())
+                .unwrap()
+                .0;
+            guard.take().unwrap()
+        };
+
+        for tag in [1, 2] {
+            sender.submit_into_slot(MockEntry::new(tag), Arc::clone(&slot), Arc::clone(&notify));
+            assert_eq!(driver.step(), StepOutcome::Handled);
+            assert_eq!(ring.complete_all(), 1);
+            sender.reap();
+            assert_eq!(driver.step(), StepOutcome::Handled);
+            assert_eq!(take().unwrap().tag(), tag);
+        }
+
+        // Errors land in the slot too.
+        let cancelled =
+            sender.submit_into_slot(MockEntry::new(3), Arc::clone(&slot), Arc::clone(&notify));
+        sender.send(Signal::Cancel(cancelled));
+        while driver.try_step() == StepOutcome::Handled {}
+        assert_eq!(take().unwrap_err(), RingError::Cancelled);
+    }
+
+    /// A backend whose OS state cannot be recreated after a fork.
+    struct UnforkableRing(MockRing<MockEntry, MockEntry>);
+
+    impl Submitter for UnforkableRing {
+        fn submit(&mut self) {
+            self.0.submit();
+        }
+    }
+
+    impl FullRing<MockEntry, MockEntry, QueueSubmissions<MockEntry>, Completions<MockEntry>>
+        for UnforkableRing
+    {
+        fn completion(&mut self) -> Completions<MockEntry> {
+            self.0.completion()
+        }
+
+        fn submission(&mut self) -> QueueSubmissions<MockEntry> {
+            self.0.submission()
+        }
+
+        fn reinit(&mut self) -> Result<(), BackendError> {
+            Err(BackendError::Other("no ring after fork".to_owned()))
+        }
+    }
+
+    #[test]
+    fn reinit_after_fork_starts_afresh_where_the_user_data_left_off() {
+        let (inherited, _driver) = PRingSender::builder().build_driver(MockRing::echo());
+        let stale = inherited.clone();
+        let before = inherited.submit(MockEntry::new(1)).user_data();
+
+        let ring = MockRing::echo();
+        let sender = inherited
+            .reinit_after_fork(PRingSender::builder(), ring.clone())
+            .unwrap();
+        assert!(!stale.is_thread_alive());
+        assert_eq!(
+            stale.submit(MockEntry::new(2)).wait().unwrap_err(),
+            RingError::ThreadGone
+        );
+
+        let submission = sender.submit(MockEntry::new(3));
+        assert!(submission.user_data().get() > before.get());
+        while ring.complete_next().is_none() {
+            thread::yield_now();
+        }
+        sender.reap();
+        assert_eq!(submission.wait().unwrap().tag(), 3);
+    }
+
+    #[test]
+    fn reinit_after_fork_hands_back_the_backend_error() {
+        let (inherited, _driver) = PRingSender::builder().build_driver(MockRing::echo());
+
+        let failed =
+            inherited.reinit_after_fork(PRingSender::builder(), UnforkableRing(MockRing::echo()));
+        assert_eq!(
+            failed.err(),
+            Some(BackendError::Other("no ring after fork".to_owned()))
+        );
+    }
+
+    /// A backend reporting every operation as three completions, flagged with their step.
+    struct ProgressRing(MockRing<MockEntry, MockEntry>);
+
+    impl ProgressRing {
+        const STEPS: u32 = 3;
+    }
+
+    impl Submitter for ProgressRing {
+        fn submit(&mut self) {
+            self.0.submit();
+        }
+    }
+
+    impl FullRing<MockEntry, MockEntry, QueueSubmissions<MockEntry>, Completions<MockEntry>>
+        for ProgressRing
+    {
+        fn completion(&mut self) -> Completions<MockEntry> {
+            let cqes: Vec<_> = self
+                .0
+                .completion()
+                .flat_map(|cqe| {
+                    (1..=Self::STEPS).map(move |step| {
+                        let mut cqe = cqe;
+                        cqe.set_flags(step);
+                        cqe
+                    })
+                })
+                .collect();
+            cqes.into()
+        }
+
+        fn submission(&mut self) -> QueueSubmissions<MockEntry> {
+            self.0.submission()
+        }
+    }
+
+    #[test]
+    fn submit_until_resolves_with_the_first_accepted_completion() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder().build_driver(ProgressRing(ring.clone()));
+
+        let last = sender.submit_until(MockEntry::new(1), |cqe| cqe.flags() == ProgressRing::STEPS);
+        let second = sender.submit_until(MockEntry::new(2), |cqe| cqe.flags() >= 2);
+        let _never = sender.submit_until(MockEntry::new(3), |_| false);
+        while driver.try_step() == StepOutcome::Handled {}
+        assert_eq!(ring.complete_all(), 3);
+        sender.reap();
+        while driver.try_step() == StepOutcome::Handled {}
+
+        assert_eq!(last.wait_nopanic().unwrap().flags(), ProgressRing::STEPS);
+        assert_eq!(second.wait_nopanic().unwrap().flags(), 2);
+        // Every completion was discarded, so the entry is still waiting for a match.
+        assert_eq!(sender.outstanding().len(), 1);
+    }
+
+    /// A resource counting how often it has been dropped.
+    #[derive(Debug)]
+    struct Counted(Arc<AtomicUsize>);
+
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn owned_resources_come_back_with_the_completion() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder().build_driver(ring.clone());
+
+        let owning = sender.submit_owning(MockEntry::new(1), vec![0_u8; 16]);
+        while driver.try_step() == StepOutcome::Handled {}
+        assert_eq!(ring.complete_all(), 1);
+        sender.reap();
+        while driver.try_step() == StepOutcome::Handled {}
+
+        let (cqe, buffer) = owning.wait_nopanic().unwrap();
+        assert_eq!((cqe.tag(), buffer.len()), (1, 16));
+    }
+
+    #[test]
+    fn owned_resources_outlive_a_cancellation_until_the_completion_arrives() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder().build_driver(ring.clone());
+        let dropped = Arc::new(AtomicUsize::new(0));
+
+        let owning = sender.submit_owning(MockEntry::new(1), Counted(Arc::clone(&dropped)));
+        while driver.try_step() == StepOutcome::Handled {}
+        let ud = ring.in_flight()[0];
+        sender.send(Signal::Cancel(ud));
+        while driver.try_step() == StepOutcome::Handled {}
+        assert_eq!(owning.wait_nopanic().unwrap_err(), RingError::Cancelled);
+        // The backend may still be using it.
+        assert_eq!(dropped.load(Ordering::SeqCst), 0);
+
+        assert_eq!(ring.complete_all(), 1);
+        sender.reap();
+        while driver.try_step() == StepOutcome::Handled {}
+        assert_eq!(dropped.load(Ordering::SeqCst), 1);
     }
 }
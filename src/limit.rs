@@ -0,0 +1,204 @@
+//! A cap on the entries in flight, enforced where they are submitted.
+//!
+//! An `InFlightLimit` is a semaphore with one permit per entry allowed in flight. A sender takes a permit before an
+//! entry is marked pending, and the permit travels with the entry's [`Origin`](crate::registry::Origin) in the set of
+//! pending user data. Every way an entry stops being pending, delivered, cancelled, timed out, failed or rejected
+//! before it was sent, removes it from that set and so drops the permit, giving it back without each path having to.
+
+use std::{
+    fmt,
+    sync::{Arc, Condvar, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+use crate::registry::lock;
+
+/// A semaphore bounding the entries in flight.
+pub(crate) struct InFlightLimit {
+    /// Most entries in flight before submitting waits.
+    max: usize,
+    /// Permits handed out and the async submissions waiting for one.
+    state: Mutex<LimitState>,
+    /// Notified whenever a permit is given back.
+    released: Condvar,
+}
+
+/// The mutable part of an [`InFlightLimit`].
+struct LimitState {
+    /// Permits currently held.
+    held: usize,
+    /// Woken whenever a permit is given back.
+    wakers: Vec<Waker>,
+}
+
+/// A permit for one entry in flight, given back when dropped.
+pub(crate) struct Permit {
+    /// The limit the permit was taken from.
+    limit: Arc<InFlightLimit>,
+}
+
+impl InFlightLimit {
+    /// Creates a limit allowing `max` entries in flight at once. A maximum of zero is treated as one.
+    pub(crate) fn new(max: usize) -> Self {
+        Self {
+            max: max.max(1),
+            state: Mutex::new(LimitState {
+                held: 0,
+                wakers: Vec::new(),
+            }),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Takes a permit, blocking until one is free.
+    pub(crate) fn acquire(self: &Arc<Self>) -> Permit {
+        let mut state = lock(&self.state);
+        while state.held >= self.max {
+            state = self
+                .released
+                .wait(state)
+                .unwrap_or_else(|err| err.into_inner());
+        }
+        state.held += 1;
+
+        self.permit()
+    }
+
+    /// Takes a permit if one is free, or has `cx`'s waker woken once one may be.
+    pub(crate) fn poll_acquire(self: &Arc<Self>, cx: &mut Context<'_>) -> Poll<Permit> {
+        let mut state = lock(&self.state);
+        if state.held >= self.max {
+            if !state.wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+                state.wakers.push(cx.waker().clone());
+            }
+            return Poll::Pending;
+        }
+        state.held += 1;
+
+        Poll::Ready(self.permit())
+    }
+
+    /// Takes `n` permits at once, blocking until that many are free. Asking for more than the maximum is treated as
+    /// asking for the maximum.
+    ///
+    /// Taken together rather than one by one, so batches waiting side by side cannot each hold part of what they need
+    /// and wait for each other forever.
+    pub(crate) fn acquire_many(self: &Arc<Self>, n: usize) -> Vec<Permit> {
+        let n = n.min(self.max);
+        let mut state = lock(&self.state);
+        while state.held + n > self.max {
+            state = self
+                .released
+                .wait(state)
+                .unwrap_or_else(|err| err.into_inner());
+        }
+        state.held += n;
+
+        (0..n).map(|_| self.permit()).collect()
+    }
+
+    /// Most entries in flight before submitting waits.
+    #[inline]
+    pub(crate) fn max(&self) -> usize {
+        self.max
+    }
+
+    /// Wraps a permit just counted as held.
+    fn permit(self: &Arc<Self>) -> Permit {
+        Permit {
+            limit: Arc::clone(self),
+        }
+    }
+}
+
+impl fmt::Debug for Permit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Permit").finish_non_exhaustive()
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let wakers = {
+            let mut state = lock(&self.limit.state);
+            state.held -= 1;
+            std::mem::take(&mut state.wakers)
+        };
+        // All of them, as the one woken alone could be a batch still short of room while a single entry would fit.
+        self.limit.released.notify_all();
+
+        // Every waiting task races for the permit; those that lose register their waker again.
+        wakers.into_iter().for_each(Waker::wake);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicBool, Ordering},
+        task::Wake,
+        thread,
+    };
+
+    use super::*;
+
+    /// Records whether it was woken.
+    #[derive(Default)]
+    struct Flag(AtomicBool);
+
+    impl Wake for Flag {
+        fn wake(self: Arc<Self>) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn asking_for_more_than_the_maximum_takes_the_maximum() {
+        let limit = Arc::new(InFlightLimit::new(3));
+
+        let permits = limit.acquire_many(10);
+        assert_eq!(permits.len(), 3);
+        assert_eq!(lock(&limit.state).held, 3);
+
+        drop(permits);
+        assert_eq!(lock(&limit.state).held, 0);
+    }
+
+    #[test]
+    fn batches_and_single_entries_waiting_side_by_side_both_get_through() {
+        let limit = Arc::new(InFlightLimit::new(2));
+        let held = limit.acquire_many(2);
+
+        let batch = thread::spawn({
+            let limit = Arc::clone(&limit);
+            move || limit.acquire_many(2).len()
+        });
+        let single = thread::spawn({
+            let limit = Arc::clone(&limit);
+            move || {
+                drop(limit.acquire());
+            }
+        });
+
+        drop(held);
+        assert_eq!(batch.join().unwrap(), 2);
+        single.join().unwrap();
+        assert_eq!(lock(&limit.state).held, 0);
+    }
+
+    #[test]
+    fn dropping_a_permit_wakes_a_pending_acquire() {
+        let limit = Arc::new(InFlightLimit::new(1));
+        let held = limit.acquire();
+
+        let flag = Arc::new(Flag::default());
+        let waker = Waker::from(Arc::clone(&flag));
+        let mut cx = Context::from_waker(&waker);
+        assert!(limit.poll_acquire(&mut cx).is_pending());
+        assert!(!flag.0.load(Ordering::SeqCst));
+
+        drop(held);
+        assert!(flag.0.load(Ordering::SeqCst));
+        assert!(limit.poll_acquire(&mut cx).is_ready());
+    }
+}
@@ -0,0 +1,356 @@
+//! A ring driven inline, without a ring thread.
+//!
+//! `LocalRing` is for environments where spawning threads is not an option, e.g. WASM. It owns the ring and a
+//! [`Registry`] directly: [`submit`](LocalRing::submit) pushes the entry onto the SQ on the calling thread, and
+//! nothing happens to it until the caller [`drive`](LocalRing::drive)s the ring. There is no channel and no thread,
+//! so every call happens on the thread that owns the `LocalRing`.
+
+use std::{collections::HashMap, marker::PhantomData};
+
+use crate::{
+    error::RingError,
+    registry::{Completer, Completion, Registry},
+    submission::Submission,
+    traits::{
+        CompletionQueue, CompletionQueueEntry as CQE, FullRing, SubmissionQueue,
+        SubmissionQueueEntry as SQE, UserData,
+    },
+};
+
+/// A ring submitted to and reaped from inline, on the thread that owns it.
+///
+/// Submissions resolve once [`drive`](Self::drive) or [`reap`](Self::reap) delivers their completions, so poll them
+/// with [`Submission::try_take`] in between rather than blocking on [`Submission::wait`], which would never return
+/// on a single thread.
+pub struct LocalRing<S, C, SQ, CQ, Ring>
+where
+    S: SQE,
+    C: CQE,
+{
+    /// The ring being driven.
+    ring: Ring,
+    /// Where completions are delivered.
+    registry: Registry<C, S>,
+    /// User data of outstanding entries whose routing key differs from it, by key.
+    keys: HashMap<u64, u64>,
+    /// Entries pushed onto the SQ but not yet submitted.
+    deferred: usize,
+    _marker: PhantomData<fn() -> (SQ, CQ)>,
+}
+
+impl<S, C, SQ, CQ, Ring> LocalRing<S, C, SQ, CQ, Ring>
+where
+    S: SQE,
+    C: CQE,
+    SQ: SubmissionQueue<S>,
+    CQ: CompletionQueue<C>,
+    Ring: FullRing<S, C, SQ, CQ>,
+{
+    /// Creates a local ring driving `ring`.
+    pub fn new(ring: Ring) -> Self {
+        Self {
+            ring,
+            registry: Registry::new(),
+            keys: HashMap::new(),
+            deferred: 0,
+            _marker: PhantomData,
+        }
+    }
+
+    /// The ring being driven.
+    pub fn ring(&self) -> &Ring {
+        &self.ring
+    }
+
+    /// The ring being driven, mutably.
+    pub fn ring_mut(&mut self) -> &mut Ring {
+        &mut self.ring
+    }
+
+    /// The registry of outstanding entries.
+    pub fn registry(&self) -> &Registry<C, S> {
+        &self.registry
+    }
+
+    /// The number of entries pushed onto the SQ but not yet submitted.
+    pub fn deferred(&self) -> usize {
+        self.deferred
+    }
+
+    /// Hands the ring back. Outstanding submissions resolve with `RingError::Disconnected`.
+    pub fn into_ring(self) -> Ring {
+        self.ring
+    }
+
+    /// Pushes an entry onto the SQ, returning a handle to its completion.
+    ///
+    /// The entry is only handed to the backend by the next [`drive`](Self::drive), unless the SQ is full, in which
+    /// case the ring is submitted and reaped until there is room. An entry the SQ refuses to
+    /// [`validate`](SubmissionQueue::validate) resolves with `RingError::Invalid` straight away.
+    pub fn submit(&mut self, entry: S) -> Submission<C> {
+        let (tx, rx) = oneshot::channel();
+        let ud = self.push(entry, tx.into());
+
+        Submission::new(ud, rx)
+    }
+
+    /// Pushes an entry onto the SQ, invoking `callback` with its completion once a reap delivers it.
+    pub fn submit_with_callback<F>(&mut self, entry: S, callback: F) -> UserData
+    where
+        C: 'static,
+        F: FnOnce(Completion<C>) + Send + 'static,
+    {
+        self.push(entry, Completer::Callback(Box::new(callback)))
+    }
+
+    /// Pushes a batch of entries onto the SQ, returning a handle for each.
+    pub fn batch_submit<I>(&mut self, entries: I) -> Vec<Submission<C>>
+    where
+        I: IntoIterator<Item = S>,
+    {
+        entries
+            .into_iter()
+            .map(|entry| self.submit(entry))
+            .collect()
+    }
+
+    /// Submits every pushed entry to the backend, then delivers whatever completions are ready.
+    ///
+    /// Returns the number of completions delivered.
+    pub fn drive(&mut self) -> usize {
+        self.submit_pushed();
+        self.reap()
+    }
+
+    /// Delivers every completion currently in the CQ without submitting. Returns the number delivered.
+    pub fn reap(&mut self) -> usize {
+        let mut reaped = 0;
+
+        for cqe in self.ring.completion() {
+            let key = self.ring.completion_key(&cqe);
+            let ud = UserData::from(self.keys.remove(&key).unwrap_or(key));
+
+            if let Some(target) = self.ring.is_cancel_completion(&cqe)
+                && self.registry.cancel(target)
+            {
+                reaped += 1;
+            }
+
+            if !self.registry.contains(ud) {
+                // Nobody is waiting for it, but it may have been holding a resource until now.
+                self.registry.release(ud);
+                continue;
+            }
+
+            let completion = self.ring.interpret(cqe).map_err(RingError::Backend);
+            if self.registry.resolve(ud, completion) {
+                reaped += 1;
+            }
+
+            // Entries expecting several completions keep routing by their key until the last one.
+            if key != ud.get() && self.registry.contains(ud) {
+                self.keys.insert(key, ud.get());
+            }
+        }
+
+        reaped
+    }
+
+    /// Resolves the outstanding entry with user data `ud` as cancelled.
+    ///
+    /// Returns `false` if there was no such entry, e.g. because it already completed. Its eventual completion is
+    /// ignored.
+    pub fn cancel(&mut self, ud: UserData) -> bool {
+        self.registry.cancel(ud)
+    }
+
+    /// Tags `entry` with fresh user data, registers `completer` under it and pushes it onto the SQ.
+    fn push(&mut self, mut entry: S, completer: Completer<C>) -> UserData {
+        let ud = self.registry.next_uuid();
+        entry.set_ud(ud);
+        self.registry.insert(ud, completer);
+
+        if let Err(err) = self.ring.submission().validate(&entry) {
+            self.registry.resolve(ud, Err(RingError::Invalid(err)));
+            return ud;
+        }
+
+        let key = self.ring.submission_key(&entry);
+        if key != ud.get() {
+            self.keys.insert(key, ud.get());
+        }
+
+        // Loops until there is room, submitting and reaping to make some.
+        let mut entry_holder = Some(entry);
+        while let Err(failure_entry) = self.ring.submission().push(entry_holder.take().unwrap()) {
+            entry_holder = Some(failure_entry);
+
+            self.submit_pushed();
+            self.reap();
+        }
+        self.deferred += 1;
+
+        // Deferring only delays the completions of a synchronous backend.
+        if self.ring.is_synchronous() {
+            self.submit_pushed();
+        }
+
+        ud
+    }
+
+    /// Calls `Submitter::submit` for the pushed entries.
+    fn submit_pushed(&mut self) {
+        self.deferred = 0;
+        self.ring.submit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use super::*;
+    use crate::{
+        adapters::{Completions, QueueSubmissions},
+        error::ValidationError,
+        test_util::{MockEntry, MockRing},
+        traits::Submitter,
+    };
+
+    /// An SQ that holds at most two entries and refuses entries tagged zero.
+    struct StrictSubmissions(QueueSubmissions<MockEntry>);
+
+    impl SubmissionQueue<MockEntry> for StrictSubmissions {
+        fn push(&mut self, entry: MockEntry) -> Result<(), MockEntry> {
+            self.0.push(entry)
+        }
+
+        fn validate(&self, entry: &MockEntry) -> Result<(), ValidationError> {
+            match entry.tag() {
+                0 => Err(ValidationError::Unsupported),
+                _ => Ok(()),
+            }
+        }
+    }
+
+    /// A mock backend behind a [`StrictSubmissions`] SQ.
+    struct StrictRing {
+        mock: MockRing<MockEntry, MockEntry>,
+        sq: QueueSubmissions<MockEntry>,
+    }
+
+    impl StrictRing {
+        fn new(mock: &MockRing<MockEntry, MockEntry>) -> Self {
+            Self {
+                mock: mock.clone(),
+                sq: QueueSubmissions::bounded(2),
+            }
+        }
+    }
+
+    impl Submitter for StrictRing {
+        fn submit(&mut self) {
+            let mut sq = self.mock.submission();
+            for entry in self.sq.drain() {
+                let _ = sq.push(entry);
+            }
+            self.mock.submit();
+        }
+    }
+
+    impl FullRing<MockEntry, MockEntry, StrictSubmissions, Completions<MockEntry>> for StrictRing {
+        fn completion(&mut self) -> Completions<MockEntry> {
+            self.mock.completion()
+        }
+
+        fn submission(&mut self) -> StrictSubmissions {
+            StrictSubmissions(self.sq.clone())
+        }
+    }
+
+    #[test]
+    fn entries_resolve_once_driven_and_reaped() {
+        let mock = MockRing::echo();
+        let mut ring = LocalRing::new(StrictRing::new(&mock));
+
+        let submission = ring.submit(MockEntry::new(1));
+        assert_eq!(ring.deferred(), 1);
+        assert!(mock.in_flight().is_empty());
+
+        assert_eq!(ring.drive(), 0);
+        assert_eq!(mock.in_flight(), [submission.user_data()]);
+        assert!(submission.try_take().is_none());
+
+        assert_eq!(mock.complete_all(), 1);
+        assert_eq!(ring.drive(), 1);
+        assert_eq!(submission.try_take().unwrap().unwrap().tag(), 1);
+        assert!(ring.registry().is_empty());
+    }
+
+    #[test]
+    fn callbacks_run_on_the_driving_thread() {
+        let mock = MockRing::echo();
+        let mut ring = LocalRing::new(StrictRing::new(&mock));
+
+        let (tx, rx) = mpsc::channel();
+        let ud = ring.submit_with_callback(MockEntry::new(1), move |completion| {
+            tx.send(completion).unwrap();
+        });
+        ring.drive();
+        assert!(rx.try_recv().is_err());
+
+        assert!(mock.complete_ud(ud));
+        assert_eq!(ring.reap(), 1);
+        assert_eq!(rx.try_recv().unwrap().unwrap().tag(), 1);
+    }
+
+    #[test]
+    fn cancelled_entries_ignore_their_completion() {
+        let mock = MockRing::echo();
+        let mut ring = LocalRing::new(StrictRing::new(&mock));
+
+        let submission = ring.submit(MockEntry::new(1));
+        ring.drive();
+        assert!(ring.cancel(submission.user_data()));
+        assert!(!ring.cancel(submission.user_data()));
+        assert_eq!(submission.try_take(), Some(Err(RingError::Cancelled)));
+
+        assert_eq!(mock.complete_all(), 1);
+        assert_eq!(ring.drive(), 0);
+    }
+
+    #[test]
+    fn a_full_sq_is_submitted_to_make_room() {
+        let mock = MockRing::echo();
+        let mut ring = LocalRing::new(StrictRing::new(&mock));
+
+        let submissions = ring.batch_submit((1..=3).map(MockEntry::new));
+        // The third push found the SQ full, so the first two went to the backend ahead of the next drive.
+        assert_eq!(
+            mock.in_flight(),
+            [submissions[0].user_data(), submissions[1].user_data()]
+        );
+        assert_eq!(ring.deferred(), 1);
+
+        ring.drive();
+        assert_eq!(mock.in_flight().len(), 3);
+        assert_eq!(ring.deferred(), 0);
+    }
+
+    #[test]
+    fn invalid_entries_are_rejected_without_being_pushed() {
+        let mock = MockRing::echo();
+        let mut ring = LocalRing::new(StrictRing::new(&mock));
+
+        let submission = ring.submit(MockEntry::new(0));
+        assert_eq!(
+            submission.try_take(),
+            Some(Err(RingError::Invalid(ValidationError::Unsupported)))
+        );
+        assert_eq!(ring.deferred(), 0);
+
+        ring.drive();
+        assert!(mock.in_flight().is_empty());
+        assert!(ring.registry().is_empty());
+    }
+}
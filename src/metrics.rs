@@ -0,0 +1,386 @@
+//! Hooks for reporting the ring thread's activity to a metrics facade.
+//!
+//! The ring thread reports to a [`MetricsRecorder`] set with
+//! [`PRingBuilder::metrics`](crate::builder::PRingBuilder::metrics) as it goes, so that the ring shows up in whatever
+//! exporter the application already uses instead of being polled for a snapshot. With the `metrics-facade` feature,
+//! [`PRingBuilder::metrics_facade`](crate::builder::PRingBuilder::metrics_facade) reports through the `metrics` crate
+//! instead, to whichever recorder is installed globally when a metric is emitted:
+//!
+//! ```no_run
+//! # #[cfg(all(feature = "metrics-facade", feature = "test-util"))]
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # use ring_promise::{PRingSender, test_util::{MockEntry, MockRing}};
+//! # let ring = MockRing::<MockEntry, MockEntry>::echo();
+//! # let recorder = metrics::NoopRecorder;
+//! // Or e.g. `metrics_exporter_prometheus::PrometheusBuilder::new().install()?`.
+//! metrics::set_global_recorder(recorder)?;
+//! let sender = PRingSender::builder().metrics_facade("ring_promise").build(ring);
+//! # Ok(())
+//! # }
+//! # #[cfg(not(all(feature = "metrics-facade", feature = "test-util")))]
+//! # fn main() {}
+//! ```
+//!
+//! Every metric is named after the prefix given to the builder, followed by a dot and one of:
+//! - `submitted`: Counter of entries pushed onto the SQ.
+//! - `completed`: Counter of completions delivered to the entries waiting for them.
+//! - `in_flight`: Gauge of entries registered but not yet delivered.
+//! - `sq_full_retries`: Counter of pushes retried because the SQ was full.
+//! - `reaps.<reason>`: Counter of reaps made by the ring thread for each [`ReapReason`], e.g. `reaps.timer`,
+//!   named as by [`ReapReason::as_str`].
+//! - `errored_completions`: Counter of panics in the backend's completion handling that were contained.
+//! - `lost_completions`: Counter of completions the backend reported dropped by an overflowing CQ.
+//! - `latency_seconds`: Histogram of the time from submission to delivery.
+//! - `queue_latency_seconds`: Histogram of the time from submission until the ring thread hands the entry to the
+//!   backend, which grows when the ring thread cannot keep up.
+//! - `device_latency_seconds`: Histogram of the time from the backend being handed the entry to its delivery, which
+//!   grows when the device is slow.
+
+use std::{sync::Arc, time::Instant};
+
+use crate::reap::ReapReason;
+
+/// Receives the ring thread's metrics.
+///
+/// Called on the ring thread, and on the reaper thread of a split ring, so implementations should be quick.
+pub trait MetricsRecorder: Send + Sync {
+    /// Adds `value` to the counter called `name`.
+    fn increment_counter(&self, name: &str, value: u64);
+
+    /// Sets the gauge called `name` to `value`.
+    fn set_gauge(&self, name: &str, value: f64);
+
+    /// Records `value` in the histogram called `name`.
+    fn record_histogram(&self, name: &str, value: f64);
+}
+
+/// Where the ring thread's metrics go.
+#[derive(Clone)]
+enum Target {
+    /// A recorder set on the builder.
+    Recorder(Arc<dyn MetricsRecorder>),
+    /// The `metrics` crate's global recorder.
+    #[cfg(feature = "metrics-facade")]
+    Facade,
+}
+
+/// Where the metrics go, along with their full names.
+#[derive(Clone)]
+pub(crate) struct Metrics {
+    target: Target,
+    submitted: Arc<str>,
+    completed: Arc<str>,
+    in_flight: Arc<str>,
+    sq_full_retries: Arc<str>,
+    reaps: [Arc<str>; ReapReason::ALL.len()],
+    errored_completions: Arc<str>,
+    lost_completions: Arc<str>,
+    latency: Arc<str>,
+    queue_latency: Arc<str>,
+    device_latency: Arc<str>,
+}
+
+impl Metrics {
+    /// Reports to `recorder` under metric names starting with `prefix`.
+    pub(crate) fn new(recorder: Arc<dyn MetricsRecorder>, prefix: &str) -> Self {
+        Self::with_target(Target::Recorder(recorder), prefix)
+    }
+
+    /// Reports through the `metrics` crate facade under metric names starting with `prefix`.
+    #[cfg(feature = "metrics-facade")]
+    pub(crate) fn facade(prefix: &str) -> Self {
+        Self::with_target(Target::Facade, prefix)
+    }
+
+    /// Reports to `target` under metric names starting with `prefix`.
+    fn with_target(target: Target, prefix: &str) -> Self {
+        let name = |metric: &str| Arc::from(format!("{prefix}.{metric}"));
+
+        Self {
+            target,
+            submitted: name("submitted"),
+            completed: name("completed"),
+            in_flight: name("in_flight"),
+            sq_full_retries: name("sq_full_retries"),
+            reaps: ReapReason::ALL.map(|reason| name(&format!("reaps.{}", reason.as_str()))),
+            errored_completions: name("errored_completions"),
+            lost_completions: name("lost_completions"),
+            latency: name("latency_seconds"),
+            queue_latency: name("queue_latency_seconds"),
+            device_latency: name("device_latency_seconds"),
+        }
+    }
+
+    /// Adds `value` to the counter called `name`.
+    #[inline]
+    fn counter(&self, name: &Arc<str>, value: u64) {
+        match &self.target {
+            Target::Recorder(recorder) => recorder.increment_counter(name, value),
+            #[cfg(feature = "metrics-facade")]
+            Target::Facade => metrics::counter!(Arc::clone(name)).increment(value),
+        }
+    }
+
+    /// Sets the gauge called `name` to `value`.
+    #[inline]
+    fn gauge(&self, name: &Arc<str>, value: f64) {
+        match &self.target {
+            Target::Recorder(recorder) => recorder.set_gauge(name, value),
+            #[cfg(feature = "metrics-facade")]
+            Target::Facade => metrics::gauge!(Arc::clone(name)).set(value),
+        }
+    }
+
+    /// Records `value` in the histogram called `name`.
+    #[inline]
+    fn histogram(&self, name: &Arc<str>, value: f64) {
+        match &self.target {
+            Target::Recorder(recorder) => recorder.record_histogram(name, value),
+            #[cfg(feature = "metrics-facade")]
+            Target::Facade => metrics::histogram!(Arc::clone(name)).record(value),
+        }
+    }
+
+    /// Reports an entry pushed onto the SQ.
+    #[inline]
+    pub(crate) fn submitted(&self) {
+        self.counter(&self.submitted, 1);
+    }
+
+    /// Reports a completion delivered now, for an entry submitted and handed to the backend at `timing`, if known.
+    pub(crate) fn completed(&self, timing: Option<(Instant, Option<Instant>)>) {
+        self.counter(&self.completed, 1);
+
+        let Some((since, handed_over)) = timing else {
+            return;
+        };
+        let now = Instant::now();
+        self.histogram(&self.latency, (now - since).as_secs_f64());
+        if let Some(handed_over) = handed_over {
+            self.histogram(
+                &self.queue_latency,
+                handed_over.saturating_duration_since(since).as_secs_f64(),
+            );
+            self.histogram(&self.device_latency, (now - handed_over).as_secs_f64());
+        }
+    }
+
+    /// Reports the number of entries registered but not yet delivered.
+    #[inline]
+    pub(crate) fn in_flight(&self, in_flight: usize) {
+        self.gauge(&self.in_flight, in_flight as f64);
+    }
+
+    /// Reports a push retried because the SQ was full.
+    #[inline]
+    pub(crate) fn sq_full_retry(&self) {
+        self.counter(&self.sq_full_retries, 1);
+    }
+
+    /// Reports a reap made by the ring thread for `reason`.
+    #[inline]
+    pub(crate) fn reaped(&self, reason: ReapReason) {
+        self.counter(&self.reaps[reason as usize], 1);
+    }
+
+    /// Reports `count` contained panics in the backend's completion handling.
+    #[inline]
+    pub(crate) fn completions_errored(&self, count: u64) {
+        self.counter(&self.errored_completions, count);
+    }
+
+    /// Reports `count` completions dropped by an overflowing CQ.
+    #[inline]
+    pub(crate) fn completions_lost(&self, count: u64) {
+        self.counter(&self.lost_completions, count);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Mutex, thread, time::Duration};
+
+    use super::*;
+    use crate::{
+        PRingSender,
+        driver::StepOutcome,
+        registry::lock,
+        test_util::{MockEntry, MockRing},
+    };
+
+    /// A recorder keeping every histogram value, by name.
+    #[derive(Clone, Default)]
+    struct Histograms(Arc<Mutex<Vec<(String, f64)>>>);
+
+    impl Histograms {
+        /// The values recorded in the histogram called `name`, in order.
+        fn values(&self, name: &str) -> Vec<f64> {
+            lock(&self.0)
+                .iter()
+                .filter(|(recorded, _)| recorded == name)
+                .map(|&(_, value)| value)
+                .collect()
+        }
+    }
+
+    impl MetricsRecorder for Histograms {
+        fn increment_counter(&self, _: &str, _: u64) {}
+
+        fn set_gauge(&self, _: &str, _: f64) {}
+
+        fn record_histogram(&self, name: &str, value: f64) {
+            lock(&self.0).push((name.to_owned(), value));
+        }
+    }
+
+    #[test]
+    fn latency_is_split_where_the_backend_is_handed_the_entry() {
+        const HELD: Duration = Duration::from_millis(100);
+        let ring = MockRing::echo();
+        let histograms = Histograms::default();
+        let (sender, mut driver) = PRingSender::builder()
+            .metrics(histograms.clone(), "ring")
+            .submit_threshold(2)
+            .max_defer(Duration::from_secs(60))
+            .build_driver(ring.clone());
+
+        // The first entry waits on the ring thread until the second makes up the threshold.
+        let first = sender.submit(MockEntry::new(1));
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        thread::sleep(HELD);
+        let _second = sender.submit(MockEntry::new(2));
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        assert_eq!(ring.in_flight().len(), 2);
+
+        assert!(ring.complete_next().is_some());
+        sender.reap();
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        first.wait().unwrap();
+
+        let queued = histograms.values("ring.queue_latency_seconds");
+        let device = histograms.values("ring.device_latency_seconds");
+        let total = histograms.values("ring.latency_seconds");
+        assert_eq!((queued.len(), device.len(), total.len()), (1, 1, 1));
+        assert!(queued[0] >= HELD.as_secs_f64());
+        assert!(device[0] < HELD.as_secs_f64());
+        assert!(total[0] >= queued[0] + device[0] - f64::EPSILON);
+    }
+
+    #[cfg(feature = "metrics-facade")]
+    mod facade {
+        use metrics::{
+            Counter, CounterFn, Gauge, GaugeFn, Histogram, HistogramFn, Key, KeyName, Metadata,
+            Recorder, SharedString, Unit,
+        };
+
+        use super::*;
+
+        /// The names of the metrics emitted so far, in order.
+        type Emitted = Arc<Mutex<Vec<String>>>;
+
+        /// A metric handle logging its name whenever it is updated.
+        struct Logged(String, Emitted);
+
+        impl Logged {
+            fn log(&self) {
+                lock(&self.1).push(self.0.clone());
+            }
+        }
+
+        impl CounterFn for Logged {
+            fn increment(&self, _: u64) {
+                self.log();
+            }
+
+            fn absolute(&self, _: u64) {
+                self.log();
+            }
+        }
+
+        impl GaugeFn for Logged {
+            fn increment(&self, _: f64) {
+                self.log();
+            }
+
+            fn decrement(&self, _: f64) {
+                self.log();
+            }
+
+            fn set(&self, _: f64) {
+                self.log();
+            }
+        }
+
+        impl HistogramFn for Logged {
+            fn record(&self, _: f64) {
+                self.log();
+            }
+        }
+
+        /// A `metrics` recorder logging the name of every metric emitted.
+        #[derive(Default)]
+        struct LoggingRecorder(Emitted);
+
+        impl LoggingRecorder {
+            fn handle(&self, key: &Key) -> Arc<Logged> {
+                Arc::new(Logged(key.name().to_owned(), Arc::clone(&self.0)))
+            }
+        }
+
+        impl Recorder for LoggingRecorder {
+            fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+            fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+            fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+            fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+                Counter::from_arc(self.handle(key))
+            }
+
+            fn register_gauge(&self, key: &Key, _: &Metadata<'_>) -> Gauge {
+                Gauge::from_arc(self.handle(key))
+            }
+
+            fn register_histogram(&self, key: &Key, _: &Metadata<'_>) -> Histogram {
+                Histogram::from_arc(self.handle(key))
+            }
+        }
+
+        #[test]
+        fn facade_emits_prefixed_metrics_at_every_instrumentation_point() {
+            let recorder = LoggingRecorder::default();
+
+            // The driver runs on this thread, so a recorder local to it sees everything.
+            metrics::with_local_recorder(&recorder, || {
+                let ring = MockRing::echo();
+                let (sender, mut driver) = PRingSender::builder()
+                    .metrics_facade("ring")
+                    .build_driver(ring.clone());
+
+                let submission = sender.submit(MockEntry::new(1));
+                assert_eq!(driver.step(), StepOutcome::Handled);
+                assert!(ring.complete_next().is_some());
+                sender.reap();
+                assert_eq!(driver.step(), StepOutcome::Handled);
+                submission.wait().unwrap();
+            });
+
+            let emitted = lock(&recorder.0);
+            for name in [
+                "ring.in_flight",
+                "ring.submitted",
+                "ring.reaps.explicit",
+                "ring.completed",
+                "ring.latency_seconds",
+                "ring.queue_latency_seconds",
+                "ring.device_latency_seconds",
+            ] {
+                assert!(
+                    emitted.iter().any(|emitted| emitted == name),
+                    "{name} not emitted in {emitted:?}"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,291 @@
+//! Driving several rings from one thread.
+//!
+//! A ring thread per ring adds up for designs with many small, mostly idle rings, e.g. a ring per connection. A
+//! [`RingGroup`] takes the drivers of any number of rings, each created with
+//! [`PRingBuilder::build_driver`](crate::builder::PRingBuilder::build_driver) and keeping its own sender and registry,
+//! and services them all from a single thread. It visits the rings round-robin, handling the signals each has ready
+//! without blocking and reaping its CQ when it has none, and only sleeps once a whole round found nothing to do.
+//!
+//! ```no_run
+//! # #[cfg(feature = "test-util")]
+//! # fn main() {
+//! # use ring_promise::{PRingSender, multiplex::RingGroup, test_util::{MockEntry, MockRing}};
+//! # let rings = vec![MockRing::<MockEntry, MockEntry>::echo(), MockRing::echo()];
+//! let mut group = RingGroup::new();
+//! let senders: Vec<_> = rings
+//!     .into_iter()
+//!     .map(|ring| {
+//!         let (sender, driver) = PRingSender::builder().build_driver(ring);
+//!         group.add(driver);
+//!         sender
+//!     })
+//!     .collect();
+//! group.spawn();
+//! # }
+//! # #[cfg(not(feature = "test-util"))]
+//! # fn main() {}
+//! ```
+
+use std::{
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::{
+    driver::RingDriver,
+    traits::{
+        CompletionQueue, CompletionQueueEntry as CQE, FullRing, SubmissionQueue,
+        SubmissionQueueEntry as SQE,
+    },
+};
+
+/// Signals a ring handles per turn at most, so a busy ring cannot starve the rest of the group.
+const BUDGET: usize = 64;
+
+/// What a driver did with its turn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Turn {
+    /// It handled signals, or had something due.
+    Busy,
+    /// It had no signal ready and nothing due, and reaped its CQ.
+    Idle,
+    /// Every sender is gone, or the ring panicked, so it is done.
+    Exited,
+}
+
+/// A driver of any ring type, as far as the group is concerned.
+trait Member: Send {
+    /// Gives the driver a turn. See [`RingDriver::turn`].
+    fn turn(&mut self) -> Turn;
+}
+
+impl<S, C, SQ, CQ, Ring> Member for RingDriver<S, C, SQ, CQ, Ring>
+where
+    S: SQE,
+    C: CQE,
+    SQ: SubmissionQueue<S>,
+    CQ: CompletionQueue<C>,
+    Ring: FullRing<S, C, SQ, CQ>,
+    Self: Send,
+{
+    fn turn(&mut self) -> Turn {
+        RingDriver::turn(self, BUDGET)
+    }
+}
+
+/// Several rings serviced in turn by one thread.
+///
+/// Each ring behaves as if it had a ring thread of its own, except that
+/// [`split_threads`](crate::builder::PRingBuilder::split_threads) is ignored, since the group's thread reaps every
+/// ring itself. A ring leaves the group once all its senders are dropped, or if it panics, in which case its
+/// outstanding entries are left for [`PRingSender::take_recovered`](crate::PRingSender::take_recovered) as usual and
+/// the other rings carry on. The group's thread exits once every ring has left.
+pub struct RingGroup {
+    /// The drivers of the rings still in the group.
+    members: Vec<Box<dyn Member>>,
+    /// How long to sleep after a round in which no ring had anything to do.
+    poll_interval: Duration,
+}
+
+impl Default for RingGroup {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RingGroup {
+    /// Creates an empty group.
+    pub fn new() -> Self {
+        Self {
+            members: Vec::new(),
+            poll_interval: Duration::from_micros(100),
+        }
+    }
+
+    /// Sets how long the group's thread sleeps after a round in which no ring had anything to do.
+    ///
+    /// Bounds how long a signal or a completion can wait to be noticed while the group is idle, at the cost of waking
+    /// up more often. Zero yields instead of sleeping, polling continuously. Defaults to 100µs.
+    pub fn poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Adds a ring to the group, by the driver [`build_driver`](crate::builder::PRingBuilder::build_driver) handed
+    /// back along with its sender.
+    pub fn add<S, C, SQ, CQ, Ring>(&mut self, driver: RingDriver<S, C, SQ, CQ, Ring>)
+    where
+        S: SQE + 'static,
+        C: CQE + 'static,
+        SQ: SubmissionQueue<S> + 'static,
+        CQ: CompletionQueue<C> + 'static,
+        Ring: FullRing<S, C, SQ, CQ> + Send + 'static,
+        RingDriver<S, C, SQ, CQ, Ring>: Send,
+    {
+        self.members.push(Box::new(driver));
+    }
+
+    /// The number of rings in the group.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    /// Whether every ring has left the group.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    /// Gives every ring one turn, dropping those that are done. Never blocks.
+    ///
+    /// Returns whether any ring had something to do. [`run`](Self::run) calls this in a loop.
+    pub fn poll(&mut self) -> bool {
+        let mut busy = false;
+        self.members.retain_mut(|member| match member.turn() {
+            Turn::Busy => {
+                busy = true;
+                true
+            }
+            Turn::Idle => true,
+            Turn::Exited => false,
+        });
+
+        busy
+    }
+
+    /// Services the rings on the calling thread until every ring has left the group.
+    pub fn run(mut self) {
+        while !self.is_empty() {
+            if self.poll() {
+                continue;
+            }
+
+            if self.poll_interval.is_zero() {
+                thread::yield_now();
+            } else {
+                thread::sleep(self.poll_interval);
+            }
+        }
+    }
+
+    /// Spawns the group's thread, servicing the rings until every ring has left the group.
+    pub fn spawn(self) -> JoinHandle<()> {
+        thread::spawn(move || self.run())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        PRingSender,
+        adapters::{Completions, QueueSubmissions},
+        error::RingError,
+        test_util::{MockEntry, MockRing},
+        traits::Submitter,
+    };
+
+    /// A backend that panics as soon as anything is submitted to it.
+    struct PanickingRing(MockRing<MockEntry, MockEntry>);
+
+    impl Submitter for PanickingRing {
+        fn submit(&mut self) {
+            panic!("the backend went away");
+        }
+    }
+
+    impl FullRing<MockEntry, MockEntry, QueueSubmissions<MockEntry>, Completions<MockEntry>>
+        for PanickingRing
+    {
+        fn completion(&mut self) -> Completions<MockEntry> {
+            self.0.completion()
+        }
+
+        fn submission(&mut self) -> QueueSubmissions<MockEntry> {
+            self.0.submission()
+        }
+    }
+
+    #[test]
+    fn polling_services_every_ring() {
+        let mut group = RingGroup::new();
+        let rings = [MockRing::echo(), MockRing::echo()];
+        let senders: Vec<_> = rings
+            .iter()
+            .map(|ring| {
+                let (sender, driver) = PRingSender::builder().build_driver(ring.clone());
+                group.add(driver);
+                sender
+            })
+            .collect();
+        assert!(!group.poll());
+
+        let submissions: Vec<_> = senders
+            .iter()
+            .zip(1..)
+            .map(|(sender, tag)| sender.submit(MockEntry::new(tag)))
+            .collect();
+        assert!(group.poll());
+        for (ring, submission) in rings.iter().zip(&submissions) {
+            assert_eq!(ring.in_flight(), [submission.user_data()]);
+            assert_eq!(ring.complete_all(), 1);
+        }
+
+        // An idle turn reaps, so the completions are delivered without asking.
+        group.poll();
+        for (submission, tag) in submissions.into_iter().zip(1..) {
+            assert_eq!(submission.try_take().unwrap().unwrap().tag(), tag);
+        }
+    }
+
+    #[test]
+    fn rings_leave_once_their_senders_are_gone() {
+        let mut group = RingGroup::new();
+        let (gone, driver) =
+            PRingSender::builder().build_driver(MockRing::<MockEntry, MockEntry>::echo());
+        group.add(driver);
+        let ring = MockRing::echo();
+        let (sender, driver) = PRingSender::builder().build_driver(ring.clone());
+        group.add(driver);
+
+        drop(gone);
+        group.poll();
+        assert_eq!(group.len(), 1);
+
+        let submission = sender.submit(MockEntry::new(1));
+        assert!(group.poll());
+        assert_eq!(ring.in_flight(), [submission.user_data()]);
+
+        drop(sender);
+        group.poll();
+        assert!(group.is_empty());
+    }
+
+    #[test]
+    fn a_panicking_ring_leaves_without_taking_the_others_down() {
+        let mut group = RingGroup::new();
+        let (panicking, driver) = PRingSender::builder()
+            .on_panic(|_| {})
+            .build_driver(PanickingRing(MockRing::echo()));
+        group.add(driver);
+        let ring = MockRing::echo();
+        let (sender, driver) = PRingSender::builder().build_driver(ring.clone());
+        group.add(driver);
+
+        let lost = panicking.submit(MockEntry::new(1));
+        group.poll();
+        assert_eq!(group.len(), 1);
+        assert_eq!(lost.try_take(), Some(Err(RingError::Disconnected)));
+        assert_eq!(
+            panicking.take_recovered().unwrap().user_data,
+            [lost.user_data()]
+        );
+
+        let submission = sender.submit(MockEntry::new(2));
+        assert!(group.poll());
+        assert_eq!(ring.complete_all(), 1);
+        group.poll();
+        assert_eq!(submission.try_take().unwrap().unwrap().tag(), 2);
+    }
+}
@@ -0,0 +1,108 @@
+//! A small, fixed-size worker pool for running completion callbacks off the ring thread.
+//!
+//! The pool is owned by the ring thread's `Registry`. When the ring thread exits, the pool is dropped,
+//! which closes its job channels and joins every worker once the queued callbacks have run.
+//!
+//! Workers are split into one group per NUMA node, each pulling from its own queue, so that a callback can be routed
+//! to a worker on the node it was submitted from. A pool without NUMA awareness is a single group.
+
+use std::{
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{Receiver, Sender, channel},
+    },
+    thread::{self, JoinHandle},
+};
+
+/// A unit of work handed to the pool.
+pub(crate) type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// A bounded set of worker threads pulling jobs from per-node queues.
+pub(crate) struct CallbackPool {
+    /// The job queue of each node. Empty once the pool has begun draining.
+    senders: Vec<Sender<Job>>,
+    /// The node the next job without a node goes to, modulo the number of nodes.
+    next: AtomicUsize,
+    /// Handles for every worker, joined on drop.
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl CallbackPool {
+    /// Spawn a pool with `size` workers for each of `nodes` nodes, running `pin` with its node on each worker first.
+    ///
+    /// A `size` or `nodes` of zero is treated as one.
+    pub(crate) fn new(nodes: usize, size: usize, pin: Option<fn(usize)>) -> Self {
+        let mut senders = Vec::with_capacity(nodes.max(1));
+        let mut workers = Vec::with_capacity(nodes.max(1) * size.max(1));
+
+        for node in 0..nodes.max(1) {
+            let (sender, receiver) = channel::<Job>();
+            let receiver = Arc::new(Mutex::new(receiver));
+
+            workers.extend((0..size.max(1)).map(|_| {
+                let receiver = receiver.clone();
+                thread::spawn(move || {
+                    if let Some(pin) = pin {
+                        pin(node);
+                    }
+                    Self::worker(receiver)
+                })
+            }));
+            senders.push(sender);
+        }
+
+        Self {
+            senders,
+            next: AtomicUsize::new(0),
+            workers,
+        }
+    }
+
+    /// The body of each worker thread. Exits once the queue is closed and empty.
+    fn worker(receiver: Arc<Mutex<Receiver<Job>>>) {
+        loop {
+            // The lock is only held while waiting for the next job, not while running it.
+            let job = match receiver.lock() {
+                Ok(receiver) => receiver.recv(),
+                Err(poisoned) => poisoned.into_inner().recv(),
+            };
+
+            match job {
+                Ok(job) => job(),
+                Err(_) => break,
+            }
+        }
+    }
+
+    /// Queue a job to be run by the next free worker, spreading jobs over the nodes in turn.
+    pub(crate) fn execute(&self, job: Job) {
+        let node = match self.senders.len() {
+            1 => 0,
+            _ => self.next.fetch_add(1, Ordering::Relaxed),
+        };
+        self.execute_on(node, job);
+    }
+
+    /// Queue a job to be run by the next free worker of `node`, modulo the number of nodes.
+    pub(crate) fn execute_on(&self, node: usize, job: Job) {
+        if self.senders.is_empty() {
+            return;
+        }
+
+        // Workers only hang up once their sender is dropped, so this cannot fail.
+        let _ = self.senders[node % self.senders.len()].send(job);
+    }
+}
+
+impl Drop for CallbackPool {
+    /// Drains the pool: closes the queues, then waits for every queued job to finish.
+    fn drop(&mut self) {
+        self.senders.clear();
+
+        for worker in self.workers.drain(..) {
+            // A panicking callback only takes down its own worker; don't cascade it here.
+            let _ = worker.join();
+        }
+    }
+}
@@ -0,0 +1,325 @@
+//! Policies for when the ring thread reaps the completion queue.
+//!
+//! After submitting entries, the ring thread asks its [`ReapStrategy`] whether to reap straight away or leave the
+//! completions for a later reap. Reaps the ring thread needs for correctness, such as when the SQ is full, on an
+//! explicit [`PRingSender::reap`](crate::PRingSender::reap) or before a cancellation, always happen regardless of the
+//! strategy. Completions skipped at one decision point are delivered by the next reap.
+//!
+//! Every reap the ring thread makes is counted by its [`ReapReason`], readable with
+//! [`PRingSender::reaps`](crate::PRingSender::reaps) and reported as metrics. A strategy tags the reaps it asks for
+//! with [`ReapStrategy::reason`], so a mostly timer-driven breakdown shows at a glance that e.g. a watermark is set
+//! too high to ever be crossed.
+//!
+//! A [`reap_cooldown`](crate::builder::PRingBuilder::reap_cooldown) puts a floor under the time between reaps, so a
+//! flood of completions cannot keep the ring thread reaping back to back. It holds back the reaps a strategy asks for,
+//! along with idle and timer reaps, but never those for a [`Watermark`] being crossed, CQ pressure, a full SQ, an
+//! explicit request or shutdown.
+
+use std::time::{Duration, Instant};
+
+/// What the ring thread knows when deciding whether to reap.
+#[derive(Debug, Clone, Copy)]
+pub struct RingThreadCtx {
+    /// Entries registered but not yet delivered.
+    pub(crate) in_flight: usize,
+    /// Entries pushed since the last reap.
+    pub(crate) submitted_since_reap: usize,
+    /// Completions delivered by the last reap.
+    pub(crate) last_reaped: usize,
+    /// When the last reap happened.
+    pub(crate) last_reap: Instant,
+}
+
+impl RingThreadCtx {
+    /// The number of entries registered but not yet delivered.
+    #[inline]
+    pub fn in_flight(&self) -> usize {
+        self.in_flight
+    }
+
+    /// The number of entries pushed since the last reap.
+    #[inline]
+    pub fn submitted_since_reap(&self) -> usize {
+        self.submitted_since_reap
+    }
+
+    /// The number of completions delivered by the last reap.
+    #[inline]
+    pub fn last_reaped(&self) -> usize {
+        self.last_reaped
+    }
+
+    /// The time elapsed since the last reap.
+    #[inline]
+    pub fn since_last_reap(&self) -> Duration {
+        self.last_reap.elapsed()
+    }
+}
+
+/// Why the ring thread reaped the completion queue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReapReason {
+    /// The reap strategy asked for it right after submitting, e.g. [`Always`] or [`EveryN`].
+    Submitted,
+    /// A [`Watermark`] strategy saw the in-flight count cross its high watermark, and is still draining.
+    Watermark,
+    /// A deadline came up: an [`Interval`] strategy's, a timeout's, or the periodic housekeeping reap's.
+    Timer,
+    /// A sender asked for it, directly or as part of a flush, cancellation, barrier or fence.
+    Explicit,
+    /// The CQ was close to overflowing. See [`PRingSender::cq_pressure_reaps`](crate::PRingSender::cq_pressure_reaps).
+    CqPressure,
+    /// The SQ was full, possibly because the CQ was.
+    SqFull,
+    /// The ring thread had no signal to handle.
+    Idle,
+    /// Every sender was dropped, and the ring thread is finishing up.
+    Shutdown,
+}
+
+impl ReapReason {
+    /// Every reason, in declaration order.
+    pub const ALL: [ReapReason; 8] = [
+        ReapReason::Submitted,
+        ReapReason::Watermark,
+        ReapReason::Timer,
+        ReapReason::Explicit,
+        ReapReason::CqPressure,
+        ReapReason::SqFull,
+        ReapReason::Idle,
+        ReapReason::Shutdown,
+    ];
+
+    /// The reason's name in snake case, as used in metric names.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ReapReason::Submitted => "submitted",
+            ReapReason::Watermark => "watermark",
+            ReapReason::Timer => "timer",
+            ReapReason::Explicit => "explicit",
+            ReapReason::CqPressure => "cq_pressure",
+            ReapReason::SqFull => "sq_full",
+            ReapReason::Idle => "idle",
+            ReapReason::Shutdown => "shutdown",
+        }
+    }
+
+    /// Whether a reap for this reason happens even while a
+    /// [`reap_cooldown`](crate::builder::PRingBuilder::reap_cooldown) is in effect. Only the reaps the ring thread
+    /// makes at its own discretion wait out the cooldown.
+    pub(crate) fn overrides_cooldown(self) -> bool {
+        !matches!(
+            self,
+            ReapReason::Submitted | ReapReason::Timer | ReapReason::Idle
+        )
+    }
+}
+
+/// Decides whether the ring thread reaps the completion queue right after submitting.
+pub trait ReapStrategy {
+    /// Whether to reap now.
+    fn should_reap(&mut self, ctx: &RingThreadCtx) -> bool;
+
+    /// What the reap just asked for by [`should_reap`](Self::should_reap) is counted as. Defaults to
+    /// [`ReapReason::Submitted`].
+    fn reason(&self) -> ReapReason {
+        ReapReason::Submitted
+    }
+
+    /// Describes the strategy for [`PRingSender::dump`](crate::PRingSender::dump). Defaults to the strategy's type
+    /// name; the strategies provided here describe their parameters as well.
+    fn describe(&self) -> String {
+        std::any::type_name::<Self>().to_owned()
+    }
+}
+
+/// Reaps after every submission. This is the default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Always;
+
+impl ReapStrategy for Always {
+    fn should_reap(&mut self, _: &RingThreadCtx) -> bool {
+        true
+    }
+
+    fn describe(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+/// Starts reaping once `high` entries are in flight, and keeps reaping until no more than `low` are.
+#[derive(Debug, Clone, Copy)]
+pub struct Watermark {
+    /// The in-flight count at which reaping starts.
+    high: usize,
+    /// The in-flight count at which reaping stops.
+    low: usize,
+    /// Whether the in-flight count has reached `high` without dropping back to `low` since.
+    draining: bool,
+}
+
+impl Watermark {
+    /// Creates a strategy reaping between the `high` and `low` in-flight counts. `low` is capped at `high`.
+    pub fn new(high: usize, low: usize) -> Self {
+        Self {
+            high,
+            low: low.min(high),
+            draining: false,
+        }
+    }
+}
+
+impl ReapStrategy for Watermark {
+    fn should_reap(&mut self, ctx: &RingThreadCtx) -> bool {
+        if ctx.in_flight >= self.high {
+            self.draining = true;
+        } else if ctx.in_flight <= self.low {
+            self.draining = false;
+        }
+
+        self.draining
+    }
+
+    fn reason(&self) -> ReapReason {
+        ReapReason::Watermark
+    }
+
+    fn describe(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+/// Reaps once at least this many entries have been pushed since the last reap.
+#[derive(Debug, Clone, Copy)]
+pub struct EveryN(pub usize);
+
+impl ReapStrategy for EveryN {
+    fn should_reap(&mut self, ctx: &RingThreadCtx) -> bool {
+        ctx.submitted_since_reap >= self.0
+    }
+
+    fn describe(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+/// Reaps once at least this much time has passed since the last reap.
+#[derive(Debug, Clone, Copy)]
+pub struct Interval(pub Duration);
+
+impl ReapStrategy for Interval {
+    fn should_reap(&mut self, ctx: &RingThreadCtx) -> bool {
+        ctx.since_last_reap() >= self.0
+    }
+
+    fn reason(&self) -> ReapReason {
+        ReapReason::Timer
+    }
+
+    fn describe(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+    use crate::{
+        PRingSender,
+        driver::StepOutcome,
+        test_util::{MockEntry, MockRing},
+    };
+
+    fn ctx(in_flight: usize) -> RingThreadCtx {
+        RingThreadCtx {
+            in_flight,
+            submitted_since_reap: 0,
+            last_reaped: 0,
+            last_reap: Instant::now(),
+        }
+    }
+
+    #[test]
+    fn watermark_reaps_from_high_down_to_low() {
+        let mut strategy = Watermark::new(4, 2);
+
+        let decisions: Vec<_> = [1, 3, 4, 3, 2, 3]
+            .into_iter()
+            .map(|in_flight| strategy.should_reap(&ctx(in_flight)))
+            .collect();
+        assert_eq!(decisions, [false, false, true, true, false, false]);
+    }
+
+    #[test]
+    fn completions_wait_for_the_strategy_to_reap() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder()
+            .reap_strategy(EveryN(2))
+            .build_driver(ring.clone());
+
+        let first = sender.submit(MockEntry::new(1));
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        assert_eq!(ring.complete_all(), 1);
+        assert!(first.try_take().is_none());
+        assert_eq!(sender.reaps(ReapReason::Submitted), 0);
+
+        // The second push is the one the strategy waits for, and delivers the first completion.
+        let _second = sender.submit(MockEntry::new(2));
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        assert_eq!(sender.reaps(ReapReason::Submitted), 1);
+        assert_eq!(first.try_take().unwrap().unwrap().tag(), 1);
+    }
+
+    #[test]
+    fn reaps_are_counted_by_why_they_happened() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder()
+            .reap_strategy(EveryN(8))
+            .build_driver(ring.clone());
+        let counts = |sender: &PRingSender<MockEntry, MockEntry>| {
+            ReapReason::ALL.map(|reason| sender.reaps(reason))
+        };
+
+        let _submission = sender.submit(MockEntry::new(1));
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        assert_eq!(counts(&sender), [0; 8]);
+
+        sender.reap();
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        assert_eq!(sender.reaps(ReapReason::Explicit), 1);
+
+        assert_eq!(driver.try_step(), StepOutcome::Idle);
+        assert_eq!(sender.reaps(ReapReason::Idle), 1);
+        // Nothing else reaped along the way.
+        assert_eq!(counts(&sender).iter().sum::<u64>(), 2);
+    }
+
+    #[test]
+    fn the_cooldown_postpones_discretionary_reaps_but_not_explicit_ones() {
+        const COOLDOWN: Duration = Duration::from_millis(50);
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder()
+            .reap_cooldown(COOLDOWN)
+            .build_driver(ring.clone());
+
+        let first = sender.submit(MockEntry::new(1));
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        assert_eq!(ring.complete_all(), 1);
+        // The strategy just reaped after the push, so the idle reap has to wait.
+        let _ = driver.try_step();
+        assert!(first.try_take().is_none());
+
+        thread::sleep(COOLDOWN);
+        let _ = driver.try_step();
+        assert_eq!(first.try_take().unwrap().unwrap().tag(), 1);
+
+        let second = sender.submit(MockEntry::new(2));
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        assert_eq!(ring.complete_all(), 1);
+        sender.reap();
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        assert_eq!(second.try_take().unwrap().unwrap().tag(), 2);
+    }
+}
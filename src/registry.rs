@@ -1,31 +1,513 @@
 //! Registry for associating user data with completion senders.
 //!
-//! The `Registry` manages the mapping between user data (u64) and oneshot senders for completion queue entries.
+//! The `Registry` manages the mapping between user data (u64) and completion destinations for completion queue entries.
 //! It is used internally by the ring thread to track outstanding submissions and deliver completions.
 
-use crate::traits::CompletionQueueEntry as CQE;
-use std::collections::HashMap;
+use crate::{
+    completion_pool::PooledSender,
+    error::{BackendError, RingError},
+    limit::Permit,
+    pool::CallbackPool,
+    retry::Retry,
+    stream::StreamSender,
+    submission::SlotSender,
+    traits::{CompletionQueueEntry as CQE, UserData},
+};
+#[cfg(feature = "debug-backtrace")]
+use std::backtrace::Backtrace;
+use std::{
+    any::Any,
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    sync::{
+        Arc, Condvar, Mutex, MutexGuard, PoisonError,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+/// The user data values submitted but not yet delivered, shared between senders and the ring thread.
+pub(crate) type PendingSet = Arc<Mutex<HashMap<u64, Origin>>>;
+
+/// User data of the tracked submissions whose completion has not been taken yet, with when it was delivered, if it
+/// has been.
+pub(crate) type DeliveredSet = Arc<Mutex<HashMap<u64, Option<Instant>>>>;
+
+/// When, and with `debug-backtrace` where from, a pending entry was submitted.
+#[derive(Debug)]
+pub(crate) struct Origin {
+    /// When the entry was submitted.
+    since: Instant,
+    /// When the ring thread last handed the entry to the backend, if it has and that is being tracked.
+    handed_over: Option<Instant>,
+    /// The entry's share of a capped in-flight count, given back once it is no longer pending.
+    permit: Option<Permit>,
+    /// Whether this only holds on to user data for the ring thread, e.g. for a linked timeout entry, rather than
+    /// tracking a submission.
+    reserved: bool,
+    /// The stack the entry was submitted from.
+    #[cfg(feature = "debug-backtrace")]
+    backtrace: Arc<Backtrace>,
+}
+
+impl Origin {
+    /// Records a submission made right now, from the calling stack.
+    #[inline]
+    pub(crate) fn capture() -> Self {
+        Self {
+            since: Instant::now(),
+            handed_over: None,
+            permit: None,
+            reserved: false,
+            #[cfg(feature = "debug-backtrace")]
+            backtrace: Arc::new(Backtrace::force_capture()),
+        }
+    }
+
+    /// Holds on to user data the ring thread is going to use for an entry of its own, so it is not handed out again
+    /// until the ring thread is done with it.
+    #[inline]
+    pub(crate) fn reservation() -> Self {
+        Self {
+            reserved: true,
+            ..Self::capture()
+        }
+    }
+
+    /// Whether this only holds on to user data rather than tracking a submission.
+    #[inline]
+    pub(crate) fn is_reservation(&self) -> bool {
+        self.reserved
+    }
+
+    /// Holds `permit` for as long as the entry is pending.
+    #[inline]
+    pub(crate) fn holding(mut self, permit: Option<Permit>) -> Self {
+        self.permit = permit;
+        self
+    }
+
+    /// When the entry was submitted.
+    #[inline]
+    pub(crate) fn since(&self) -> Instant {
+        self.since
+    }
+
+    /// Records that the ring thread handed the entry to the backend `at`.
+    #[inline]
+    pub(crate) fn hand_over(&mut self, at: Instant) {
+        self.handed_over = Some(at);
+    }
+
+    /// When the entry was submitted, and when the ring thread last handed it to the backend if that was recorded.
+    #[inline]
+    pub(crate) fn timing(&self) -> (Instant, Option<Instant>) {
+        (self.since, self.handed_over)
+    }
+
+    /// Reports the entry submitted under `user_data` as still outstanding.
+    pub(crate) fn outstanding(&self, user_data: UserData) -> Outstanding {
+        Outstanding {
+            user_data,
+            pending_for: self.since.elapsed(),
+            #[cfg(feature = "debug-backtrace")]
+            backtrace: Arc::clone(&self.backtrace),
+        }
+    }
+}
+
+/// A submitted entry whose completion has yet to be delivered.
+#[derive(Debug, Clone)]
+pub struct Outstanding {
+    /// The entry's user data.
+    pub user_data: UserData,
+    /// How long ago the entry was submitted.
+    pub pending_for: Duration,
+    /// Where the entry was submitted from.
+    #[cfg(feature = "debug-backtrace")]
+    pub backtrace: Arc<Backtrace>,
+}
+
+/// The in-flight counts around one submission, for adaptive concurrency limits.
+///
+/// Delivered along with the completion by [`PRingSender::submit_sampled`](crate::PRingSender::submit_sampled). Both
+/// counts include the submission itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InflightSample {
+    /// Entries in flight right after the submission was made.
+    pub at_submit: usize,
+    /// Entries in flight as its completion was delivered.
+    pub at_complete: usize,
+    /// How long the submission took to complete.
+    pub latency: Duration,
+}
+
+/// Lock a mutex, ignoring poisoning. Everything guarded this way stays consistent across a panic.
+pub(crate) fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// The outcome delivered for a submission.
+pub type Completion<C> = Result<C, RingError>;
+
+/// A completion callback, run on the ring thread or handed to a callback worker.
+///
+/// Implemented by every `FnOnce(Completion<C>) + Send + 'static` closure, so `Box::new(closure)` makes a
+/// [`Destination`]. Handing a callback to a worker requires it and its completion to be `'static`, which only
+/// the closure's implementation knows; the ring thread itself has no need for that.
+pub trait CompletionCallback<C>: Send {
+    /// Runs the callback with `completion`.
+    fn call(self: Box<Self>, completion: Completion<C>);
+
+    /// Bundles the callback with `completion` into a job for a callback worker to run.
+    #[doc(hidden)]
+    fn into_job(self: Box<Self>, completion: Completion<C>) -> Box<dyn FnOnce() + Send>;
+}
+
+impl<C, F> CompletionCallback<C> for F
+where
+    C: CQE + 'static,
+    F: FnOnce(Completion<C>) + Send + 'static,
+{
+    #[inline]
+    fn call(self: Box<Self>, completion: Completion<C>) {
+        self(completion);
+    }
+
+    #[inline]
+    fn into_job(self: Box<Self>, completion: Completion<C>) -> Box<dyn FnOnce() + Send> {
+        Box::new(move || self(completion))
+    }
+}
+
+/// Delivers a completion in place of sending it over its oneshot channel.
+pub(crate) type DeliverFn<C> = Box<dyn FnMut(Completion<C>, oneshot::Sender<Completion<C>>) + Send>;
+
+/// Where a completion is delivered once its entry completes.
+pub(crate) enum Completer<C: CQE> {
+    /// Resolve a promise by sending the completion over a oneshot channel.
+    Promise(oneshot::Sender<Completion<C>>),
+    /// Invoke a callback with the completion.
+    Callback(Box<dyn CompletionCallback<C>>),
+    /// Invoke a callback with the completion, on a callback worker of the given NUMA node if there are any.
+    Routed(Box<dyn CompletionCallback<C>>, usize),
+    /// Fill a [`CompletionSlot`](crate::submission::CompletionSlot) and wake its waker.
+    Slot(SlotSender<C>),
+    /// Resolve a submission through a channel borrowed from the ring's completion pool.
+    Pooled(PooledSender<C>),
+    /// Collect a fixed number of completions for the entry before sending them all at once.
+    Collect(Collector<C>),
+    /// Discard the entry's completions until one matches the filter, then send that one over a oneshot channel.
+    Until(
+        Box<dyn Fn(&C) -> bool + Send>,
+        oneshot::Sender<Completion<C>>,
+    ),
+    /// Hold a resource the backend may use until the entry completes, then send it back along with the completion.
+    Owning(
+        Box<dyn Any + Send>,
+        oneshot::Sender<Completion<(C, Box<dyn Any + Send>)>>,
+    ),
+    /// Send the completion along with the in-flight count at submission, given with when it was made, and the count at
+    /// delivery, read from the given pending set.
+    Sampled(
+        usize,
+        Instant,
+        PendingSet,
+        oneshot::Sender<Completion<(C, InflightSample)>>,
+    ),
+    /// Push the completion into the ring's completion stream, tagged with the given user data.
+    Stream(UserData, StreamSender<C>),
+    /// Count the completion towards a detached bulk submission.
+    Count(Arc<Tally>),
+    /// Write the completion into a slot owned by the caller and notify everyone waiting on the condvar.
+    Into(Arc<Mutex<Option<Completion<C>>>>, Arc<Condvar>),
+}
+
+/// Collects the completions of an entry expected to complete a fixed number of times.
+///
+/// The entry stays registered until its last completion arrives, or until one of them is an error, which is sent on
+/// its own in place of the collected completions.
+pub(crate) struct Collector<C: CQE> {
+    /// Completions still expected, including the one about to arrive.
+    remaining: usize,
+    /// The completions collected so far, in the order they arrived.
+    collected: Vec<C>,
+    /// Where the collected completions are sent.
+    tx: oneshot::Sender<Result<Vec<C>, RingError>>,
+}
+
+impl<C: CQE> Collector<C> {
+    /// Creates a collector for `n` completions. A count of zero is treated as one.
+    pub(crate) fn new(n: usize, tx: oneshot::Sender<Result<Vec<C>, RingError>>) -> Self {
+        Self {
+            remaining: n.max(1),
+            collected: Vec::with_capacity(n.max(1)),
+            tx,
+        }
+    }
+}
+
+/// Counts the completions of a detached bulk submission, sending the total once every entry is done with.
+///
+/// Every entry holds a reference, so the total goes out when the last one is dropped, whether its entry completed or
+/// was dropped by the ring thread. Only completions reported by the backend count, failed or not.
+pub(crate) struct Tally {
+    /// Completions counted so far.
+    completed: AtomicUsize,
+    /// Where the total is sent, or `None` once it has been.
+    tx: Option<oneshot::Sender<usize>>,
+}
+
+impl Tally {
+    /// Creates a tally sending its total over `tx`.
+    pub(crate) fn new(tx: oneshot::Sender<usize>) -> Self {
+        Self {
+            completed: AtomicUsize::new(0),
+            tx: Some(tx),
+        }
+    }
+}
+
+impl Drop for Tally {
+    fn drop(&mut self) {
+        if let Some(tx) = self.tx.take() {
+            let _ = tx.send(*self.completed.get_mut());
+        }
+    }
+}
+
+impl<C: CQE> From<oneshot::Sender<Completion<C>>> for Completer<C> {
+    fn from(sender: oneshot::Sender<Completion<C>>) -> Self {
+        Self::Promise(sender)
+    }
+}
+
+/// Where a completion is delivered once its entry completes, as registered with a [`Registry`].
+///
+/// Made from the sending half of a promise's channel, or from a boxed [`CompletionCallback`]. Dropping a destination
+/// without resolving it resolves a waiting promise with `RingError::Disconnected`.
+pub struct Destination<C: CQE>(pub(crate) Completer<C>);
+
+impl<C: CQE> Destination<C> {
+    /// Deliver `completion`, running a callback on the calling thread.
+    pub fn resolve(self, completion: Completion<C>) {
+        self.0.resolve(completion, None);
+    }
+}
+
+impl<C: CQE> From<oneshot::Sender<Completion<C>>> for Destination<C> {
+    fn from(sender: oneshot::Sender<Completion<C>>) -> Self {
+        Self(Completer::Promise(sender))
+    }
+}
+
+impl<C: CQE> From<Completer<C>> for Destination<C> {
+    fn from(completer: Completer<C>) -> Self {
+        Self(completer)
+    }
+}
+
+impl<C: CQE> From<Box<dyn CompletionCallback<C>>> for Destination<C> {
+    fn from(callback: Box<dyn CompletionCallback<C>>) -> Self {
+        Self(Completer::Callback(callback))
+    }
+}
+
+impl<C: CQE> Completer<C> {
+    /// Deliver `completion`, queueing callbacks onto `pool` if there is one.
+    ///
+    /// Sending a completion or reply over a channel, here and throughout the crate, ignores a receiver that has been
+    /// dropped: the caller gave up on it, so nobody is interested any more.
+    pub(crate) fn resolve(self, completion: Completion<C>, pool: Option<&CallbackPool>) {
+        match self {
+            Self::Promise(sender) => {
+                let _ = sender.send(completion);
+            }
+            Self::Callback(callback) => match pool {
+                Some(pool) => pool.execute(callback.into_job(completion)),
+                None => callback.call(completion),
+            },
+            Self::Routed(callback, node) => match pool {
+                Some(pool) => pool.execute_on(node, callback.into_job(completion)),
+                None => callback.call(completion),
+            },
+            // Waking is cheap, so it never goes through the pool.
+            Self::Slot(sender) => sender.send(completion),
+            Self::Pooled(sender) => sender.send(completion),
+            Self::Until(_, sender) => {
+                let _ = sender.send(completion);
+            }
+            // Resolved here rather than by the registry, the entry never reached the backend, so its resource can go.
+            Self::Owning(resource, sender) => {
+                let _ = sender.send(completion.map(|cqe| (cqe, resource)));
+            }
+            Self::Sampled(at_submit, since, pending, sender) => {
+                let _ = sender.send(completion.map(|cqe| {
+                    let sample = InflightSample {
+                        at_submit,
+                        // The entry itself is no longer pending by the time it is delivered.
+                        at_complete: lock(&pending).len() + 1,
+                        latency: since.elapsed(),
+                    };
+                    (cqe, sample)
+                }));
+            }
+            Self::Stream(user_data, sender) => sender.send(user_data, completion),
+            Self::Collect(Collector {
+                mut collected, tx, ..
+            }) => {
+                let _ = tx.send(completion.map(|cqe| {
+                    collected.push(cqe);
+                    collected
+                }));
+            }
+            Self::Count(tally) => {
+                if matches!(completion, Ok(_) | Err(RingError::Backend(_))) {
+                    tally.completed.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            // Like waking, notifying is cheap, so it never goes through the pool.
+            Self::Into(slot, notify) => {
+                *lock(&slot) = Some(completion);
+                notify.notify_all();
+            }
+        }
+    }
+}
+
+/// A registered entry: where its completion goes and, optionally, a copy of the entry itself.
+struct Slot<C: CQE, S> {
+    /// Where the completion is delivered.
+    completer: Completer<C>,
+    /// The retained submission entry, if retention is enabled.
+    entry: Option<S>,
+    /// How to resubmit the entry on transient failures, if it is retrying.
+    retry: Option<Retry<S>>,
+    /// Where the entry's completion goes in a prioritized reap; higher goes first.
+    priority: i32,
+}
+
+/// A barrier waiting on the entries that were outstanding when it was registered.
+struct Barrier {
+    /// User data of the entries still to be delivered.
+    outstanding: HashSet<u64>,
+    /// Fired once `outstanding` is empty.
+    tx: oneshot::Sender<()>,
+}
+
+/// Outstanding state recovered from a registry, e.g. after the ring thread panicked.
+#[derive(Debug)]
+pub struct Recovered<S> {
+    /// User data of every entry that was registered but not completed.
+    pub user_data: Vec<UserData>,
+    /// The retained entries that were registered but not completed. Empty unless entries are retained.
+    pub entries: Vec<S>,
+}
 
 /// A registry mapping user data to completion senders.
-pub struct Registry<C: CQE> {
-    /// Map from user data to oneshot senders.
-    senders: HashMap<u64, oneshot::Sender<C>>,
+///
+/// `S` is the type of retained submission entries, if any are kept alongside their destinations.
+pub struct Registry<C: CQE, S = ()> {
+    /// Map from user data to registered entries.
+    senders: HashMap<u64, Slot<C, S>>,
     /// The current user data counter.
     curr_ud: u64,
+    /// Optional worker pool that callbacks are dispatched to instead of running inline.
+    pool: Option<CallbackPool>,
+    /// Optional hook delivering completions bound for a oneshot channel, instead of sending them directly.
+    deliver: Option<DeliverFn<C>>,
+    /// Outstanding user data, cleared as completions are delivered.
+    pending: PendingSet,
+    /// Delivered completions waiting to be taken, if that is being tracked.
+    delivered: Option<DeliveredSet>,
+    /// Barriers waiting for their entries to be delivered.
+    barriers: Vec<Barrier>,
+    /// Waiters for the number of registered entries to drop below a threshold, with their threshold.
+    thresholds: Vec<(usize, oneshot::Sender<()>)>,
+    /// Resources of entries resolved before their completion arrived, kept until it does since the backend may still
+    /// be using them.
+    parked: HashMap<u64, Box<dyn Any + Send>>,
 }
 
-impl<C: CQE> Registry<C> {
+impl<C: CQE, S> Drop for Registry<C, S> {
+    /// Resolves every entry still registered with `RingError::Disconnected`, so that callbacks learn of it too rather
+    /// than being dropped unheard.
+    fn drop(&mut self) {
+        let mut pending = lock(&self.pending);
+        for (user_data, slot) in self.senders.drain() {
+            pending.remove(&user_data);
+            slot.completer
+                .resolve(Err(RingError::Disconnected), self.pool.as_ref());
+        }
+    }
+}
+
+impl<C: CQE, S> Default for Registry<C, S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C: CQE, S> Registry<C, S> {
     /// Create a new, empty registry.
     pub fn new() -> Self {
         Self {
             senders: HashMap::new(),
             curr_ud: 0,
+            pool: None,
+            deliver: None,
+            pending: PendingSet::default(),
+            delivered: None,
+            barriers: Vec::new(),
+            thresholds: Vec::new(),
+            parked: HashMap::new(),
         }
     }
 
+    /// Keep `pending` in sync with delivered completions.
+    pub(crate) fn share_pending(&mut self, pending: PendingSet) {
+        self.pending = pending;
+    }
+
+    /// Reserve room for at least `additional` more entries, in the registry and in the set of pending user data.
+    pub(crate) fn reserve(&mut self, additional: usize) {
+        self.senders.reserve(additional);
+        lock(&self.pending).reserve(additional);
+    }
+
+    /// Start recording when completions are delivered to a submission, until the submission takes them.
+    pub(crate) fn track_delivered(&mut self) {
+        self.delivered.get_or_insert_default();
+    }
+
+    /// The completions delivered but not yet taken, if tracked.
+    pub(crate) fn delivered(&self) -> Option<&DeliveredSet> {
+        self.delivered.as_ref()
+    }
+
+    /// Dispatch callbacks to `pool` rather than running them on the calling thread.
+    pub(crate) fn set_callback_pool(&mut self, pool: CallbackPool) {
+        self.pool = Some(pool);
+    }
+
+    /// Hand completions bound for a oneshot channel to `deliver` rather than sending them directly.
+    pub(crate) fn set_deliver(&mut self, deliver: DeliverFn<C>) {
+        self.deliver = Some(deliver);
+    }
+
+    /// The number of outstanding entries.
+    pub fn len(&self) -> usize {
+        self.senders.len()
+    }
+
+    /// Whether there are no outstanding entries.
+    pub fn is_empty(&self) -> bool {
+        self.senders.is_empty()
+    }
+
     /// Get the current user data value.
-    pub fn curr_ud(&self) -> u64 {
-        self.curr_ud
+    pub fn curr_ud(&self) -> UserData {
+        self.curr_ud.into()
     }
 
     /// Return the current user data value, incrementing the internal user data value, wrapping on overflow.
@@ -36,29 +518,367 @@ impl<C: CQE> Registry<C> {
     }
 
     /// Get the next unused user data value.
-    pub fn next_uuid(&mut self) -> u64 {
+    pub fn next_uuid(&mut self) -> UserData {
         loop {
             let id = self.incr_ud();
             if !self.senders.contains_key(&id) {
-                break id;
+                break id.into();
             }
         }
     }
 
-    /// Insert a sender for a given user data value.
-    pub fn insert(&mut self, user_data: u64, sender: oneshot::Sender<C>) {
-        self.senders.insert(user_data, sender);
+    /// Insert a completion destination for a given user data value.
+    ///
+    /// Replaces the destination of an entry already outstanding under `user_data`, which is then never resolved; see
+    /// [`try_insert`](Self::try_insert).
+    pub fn insert(&mut self, user_data: UserData, destination: impl Into<Destination<C>>) {
+        let slot = Slot {
+            completer: destination.into().0,
+            entry: None,
+            retry: None,
+            priority: 0,
+        };
+        self.senders.insert(user_data.get(), slot);
     }
 
-    /// Complete an entry, sending it to the registered sender if present.
-    pub fn complete(&mut self, entry: C) {
+    /// Insert a completion destination for a given user data value, unless an entry is already outstanding under it.
+    ///
+    /// Returns the destination back on a collision, leaving the outstanding entry's destination in place.
+    pub fn try_insert<T: Into<Destination<C>>>(
+        &mut self,
+        user_data: UserData,
+        destination: T,
+    ) -> Result<(), T> {
+        if self.senders.contains_key(&user_data.get()) {
+            return Err(destination);
+        }
+
+        self.insert(user_data, destination);
+        Ok(())
+    }
+
+    /// Resolve a destination that was never inserted with `err`, dispatching it like any other.
+    pub(crate) fn reject(&self, completer: Completer<C>, err: RingError) {
+        completer.resolve(Err(err), self.pool.as_ref());
+    }
+
+    /// Retain a copy of the submission entry registered for `user_data`, so it can be recovered later.
+    ///
+    /// Does nothing if there is no entry registered for `user_data`.
+    pub fn retain(&mut self, user_data: UserData, entry: S) {
+        if let Some(slot) = self.senders.get_mut(&user_data.get()) {
+            slot.entry = Some(entry);
+        }
+    }
+
+    /// Resubmit the entry registered for `user_data` according to `retry` when it fails.
+    ///
+    /// Does nothing if there is no entry registered for `user_data`.
+    pub(crate) fn set_retry(&mut self, user_data: UserData, retry: Retry<S>) {
+        if let Some(slot) = self.senders.get_mut(&user_data.get()) {
+            slot.retry = Some(retry);
+        }
+    }
+
+    /// Count a failed attempt of the retrying entry registered for `user_data`.
+    ///
+    /// Returns a copy of the entry to resubmit and the delay before resubmitting it, or `None` if the entry is not
+    /// retrying or its policy gives up on `err`.
+    pub(crate) fn retry(
+        &mut self,
+        user_data: UserData,
+        err: &BackendError,
+    ) -> Option<(S, Duration)> {
+        let retry = self.senders.get_mut(&user_data.get())?.retry.as_mut()?;
+
+        let delay = retry.policy.delay(retry.attempts + 1, err)?;
+        retry.attempts += 1;
+
+        Some(((retry.copy)(&retry.spare), delay))
+    }
+
+    /// Deliver the completion of the entry registered for `user_data` ahead of those with a lower `priority`.
+    ///
+    /// Only used if the ring delivers completions by priority. Does nothing if there is no entry registered for
+    /// `user_data`.
+    pub(crate) fn set_priority(&mut self, user_data: UserData, priority: i32) {
+        if let Some(slot) = self.senders.get_mut(&user_data.get()) {
+            slot.priority = priority;
+        }
+    }
+
+    /// The delivery priority of the entry registered for `user_data`, zero unless set or if there is no such entry.
+    pub fn priority(&self, user_data: UserData) -> i32 {
         self.senders
-            .remove(&entry.get_ud())
-            // If there is no sender with this user data value, simply ignore it.
-            .map(|sender| sender.send(entry));
+            .get(&user_data.get())
+            .map_or(0, |slot| slot.priority)
+    }
+
+    /// Whether an entry is registered for `user_data`.
+    pub fn contains(&self, user_data: UserData) -> bool {
+        self.senders.contains_key(&user_data.get())
+    }
+
+    /// When and where each pending entry was submitted, by user data.
+    pub(crate) fn origins(&self) -> MutexGuard<'_, HashMap<u64, Origin>> {
+        lock(&self.pending)
+    }
+
+    /// Remove the slot registered for `user_data`, marking it as no longer pending.
+    fn take(&mut self, user_data: UserData) -> Option<Slot<C, S>> {
+        let user_data = user_data.get();
+        let slot = self.senders.remove(&user_data)?;
+        lock(&self.pending).remove(&user_data);
+
+        if let Some(delivered) = &self.delivered {
+            // Only submissions still around to take the completion are in the set.
+            if let Some(at) = lock(delivered).get_mut(&user_data) {
+                *at = Some(Instant::now());
+            }
+        }
+
+        let mut i = 0;
+        while i < self.barriers.len() {
+            let barrier = &mut self.barriers[i];
+            if barrier.outstanding.remove(&user_data) && barrier.outstanding.is_empty() {
+                let _ = self.barriers.swap_remove(i).tx.send(());
+            } else {
+                i += 1;
+            }
+        }
+
+        let mut i = 0;
+        while i < self.thresholds.len() {
+            if self.senders.len() < self.thresholds[i].0 {
+                let _ = self.thresholds.swap_remove(i).1.send(());
+            } else {
+                i += 1;
+            }
+        }
+
+        Some(slot)
+    }
+
+    /// Reports every registered entry as outstanding, longest pending first.
+    pub(crate) fn outstanding(&self) -> Vec<Outstanding> {
+        let origins = self.origins();
+        let mut outstanding: Vec<Outstanding> = self
+            .senders
+            .keys()
+            .filter_map(|&user_data| Some(origins.get(&user_data)?.outstanding(user_data.into())))
+            .collect();
+        outstanding.sort_by_key(|outstanding| Reverse(outstanding.pending_for));
+
+        outstanding
+    }
+
+    /// Fire `tx` once every entry outstanding right now has been delivered.
+    ///
+    /// Entries inserted afterwards do not hold the barrier up. Fires immediately if nothing is outstanding.
+    pub fn barrier(&mut self, tx: oneshot::Sender<()>) {
+        if self.senders.is_empty() {
+            let _ = tx.send(());
+            return;
+        }
+
+        self.barriers.push(Barrier {
+            outstanding: self.senders.keys().copied().collect(),
+            tx,
+        });
+    }
+
+    /// Fire `tx` once fewer than `threshold` entries are registered, right away if that is already the case.
+    pub fn notify_below(&mut self, threshold: usize, tx: oneshot::Sender<()>) {
+        if self.senders.len() < threshold {
+            let _ = tx.send(());
+            return;
+        }
+
+        self.thresholds.push((threshold, tx));
+    }
+
+    /// Copy every retained entry that is still outstanding with `copy`, in order of user data.
+    ///
+    /// A retrying entry's spare stands in for a retained copy, as in [`drain_outstanding`](Self::drain_outstanding),
+    /// and is copied with its own copy function.
+    pub(crate) fn retained(&self, copy: Option<fn(&S) -> S>) -> Vec<S> {
+        let mut retained: Vec<_> = self
+            .senders
+            .iter()
+            .filter_map(
+                |(&user_data, slot)| match (&slot.entry, &slot.retry, copy) {
+                    (Some(entry), _, Some(copy)) => Some((user_data, copy(entry))),
+                    (_, Some(retry), _) => Some((user_data, (retry.copy)(&retry.spare))),
+                    _ => None,
+                },
+            )
+            .collect();
+        retained.sort_unstable_by_key(|&(user_data, _)| user_data);
+
+        retained.into_iter().map(|(_, entry)| entry).collect()
+    }
+
+    /// Remove every outstanding entry, resolving it with `RingError::Disconnected`, and return what was outstanding.
+    ///
+    /// Waiting barriers and threshold waiters are dropped.
+    pub fn drain_outstanding(&mut self) -> Recovered<S> {
+        self.barriers.clear();
+        self.thresholds.clear();
+
+        let mut pending = lock(&self.pending);
+        let mut recovered = Recovered {
+            user_data: Vec::with_capacity(self.senders.len()),
+            entries: Vec::new(),
+        };
+
+        for (user_data, slot) in self.senders.drain() {
+            pending.remove(&user_data);
+            slot.completer
+                .resolve(Err(RingError::Disconnected), self.pool.as_ref());
+            recovered.user_data.push(user_data.into());
+            // A retrying entry's spare is as good as a retained copy.
+            recovered
+                .entries
+                .extend(slot.entry.or(slot.retry.map(|retry| retry.spare)));
+        }
+
+        recovered
+    }
+
+    /// Remove the destination registered for `user_data` without completing it, handing it back to the caller.
+    ///
+    /// The entry counts as delivered: it is no longer pending and no longer holds up barriers. Dropping the returned
+    /// destination resolves a waiting promise with `RingError::Disconnected`; it can also be resolved later, or
+    /// registered again under other user data. Returns `None` if there was no such destination.
+    pub fn remove(&mut self, user_data: UserData) -> Option<Destination<C>> {
+        self.take(user_data).map(|slot| Destination(slot.completer))
+    }
+
+    /// Replace the destination registered for `user_data` with `destination`, handing the previous one back.
+    ///
+    /// Unlike [`remove`](Self::remove) followed by [`insert`](Self::insert), the entry stays pending throughout and
+    /// keeps its retained copy, retry policy and priority. Returns `None`, dropping `destination`, if there was no such
+    /// destination, e.g. because it already completed.
+    pub fn replace(
+        &mut self,
+        user_data: UserData,
+        destination: impl Into<Destination<C>>,
+    ) -> Option<Destination<C>> {
+        let slot = self.senders.get_mut(&user_data.get())?;
+
+        Some(Destination(std::mem::replace(
+            &mut slot.completer,
+            destination.into().0,
+        )))
+    }
+
+    /// Deliver `completion` to the destination registered for `user_data`.
+    ///
+    /// Returns `false` if there was no such destination, e.g. because it already completed.
+    pub fn resolve(&mut self, user_data: UserData, completion: Completion<C>) -> bool {
+        let user_data = user_data.get();
+        // A collecting entry stays registered until its last completion, a filtering one until its first match.
+        let completion = match (completion, self.senders.get_mut(&user_data)) {
+            (
+                Ok(cqe),
+                Some(Slot {
+                    completer: Completer::Collect(collector),
+                    ..
+                }),
+            ) if collector.remaining > 1 => {
+                collector.remaining -= 1;
+                collector.collected.push(cqe);
+                return true;
+            }
+            (
+                Ok(cqe),
+                Some(Slot {
+                    completer: Completer::Until(filter, _),
+                    ..
+                }),
+            ) if !filter(&cqe) => return true,
+            (completion, _) => completion,
+        };
+
+        match self.take(user_data.into()) {
+            Some(slot) => {
+                match (slot.completer, &mut self.deliver) {
+                    (Completer::Promise(sender), Some(deliver)) => deliver(completion, sender),
+                    (Completer::Owning(resource, sender), _) => {
+                        let completion = match completion {
+                            Ok(cqe) => Ok((cqe, resource)),
+                            // The operation may still be running, its completion simply no longer awaited.
+                            Err(
+                                err @ (RingError::Cancelled
+                                | RingError::Timeout
+                                | RingError::CompletionLost),
+                            ) => {
+                                self.parked.insert(user_data, resource);
+                                Err(err)
+                            }
+                            Err(err) => Err(err),
+                        };
+                        let _ = sender.send(completion);
+                    }
+                    (completer, _) => completer.resolve(completion, self.pool.as_ref()),
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Complete an entry, delivering it to the registered destination if present.
+    ///
+    /// Callbacks run inline unless a callback pool is configured, in which case they are queued onto the pool.
+    pub fn complete(&mut self, entry: C) {
+        // If there is no destination with this user data value, simply ignore it.
+        self.resolve(entry.get_ud(), Ok(entry));
+    }
+
+    /// Drop the resource held for the entry tagged `user_data`, if it was resolved before its completion arrived.
+    ///
+    /// Called once the entry's own completion shows up after all, as the backend is done with the resource by then.
+    pub(crate) fn release(&mut self, user_data: UserData) {
+        if !self.parked.is_empty() {
+            self.parked.remove(&user_data.get());
+        }
+    }
+
+    /// Gives back user data reserved with [`Origin::reservation`], letting it be handed out again.
+    pub(crate) fn unreserve(&self, user_data: UserData) {
+        lock(&self.pending).remove(&user_data.get());
+    }
+
+    /// Resolve the entry registered for `user_data` with `RingError::Cancelled`.
+    ///
+    /// Returns `false` if there was no such entry, e.g. because it already completed.
+    pub fn cancel(&mut self, user_data: UserData) -> bool {
+        self.resolve(user_data, Err(RingError::Cancelled))
     }
 
-    /// Complete a batch of entries, sending each to its registered sender.
+    /// Resolve the `n` registered entries submitted earliest with `err`, returning how many there were.
+    ///
+    /// Entries without a recorded submission time, e.g. inserted by hand, count as the most recent.
+    pub(crate) fn fail_oldest(&mut self, n: usize, err: RingError) -> usize {
+        let mut oldest: Vec<(Instant, u64)> = {
+            let origins = lock(&self.pending);
+            let now = Instant::now();
+            self.senders
+                .keys()
+                .map(|&ud| (origins.get(&ud).map_or(now, Origin::since), ud))
+                .collect()
+        };
+        oldest.sort_unstable();
+
+        oldest
+            .into_iter()
+            .take(n)
+            .filter(|&(_, ud)| self.resolve(ud.into(), Err(err.clone())))
+            .count()
+    }
+
+    /// Complete a batch of entries, sending each to its registered destination.
     pub fn batch_complete<I>(&mut self, entries: I)
     where
         I: Iterator<Item = C>,
@@ -66,3 +886,37 @@ impl<C: CQE> Registry<C> {
         entries.for_each(|entry| self.complete(entry));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use super::*;
+    use crate::test_util::MockEntry;
+
+    #[test]
+    fn dropped_registries_resolve_their_callbacks() {
+        let (tx, rx) = mpsc::channel();
+        let mut registry = Registry::<MockEntry>::new();
+        registry.insert(
+            UserData::new(1),
+            Completer::Callback(Box::new(move |completion| tx.send(completion).unwrap())),
+        );
+
+        drop(registry);
+        assert_eq!(rx.try_recv().unwrap().unwrap_err(), RingError::Disconnected);
+    }
+
+    #[test]
+    fn removed_destinations_can_be_resolved_later() {
+        let (tx, rx) = oneshot::channel();
+        let mut registry = Registry::<MockEntry>::new();
+        registry.insert(UserData::new(1), tx);
+
+        let destination = registry.remove(UserData::new(1)).unwrap();
+        assert!(!registry.contains(UserData::new(1)));
+
+        destination.resolve(Ok(MockEntry::new(7)));
+        assert_eq!(rx.recv().unwrap().unwrap().tag(), 7);
+    }
+}
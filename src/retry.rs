@@ -0,0 +1,203 @@
+//! Automatic resubmission of entries that fail with a transient backend error.
+//!
+//! An entry submitted with [`PRingSender::submit_retry`](crate::PRingSender::submit_retry) keeps a spare copy on
+//! the ring thread. When its completion is interpreted as an error the [`RetryPolicy`] deems transient, the ring
+//! thread pushes a fresh copy after a backoff instead of resolving the submission, until the policy runs out of
+//! attempts.
+
+use std::{io, time::Duration};
+
+use crate::error::BackendError;
+
+/// When and how often to resubmit a failed entry.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// How many times to resubmit before giving up.
+    max_retries: u32,
+    /// The delay before the first resubmission, doubling with each further one.
+    backoff: Duration,
+    /// Whether an error is worth retrying.
+    retryable: fn(&BackendError) -> bool,
+}
+
+impl RetryPolicy {
+    /// Creates a policy resubmitting up to `max_retries` times, straight away, on `EAGAIN` or `EINTR`.
+    pub fn new(max_retries: u32) -> Self {
+        Self {
+            max_retries,
+            backoff: Duration::ZERO,
+            retryable: is_transient,
+        }
+    }
+
+    /// Waits `backoff` before the first resubmission, doubling the delay for each further one.
+    pub fn backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Retries only the errors for which `retryable` returns `true`.
+    pub fn retry_if(mut self, retryable: fn(&BackendError) -> bool) -> Self {
+        self.retryable = retryable;
+        self
+    }
+
+    /// How many times to resubmit before giving up.
+    #[inline]
+    pub fn max_retries(&self) -> u32 {
+        self.max_retries
+    }
+
+    /// The delay before resubmission number `attempt`, counting from one, or `None` if the policy gives up on `err`.
+    pub(crate) fn delay(&self, attempt: u32, err: &BackendError) -> Option<Duration> {
+        if attempt > self.max_retries || !(self.retryable)(err) {
+            return None;
+        }
+
+        let factor = 1u32.checked_shl(attempt - 1).unwrap_or(u32::MAX);
+        Some(self.backoff.saturating_mul(factor))
+    }
+}
+
+/// Whether `err` is an OS error that usually goes away on its own, i.e. `EAGAIN` or `EINTR`.
+pub fn is_transient(err: &BackendError) -> bool {
+    match err {
+        BackendError::Os(code) => matches!(
+            io::Error::from_raw_os_error(*code).kind(),
+            io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted
+        ),
+        _ => false,
+    }
+}
+
+/// A retrying entry's state on the ring thread.
+pub(crate) struct Retry<S> {
+    /// A copy of the entry to resubmit.
+    pub(crate) spare: S,
+    /// Copies `spare` for each resubmission.
+    pub(crate) copy: fn(&S) -> S,
+    /// When to give up.
+    pub(crate) policy: RetryPolicy,
+    /// Resubmissions so far.
+    pub(crate) attempts: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    use super::*;
+    use crate::{
+        PRingSender,
+        adapters::{Completions, QueueSubmissions},
+        driver::StepOutcome,
+        error::RingError,
+        test_util::{MockEntry, MockRing},
+        traits::{FullRing, Submitter},
+    };
+
+    /// A backend failing its next few completions.
+    struct FlakyRing(MockRing<MockEntry, MockEntry>, Arc<AtomicUsize>);
+
+    impl Submitter for FlakyRing {
+        fn submit(&mut self) {
+            self.0.submit();
+        }
+
+        fn submit_counted(&mut self) -> Option<usize> {
+            self.0.submit_counted()
+        }
+    }
+
+    impl FullRing<MockEntry, MockEntry, QueueSubmissions<MockEntry>, Completions<MockEntry>>
+        for FlakyRing
+    {
+        fn completion(&mut self) -> Completions<MockEntry> {
+            self.0.completion()
+        }
+
+        fn submission(&mut self) -> QueueSubmissions<MockEntry> {
+            self.0.submission()
+        }
+
+        fn interpret(&self, cqe: MockEntry) -> Result<MockEntry, BackendError> {
+            match self
+                .1
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+            {
+                Ok(_) => Err(BackendError::Other("busy".into())),
+                Err(_) => Ok(cqe),
+            }
+        }
+    }
+
+    fn is_busy(err: &BackendError) -> bool {
+        matches!(err, BackendError::Other(message) if message == "busy")
+    }
+
+    #[test]
+    fn the_backoff_doubles_until_the_policy_gives_up() {
+        let policy = RetryPolicy::new(3).backoff(Duration::from_millis(10));
+        let err = BackendError::Os(
+            io::Error::from(io::ErrorKind::Interrupted)
+                .raw_os_error()
+                .unwrap_or(4),
+        );
+
+        assert_eq!(policy.delay(1, &err), Some(Duration::from_millis(10)));
+        assert_eq!(policy.delay(3, &err), Some(Duration::from_millis(40)));
+        assert_eq!(policy.delay(4, &err), None);
+        assert_eq!(policy.delay(1, &BackendError::Unsupported), None);
+    }
+
+    #[test]
+    fn failed_attempts_are_resubmitted_until_one_succeeds() {
+        let ring = MockRing::echo();
+        let failures = Arc::new(AtomicUsize::new(2));
+        let (sender, mut driver) =
+            PRingSender::builder().build_driver(FlakyRing(ring.clone(), Arc::clone(&failures)));
+
+        let submission =
+            sender.submit_retry(MockEntry::new(1), RetryPolicy::new(3).retry_if(is_busy));
+        assert_eq!(driver.step(), StepOutcome::Handled);
+
+        for _ in 0..3 {
+            assert_eq!(ring.in_flight().len(), 1);
+            assert_eq!(ring.complete_all(), 1);
+            sender.reap();
+            while driver.try_step() != StepOutcome::Idle {}
+        }
+
+        assert_eq!(failures.load(Ordering::SeqCst), 0);
+        assert!(ring.in_flight().is_empty());
+        assert_eq!(submission.wait().unwrap().tag(), 1);
+    }
+
+    #[test]
+    fn the_last_error_is_delivered_once_retries_run_out() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder().build_driver(FlakyRing(
+            ring.clone(),
+            Arc::new(AtomicUsize::new(usize::MAX)),
+        ));
+
+        let submission =
+            sender.submit_retry(MockEntry::new(1), RetryPolicy::new(1).retry_if(is_busy));
+        assert_eq!(driver.step(), StepOutcome::Handled);
+
+        for _ in 0..2 {
+            assert_eq!(ring.complete_all(), 1);
+            sender.reap();
+            while driver.try_step() != StepOutcome::Idle {}
+        }
+
+        assert!(ring.in_flight().is_empty());
+        assert_eq!(
+            submission.wait().unwrap_err(),
+            RingError::Backend(BackendError::Other("busy".into()))
+        );
+    }
+}
@@ -0,0 +1,170 @@
+//! Spreading submissions over several rings.
+//!
+//! A [`ShardedRingSender`] fronts any number of rings, each with its own [`PRingSender`] and ring thread. Plain
+//! [`submit`](ShardedRingSender::submit) spreads entries round-robin for pure load spreading, while
+//! [`submit_keyed`](ShardedRingSender::submit_keyed) picks the shard from a key the caller chooses, e.g. a file
+//! descriptor, so related operations land on the same ring:
+//!
+//! ```no_run
+//! # #[cfg(feature = "test-util")]
+//! # fn main() {
+//! # use ring_promise::{PRingSender, shard::ShardedRingSender, test_util::{MockEntry, MockRing}};
+//! # let rings = vec![MockRing::<MockEntry, MockEntry>::echo(), MockRing::echo()];
+//! # let (fd, write) = (3, |_fd: i32, tag: u64| MockEntry::new(tag));
+//! # let (header, body) = (1, 2);
+//! let sharded = ShardedRingSender::new(rings.into_iter().map(PRingSender::new).collect()).unwrap();
+//! // Both writes go to the same ring, in this order.
+//! let first = sharded.submit_keyed(write(fd, header), fd as u64);
+//! let second = sharded.submit_keyed(write(fd, body), fd as u64);
+//! # }
+//! # #[cfg(not(feature = "test-util"))]
+//! # fn main() {}
+//! ```
+
+use std::sync::{
+    Arc,
+    atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{
+    PRingSender,
+    submission::Submission,
+    traits::{CompletionQueueEntry as CQE, SubmissionQueueEntry as SQE},
+};
+
+/// A sender spreading submissions over several rings.
+///
+/// Clones share the same shards and round-robin position. Every shard hands out user data of its own, so the user
+/// data of submissions on different shards can coincide.
+pub struct ShardedRingSender<S: SQE, C: CQE> {
+    /// The sender of every shard, by index.
+    shards: Arc<[PRingSender<S, C>]>,
+    /// The shard the next round-robin submission goes to, modulo the shard count.
+    next: Arc<AtomicUsize>,
+}
+
+impl<S: SQE, C: CQE> Clone for ShardedRingSender<S, C> {
+    fn clone(&self) -> Self {
+        Self {
+            shards: Arc::clone(&self.shards),
+            next: Arc::clone(&self.next),
+        }
+    }
+}
+
+impl<S: SQE, C: CQE> ShardedRingSender<S, C> {
+    /// Spreads submissions over the rings `shards` submit to, in that order. Returns `None` if there are none.
+    pub fn new(shards: Vec<PRingSender<S, C>>) -> Option<Self> {
+        if shards.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            shards: shards.into(),
+            next: Arc::new(AtomicUsize::new(0)),
+        })
+    }
+
+    /// The number of shards.
+    #[inline]
+    pub fn num_shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// The sender of every shard, by index.
+    #[inline]
+    pub fn shards(&self) -> &[PRingSender<S, C>] {
+        &self.shards
+    }
+
+    /// The sender of the shard `key` maps to, the one [`submit_keyed`](Self::submit_keyed) uses for it.
+    #[inline]
+    pub fn shard_for(&self, key: u64) -> &PRingSender<S, C> {
+        // The remainder is below the shard count, which fits a usize.
+        &self.shards[(key % self.shards.len() as u64) as usize]
+    }
+
+    /// Submits an entry to the next shard in round-robin order, returning a handle to its completion.
+    ///
+    /// # Arguments
+    /// * `entry` - The submission queue entry to submit.
+    #[inline]
+    pub fn submit(&self, entry: S) -> Submission<C> {
+        let shard = self.next.fetch_add(1, Ordering::Relaxed) % self.shards.len();
+        self.shards[shard].submit(entry)
+    }
+
+    /// Submits an entry to shard `key % num_shards`, returning a handle to its completion.
+    ///
+    /// Entries with the same key always go to the same ring and ring thread, keeping related operations local to
+    /// it. Entries submitted with the same key from one thread reach their ring in the order they were submitted,
+    /// as with a single [`PRingSender`].
+    ///
+    /// # Arguments
+    /// * `entry` - The submission queue entry to submit.
+    /// * `key` - Picks the shard; equal keys pick the same one.
+    #[inline]
+    pub fn submit_keyed(&self, entry: S, key: u64) -> Submission<C> {
+        self.shard_for(key).submit(entry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        driver::StepOutcome,
+        test_util::{MockEntry, MockRing},
+    };
+
+    #[test]
+    fn there_are_no_shards_without_rings() {
+        assert!(ShardedRingSender::<MockEntry, MockEntry>::new(Vec::new()).is_none());
+    }
+
+    #[test]
+    fn plain_submissions_go_round_robin() {
+        let rings = [MockRing::echo(), MockRing::echo(), MockRing::echo()];
+        let (senders, mut drivers): (Vec<_>, Vec<_>) = rings
+            .iter()
+            .map(|ring| PRingSender::builder().build_driver(ring.clone()))
+            .unzip();
+        let sharded = ShardedRingSender::new(senders).unwrap();
+        assert_eq!(sharded.num_shards(), 3);
+
+        let _submissions: Vec<_> = (1..=6)
+            .map(|tag| sharded.submit(MockEntry::new(tag)))
+            .collect();
+        for driver in &mut drivers {
+            while driver.try_step() == StepOutcome::Handled {}
+        }
+
+        for ring in &rings {
+            assert_eq!(ring.in_flight().len(), 2);
+        }
+    }
+
+    #[test]
+    fn equal_keys_go_to_the_same_shard() {
+        let rings = [MockRing::echo(), MockRing::echo()];
+        let (senders, mut drivers): (Vec<_>, Vec<_>) = rings
+            .iter()
+            .map(|ring| PRingSender::builder().build_driver(ring.clone()))
+            .unzip();
+        let sharded = ShardedRingSender::new(senders).unwrap();
+
+        let first = sharded.submit_keyed(MockEntry::new(1), 7);
+        let second = sharded.submit_keyed(MockEntry::new(2), 7);
+        for driver in &mut drivers {
+            while driver.try_step() == StepOutcome::Handled {}
+        }
+
+        // Shard 7 % 2, in submission order.
+        assert!(rings[0].in_flight().is_empty());
+        assert_eq!(
+            rings[1].in_flight(),
+            [first.user_data(), second.user_data()]
+        );
+        assert!(std::ptr::eq(sharded.shard_for(7), &sharded.shards()[1]));
+    }
+}
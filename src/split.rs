@@ -0,0 +1,182 @@
+//! Submitting operations too large for a single entry, split across several.
+//!
+//! Backends often cap how much a single operation can transfer. [`PRingSender::submit_split`] covers a range of any
+//! length with entries of at most a chunk's length each, built from a template by the backend's [`SplitEntry`], and
+//! resolves once every chunk has completed:
+//!
+//! ```no_run
+//! # use ring_promise::{
+//! #     PRingSender,
+//! #     error::SplitError,
+//! #     split::SplitEntry,
+//! #     traits::{CompletionQueueEntry, SubmissionQueueEntry, UserData},
+//! # };
+//! # #[derive(Clone)]
+//! # struct Sqe { ud: UserData, fd: i32, buf: usize, len: usize, offset: u64 }
+//! # impl Sqe {
+//! #     fn read(fd: i32, buf: usize, offset: u64) -> Self { Sqe { ud: UserData::new(0), fd, buf, len: 0, offset } }
+//! # }
+//! # impl SubmissionQueueEntry for Sqe {
+//! #     fn set_ud(&mut self, ud: UserData) { self.ud = ud; }
+//! #     fn get_ud(&self) -> UserData { self.ud }
+//! # }
+//! # impl CompletionQueueEntry for Sqe {
+//! #     fn get_ud(&self) -> UserData { self.ud }
+//! # }
+//! impl SplitEntry for Sqe {
+//!     fn chunk(&self, offset: u64, len: usize) -> Self {
+//!         Sqe { buf: self.buf.wrapping_add(offset as usize), len, offset: self.offset + offset, ..self.clone() }
+//!     }
+//! }
+//!
+//! # fn read_all(sender: PRingSender<Sqe, Sqe>, fd: i32, buf: usize, total_len: u64) -> Result<(), SplitError<Sqe>> {
+//! let chunks = sender.submit_split(&Sqe::read(fd, buf, 0), total_len, 1 << 20).wait_nopanic()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use promisery::Promise;
+
+use crate::{
+    PRingSender,
+    error::{RingError, SplitError, SubmitError},
+    traits::{CompletionQueueEntry as CQE, SubmissionQueueEntry as SQE},
+};
+
+/// Builds the entries covering each chunk of a split submission.
+pub trait SplitEntry: SQE {
+    /// An entry doing what `self` does, but only for the `len` bytes starting `offset` bytes into its range.
+    ///
+    /// Both the buffer and, for positioned operations, the file offset move along by `offset`.
+    fn chunk(&self, offset: u64, len: usize) -> Self;
+}
+
+impl<S: SplitEntry, C: CQE> PRingSender<S, C> {
+    /// Submits the operation `template` describes over `total_len` bytes, as entries of at most `chunk_len` bytes each,
+    /// returning a promise for their completions in offset order.
+    ///
+    /// Every chunk is built with [`SplitEntry::chunk`] and sent to the ring thread in one batch, as with
+    /// [`batch_submit`](Self::batch_submit). The promise resolves once every chunk has completed. A `total_len` of zero
+    /// submits nothing and resolves with no completions; a `chunk_len` of zero is treated as one.
+    ///
+    /// # Errors
+    /// The promise rejects with a [`SplitError`] if any chunk fails, carrying the outcome of every chunk so that
+    /// the completed ones are not lost.
+    pub fn submit_split(
+        &self,
+        template: &S,
+        total_len: u64,
+        chunk_len: usize,
+    ) -> Promise<Vec<C>, SplitError<C>>
+    where
+        S: 'static,
+        C: 'static,
+    {
+        let chunk_len = chunk_len.max(1);
+        let chunks = (0..total_len).step_by(chunk_len).map(|offset| {
+            let len = (chunk_len as u64).min(total_len - offset) as usize;
+            template.chunk(offset, len)
+        });
+        let submissions = self.batch_submit(chunks);
+
+        Promise::new(move || {
+            let chunks: Vec<_> = submissions
+                .into_iter()
+                .map(|submission| match submission {
+                    Ok(submission) => submission.wait(),
+                    Err((_, SubmitError::AtCapacity)) => Err(RingError::AtCapacity),
+                    Err((_, SubmitError::Disconnected)) => Err(RingError::ThreadGone),
+                })
+                .collect();
+
+            if chunks.iter().any(Result::is_err) {
+                return Err(SplitError { chunks });
+            }
+            Ok(chunks.into_iter().flatten().collect())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{driver::StepOutcome, test_util::MockRing, traits::UserData};
+
+    /// An entry covering `len` bytes from `offset`.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    struct Range {
+        ud: UserData,
+        offset: u64,
+        len: usize,
+    }
+
+    impl SQE for Range {
+        fn set_ud(&mut self, ud: UserData) {
+            self.ud = ud;
+        }
+
+        fn get_ud(&self) -> UserData {
+            self.ud
+        }
+    }
+
+    impl CQE for Range {
+        fn get_ud(&self) -> UserData {
+            self.ud
+        }
+    }
+
+    impl SplitEntry for Range {
+        fn chunk(&self, offset: u64, len: usize) -> Self {
+            Self {
+                offset: self.offset + offset,
+                len,
+                ..*self
+            }
+        }
+    }
+
+    const TEMPLATE: Range = Range {
+        ud: UserData::new(0),
+        offset: 100,
+        len: 0,
+    };
+
+    #[test]
+    fn chunks_cover_the_range_in_order() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder().build_driver(ring.clone());
+
+        let split = sender.submit_split(&TEMPLATE, 10, 4);
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        assert_eq!(ring.complete_all(), 3);
+        sender.reap();
+        assert_eq!(driver.step(), StepOutcome::Handled);
+
+        let chunks: Vec<_> = split
+            .wait_nopanic()
+            .unwrap()
+            .into_iter()
+            .map(|chunk| (chunk.offset, chunk.len))
+            .collect();
+        assert_eq!(chunks, [(100, 4), (104, 4), (108, 2)]);
+    }
+
+    #[test]
+    fn a_failed_chunk_keeps_the_others() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder().build_driver(ring.clone());
+
+        let split = sender.submit_split(&TEMPLATE, 8, 4);
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        assert!(ring.complete_next().is_some());
+        sender.reap();
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        // The second chunk never completes.
+        drop(driver);
+
+        let err = split.wait_nopanic().unwrap_err();
+        assert_eq!(err.chunks[0].as_ref().unwrap().offset, 100);
+        assert_eq!(err.chunks[1], Err(RingError::Disconnected));
+    }
+}
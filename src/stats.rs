@@ -0,0 +1,190 @@
+//! Counters shared between senders and the ring thread.
+//!
+//! `RingStats` is a set of atomics updated as signals flow through the ring. Senders read them to expose cheap,
+//! lock-free introspection of the ring thread's state. The one exception is the ring thread's last error, which sits
+//! behind a lock only taken when something goes wrong or a sender asks for it.
+
+use std::{
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+    },
+    time::Instant,
+};
+
+use crate::{error::RingError, reap::ReapReason, registry::lock};
+
+/// Live counters for a single ring thread.
+#[derive(Debug, Default)]
+pub(crate) struct RingStats {
+    /// Signals sent but not yet received by the ring thread.
+    backlog: AtomicUsize,
+    /// Completions recognised as duplicates of a recent completion.
+    duplicates: AtomicU64,
+    /// The submit threshold the ring thread currently applies.
+    coalesce: AtomicUsize,
+    /// Calls to `submit` that handed deferred entries to the backend.
+    batches: AtomicU64,
+    /// Entries handed to the backend by those calls.
+    batched: AtomicU64,
+    /// Reaps made by the ring thread, indexed by their [`ReapReason`].
+    reaps: [AtomicU64; ReapReason::ALL.len()],
+    /// The most entries registered at once since the peak was last reset.
+    peak_in_flight: AtomicUsize,
+    /// Panics of the backend's completion handling contained by the reap path.
+    errored_completions: AtomicU64,
+    /// Completions the backend reported dropped by an overflowing CQ.
+    lost_completions: AtomicU64,
+    /// Whether the ring thread is known to have exited.
+    exited: AtomicBool,
+    /// The most recent error the ring thread ran into, with when it did.
+    last_error: Mutex<Option<(RingError, Instant)>>,
+}
+
+impl RingStats {
+    /// Records a signal about to be sent.
+    #[inline]
+    pub(crate) fn signal_sent(&self) {
+        self.backlog.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records a signal taken off the channel by the ring thread, or one that never made it onto the channel.
+    #[inline]
+    pub(crate) fn signal_received(&self) {
+        self.backlog.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Signals sent but not yet received by the ring thread.
+    #[inline]
+    pub(crate) fn backlog(&self) -> usize {
+        self.backlog.load(Ordering::Relaxed)
+    }
+
+    /// Records a completion recognised as a duplicate of a recent completion.
+    #[inline]
+    pub(crate) fn duplicate_completion(&self) {
+        self.duplicates.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Completions recognised as duplicates of a recent completion.
+    #[inline]
+    pub(crate) fn duplicates(&self) -> u64 {
+        self.duplicates.load(Ordering::Relaxed)
+    }
+
+    /// Records the submit threshold the ring thread now applies.
+    #[inline]
+    pub(crate) fn set_coalesce(&self, threshold: usize) {
+        self.coalesce.store(threshold, Ordering::Relaxed);
+    }
+
+    /// The submit threshold the ring thread currently applies.
+    #[inline]
+    pub(crate) fn coalesce(&self) -> usize {
+        self.coalesce.load(Ordering::Relaxed)
+    }
+
+    /// Records a call to `submit` handing `entries` deferred entries to the backend.
+    #[inline]
+    pub(crate) fn batch_submitted(&self, entries: usize) {
+        self.batches.fetch_add(1, Ordering::Relaxed);
+        self.batched.fetch_add(entries as u64, Ordering::Relaxed);
+    }
+
+    /// Entries handed to the backend so far.
+    #[inline]
+    pub(crate) fn submitted(&self) -> u64 {
+        self.batched.load(Ordering::Relaxed)
+    }
+
+    /// The average number of entries handed to the backend per call to `submit`, or zero before the first.
+    #[inline]
+    pub(crate) fn average_batch_size(&self) -> f64 {
+        // Read the batch count first, so a concurrent submit can only make the average look larger.
+        let batches = self.batches.load(Ordering::Relaxed);
+        let batched = self.batched.load(Ordering::Relaxed);
+        if batches == 0 {
+            0.0
+        } else {
+            batched as f64 / batches as f64
+        }
+    }
+
+    /// Records a reap made by the ring thread for `reason`.
+    #[inline]
+    pub(crate) fn reaped(&self, reason: ReapReason) {
+        self.reaps[reason as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Reaps made by the ring thread for `reason`.
+    #[inline]
+    pub(crate) fn reaps(&self, reason: ReapReason) -> u64 {
+        self.reaps[reason as usize].load(Ordering::Relaxed)
+    }
+
+    /// Records `in_flight` entries registered at once, raising the peak if it is a new high.
+    #[inline]
+    pub(crate) fn record_in_flight(&self, in_flight: usize) {
+        self.peak_in_flight.fetch_max(in_flight, Ordering::Relaxed);
+    }
+
+    /// The most entries registered at once since the peak was last reset.
+    #[inline]
+    pub(crate) fn peak_in_flight(&self) -> usize {
+        self.peak_in_flight.load(Ordering::Relaxed)
+    }
+
+    /// Starts tracking the peak afresh from `in_flight` entries.
+    #[inline]
+    pub(crate) fn reset_peak(&self, in_flight: usize) {
+        self.peak_in_flight.store(in_flight, Ordering::Relaxed);
+    }
+
+    /// Records `count` panics of the backend's completion handling that the reap path contained.
+    #[inline]
+    pub(crate) fn completions_errored(&self, count: u64) {
+        self.errored_completions.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Panics of the backend's completion handling contained by the reap path.
+    #[inline]
+    pub(crate) fn errored_completions(&self) -> u64 {
+        self.errored_completions.load(Ordering::Relaxed)
+    }
+
+    /// Records `count` completions the backend reported dropped by an overflowing CQ.
+    #[inline]
+    pub(crate) fn completions_lost(&self, count: u64) {
+        self.lost_completions.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Completions the backend reported dropped by an overflowing CQ.
+    #[inline]
+    pub(crate) fn lost_completions(&self) -> u64 {
+        self.lost_completions.load(Ordering::Relaxed)
+    }
+
+    /// Records `err` as the most recent error the ring thread ran into.
+    #[inline]
+    pub(crate) fn record_error(&self, err: RingError) {
+        *lock(&self.last_error) = Some((err, Instant::now()));
+    }
+
+    /// The most recent error the ring thread ran into, with when it did.
+    #[inline]
+    pub(crate) fn last_error(&self) -> Option<(RingError, Instant)> {
+        lock(&self.last_error).clone()
+    }
+
+    /// Records that the ring thread has exited, or that a sender found it gone.
+    #[inline]
+    pub(crate) fn ring_exited(&self) {
+        self.exited.store(true, Ordering::Release);
+    }
+
+    /// Whether the ring thread is known to have exited.
+    #[inline]
+    pub(crate) fn has_exited(&self) -> bool {
+        self.exited.load(Ordering::Acquire)
+    }
+}
@@ -0,0 +1,207 @@
+//! A ring-wide stream of completions, pulled by any number of worker threads.
+//!
+//! Resolving one promise per submission and funnelling the results back into a worker pool costs a channel per
+//! entry and a hop per completion. With [`completion_stream`](crate::builder::PRingBuilder::completion_stream) set on
+//! the builder, the ring thread instead pushes completions into a single channel shared by the whole ring, tagged
+//! with the user data of their entry: those of entries submitted with
+//! [`PRingSender::submit_streamed`](crate::PRingSender::submit_streamed), and those no entry is waiting for, unless an
+//! [`orphan_sink`](crate::builder::PRingBuilder::orphan_sink) takes them. Every clone of the [`CompletionStream`]
+//! pulls from the same channel, each completion going to exactly one of them. Every other submission still resolves
+//! its own promise or callback, so its completion never shows up in the stream.
+//!
+//! The channel is `crossbeam-channel`'s with the `crossbeam` feature, which lets workers receive concurrently.
+//! Otherwise it is a `std::sync::mpsc` channel behind a mutex, so workers take turns receiving. Either way the ring
+//! thread never blocks pushing into it, as it is unbounded.
+//!
+//! ```no_run
+//! # #[cfg(feature = "test-util")]
+//! # fn main() {
+//! # use std::thread;
+//! # use ring_promise::{PRingSender, test_util::{MockEntry, MockRing}};
+//! # let ring = MockRing::<MockEntry, MockEntry>::echo();
+//! # let entries = (0..16).map(MockEntry::new);
+//! # let handle = |_user_data, _completion| {};
+//! let sender = PRingSender::builder().completion_stream().build(ring);
+//! let stream = sender.completion_stream().unwrap();
+//! for _ in 0..4 {
+//!     let stream = stream.clone();
+//!     thread::spawn(move || {
+//!         for (user_data, completion) in stream {
+//!             handle(user_data, completion);
+//!         }
+//!     });
+//! }
+//! for entry in entries {
+//!     sender.submit_streamed(entry).unwrap();
+//! }
+//! # }
+//! # #[cfg(not(feature = "test-util"))]
+//! # fn main() {}
+//! ```
+
+#[cfg(not(feature = "crossbeam"))]
+use std::sync::{Arc, Mutex, mpsc};
+use std::time::Duration;
+
+#[cfg(not(feature = "crossbeam"))]
+use crate::registry::lock;
+use crate::{
+    registry::Completion,
+    traits::{CompletionQueueEntry as CQE, UserData},
+};
+
+/// A completion pulled from a [`CompletionStream`], tagged with the user data of its entry.
+pub type StreamedCompletion<C> = (UserData, Completion<C>);
+
+/// The sending half of the stream's channel.
+#[cfg(not(feature = "crossbeam"))]
+type Tx<C> = mpsc::Sender<StreamedCompletion<C>>;
+/// The sending half of the stream's channel.
+#[cfg(feature = "crossbeam")]
+type Tx<C> = crossbeam_channel::Sender<StreamedCompletion<C>>;
+
+/// The receiving half of the stream's channel, shared by every clone of the stream.
+#[cfg(not(feature = "crossbeam"))]
+type Rx<C> = Arc<Mutex<mpsc::Receiver<StreamedCompletion<C>>>>;
+/// The receiving half of the stream's channel, shared by every clone of the stream.
+#[cfg(feature = "crossbeam")]
+type Rx<C> = crossbeam_channel::Receiver<StreamedCompletion<C>>;
+
+/// The ring thread's end of a [`CompletionStream`].
+pub struct StreamSender<C> {
+    /// The channel the completions are pushed into.
+    tx: Tx<C>,
+}
+
+impl<C: CQE> Clone for StreamSender<C> {
+    fn clone(&self) -> Self {
+        Self {
+            tx: self.tx.clone(),
+        }
+    }
+}
+
+impl<C: CQE> StreamSender<C> {
+    /// Pushes the completion of the entry tagged `user_data` into the stream.
+    pub(crate) fn send(&self, user_data: UserData, completion: Completion<C>) {
+        let _ = self.tx.send((user_data, completion));
+    }
+}
+
+/// The completions a ring streams out, shared by any number of consumers.
+///
+/// Obtained from [`PRingSender::completion_stream`](crate::PRingSender::completion_stream). Clones pull from the same
+/// channel, so each completion is yielded by exactly one of them. Iterating blocks for the next completion and ends
+/// once the ring thread has exited and every completion has been yielded.
+pub struct CompletionStream<C: CQE> {
+    /// The channel the ring thread pushes completions into.
+    rx: Rx<C>,
+}
+
+impl<C: CQE> Clone for CompletionStream<C> {
+    fn clone(&self) -> Self {
+        Self {
+            rx: self.rx.clone(),
+        }
+    }
+}
+
+impl<C: CQE> CompletionStream<C> {
+    /// Takes the next completion if one has arrived, without blocking.
+    ///
+    /// Returns `None` both when nothing has arrived yet and once the stream has ended.
+    pub fn try_next(&self) -> Option<StreamedCompletion<C>> {
+        #[cfg(not(feature = "crossbeam"))]
+        return lock(&self.rx).try_recv().ok();
+        #[cfg(feature = "crossbeam")]
+        return self.rx.try_recv().ok();
+    }
+
+    /// Blocks for the next completion for at most `timeout`.
+    ///
+    /// Returns `None` if none arrived in time, or once the stream has ended. Without the `crossbeam` feature, the
+    /// wait includes taking turns with other consumers.
+    pub fn next_timeout(&self, timeout: Duration) -> Option<StreamedCompletion<C>> {
+        #[cfg(not(feature = "crossbeam"))]
+        return lock(&self.rx).recv_timeout(timeout).ok();
+        #[cfg(feature = "crossbeam")]
+        return self.rx.recv_timeout(timeout).ok();
+    }
+}
+
+impl<C: CQE> Iterator for CompletionStream<C> {
+    type Item = StreamedCompletion<C>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        #[cfg(not(feature = "crossbeam"))]
+        return lock(&self.rx).recv().ok();
+        #[cfg(feature = "crossbeam")]
+        return self.rx.recv().ok();
+    }
+}
+
+/// Creates the channel behind a ring's completion stream.
+pub(crate) fn completion_stream<C: CQE>() -> (StreamSender<C>, CompletionStream<C>) {
+    #[cfg(not(feature = "crossbeam"))]
+    let (tx, rx) = {
+        let (tx, rx) = mpsc::channel();
+        (tx, Arc::new(Mutex::new(rx)))
+    };
+    #[cfg(feature = "crossbeam")]
+    let (tx, rx) = crossbeam_channel::unbounded();
+
+    (StreamSender { tx }, CompletionStream { rx })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        PRingSender,
+        driver::StepOutcome,
+        test_util::{MockEntry, MockRing},
+        traits::{SubmissionQueueEntry as SQE, UserData},
+    };
+
+    /// User data no entry is ever submitted under.
+    const STRAY: UserData = UserData::new(u64::MAX);
+
+    #[test]
+    fn only_streamed_entries_and_orphans_reach_the_stream() {
+        // The entry tagged 3 completes under user data nobody is waiting for.
+        let ring = MockRing::new(|mut entry: MockEntry| {
+            if entry.tag() == 3 {
+                SQE::set_ud(&mut entry, STRAY);
+            }
+            entry
+        });
+        let (sender, mut driver) = PRingSender::builder()
+            .completion_stream()
+            .build_driver(ring.clone());
+        let stream = sender.completion_stream().unwrap();
+
+        let streamed = sender.submit_streamed(MockEntry::new(1)).unwrap();
+        let plain = sender.submit(MockEntry::new(2));
+        let _lost = sender.submit(MockEntry::new(3));
+        while driver.try_step() == StepOutcome::Handled {}
+        assert_eq!(ring.complete_all(), 3);
+        sender.reap();
+        while driver.try_step() == StepOutcome::Handled {}
+
+        assert_eq!(plain.wait().unwrap().tag(), 2);
+        let pulled: Vec<_> = std::iter::from_fn(|| stream.try_next())
+            .map(|(ud, completion)| (ud, completion.unwrap().tag()))
+            .collect();
+        assert_eq!(pulled, [(streamed, 1), (STRAY, 3)]);
+    }
+
+    #[test]
+    fn streaming_needs_the_builder_option() {
+        let (sender, _driver) = PRingSender::builder().build_driver(MockRing::echo());
+
+        assert!(sender.completion_stream().is_none());
+        assert_eq!(
+            sender.submit_streamed(MockEntry::new(1)).unwrap_err().tag(),
+            1
+        );
+    }
+}
@@ -0,0 +1,1073 @@
+//! Handles for submitted entries.
+//!
+//! A `Submission` is returned for every entry submitted through a [`PRingSender`](crate::PRingSender). It carries the
+//! user data assigned to the entry and the receiving end of its completion channel. Entries submitted with
+//! [`PRingSender::submit_shared`](crate::PRingSender::submit_shared) also come with a `SubscriptionHandle`, from which
+//! any number of further `Submission`s for the same completion can be taken. Entries submitted with
+//! [`PRingSender::submit_with_waker`](crate::PRingSender::submit_with_waker) instead come with a `CompletionSlot`,
+//! which the ring thread fills before waking a `Waker`. A chain submitted with
+//! [`PRingSender::submit_pipeline`](crate::PRingSender::submit_pipeline) comes with a `Pipeline`, which yields the
+//! completions of its entries in chain order. [`PRingSender::submit_async`](crate::PRingSender::submit_async) returns
+//! a `SubmitFuture`, which cancels its entry if dropped before the completion arrives. Chaining
+//! [`Submission::map`] and friends onto a submission gives a `Mapped`, which transforms the completion lazily once
+//! waited on. [`join_all_timeout`] waits for a batch of submissions against a single deadline.
+
+use std::{
+    collections::VecDeque,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+    time::{Duration, Instant},
+};
+
+use promisery::Promise;
+
+use crate::{
+    Signal, WeakRingSender,
+    completion_pool::PooledReceiver,
+    error::RingError,
+    flags::SubmitFlags,
+    registry::{Completer, Completion, DeliveredSet, lock},
+    traits::{CompletionQueueEntry as CQE, SubmissionQueueEntry as SQE, UserData},
+};
+
+/// A handle to a submitted entry and its eventual completion.
+#[derive(Debug)]
+pub struct Submission<C: CQE> {
+    /// The user data assigned to the entry.
+    user_data: UserData,
+    /// Receives the completion from the ring thread.
+    receiver: CompletionReceiver<C>,
+    /// Clears the completion from the ring's tracking once taken, if it is tracked.
+    consumer: Option<Consumer>,
+}
+
+/// Clears a submission from the ring's [`DeliveredSet`] when its completion is taken or the submission dropped.
+#[derive(Debug)]
+struct Consumer {
+    /// The user data the completion is delivered under.
+    user_data: UserData,
+    /// Delivered completions waiting to be taken, shared with the ring thread.
+    delivered: DeliveredSet,
+}
+
+impl Consumer {
+    /// Records the completion as taken.
+    fn consume(&self) {
+        lock(&self.delivered).remove(&self.user_data.get());
+    }
+}
+
+impl Drop for Consumer {
+    fn drop(&mut self) {
+        self.consume();
+    }
+}
+
+/// Where a `Submission` receives its completion from.
+#[derive(Debug)]
+pub(crate) enum CompletionReceiver<C: CQE> {
+    /// A oneshot channel allocated for this submission.
+    Oneshot(oneshot::Receiver<Completion<C>>),
+    /// A channel borrowed from the ring's completion pool.
+    Pooled(PooledReceiver<C>),
+    /// The entry was never sent to the ring thread.
+    Rejected(RingError),
+}
+
+impl<C: CQE> From<oneshot::Receiver<Completion<C>>> for CompletionReceiver<C> {
+    fn from(receiver: oneshot::Receiver<Completion<C>>) -> Self {
+        Self::Oneshot(receiver)
+    }
+}
+
+impl<C: CQE> Submission<C> {
+    /// Creates a handle for the entry tagged with `user_data`.
+    pub(crate) fn new(user_data: UserData, receiver: impl Into<CompletionReceiver<C>>) -> Self {
+        Self {
+            user_data,
+            receiver: receiver.into(),
+            consumer: None,
+        }
+    }
+
+    /// Has the completion tracked in `delivered` until it is taken.
+    ///
+    /// Must be called before the entry is sent, so the ring thread cannot deliver it untracked.
+    pub(crate) fn tracked(mut self, delivered: &DeliveredSet) -> Self {
+        lock(delivered).insert(self.user_data.get(), None);
+        self.consumer = Some(Consumer {
+            user_data: self.user_data,
+            delivered: Arc::clone(delivered),
+        });
+        self
+    }
+
+    /// Records the completion as taken, if it is tracked.
+    fn consume(&self) {
+        if let Some(consumer) = &self.consumer {
+            consumer.consume();
+        }
+    }
+
+    /// Records the completion as taken unless the wait for it timed out, passing it through.
+    fn unless_timed_out(&self, completion: Result<C, RingError>) -> Result<C, RingError> {
+        if !matches!(completion, Err(RingError::Timeout)) {
+            self.consume();
+        }
+        completion
+    }
+
+    /// Creates a handle for an entry that was rejected before reaching the ring thread.
+    pub(crate) fn rejected(user_data: UserData, err: RingError) -> Self {
+        Self::new(user_data, CompletionReceiver::Rejected(err))
+    }
+
+    /// The user data assigned to the submitted entry.
+    #[inline]
+    pub fn user_data(&self) -> UserData {
+        self.user_data
+    }
+
+    /// Blocks until the completion arrives.
+    ///
+    /// Returns `RingError::Disconnected` if the ring thread dropped the entry without completing it.
+    #[inline]
+    pub fn wait(self) -> Result<C, RingError> {
+        match self.receiver {
+            CompletionReceiver::Oneshot(receiver) => receiver.recv()?,
+            CompletionReceiver::Pooled(receiver) => receiver.recv(),
+            CompletionReceiver::Rejected(err) => Err(err),
+        }
+    }
+
+    /// Takes the completion if it has arrived, without blocking.
+    ///
+    /// Returns `None` if the completion is still outstanding. Once the completion has been taken, further calls return
+    /// `Some(Err(RingError::Disconnected))`.
+    #[inline]
+    pub fn try_take(&self) -> Option<Result<C, RingError>> {
+        let completion = match &self.receiver {
+            CompletionReceiver::Oneshot(receiver) => match receiver.try_recv() {
+                Ok(completion) => Some(completion),
+                Err(oneshot::TryRecvError::Empty) => None,
+                Err(oneshot::TryRecvError::Disconnected) => Some(Err(RingError::Disconnected)),
+            },
+            CompletionReceiver::Pooled(receiver) => receiver.try_recv(),
+            CompletionReceiver::Rejected(err) => Some(Err(err.clone())),
+        };
+
+        if completion.is_some() {
+            self.consume();
+        }
+        completion
+    }
+
+    /// Blocks until the completion arrives or `timeout` elapses.
+    ///
+    /// On `RingError::Timeout` the handle can be waited on again.
+    #[inline]
+    pub fn wait_timeout(&self, timeout: Duration) -> Result<C, RingError> {
+        let completion = match &self.receiver {
+            CompletionReceiver::Oneshot(receiver) => receiver.recv_timeout(timeout)?,
+            CompletionReceiver::Pooled(receiver) => receiver.recv_timeout(timeout),
+            CompletionReceiver::Rejected(err) => Err(err.clone()),
+        };
+
+        self.unless_timed_out(completion)
+    }
+
+    /// Blocks until the completion arrives or `deadline` passes.
+    ///
+    /// On `RingError::Timeout` the handle can be waited on again.
+    #[inline]
+    pub fn wait_deadline(&self, deadline: Instant) -> Result<C, RingError> {
+        let completion = match &self.receiver {
+            CompletionReceiver::Oneshot(receiver) => receiver.recv_deadline(deadline)?,
+            CompletionReceiver::Pooled(receiver) => receiver.recv_deadline(deadline),
+            CompletionReceiver::Rejected(err) => Err(err.clone()),
+        };
+
+        self.unless_timed_out(completion)
+    }
+
+    /// Converts this handle into a `Promise` for the completion.
+    #[inline]
+    pub fn into_promise(self) -> Promise<C, RingError>
+    where
+        C: 'static,
+    {
+        Promise::new(move || self.wait())
+    }
+
+    /// Converts this handle into a function that blocks until the completion arrives, like [`wait`](Self::wait).
+    ///
+    /// Lets callers adapt a completion into whatever abstraction they prefer without `promisery` showing up in their
+    /// own signatures.
+    #[inline]
+    pub fn into_recv_fn(self) -> impl FnOnce() -> Result<C, RingError> + Send + 'static
+    where
+        C: 'static,
+    {
+        move || self.wait()
+    }
+
+    /// Transforms the completion with `f` once it arrives, without blocking now.
+    ///
+    /// Nothing runs until the returned handle is waited on, at which point `f` runs on the waiting thread.
+    #[inline]
+    pub fn map<T: 'static, F>(self, f: F) -> Mapped<T>
+    where
+        C: 'static,
+        F: FnOnce(C) -> T + Send + 'static,
+    {
+        Mapped::new(move || self.wait().map(f))
+    }
+
+    /// Chains `f` onto the completion once it arrives, without blocking now.
+    ///
+    /// Nothing runs until the returned handle is waited on. `f` only runs if the submission succeeded, and its error
+    /// becomes the handle's, e.g. to wait on a follow-up submission made from the completion.
+    #[inline]
+    pub fn and_then<T: 'static, F>(self, f: F) -> Mapped<T>
+    where
+        C: 'static,
+        F: FnOnce(C) -> Result<T, RingError> + Send + 'static,
+    {
+        Mapped::new(move || self.wait().and_then(f))
+    }
+
+    /// Transforms the error the submission fails with, if it does, without blocking now.
+    ///
+    /// Nothing runs until the returned handle is waited on.
+    #[inline]
+    pub fn map_err<E: 'static, F>(self, f: F) -> Mapped<C, E>
+    where
+        C: 'static,
+        F: FnOnce(RingError) -> E + Send + 'static,
+    {
+        Mapped::new(move || self.wait().map_err(f))
+    }
+
+    /// Converts this handle into the oneshot receiver its completion is delivered through.
+    ///
+    /// A handle rejected before reaching the ring thread yields a receiver already holding the error.
+    ///
+    /// # Errors
+    /// Hands the handle back unchanged if its completion comes through a channel borrowed from a
+    /// [`completion_pool`](crate::builder::PRingBuilder::completion_pool), which has no oneshot receiver.
+    #[inline]
+    pub fn into_receiver(self) -> Result<oneshot::Receiver<Completion<C>>, Self> {
+        match self.receiver {
+            CompletionReceiver::Oneshot(receiver) => Ok(receiver),
+            CompletionReceiver::Rejected(err) => {
+                let (tx, rx) = oneshot::channel();
+                let _ = tx.send(Err(err));
+                Ok(rx)
+            }
+            receiver @ CompletionReceiver::Pooled(_) => Err(Self {
+                user_data: self.user_data,
+                receiver,
+                consumer: self.consumer,
+            }),
+        }
+    }
+}
+
+/// Waits for every submission in `submissions` until `deadline`, returning their completions in the same order.
+///
+/// The deadline is shared rather than per submission, so the whole batch takes no longer than until `deadline`, however
+/// many submissions it has. Those whose completion has not arrived by then yield `RingError::Timeout` in their slot,
+/// and are dropped along with any completion that arrives later.
+pub fn join_all_timeout<C, I>(submissions: I, deadline: Instant) -> Vec<Result<C, RingError>>
+where
+    C: CQE,
+    I: IntoIterator<Item = Submission<C>>,
+{
+    submissions
+        .into_iter()
+        .map(|submission| submission.wait_deadline(deadline))
+        .collect()
+}
+
+/// A completion transformed by [`Submission::map`], [`and_then`](Submission::and_then) or
+/// [`map_err`](Submission::map_err), and any further combinators chained onto it.
+///
+/// Lazy, unlike a `Promise`: the combinators only run, in the order they were chained, on the thread that waits for
+/// the result.
+#[must_use = "the combinators only run once the handle is waited on"]
+pub struct Mapped<T, E = RingError> {
+    /// Waits for the completion and runs the combinators on it.
+    run: Box<dyn FnOnce() -> Result<T, E> + Send>,
+}
+
+impl<T: 'static, E: 'static> Mapped<T, E> {
+    /// Runs `run` once waited on.
+    fn new(run: impl FnOnce() -> Result<T, E> + Send + 'static) -> Self {
+        Self { run: Box::new(run) }
+    }
+
+    /// Blocks until the completion arrives, then runs the combinators on it.
+    #[inline]
+    pub fn wait(self) -> Result<T, E> {
+        (self.run)()
+    }
+
+    /// Transforms the value with `f`, once there is one.
+    #[inline]
+    pub fn map<U: 'static, F>(self, f: F) -> Mapped<U, E>
+    where
+        F: FnOnce(T) -> U + Send + 'static,
+    {
+        Mapped::new(move || self.wait().map(f))
+    }
+
+    /// Chains `f` onto the value, once there is one. `f` only runs if everything before it succeeded.
+    #[inline]
+    pub fn and_then<U: 'static, F>(self, f: F) -> Mapped<U, E>
+    where
+        F: FnOnce(T) -> Result<U, E> + Send + 'static,
+    {
+        Mapped::new(move || self.wait().and_then(f))
+    }
+
+    /// Transforms the error, if anything before this failed.
+    #[inline]
+    pub fn map_err<G: 'static, F>(self, f: F) -> Mapped<T, G>
+    where
+        F: FnOnce(E) -> G + Send + 'static,
+    {
+        Mapped::new(move || self.wait().map_err(f))
+    }
+
+    /// Converts this handle into a `Promise`, which starts waiting and running the combinators straight away.
+    #[inline]
+    pub fn into_promise(self) -> Promise<T, E>
+    where
+        T: Send,
+        E: Send,
+    {
+        Promise::new(self.run)
+    }
+}
+
+impl<T: Send + 'static, E: Send + 'static> From<Mapped<T, E>> for Promise<T, E> {
+    fn from(mapped: Mapped<T, E>) -> Self {
+        mapped.into_promise()
+    }
+}
+
+/// The state shared by a `CompletionSlot` and its `SlotSender`.
+struct SlotShared<C: CQE> {
+    /// The completion once delivered, and the waker to wake when it is.
+    state: Mutex<SlotState<C>>,
+}
+
+/// The contents of a completion slot.
+struct SlotState<C: CQE> {
+    /// The delivered completion, until it is taken.
+    completion: Option<Completion<C>>,
+    /// Whether a completion has been delivered, taken or not.
+    delivered: bool,
+    /// Woken once the completion is delivered.
+    waker: Option<Waker>,
+}
+
+/// A slot the ring thread fills with a completion before waking a `Waker`.
+///
+/// Returned by [`PRingSender::submit_with_waker`](crate::PRingSender::submit_with_waker) for executors that poll
+/// for completions themselves.
+pub struct CompletionSlot<C: CQE> {
+    /// The user data assigned to the entry.
+    user_data: UserData,
+    /// State shared with the ring thread.
+    shared: Arc<SlotShared<C>>,
+}
+
+impl<C: CQE> CompletionSlot<C> {
+    /// Creates an empty slot for the entry tagged with `user_data`, along with the sender filling it.
+    pub(crate) fn new(user_data: UserData, waker: Waker) -> (Self, SlotSender<C>) {
+        let shared = Arc::new(SlotShared {
+            state: Mutex::new(SlotState {
+                completion: None,
+                delivered: false,
+                waker: Some(waker),
+            }),
+        });
+
+        let slot = Self {
+            user_data,
+            shared: Arc::clone(&shared),
+        };
+        (slot, SlotSender { shared })
+    }
+
+    /// The user data assigned to the submitted entry.
+    #[inline]
+    pub fn user_data(&self) -> UserData {
+        self.user_data
+    }
+
+    /// Replaces the waker woken when the completion arrives, e.g. when the task is polled with a new one.
+    ///
+    /// The waker is woken straight away if the completion has already arrived.
+    pub fn set_waker(&self, waker: &Waker) {
+        let mut state = lock(&self.shared.state);
+
+        if state.delivered {
+            drop(state);
+            waker.wake_by_ref();
+        } else {
+            match &mut state.waker {
+                Some(current) if current.will_wake(waker) => {}
+                current => *current = Some(waker.clone()),
+            }
+        }
+    }
+
+    /// Whether the completion has arrived and not been taken yet.
+    pub fn is_ready(&self) -> bool {
+        lock(&self.shared.state).completion.is_some()
+    }
+
+    /// Takes the completion if it has arrived.
+    ///
+    /// Returns `None` while the completion is outstanding, and again once it has been taken.
+    pub fn take(&self) -> Option<Completion<C>> {
+        lock(&self.shared.state).completion.take()
+    }
+
+    /// Whether the completion has arrived, taken or not.
+    pub(crate) fn is_delivered(&self) -> bool {
+        lock(&self.shared.state).delivered
+    }
+}
+
+/// A future resolving with the completion of a submitted entry, cancelling the entry if dropped before then.
+///
+/// Returned by [`PRingSender::submit_async`](crate::PRingSender::submit_async). Dropping it before the completion
+/// arrives, e.g. when it loses a `select!` or a timeout fires, has the ring thread resolve the entry with
+/// `RingError::Cancelled` and ignore its eventual completion, as [`cancel_batch`](crate::PRingSender::cancel_batch)
+/// would. This is best-effort: an entry already handed to the backend may still be carried out, only its outcome is
+/// lost, and the cancellation is skipped rather than waited for if a bounded channel is full.
+///
+/// It works with any executor, tokio included, without a runtime-specific feature: polling and dropping never block,
+/// the ring thread wakes the waker of the latest poll once the completion lands, and the future is `Send` and `Unpin`,
+/// as well as `'static` for `'static` entries, so it can be spawned, raced by reference in `select!` and wrapped in
+/// `tokio::time::timeout`:
+///
+/// ```no_run
+/// # #[cfg(feature = "test-util")]
+/// # async fn run() -> Result<(), ring_promise::error::RingError> {
+/// # use std::time::Duration;
+/// # use ring_promise::{PRingSender, test_util::{MockEntry, MockRing}};
+/// # let sender = PRingSender::new(MockRing::<MockEntry, MockEntry>::echo());
+/// # let (read_entry, write_entry) = (MockEntry::new(1), MockEntry::new(2));
+/// # let handle = |_: MockEntry| {};
+/// # struct Shutdown;
+/// # impl Shutdown { async fn changed(&mut self) {} }
+/// # let mut shutdown = Shutdown;
+/// let mut read = sender.submit_async(read_entry);
+/// tokio::select! {
+///     completion = &mut read => handle(completion?),
+///     _ = shutdown.changed() => return Ok(()), // `read` is cancelled once dropped
+/// }
+///
+/// match tokio::time::timeout(Duration::from_secs(1), sender.submit_async(write_entry)).await {
+///     Ok(completion) => handle(completion?),
+///     Err(_elapsed) => {} // the write was cancelled when the timeout dropped it
+/// }
+/// # Ok(())
+/// # }
+/// # fn main() {}
+/// ```
+///
+/// With the `tokio` feature, [`timeout`](Self::timeout) is a shorthand for the latter.
+///
+/// Polling it again once it has resolved returns `Pending` forever, as `select!` and `timeout` never do.
+pub struct SubmitFuture<S: SQE, C: CQE> {
+    /// Where the completion lands.
+    slot: CompletionSlot<C>,
+    /// Reaches the ring thread to cancel the entry on drop, without keeping it alive.
+    sender: WeakRingSender<S, C>,
+    /// The entry and the sender filling the slot, while waiting for room under a
+    /// [`max_in_flight`](crate::builder::PRingBuilder::max_in_flight) cap before being sent.
+    waiting: Option<(S, SlotSender<C>)>,
+    /// Whether the future has resolved.
+    done: bool,
+}
+
+// The entry waiting to be sent is only ever moved out, never pinned.
+impl<S: SQE, C: CQE> Unpin for SubmitFuture<S, C> {}
+
+impl<S: SQE, C: CQE> SubmitFuture<S, C> {
+    /// Creates a future resolving once `slot` is filled, cancelling through `sender` if dropped before then.
+    pub(crate) fn new(slot: CompletionSlot<C>, sender: WeakRingSender<S, C>) -> Self {
+        Self {
+            slot,
+            sender,
+            waiting: None,
+            done: false,
+        }
+    }
+
+    /// Like [`new`](Self::new), for an entry to send once there is room under the in-flight cap, with `filler` filling
+    /// `slot`.
+    pub(crate) fn waiting(
+        slot: CompletionSlot<C>,
+        sender: WeakRingSender<S, C>,
+        entry: S,
+        filler: SlotSender<C>,
+    ) -> Self {
+        Self {
+            slot,
+            sender,
+            waiting: Some((entry, filler)),
+            done: false,
+        }
+    }
+
+    /// The user data assigned to the submitted entry.
+    #[inline]
+    pub fn user_data(&self) -> UserData {
+        self.slot.user_data()
+    }
+
+    /// Wraps the future in [`tokio::time::timeout`], which drops it, cancelling the entry, if `duration` elapses
+    /// before the completion arrives.
+    ///
+    /// Must be awaited within a tokio runtime with the time driver enabled.
+    ///
+    /// # Arguments
+    /// * `duration` - How long to wait for the completion.
+    #[cfg(feature = "tokio")]
+    #[inline]
+    pub fn timeout(self, duration: Duration) -> tokio::time::Timeout<Self> {
+        tokio::time::timeout(duration, self)
+    }
+}
+
+impl<S: SQE, C: CQE> Future for SubmitFuture<S, C> {
+    type Output = Completion<C>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.waiting.is_some() {
+            let Some(sender) = self.sender.upgrade() else {
+                self.waiting = None;
+                self.done = true;
+                return Poll::Ready(Err(RingError::ThreadGone));
+            };
+            let Poll::Ready(permit) = sender.poll_acquire(cx) else {
+                return Poll::Pending;
+            };
+
+            let (entry, filler) = self.waiting.take().expect("checked above");
+            let ud = self.slot.user_data();
+            sender.enqueue_holding(ud, entry, permit, |entry| {
+                Signal::Entry(entry, Completer::Slot(filler), SubmitFlags::empty())
+            });
+        }
+
+        let completion = match self.slot.take() {
+            Some(completion) => completion,
+            None => {
+                self.slot.set_waker(cx.waker());
+                // The completion may have landed before the waker was in place.
+                match self.slot.take() {
+                    Some(completion) => completion,
+                    None => return Poll::Pending,
+                }
+            }
+        };
+
+        self.done = true;
+        Poll::Ready(completion)
+    }
+}
+
+impl<S: SQE, C: CQE> Drop for SubmitFuture<S, C> {
+    fn drop(&mut self) {
+        // An entry still waiting for room was never sent, so there is nothing to cancel.
+        if self.done || self.waiting.is_some() || self.slot.is_delivered() {
+            return;
+        }
+
+        // The ring thread may be gone, in which case the entry has already resolved. Futures are dropped on executor
+        // threads, so a full channel costs the cancellation rather than blocking; the completion is ignored either way.
+        if let Some(sender) = self.sender.upgrade() {
+            let _ = sender.try_dispatch(Signal::Cancel(self.slot.user_data()));
+        }
+    }
+}
+
+/// The completions of a chain of linked entries, yielded in chain order.
+///
+/// Returned by [`PRingSender::submit_pipeline`](crate::PRingSender::submit_pipeline). With the `futures` feature, a
+/// pipeline is a `futures_core::Stream` of the completions. Otherwise [`poll_next`](Self::poll_next) follows the
+/// contract of `Stream::poll_next`, so a pipeline turns into an async stream with an adapter such as
+/// `futures::stream::poll_fn`. The first error ends the pipeline: the backend does not run the rest of a chain once an
+/// entry fails, so their completions are not yielded.
+pub struct Pipeline<C: CQE> {
+    /// The slots of the entries whose completions are still to be yielded, in chain order.
+    slots: VecDeque<CompletionSlot<C>>,
+}
+
+impl<C: CQE> Pipeline<C> {
+    /// Creates a pipeline yielding the completions filled into `slots`, in order.
+    pub(crate) fn new(slots: VecDeque<CompletionSlot<C>>) -> Self {
+        Self { slots }
+    }
+
+    /// The user data of the entries whose completions are still to be yielded, in chain order.
+    pub fn user_data(&self) -> impl Iterator<Item = UserData> + '_ {
+        self.slots.iter().map(CompletionSlot::user_data)
+    }
+
+    /// How many completions are still to be yielded, at most.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Whether the pipeline has ended.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// Polls for the completion of the next entry in the chain.
+    ///
+    /// Returns `Poll::Ready(None)` once every completion has been yielded, or right after the first error. Otherwise
+    /// registers the waker of `cx` to be woken once the next completion lands.
+    pub fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<Completion<C>>> {
+        let Some(slot) = self.slots.front() else {
+            return Poll::Ready(None);
+        };
+
+        let completion = match slot.take() {
+            Some(completion) => completion,
+            None => {
+                slot.set_waker(cx.waker());
+                // The completion may have landed before the waker was in place.
+                match slot.take() {
+                    Some(completion) => completion,
+                    None => return Poll::Pending,
+                }
+            }
+        };
+
+        self.slots.pop_front();
+        if completion.is_err() {
+            // The rest of the chain was cancelled along with it.
+            self.slots.clear();
+        }
+        Poll::Ready(Some(completion))
+    }
+}
+
+#[cfg(feature = "futures")]
+impl<C: CQE> futures_core::Stream for Pipeline<C> {
+    type Item = Completion<C>;
+
+    #[inline]
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pipeline::poll_next(self.get_mut(), cx)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        // An error may end the pipeline early.
+        (0, Some(self.len()))
+    }
+}
+
+/// The ring thread's end of a [`CompletionSlot`].
+///
+/// If this is dropped without sending, e.g. because the ring thread exited, the slot is filled with
+/// `RingError::Disconnected` instead.
+pub struct SlotSender<C: CQE> {
+    /// State shared with the slot.
+    shared: Arc<SlotShared<C>>,
+}
+
+impl<C: CQE> SlotSender<C> {
+    /// Fills the slot with `completion` and wakes its waker.
+    pub(crate) fn send(self, completion: Completion<C>) {
+        self.fill(completion);
+    }
+
+    /// Fills the slot unless it is already filled, then wakes its waker.
+    fn fill(&self, completion: Completion<C>) {
+        let waker = {
+            let mut state = lock(&self.shared.state);
+            if state.delivered {
+                return;
+            }
+
+            state.completion = Some(completion);
+            state.delivered = true;
+            state.waker.take()
+        };
+
+        // Woken outside the lock, the executor may poll the slot straight away.
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl<C: CQE> Drop for SlotSender<C> {
+    fn drop(&mut self) {
+        self.fill(Err(RingError::Disconnected));
+    }
+}
+
+impl<C: CQE + 'static> From<Submission<C>> for Promise<C, RingError> {
+    fn from(submission: Submission<C>) -> Self {
+        submission.into_promise()
+    }
+}
+
+/// The subscribers to a shared completion, or the completion itself once it has arrived.
+enum FanoutState<C: CQE> {
+    /// Still waiting; every sender receives a clone of the completion.
+    Waiting(Vec<oneshot::Sender<Completion<C>>>),
+    /// Delivered; late subscribers receive a clone straight away.
+    Done(Completion<C>),
+}
+
+/// A completion shared between every subscriber of a single entry.
+pub(crate) struct Fanout<C: CQE> {
+    /// The subscribers or the delivered completion.
+    state: Mutex<FanoutState<C>>,
+}
+
+impl<C: CQE + Clone> Fanout<C> {
+    /// Creates a fan-out with no subscribers.
+    pub(crate) fn new() -> Self {
+        Self {
+            state: Mutex::new(FanoutState::Waiting(Vec::new())),
+        }
+    }
+
+    /// Sends a clone of `completion` to every subscriber, and to any that subscribe later.
+    ///
+    /// Does nothing if a completion has already been delivered.
+    pub(crate) fn deliver(&self, completion: Completion<C>) {
+        let mut state = lock(&self.state);
+
+        let FanoutState::Waiting(subscribers) = &mut *state else {
+            return;
+        };
+        for subscriber in subscribers.drain(..) {
+            // Subscribers that dropped their handle are not interested.
+            let _ = subscriber.send(completion.clone());
+        }
+
+        *state = FanoutState::Done(completion);
+    }
+
+    /// Adds a subscriber, returning the receiving end of its completion channel.
+    fn subscribe(&self) -> oneshot::Receiver<Completion<C>> {
+        let (tx, rx) = oneshot::channel();
+
+        match &mut *lock(&self.state) {
+            FanoutState::Waiting(subscribers) => subscribers.push(tx),
+            FanoutState::Done(completion) => {
+                let _ = tx.send(completion.clone());
+            }
+        }
+
+        rx
+    }
+}
+
+/// Delivers to a `Fanout` from the ring thread.
+///
+/// Subscribers keep the fan-out alive, so dropping it does not disconnect them. If the ring drops this without
+/// delivering, e.g. because the ring thread exited, the subscribers resolve with `RingError::Disconnected` instead.
+pub(crate) struct FanoutDelivery<C: CQE + Clone>(pub(crate) Arc<Fanout<C>>);
+
+impl<C: CQE + Clone> Drop for FanoutDelivery<C> {
+    fn drop(&mut self) {
+        self.0.deliver(Err(RingError::Disconnected));
+    }
+}
+
+/// A handle for subscribing to the completion of an entry submitted with
+/// [`PRingSender::submit_shared`](crate::PRingSender::submit_shared).
+///
+/// Every subscriber receives its own clone of the completion. Subscribing after the completion has arrived yields a
+/// `Submission` that resolves immediately.
+pub struct SubscriptionHandle<C: CQE> {
+    /// The user data assigned to the entry.
+    user_data: UserData,
+    /// The completion shared between subscribers.
+    fanout: Arc<Fanout<C>>,
+}
+
+impl<C: CQE> Clone for SubscriptionHandle<C> {
+    fn clone(&self) -> Self {
+        Self {
+            user_data: self.user_data,
+            fanout: Arc::clone(&self.fanout),
+        }
+    }
+}
+
+impl<C: CQE + Clone> SubscriptionHandle<C> {
+    /// Creates a handle for the entry tagged with `user_data`.
+    pub(crate) fn new(user_data: UserData, fanout: Arc<Fanout<C>>) -> Self {
+        Self { user_data, fanout }
+    }
+
+    /// The user data assigned to the submitted entry.
+    #[inline]
+    pub fn user_data(&self) -> UserData {
+        self.user_data
+    }
+
+    /// Subscribes to the completion, returning a handle that resolves with a clone of it.
+    #[inline]
+    pub fn subscribe(&self) -> Submission<C> {
+        Submission::new(self.user_data, self.fanout.subscribe())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        task::Wake,
+    };
+
+    use super::*;
+    use crate::{
+        PRingSender,
+        driver::StepOutcome,
+        test_util::{MockEntry, MockRing},
+    };
+
+    #[test]
+    fn pipeline_ends_after_the_first_error() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder().build_driver(ring.clone());
+        let mut pipeline = sender.submit_pipeline((0..3).map(MockEntry::new));
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        assert_eq!(pipeline.len(), 3);
+
+        let first = pipeline.user_data().next().unwrap();
+        sender.send(Signal::Cancel(first));
+        assert_eq!(driver.step(), StepOutcome::Handled);
+
+        let mut cx = Context::from_waker(Waker::noop());
+        assert!(matches!(
+            pipeline.poll_next(&mut cx),
+            Poll::Ready(Some(Err(RingError::Cancelled)))
+        ));
+        assert!(pipeline.is_empty());
+        assert!(matches!(pipeline.poll_next(&mut cx), Poll::Ready(None)));
+    }
+
+    #[test]
+    fn handles_resolve_on_another_thread() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder().build_driver(ring.clone());
+        let submission = sender.submit(MockEntry::new(1));
+        let (handle, shared) = sender.submit_shared(MockEntry::new(2));
+        let slot = sender.submit_with_waker(MockEntry::new(3), Waker::noop().clone());
+        let mut pipeline = sender.submit_pipeline([MockEntry::new(4)]);
+        let mut future = sender.submit_async(MockEntry::new(5));
+        while driver.try_step() == StepOutcome::Handled {}
+
+        assert_eq!(ring.complete_all(), 5);
+        sender.reap();
+        assert_eq!(driver.step(), StepOutcome::Handled);
+
+        // Every kind of handle can be moved to, and resolved on, a thread other than the submitter's.
+        std::thread::spawn(move || {
+            let mut cx = Context::from_waker(Waker::noop());
+            let tag = |completion: Completion<MockEntry>| completion.unwrap().tag();
+
+            assert_eq!(tag(submission.wait()), 1);
+            assert_eq!(tag(shared.wait()), 2);
+            assert_eq!(tag(handle.subscribe().wait()), 2);
+            assert_eq!(slot.take().map(tag), Some(3));
+            assert_eq!(
+                pipeline.poll_next(&mut cx).map(|next| next.map(tag)),
+                Poll::Ready(Some(4))
+            );
+            // Unpin, so it can be polled through a plain `&mut`, as `select!` does.
+            assert_eq!(Pin::new(&mut future).poll(&mut cx).map(tag), Poll::Ready(5));
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn dropping_a_pending_future_cancels_its_entry() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder().build_driver(ring.clone());
+        let mut future = sender.submit_async(MockEntry::new(1));
+        assert_eq!(driver.step(), StepOutcome::Handled);
+
+        let mut cx = Context::from_waker(Waker::noop());
+        assert!(Pin::new(&mut future).poll(&mut cx).is_pending());
+        let ud = future.user_data();
+        drop(future);
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        assert!(sender.outstanding().is_empty());
+
+        // The entry is still with the backend, and its completion is ignored once it arrives.
+        assert_eq!(ring.in_flight(), [ud]);
+        assert!(ring.complete_ud(ud));
+        sender.reap();
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        assert_eq!(sender.duplicate_completions(), 0);
+    }
+
+    #[cfg(feature = "futures")]
+    #[test]
+    fn pipeline_streams_completions_in_chain_order() {
+        use futures_core::Stream;
+
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder().build_driver(ring.clone());
+        let mut pipeline = sender.submit_pipeline([MockEntry::new(0), MockEntry::new(1)]);
+        assert_eq!(Stream::size_hint(&pipeline), (0, Some(2)));
+        assert_eq!(driver.step(), StepOutcome::Handled);
+
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut poll = |pipeline: &mut Pipeline<MockEntry>| {
+            Stream::poll_next(Pin::new(pipeline), &mut cx)
+                .map(|completion| completion.map(|completion| completion.unwrap().tag()))
+        };
+        assert_eq!(poll(&mut pipeline), Poll::Pending);
+
+        // Completed out of order, yet yielded in chain order.
+        let in_flight = ring.in_flight();
+        assert!(ring.complete_ud(in_flight[1]));
+        sender.reap();
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        assert_eq!(poll(&mut pipeline), Poll::Pending);
+
+        assert!(ring.complete_ud(in_flight[0]));
+        sender.reap();
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        assert_eq!(poll(&mut pipeline), Poll::Ready(Some(0)));
+        assert_eq!(poll(&mut pipeline), Poll::Ready(Some(1)));
+        assert_eq!(poll(&mut pipeline), Poll::Ready(None));
+        assert_eq!(Stream::size_hint(&pipeline), (0, Some(0)));
+    }
+
+    /// Counts how often it was woken.
+    #[derive(Default)]
+    struct WakeCount(AtomicUsize);
+
+    impl Wake for WakeCount {
+        fn wake(self: Arc<Self>) {
+            self.wake_by_ref();
+        }
+
+        fn wake_by_ref(self: &Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    impl WakeCount {
+        fn get(&self) -> usize {
+            self.0.load(Ordering::SeqCst)
+        }
+    }
+
+    #[test]
+    fn slots_wake_their_waker_once_filled() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder().build_driver(ring.clone());
+        let wakes = Arc::new(WakeCount::default());
+
+        let slot = sender.submit_with_waker(MockEntry::new(1), Waker::from(Arc::clone(&wakes)));
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        assert!(!slot.is_ready());
+        assert!(slot.take().is_none());
+
+        assert!(ring.complete_ud(slot.user_data()));
+        sender.reap();
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        assert_eq!(wakes.get(), 1);
+        assert!(slot.is_ready());
+        assert_eq!(slot.take().unwrap().unwrap().tag(), 1);
+        assert!(slot.take().is_none());
+
+        // A waker set once the completion is in is woken straight away.
+        slot.set_waker(&Waker::from(Arc::clone(&wakes)));
+        assert_eq!(wakes.get(), 2);
+    }
+
+    #[test]
+    fn combinators_run_in_order_once_waited_on() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder().build_driver(ring.clone());
+        let ran = Arc::new(AtomicUsize::new(0));
+
+        let counter = Arc::clone(&ran);
+        let mapped = sender
+            .submit(MockEntry::new(2))
+            .map(move |cqe| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                cqe.tag()
+            })
+            .and_then(|tag| Ok(tag * 10))
+            .map(|tag| tag + 1);
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        assert_eq!(ring.complete_all(), 1);
+        sender.reap();
+        assert_eq!(driver.step(), StepOutcome::Handled);
+
+        // Delivered, but nothing has waited yet.
+        assert_eq!(ran.load(Ordering::SeqCst), 0);
+        assert_eq!(mapped.wait(), Ok(21));
+        assert_eq!(ran.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn combinators_after_a_failure_only_see_the_error() {
+        let failed = Submission::<MockEntry>::rejected(UserData::from(1), RingError::AtCapacity)
+            .map(|_| unreachable!("the submission failed"))
+            .and_then(|()| Ok(()))
+            .map_err(|err| err.to_string());
+        assert_eq!(failed.wait(), Err("ring at capacity".to_owned()));
+
+        let errored = Submission::<MockEntry>::rejected(UserData::from(2), RingError::AtCapacity)
+            .map_err(|_| "gave up");
+        assert_eq!(
+            Promise::from(errored).wait_nopanic().map(|cqe| cqe.tag()),
+            Err("gave up")
+        );
+    }
+
+    #[test]
+    fn join_all_timeout_times_out_only_what_is_still_outstanding() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder().build_driver(ring.clone());
+
+        let submissions: Vec<_> = (1..=3)
+            .map(|tag| sender.submit(MockEntry::new(tag)))
+            .collect();
+        while driver.try_step() == StepOutcome::Handled {}
+        assert!(ring.complete_ud(submissions[0].user_data()));
+        assert!(ring.complete_ud(submissions[2].user_data()));
+        sender.reap();
+        while driver.try_step() == StepOutcome::Handled {}
+
+        let started = Instant::now();
+        let deadline = started + Duration::from_millis(20);
+        let results: Vec<_> = join_all_timeout(submissions, deadline)
+            .into_iter()
+            .map(|result| result.map(|cqe| cqe.tag()))
+            .collect();
+        assert_eq!(results, [Ok(1), Err(RingError::Timeout), Ok(3)]);
+        // The deadline bounds the whole batch.
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,299 @@
+//! Helpers for test suites built on top of this crate.
+//!
+//! Enabled with the `test-util` feature. [`MockRing`] is a fully scriptable backend: entries submitted to it stay in
+//! flight until the test completes them, in whatever order it likes. [`MockEntry`] is a minimal entry to drive it
+//! with, completing as itself.
+
+use std::{
+    collections::VecDeque,
+    fmt::Write,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    adapters::{Completions, QueueSubmissions},
+    registry::lock,
+    submission::Submission,
+    traits::{
+        CompletionQueueEntry as CQE, FullRing, SubmissionQueueEntry as SQE, Submitter, UserData,
+    },
+};
+
+/// A minimal entry for tests, serving as both submission and completion queue entry.
+///
+/// The tag tells entries apart regardless of the user data the sender assigns them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MockEntry {
+    /// The user data the sender tagged the entry with.
+    ud: UserData,
+    /// Identifies the entry to the test.
+    tag: u64,
+    /// The flags applied by the ring thread.
+    flags: u32,
+}
+
+impl MockEntry {
+    /// Creates an entry identified by `tag`.
+    pub const fn new(tag: u64) -> Self {
+        Self {
+            ud: UserData::new(0),
+            tag,
+            flags: 0,
+        }
+    }
+
+    /// The tag the entry was created with.
+    pub const fn tag(&self) -> u64 {
+        self.tag
+    }
+
+    /// The flags the ring thread applied to the entry, if any.
+    pub const fn flags(&self) -> u32 {
+        self.flags
+    }
+}
+
+impl SQE for MockEntry {
+    fn set_ud(&mut self, ud: UserData) {
+        self.ud = ud;
+    }
+
+    fn get_ud(&self) -> UserData {
+        self.ud
+    }
+
+    fn set_flags(&mut self, flags: u32) {
+        self.flags = flags;
+    }
+}
+
+impl CQE for MockEntry {
+    fn get_ud(&self) -> UserData {
+        self.ud
+    }
+}
+
+/// Waits for every submission to resolve within `timeout`, returning their completions in order.
+///
+/// The timeout is shared: it bounds the whole batch, not each submission.
+///
+/// # Panics
+/// Panics if any submission fails to resolve in time, or resolves with an error.
+/// The message lists the index and user data of every submission that failed.
+pub fn assert_all_resolve<C, I>(submissions: I, timeout: Duration) -> Vec<C>
+where
+    C: CQE,
+    I: IntoIterator<Item = Submission<C>>,
+{
+    let deadline = Instant::now() + timeout;
+
+    let mut completions = Vec::new();
+    let mut failures = Vec::new();
+    let mut total = 0;
+
+    for (index, submission) in submissions.into_iter().enumerate() {
+        total += 1;
+        match submission.wait_deadline(deadline) {
+            Ok(completion) => completions.push(completion),
+            Err(err) => failures.push((index, submission.user_data(), err)),
+        }
+    }
+
+    if !failures.is_empty() {
+        let mut message = format!(
+            "{} of {} submissions did not resolve within {:?}:",
+            failures.len(),
+            total,
+            timeout
+        );
+        for (index, user_data, reason) in failures {
+            let _ = write!(message, "\n  [{index}] user data {user_data}: {reason}");
+        }
+        panic!("{message}");
+    }
+
+    completions
+}
+
+/// Turns a submitted entry into its completion.
+type CompleteFn<S, C> = dyn Fn(S) -> C + Send + Sync + 'static;
+
+/// The state shared by every clone of a [`MockRing`].
+struct MockState<S, C> {
+    /// Entries handed to the "kernel" by `submit`, oldest first.
+    in_flight: VecDeque<S>,
+    /// Completions made available by the test, waiting to be reaped.
+    completed: VecDeque<C>,
+}
+
+/// A scriptable in-memory backend for tests.
+///
+/// Entries pushed onto the mock's submission queue move into flight when the ring thread calls `submit`; from
+/// there, nothing completes until the test says so with [`complete_next`](Self::complete_next),
+/// [`complete_ud`](Self::complete_ud) or [`complete_all`](Self::complete_all). Completed entries are only delivered
+/// once the ring thread next reaps, so follow them up with [`PRingSender::reap`](crate::PRingSender::reap).
+///
+/// Clones share the same state: keep one to script completions and hand the other to the sender.
+pub struct MockRing<S, C> {
+    /// The submission queue drained on `submit`.
+    submissions: QueueSubmissions<S>,
+    /// In-flight and completed entries.
+    state: Arc<Mutex<MockState<S, C>>>,
+    /// Produces the completion for an in-flight entry.
+    complete: Arc<CompleteFn<S, C>>,
+}
+
+impl<S, C> Clone for MockRing<S, C> {
+    fn clone(&self) -> Self {
+        Self {
+            submissions: self.submissions.clone(),
+            state: Arc::clone(&self.state),
+            complete: Arc::clone(&self.complete),
+        }
+    }
+}
+
+impl<E: SQE + CQE> MockRing<E, E> {
+    /// Creates a mock whose entries complete as themselves.
+    pub fn echo() -> Self {
+        Self::new(|entry| entry)
+    }
+}
+
+impl<S: SQE, C: CQE> MockRing<S, C> {
+    /// Creates a mock that produces completions with `complete`.
+    ///
+    /// `complete` must preserve the entry's user data for the completion to reach its submission.
+    pub fn new<F>(complete: F) -> Self
+    where
+        F: Fn(S) -> C + Send + Sync + 'static,
+    {
+        Self {
+            submissions: QueueSubmissions::new(),
+            state: Arc::new(Mutex::new(MockState {
+                in_flight: VecDeque::new(),
+                completed: VecDeque::new(),
+            })),
+            complete: Arc::new(complete),
+        }
+    }
+
+    /// User data of every in-flight entry, oldest first.
+    pub fn in_flight(&self) -> Vec<UserData> {
+        lock(&self.state)
+            .in_flight
+            .iter()
+            .map(|entry| entry.get_ud())
+            .collect()
+    }
+
+    /// Completes the oldest in-flight entry, returning its user data.
+    pub fn complete_next(&self) -> Option<UserData> {
+        let mut state = lock(&self.state);
+        let entry = state.in_flight.pop_front()?;
+        let ud = entry.get_ud();
+        state.completed.push_back((self.complete)(entry));
+        Some(ud)
+    }
+
+    /// Completes the in-flight entry tagged with `ud`, returning whether there was one.
+    pub fn complete_ud(&self, ud: UserData) -> bool {
+        let mut state = lock(&self.state);
+        let Some(index) = state
+            .in_flight
+            .iter()
+            .position(|entry| entry.get_ud() == ud)
+        else {
+            return false;
+        };
+        let entry = state.in_flight.remove(index).expect("index is in bounds");
+        state.completed.push_back((self.complete)(entry));
+        true
+    }
+
+    /// Completes every in-flight entry in submission order, returning how many there were.
+    pub fn complete_all(&self) -> usize {
+        let mut state = lock(&self.state);
+        let entries = std::mem::take(&mut state.in_flight);
+        let count = entries.len();
+        state
+            .completed
+            .extend(entries.into_iter().map(|entry| (self.complete)(entry)));
+        count
+    }
+}
+
+impl<S: SQE, C: CQE> Submitter for MockRing<S, C> {
+    fn submit(&mut self) {
+        self.submit_counted();
+    }
+
+    fn submit_counted(&mut self) -> Option<usize> {
+        let submitted = self.submissions.drain();
+        let count = submitted.len();
+        lock(&self.state).in_flight.extend(submitted);
+        Some(count)
+    }
+}
+
+impl<S: SQE, C: CQE> FullRing<S, C, QueueSubmissions<S>, Completions<C>> for MockRing<S, C> {
+    fn completion(&mut self) -> Completions<C> {
+        std::mem::take(&mut lock(&self.state).completed).into()
+    }
+
+    fn submission(&mut self) -> QueueSubmissions<S> {
+        self.submissions.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PRingSender, driver::StepOutcome};
+
+    #[test]
+    fn completions_follow_the_script() {
+        let ring = MockRing::echo();
+        let (sender, mut driver) = PRingSender::builder().build_driver(ring.clone());
+
+        let submissions: Vec<_> = (1..=3)
+            .map(|tag| sender.submit(MockEntry::new(tag)))
+            .collect();
+        while driver.try_step() == StepOutcome::Handled {}
+        let user_data: Vec<_> = submissions.iter().map(Submission::user_data).collect();
+        assert_eq!(ring.in_flight(), user_data);
+
+        // Nothing is delivered before it is completed and reaped.
+        assert!(ring.complete_ud(user_data[2]));
+        assert!(!ring.complete_ud(user_data[2]));
+        assert!(submissions[2].try_take().is_none());
+        sender.reap();
+        assert_eq!(driver.step(), StepOutcome::Handled);
+        assert_eq!(submissions[2].try_take().unwrap().unwrap().tag(), 3);
+
+        assert_eq!(ring.complete_next(), Some(user_data[0]));
+        assert_eq!(ring.complete_all(), 1);
+        assert_eq!(ring.complete_next(), None);
+        sender.reap();
+        assert_eq!(driver.step(), StepOutcome::Handled);
+
+        let tags: Vec<_> =
+            assert_all_resolve(submissions.into_iter().take(2), Duration::from_secs(5))
+                .iter()
+                .map(MockEntry::tag)
+                .collect();
+        assert_eq!(tags, [1, 2]);
+    }
+
+    #[test]
+    #[should_panic(expected = "1 of 1 submissions did not resolve")]
+    fn unresolved_submissions_fail_the_assertion() {
+        let (sender, _driver) = PRingSender::builder().build_driver(MockRing::echo());
+
+        assert_all_resolve(
+            [sender.submit(MockEntry::new(1))],
+            Duration::from_millis(10),
+        );
+    }
+}
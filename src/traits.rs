@@ -3,14 +3,66 @@
 //! These traits define the core abstractions for submission and completion queues, entries, and rings.
 //! They are designed to be flexible and extensible for a variety of I/O backends.
 
+use std::{fmt, time::Duration};
+
+use crate::error::{BackendError, ValidationError};
+
+/// The user data tagging an entry and its completions.
+///
+/// A newtype rather than a bare `u64`, so that user data cannot be mixed up with the offsets, descriptors and lengths
+/// sharing an entry with it. Convert explicitly with `From`, e.g. when copying it into a backend's own entry type.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+pub struct UserData(u64);
+
+impl UserData {
+    /// Wraps raw user data.
+    #[inline]
+    pub const fn new(ud: u64) -> Self {
+        Self(ud)
+    }
+
+    /// The raw user data.
+    #[inline]
+    pub const fn get(self) -> u64 {
+        self.0
+    }
+}
+
+impl From<u64> for UserData {
+    #[inline]
+    fn from(ud: u64) -> Self {
+        Self(ud)
+    }
+}
+
+impl From<UserData> for u64 {
+    #[inline]
+    fn from(ud: UserData) -> Self {
+        ud.0
+    }
+}
+
+impl fmt::Display for UserData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
 /// A submission queue entry.
 ///
 /// Types implementing this trait can be submitted to a submission queue.
-pub trait SubmissionQueueEntry: Send + 'static {
+pub trait SubmissionQueueEntry: Send {
     /// Set the user data field for this entry.
-    fn set_ud(&mut self, ud: u64);
+    fn set_ud(&mut self, ud: UserData);
     /// Get the user data field for this entry.
-    fn get_ud(&self) -> u64;
+    fn get_ud(&self) -> UserData;
+    /// Apply backend-defined submission flags to this entry.
+    ///
+    /// Only called with a non-empty flag set. Defaults to ignoring the flags.
+    fn set_flags(&mut self, flags: u32) {
+        let _ = flags;
+    }
 }
 
 /// A submission queue for entries of type `S`.
@@ -19,14 +71,55 @@ pub trait SubmissionQueue<S: SubmissionQueueEntry> {
     ///
     /// Returns `Ok(())` if successful, or `Err(entry)` if the queue is full.
     fn push(&mut self, entry: S) -> Result<(), S>;
+
+    /// Attempt to push an entry into slot `index` of the submission queue, for queues with per-slot semantics.
+    ///
+    /// Returns `Err(entry)` if the slot cannot take the entry right now. Defaults to ignoring `index` and calling
+    /// [`push`](Self::push).
+    fn push_at(&mut self, index: usize, entry: S) -> Result<(), S> {
+        let _ = index;
+        self.push(entry)
+    }
+
+    /// The maximum number of entries the queue holds, or `None` if unknown or unbounded.
+    ///
+    /// Only used for diagnostics. Defaults to `None`.
+    fn capacity(&self) -> Option<usize> {
+        None
+    }
+
+    /// Check an entry against the backend's constraints before it is pushed, e.g. its opcode or alignment.
+    ///
+    /// The ring thread never pushes an entry this rejects, resolving its submission with `RingError::Invalid`
+    /// instead. Defaults to accepting every entry.
+    fn validate(&self, entry: &S) -> Result<(), ValidationError> {
+        let _ = entry;
+        Ok(())
+    }
 }
 
 /// A completion queue entry.
 ///
 /// Types implementing this trait are produced by completion queues.
-pub trait CompletionQueueEntry: Send + 'static {
+pub trait CompletionQueueEntry: Send {
     /// Get the user data field for this entry.
-    fn get_ud(&self) -> u64;
+    fn get_ud(&self) -> UserData;
+    /// The index of the provided buffer the backend picked for this completion's data, e.g. from `IORING_CQE_F_BUFFER`.
+    ///
+    /// With provided buffers the backend chooses a buffer from a pool when the operation completes rather than the
+    /// entry naming one up front. Completions are delivered as reaped, so whoever receives one can read the index here
+    /// to find its data and hand the buffer back to the pool once done with it. Defaults to `None`, for backends
+    /// without provided buffers and completions that did not consume one.
+    fn buffer_id(&self) -> Option<u16> {
+        None
+    }
+    /// Whether this completion reports a failed operation, e.g. a negative result code.
+    ///
+    /// Decides which completions go to an [`error_sink`](crate::builder::PRingBuilder::error_sink), so it should agree
+    /// with [`FullRing::interpret`]. Defaults to `false`.
+    fn is_error(&self) -> bool {
+        false
+    }
 }
 
 /// A completion queue for entries of type `C`.
@@ -34,10 +127,62 @@ pub trait CompletionQueueEntry: Send + 'static {
 /// This trait is auto-implemented for any iterator over `C`.
 pub trait CompletionQueue<C: CompletionQueueEntry>: Iterator<Item = C> {}
 
+impl<C: CompletionQueueEntry, I: Iterator<Item = C>> CompletionQueue<C> for I {}
+
+/// The completion side of a ring, split off so it can be reaped on a thread of its own.
+///
+/// Returned by [`FullRing::split_completion`] for
+/// [`PRingBuilder::split_threads`](crate::builder::PRingBuilder::split_threads). Its
+/// [`completion_key`](Self::completion_key) and [`interpret`](Self::interpret) must agree with the ring's.
+pub trait CompletionHalf<C, CQ>: Send
+where
+    C: CompletionQueueEntry,
+    CQ: CompletionQueue<C>,
+{
+    /// Get the completion queue.
+    fn completion(&mut self) -> CQ;
+
+    /// The key a completion is routed by. See [`FullRing::completion_key`].
+    fn completion_key(&self, cqe: &C) -> u64 {
+        cqe.get_ud().get()
+    }
+
+    /// Interpret a reaped completion before it is delivered. See [`FullRing::interpret`].
+    fn interpret(&self, cqe: C) -> Result<C, BackendError> {
+        Ok(cqe)
+    }
+
+    /// The user data of the entry a completion reports as cancelled. See [`FullRing::is_cancel_completion`].
+    fn is_cancel_completion(&self, cqe: &C) -> Option<UserData> {
+        let _ = cqe;
+        None
+    }
+}
+
 /// A type that can submit entries to the kernel or underlying system.
 pub trait Submitter {
     /// Notify the kernel or system that new entries are ready for processing.
     fn submit(&mut self);
+
+    /// Like [`submit`](Self::submit), also reporting how many entries the kernel or system accepted, e.g. the return
+    /// value of `io_uring_submit`.
+    ///
+    /// The ring thread submits through this, so an implementation must submit just like `submit` does. Defaults to
+    /// calling `submit` and returning `None`, in which case every pushed entry counts as accepted.
+    fn submit_counted(&mut self) -> Option<usize> {
+        self.submit();
+        None
+    }
+
+    /// Like [`submit`](Self::submit), then blocks until at least `want` completions are available, e.g. with
+    /// `io_uring_submit_and_wait`.
+    ///
+    /// Only used while [`drain_on_shutdown`](crate::builder::PRingBuilder::drain_on_shutdown) drains the ring, so it
+    /// can wait on the backend rather than spin on the CQ. Defaults to calling `submit` without blocking.
+    fn submit_and_wait(&mut self, want: usize) {
+        let _ = want;
+        self.submit();
+    }
 }
 
 /// A full ring abstraction, combining submission and completion queues and a submitter.
@@ -55,4 +200,117 @@ where
     fn completion(&mut self) -> CQ;
     /// Get the submission queue.
     fn submission(&mut self) -> SQ;
+
+    /// The key a submission's completion will be routed by.
+    ///
+    /// Backends that identify completions by something other than their user data, e.g. an (fd, op) pair, can derive
+    /// a key from the entry here and from the completion in [`completion_key`](Self::completion_key). The two must
+    /// agree for every entry. Keys of outstanding entries must be unique. Defaults to the entry's user data.
+    fn submission_key(&self, sqe: &S) -> u64 {
+        sqe.get_ud().get()
+    }
+
+    /// The key a completion is routed by. See [`submission_key`](Self::submission_key).
+    ///
+    /// Defaults to the completion's user data.
+    fn completion_key(&self, cqe: &C) -> u64 {
+        cqe.get_ud().get()
+    }
+
+    /// Interpret a reaped completion before it is delivered.
+    ///
+    /// Backends whose completions encode failure (e.g. a negative result code) can surface it here, resolving the
+    /// submission with `RingError::Backend` instead of a successful completion. A failed completion that still
+    /// consumed a provided [`buffer_id`](CompletionQueueEntry::buffer_id) has to give the buffer back here, since
+    /// the error carries no completion to read it from. Defaults to always `Ok`.
+    fn interpret(&self, cqe: C) -> Result<C, BackendError> {
+        Ok(cqe)
+    }
+
+    /// The user data of the entry a completion reports as cancelled, e.g. the target of a successful
+    /// `IORING_OP_ASYNC_CANCEL`.
+    ///
+    /// Kernel-side cancel operations are entries of their own that name another entry by its user data. When one of
+    /// their completions says the cancellation went through, the targeted submission resolves with
+    /// `RingError::Cancelled` straight away; its own completion, whenever it arrives, finds nothing registered. The
+    /// cancel operation's completion is still delivered to whoever submitted it. Return `None` for failed
+    /// cancellations, e.g. when the target had already completed. Defaults to `None`.
+    fn is_cancel_completion(&self, cqe: &C) -> Option<UserData> {
+        let _ = cqe;
+        None
+    }
+
+    /// Whether every submitted entry has its completion available as soon as `submit` returns.
+    ///
+    /// Synchronous backends gain nothing from deferring submissions, so the ring thread always submits and reaps
+    /// right after pushing an entry. Defaults to `false`.
+    fn is_synchronous(&self) -> bool {
+        false
+    }
+
+    /// How many completions the CQ holds before it overflows, if known.
+    ///
+    /// Many backends drop completions once the CQ overflows, so the ring thread reaps ahead of any submit that could
+    /// leave the CQ close to this many unreaped completions, whatever its reap strategy says. Defaults to `None`,
+    /// leaving reaping to the strategy alone. `Some(0)` counts as unknown too.
+    fn completion_capacity(&self) -> Option<usize> {
+        None
+    }
+
+    /// The number of completions the CQ has dropped because it was full, e.g. io_uring's `cq.koverflow`.
+    ///
+    /// The ring thread checks it after every reap, counting any increase as lost completions and, with
+    /// [`fail_lost_completions`](crate::builder::PRingBuilder::fail_lost_completions), failing entries in their place.
+    /// Only increases since the ring thread started count. Defaults to zero, for backends whose CQ cannot overflow.
+    fn overflow_count(&self) -> u64 {
+        0
+    }
+
+    /// Register a set of fixed buffers that later entries can refer to by index.
+    ///
+    /// How an entry names a registered buffer is up to the backend's entry type. Registering replaces any previously
+    /// registered set. Defaults to `Err(BackendError::Unsupported)`.
+    fn register_buffers(&mut self, bufs: Vec<Vec<u8>>) -> Result<(), BackendError> {
+        let _ = bufs;
+        Err(BackendError::Unsupported)
+    }
+
+    /// An entry that times out the entry pushed right before it, e.g. an `IORING_OP_LINK_TIMEOUT` for io_uring.
+    ///
+    /// Lets [`PRingSender::submit_timeout`](crate::PRingSender::submit_timeout) have the backend cancel an entry that
+    /// does not complete within `timeout`. The ring thread [`link`](Self::link)s the entry, pushes this one straight
+    /// behind it without submitting in between, and ignores its completion. Defaults to `None`, leaving the ring
+    /// thread to enforce the timeout itself.
+    fn timeout_entry(&self, timeout: Duration) -> Option<S> {
+        let _ = timeout;
+        None
+    }
+
+    /// Link `sqe` to the entry pushed right after it, e.g. by setting `IOSQE_IO_LINK`.
+    ///
+    /// Called on entries followed by a [`timeout_entry`](Self::timeout_entry), and on every entry of a chain but the
+    /// last. Defaults to doing nothing.
+    fn link(&self, sqe: &mut S) {
+        let _ = sqe;
+    }
+
+    /// Hand out the completion side of the ring, to be reaped from another thread than the one submitting.
+    ///
+    /// Called once, when a ring built with [`split_threads`](crate::builder::PRingBuilder::split_threads) starts
+    /// running. From then on, [`completion`](Self::completion) is no longer called and every completion is reaped
+    /// through the returned half instead, while the ring keeps submitting. Defaults to `None`, keeping both on the
+    /// ring thread.
+    fn split_completion(&mut self) -> Option<Box<dyn CompletionHalf<C, CQ>>> {
+        None
+    }
+
+    /// Recreate whatever of the ring's OS state does not survive a `fork`, e.g. the ring fd itself or an eventfd
+    /// registered with it for readiness.
+    ///
+    /// Called in the child on the ring handed to
+    /// [`PRingSender::reinit_after_fork`](crate::PRingSender::reinit_after_fork), before its ring thread starts. An
+    /// error is handed back to the caller and no thread is started. Defaults to doing nothing.
+    fn reinit(&mut self) -> Result<(), BackendError> {
+        Ok(())
+    }
 }
@@ -0,0 +1,127 @@
+//! Async submissions under tokio: raced in `select!`, bounded by `timeout`, and cancelled when dropped.
+
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use ring_promise::{
+    PRingSender,
+    test_util::{MockEntry, MockRing},
+    traits::UserData,
+};
+
+type Sender = PRingSender<MockEntry, MockEntry>;
+
+/// Waits for `condition` to hold, failing the test if it does not within a few seconds.
+fn eventually(mut condition: impl FnMut() -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !condition() {
+        assert!(Instant::now() < deadline, "condition never held");
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+/// Completes the entry with user data `ud` from another thread after `delay`, so the completion lands while the
+/// runtime is parked on it.
+fn complete_later(
+    ring: &MockRing<MockEntry, MockEntry>,
+    sender: &Sender,
+    ud: UserData,
+    delay: Duration,
+) {
+    let (ring, sender) = (ring.clone(), sender.clone());
+    thread::spawn(move || {
+        thread::sleep(delay);
+        eventually(|| ring.complete_ud(ud));
+        sender.reap();
+    });
+}
+
+/// Asserts the entry with user data `ud` was cancelled: nothing waits for it any more, though the backend still has it.
+fn assert_cancelled(ring: &MockRing<MockEntry, MockEntry>, sender: &Sender, ud: UserData) {
+    eventually(|| {
+        sender
+            .outstanding()
+            .iter()
+            .all(|outstanding| outstanding.user_data != ud)
+    });
+    assert!(ring.in_flight().contains(&ud));
+}
+
+#[tokio::test]
+async fn select_takes_the_first_completion_and_cancels_the_other() {
+    let ring = MockRing::echo();
+    let sender = PRingSender::new(ring.clone());
+
+    let mut slow = sender.submit_async(MockEntry::new(1));
+    let fast = sender.submit_async(MockEntry::new(2));
+    let (slow_ud, fast_ud) = (slow.user_data(), fast.user_data());
+    complete_later(&ring, &sender, fast_ud, Duration::from_millis(10));
+
+    tokio::select! {
+        _ = &mut slow => panic!("the slow entry never completes"),
+        completion = fast => assert_eq!(completion.unwrap().tag(), 2),
+    }
+
+    // Raced by reference, the loser is still pending until dropped.
+    assert!(
+        sender
+            .outstanding()
+            .iter()
+            .any(|outstanding| outstanding.user_data == slow_ud)
+    );
+    drop(slow);
+    assert_cancelled(&ring, &sender, slow_ud);
+}
+
+#[tokio::test]
+async fn timeout_cancels_an_entry_that_takes_too_long() {
+    let ring = MockRing::echo();
+    let sender = PRingSender::new(ring.clone());
+
+    let future = sender.submit_async(MockEntry::new(1));
+    let ud = future.user_data();
+    assert!(
+        tokio::time::timeout(Duration::from_millis(10), future)
+            .await
+            .is_err()
+    );
+    assert_cancelled(&ring, &sender, ud);
+
+    // Its completion is ignored once it arrives.
+    assert!(ring.complete_ud(ud));
+    sender.reap();
+    eventually(|| ring.in_flight().is_empty());
+}
+
+#[tokio::test]
+async fn timeout_passes_through_a_completion_arriving_in_time() {
+    let ring = MockRing::echo();
+    let sender = PRingSender::new(ring.clone());
+
+    let future = sender.submit_async(MockEntry::new(7));
+    complete_later(
+        &ring,
+        &sender,
+        future.user_data(),
+        Duration::from_millis(10),
+    );
+    let completion = future.timeout(Duration::from_secs(5)).await.unwrap();
+    assert_eq!(completion.unwrap().tag(), 7);
+    assert!(sender.outstanding().is_empty());
+}
+
+#[tokio::test]
+async fn dropping_a_spawned_submission_cancels_its_entry() {
+    let ring = MockRing::echo();
+    let sender = PRingSender::new(ring.clone());
+
+    let future = sender.submit_async(MockEntry::new(1));
+    let ud = future.user_data();
+    let task = tokio::spawn(future);
+    tokio::task::yield_now().await;
+    task.abort();
+    assert!(task.await.unwrap_err().is_cancelled());
+    assert_cancelled(&ring, &sender, ud);
+}